@@ -2,16 +2,19 @@ use criterion::{black_box, criterion_group, criterion_main, Criterion};
 use personal_shopper::{
     algorithms::bsl_psd::BSLPSD,
     algorithms::PSDSolver,
-    models::{Location, Product, ShoppingList, Store},
+    models::{DistanceMetric, Location, Product, ShoppingList, ShoppingRoute, Store},
+    utils::skyline::Skyline,
 };
+use rand::{rngs::StdRng, Rng, SeedableRng};
 use std::collections::HashMap;
 
 fn benchmark_bsl_psd(c: &mut Criterion) {
     // Create benchmark data
     let (stores, shopping_list, shopper_location, customer_location) = create_benchmark_data();
 
-    // Initialize BSL-PSD
+    // Initialize BSL-PSD on the planar benchmark grid
     let mut bsl_psd = BSLPSD::new(stores.clone());
+    bsl_psd.set_distance_metric(DistanceMetric::Euclidean);
     bsl_psd.precompute_data();
 
     // Benchmark the solve function
@@ -25,6 +28,21 @@ fn benchmark_bsl_psd(c: &mut Criterion) {
         })
     });
 
+    // Benchmark the minimum-time Dijkstra on a longer, 8-product list
+    let mut long_list = ShoppingList::new();
+    for i in 1..=8 {
+        long_list.add_item(i, 2);
+    }
+    c.bench_function("bsl_psd_min_time_dijkstra", |b| {
+        b.iter(|| {
+            bsl_psd.find_min_time_route_dijkstra(
+                black_box(&long_list),
+                black_box(shopper_location),
+                black_box(customer_location),
+            )
+        })
+    });
+
     // Benchmark precomputation (mainly for larger datasets)
     c.bench_function("bsl_psd_precompute", |b| {
         b.iter(|| {
@@ -70,5 +88,30 @@ fn create_benchmark_data() -> (HashMap<u32, Store>, ShoppingList, Location, Loca
     (stores, shopping_list, shopper_location, customer_location)
 }
 
-criterion_group!(benches, benchmark_bsl_psd);
+fn benchmark_skyline_insert(c: &mut Criterion) {
+    // 10k routes trading time against cost, so most of them reach the skyline for a while
+    let mut rng = StdRng::seed_from_u64(42);
+    let routes: Vec<ShoppingRoute> = (0..10_000)
+        .map(|_| {
+            let stores: Vec<u32> = (0..rng.gen_range(1..=4))
+                .map(|_| rng.gen_range(1..=25))
+                .collect();
+            let time = rng.gen_range(0.0..500.0);
+            let cost = 500.0 - time + rng.gen_range(0.0..5.0);
+            ShoppingRoute::new(stores, time, cost)
+        })
+        .collect();
+
+    c.bench_function("skyline_insert_10k", |b| {
+        b.iter(|| {
+            let mut skyline = Skyline::new();
+            for route in &routes {
+                skyline.insert(black_box(route.clone()));
+            }
+            skyline
+        })
+    });
+}
+
+criterion_group!(benches, benchmark_bsl_psd, benchmark_skyline_insert);
 criterion_main!(benches);
@@ -1,17 +1,74 @@
-use std::cmp::Ordering;
+use rayon::prelude::*;
+use std::collections::hash_map::Entry;
 use std::collections::{BinaryHeap, HashMap, HashSet};
 use std::f64;
+use std::fmt;
+use std::hash::Hash;
+use std::ops::Deref;
 use std::sync::atomic::Ordering::Relaxed;
-use std::sync::mpsc::{self};
-use std::sync::RwLock;
+use std::sync::mpsc::{self, RecvTimeoutError};
 use std::sync::{atomic::AtomicBool, Arc};
+use std::sync::{Mutex, RwLock};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-use crate::algorithms::PSDSolver;
+use crate::algorithms::{PSDSolver, SolveOptions};
 use crate::models::{
-    Cost, Location, ProductId, RouteCandidate, ShoppingList, ShoppingRoute, Store, StoreId, Time,
+    Cost, DistanceMetric, Location, ProductId, PurchaseAllocation, RouteCandidate, ShoppingList,
+    ShoppingRoute, Store, StoreId, Time,
 };
+use crate::utils::benchmark::BenchmarkResult;
+use crate::utils::road_network::{EdgeCost, RoadGraph, RoutingObjective};
+use crate::utils::skyline::{
+    insert_into_skyline, insert_sorted_conventional, remove_slower_permutation, select_spread,
+    three_objective_front, Domination, Skyline,
+};
+
+/// Why a solve produced no routes
+#[derive(Debug, Clone, PartialEq)]
+pub enum SolveError {
+    /// The stores together don't stock enough of some products; lists each
+    /// under-supplied product with the quantity missing, by product ID, and separately
+    /// the products no store has in stock at all
+    Infeasible {
+        missing: Vec<(ProductId, u32)>,
+        unavailable: Vec<ProductId>,
+    },
+
+    /// The list could be fulfilled, but the search found no route
+    NoRouteFound,
+}
+
+impl fmt::Display for SolveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SolveError::Infeasible {
+                missing,
+                unavailable,
+            } => {
+                write!(f, "shopping list cannot be fulfilled, missing")?;
+                for (index, (product_id, quantity)) in missing.iter().enumerate() {
+                    let separator = if index == 0 { " " } else { ", " };
+                    write!(f, "{}{} of product {}", separator, quantity, product_id)?;
+                }
+                if !unavailable.is_empty() {
+                    let products: Vec<String> =
+                        unavailable.iter().map(|id| id.to_string()).collect();
+                    let noun = if products.len() == 1 {
+                        "product"
+                    } else {
+                        "products"
+                    };
+                    write!(f, " (no store has {} {})", noun, products.join(", "))?;
+                }
+                Ok(())
+            }
+            SolveError::NoRouteFound => write!(f, "no route found for the shopping list"),
+        }
+    }
+}
+
+impl std::error::Error for SolveError {}
 
 // Custom wrapper to make f64 implement Eq
 #[derive(PartialEq, Copy, Clone, Debug)]
@@ -21,37 +78,308 @@ impl Eq for F64Wrapper {}
 
 impl PartialOrd for F64Wrapper {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        self.0.partial_cmp(&other.0)
+        Some(self.cmp(other))
     }
 }
 
 impl Ord for F64Wrapper {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        self.partial_cmp(other).unwrap_or(std::cmp::Ordering::Equal)
+        self.0
+            .partial_cmp(&other.0)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    }
+}
+
+// Quantities still to be purchased, one per shopping list product in a fixed order
+// Together with the current store it forms the Dijkstra search state
+trait Remaining: Clone + Eq + Hash {
+    /// Buys whatever `stock` offers; None when nothing could be bought
+    fn buy(&self, layout: &RemainingLayout, stock: &[u32]) -> Option<Self>;
+
+    fn is_empty(&self) -> bool;
+}
+
+// Bit offset and width of each product's quantity in a packed `u128` state
+#[derive(Default)]
+struct RemainingLayout {
+    offsets: Vec<u32>,
+    widths: Vec<u32>,
+}
+
+impl RemainingLayout {
+    // Each quantity gets just enough bits for its initial value; None when they don't
+    // fit in 128 bits together
+    fn packed(quantities: &[u32]) -> Option<Self> {
+        let widths: Vec<u32> = quantities
+            .iter()
+            .map(|q| u32::BITS - q.leading_zeros())
+            .collect();
+        let offsets = widths
+            .iter()
+            .scan(0, |offset, width| {
+                let current = *offset;
+                *offset += width;
+                Some(current)
+            })
+            .collect();
+        (widths.iter().sum::<u32>() <= u128::BITS).then_some(Self { offsets, widths })
+    }
+
+    fn pack(&self, quantities: &[u32]) -> u128 {
+        quantities
+            .iter()
+            .zip(&self.offsets)
+            .map(|(&quantity, &offset)| (quantity as u128) << offset)
+            .sum()
+    }
+}
+
+impl Remaining for u128 {
+    fn buy(&self, layout: &RemainingLayout, stock: &[u32]) -> Option<Self> {
+        let mut next = *self;
+        for (index, &available) in stock.iter().enumerate() {
+            if available == 0 {
+                continue;
+            }
+            let offset = layout.offsets[index];
+            let mask = (1u128 << layout.widths[index]) - 1;
+            let needed = ((*self >> offset) & mask) as u32;
+            next -= (needed.min(available) as u128) << offset;
+        }
+        (next != *self).then_some(next)
+    }
+
+    fn is_empty(&self) -> bool {
+        *self == 0
+    }
+}
+
+// Unpacked fallback for lists whose quantities need more than 128 bits
+impl Remaining for Box<[u32]> {
+    fn buy(&self, _layout: &RemainingLayout, stock: &[u32]) -> Option<Self> {
+        if !self
+            .iter()
+            .zip(stock)
+            .any(|(&needed, &available)| needed > 0 && available > 0)
+        {
+            return None;
+        }
+        Some(
+            self.iter()
+                .zip(stock)
+                .map(|(&needed, &available)| needed.saturating_sub(available))
+                .collect(),
+        )
+    }
+
+    fn is_empty(&self) -> bool {
+        self.iter().all(|&quantity| quantity == 0)
     }
 }
 
-// Custom ordered tuple for the priority queue
-#[derive(PartialEq, Eq, Debug)]
-struct QueueState {
+// Permutation counts from which find_shortest_path evaluates them in parallel (5 stores)
+const PARALLEL_PERMUTATIONS: usize = 120;
+
+// Largest store set find_shortest_path orders by trying every permutation (8! = 40320);
+// larger sets are ordered by cheapest insertion instead
+const MAX_PERMUTATION_STORES: usize = 8;
+
+// State of the exhaustive path enumeration behind `solve_exact`, over candidate indices
+struct ExactSearch<'a> {
+    shopping_list: &'a ShoppingList,
+    candidates: Vec<StoreId>,
+    from_shopper: Vec<Time>,
+    // Direct travel times between candidates
+    legs: Vec<Vec<Time>>,
+    to_customer: Vec<Time>,
+    // Lower bound on the time still needed from each candidate, detours included
+    finish_floor: Vec<Time>,
+    min_cost: Cost,
+    // Time of the fastest cheapest route found so far
+    time_bound: Time,
+    path: Vec<usize>,
+    visited: Vec<bool>,
+    routes: Vec<ShoppingRoute>,
+}
+
+// How `parallel_search` distributes the route space over worker threads
+#[derive(Clone, Copy)]
+enum WorkerPlan {
+    /// Two workers from the minimum-time route, one per route generator
+    Paired,
+    /// The given number of workers, each seeded with a share of the first routes
+    Partitioned(usize),
+}
+
+// Priority queue entry of the minimum-time Dijkstra (min-heap on distance)
+#[derive(Eq, PartialEq)]
+struct DijkstraEntry<K> {
     distance: F64Wrapper,
-    store_id: StoreId,
-    remaining_items: Vec<(ProductId, u32)>,
+    store: usize,
+    remaining: K,
 }
 
-// Implement Ord for the custom state
-impl Ord for QueueState {
+impl<K: Eq> Ord for DijkstraEntry<K> {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        // Reverse order so we get a min-heap
         other.distance.cmp(&self.distance)
     }
 }
 
-impl PartialOrd for QueueState {
+impl<K: Eq> PartialOrd for DijkstraEntry<K> {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
         Some(self.cmp(other))
     }
 }
+
+/// A candidate route expanded during the search, as recorded in a `SearchTrace`
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceNode {
+    /// Index of the node that generated this candidate (None for the root)
+    pub parent: Option<usize>,
+
+    /// Sequence of stores in the candidate route
+    pub stores: Vec<StoreId>,
+
+    /// Shopping time of the candidate route
+    pub shopping_time: Time,
+
+    /// Whether the candidate was accepted into the skyline when expanded
+    pub entered_skyline: bool,
+}
+
+/// Record of how the route generation scheme explored the route space
+#[derive(Debug, Clone, Default)]
+pub struct SearchTrace {
+    /// Expanded candidates in the order they were popped from the queue
+    pub nodes: Vec<TraceNode>,
+}
+
+impl SearchTrace {
+    /// The first expanded candidate (the min-time route seeding the search)
+    pub fn root(&self) -> Option<&TraceNode> {
+        self.nodes.first()
+    }
+
+    /// Appends an expanded candidate and returns its index
+    fn push(&mut self, parent: Option<usize>, candidate: &RouteCandidate) -> usize {
+        self.nodes.push(TraceNode {
+            parent,
+            stores: candidate.stores.clone(),
+            shopping_time: candidate.shopping_time,
+            entered_skyline: false,
+        });
+        self.nodes.len() - 1
+    }
+}
+
+/// What `BSLPSD::repair_travel_times` changed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct TravelTimeRepair {
+    /// Missing pairs filled with the time of the reverse direction
+    pub filled_from_reverse: usize,
+
+    /// Missing pairs (in both directions) filled with the straight-line distance
+    pub filled_with_distance: usize,
+
+    /// Pairs whose two directions have different times (counted once per pair)
+    pub asymmetric: usize,
+}
+
+impl TravelTimeRepair {
+    /// Total number of travel times added
+    pub fn filled(&self) -> usize {
+        self.filled_from_reverse + self.filled_with_distance
+    }
+}
+
+/// How the stores cover one product of a shopping list, from `BSLPSD::coverage_report`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProductCoverage {
+    /// Listed product
+    pub product: ProductId,
+
+    /// Quantity on the shopping list
+    pub required: u32,
+
+    /// Quantity all stores together have in stock
+    pub available: u32,
+
+    /// (store, units in stock, unit price) for every store with the product in stock,
+    /// cheapest first, ties by store ID
+    pub stores: Vec<(StoreId, u32, Cost)>,
+}
+
+impl ProductCoverage {
+    /// Quantity the stores can't supply; 0 when the product is covered
+    pub fn shortfall(&self) -> u32 {
+        self.required.saturating_sub(self.available)
+    }
+}
+
+/// Time, cost and distance of a route, from `BSLPSD::route_report`
+#[derive(Debug, Clone, PartialEq)]
+pub struct RouteReport {
+    pub stores: Vec<StoreId>,
+    pub shopping_time: Time,
+    pub shopping_cost: Cost,
+
+    /// Distance travelled, as `BSLPSD::route_distance` measures it
+    pub distance: f64,
+
+    /// Emissions in grams, when an emission factor was given
+    pub emissions: Option<f64>,
+}
+
+/// Cached road-network legs between an endpoint (shopper or customer) and a store
+#[derive(Debug, Default)]
+struct EndpointLegCache {
+    /// Leg time keyed by the endpoint's coordinate bits, the store ID and whether the
+    /// leg leads to the store (always true on undirected graphs, where both agree)
+    legs: HashMap<(u64, u64, StoreId, bool), Time>,
+
+    /// Number of legs computed on the road graph so far
+    computed: usize,
+}
+
+/// Candidate routes a search worker has queued, keyed by their set of stores
+/// Orderings of the same stores share one entry holding the fastest time seen, since
+/// the shopping cost only depends on which stores are visited
+#[derive(Debug, Default)]
+struct VisitedStoreSets {
+    best_times: HashMap<Vec<StoreId>, Time>,
+}
+
+impl VisitedStoreSets {
+    /// Records a candidate; returns true if its store set is new or it is faster than
+    /// every ordering of the set seen so far
+    fn insert(&mut self, candidate: &RouteCandidate) -> bool {
+        let mut store_set = candidate.stores.clone();
+        store_set.sort_unstable();
+        match self.best_times.entry(store_set) {
+            Entry::Vacant(entry) => {
+                entry.insert(candidate.shopping_time);
+                true
+            }
+            Entry::Occupied(mut entry) if candidate.shopping_time < *entry.get() => {
+                entry.insert(candidate.shopping_time);
+                true
+            }
+            Entry::Occupied(_) => false,
+        }
+    }
+}
+
+/// Memoized shopping costs keyed by the sorted store set and the sorted list items
+type CostCache = HashMap<(Vec<StoreId>, Vec<(ProductId, u32)>), Cost>;
+
+/// Inventory taken by one `reserve_inventory` call, so it can be released exactly
+#[derive(Debug, Clone)]
+struct Reservation {
+    route: Vec<StoreId>,
+    items: Vec<(ProductId, u32)>,
+    purchases: Vec<Purchase>,
+}
+
 /// BSL-PSD solver for the Personal Shopper's Dilemma with inventory tracking
 #[derive(Clone)]
 pub struct BSLPSD {
@@ -61,8 +389,57 @@ pub struct BSLPSD {
     /// Precomputed travel times between stores
     travel_times: HashMap<(StoreId, StoreId), Time>,
 
-    /// Inverted list of products to stores (sorted by cost)
+    /// Distance and time between stores, when built by `new_with_edge_costs`
+    edge_costs: HashMap<(StoreId, StoreId), EdgeCost>,
+
+    /// Inverted list of products to stores (sorted by tax-inclusive price)
     product_to_stores: HashMap<ProductId, Vec<(StoreId, Cost)>>,
+
+    /// Keep routes that tie with a skyline route on one objective
+    keep_weak_alternatives: bool,
+
+    /// Prune the skyline by linear rather than conventional domination
+    linear_skyline: bool,
+
+    /// Metric for straight-line legs (endpoint legs without a road graph, missing travel times)
+    distance_metric: DistanceMetric,
+
+    /// Minute of the day the shopper sets off; enables opening-hours checks when set
+    start_minute: Option<f64>,
+
+    /// Wait for closed stores to open instead of rejecting the route
+    wait_for_opening: bool,
+
+    /// Most stores a generated route may visit; unlimited when None
+    max_stores: Option<usize>,
+
+    /// Stores every route sent to the skyline by the parallel search must visit
+    required_stores: HashSet<StoreId>,
+
+    /// Shopper speed turning travel distances into minutes; 1 keeps them as given
+    speed_m_per_min: f64,
+
+    /// Whether the min-time search crosses store pairs without a travel time in a
+    /// straight line, as route times do
+    straight_line_fallback: bool,
+
+    /// Depot the courier returns to after the delivery; routes end at the customer when None
+    return_location: Option<Location>,
+
+    /// Inventory reservations made by `reserve_inventory`, most recent last
+    reservations: Arc<Mutex<Vec<Reservation>>>,
+
+    /// Road network used for the shopper and customer legs, if attached
+    road_graph: Option<Arc<RoadGraph>>,
+
+    /// Whether road legs follow the shortest or the fastest path
+    routing_objective: RoutingObjective,
+
+    /// Endpoint legs already computed on the road network (shared between clones)
+    endpoint_legs: Arc<Mutex<EndpointLegCache>>,
+
+    /// Shopping costs already computed, when memoization is enabled (shared between clones)
+    cost_cache: Option<Arc<Mutex<CostCache>>>,
 }
 
 impl BSLPSD {
@@ -77,7 +454,23 @@ impl BSLPSD {
         Self {
             stores: arc_stores,
             travel_times: HashMap::new(),
+            edge_costs: HashMap::new(),
             product_to_stores: HashMap::new(),
+            keep_weak_alternatives: false,
+            linear_skyline: false,
+            distance_metric: DistanceMetric::default(),
+            start_minute: None,
+            wait_for_opening: false,
+            max_stores: None,
+            required_stores: HashSet::new(),
+            speed_m_per_min: 1.0,
+            straight_line_fallback: true,
+            return_location: None,
+            reservations: Arc::new(Mutex::new(Vec::new())),
+            road_graph: None,
+            routing_objective: RoutingObjective::Distance,
+            endpoint_legs: Arc::new(Mutex::new(EndpointLegCache::default())),
+            cost_cache: None,
         }
     }
     pub fn new_with_travel_times(
@@ -92,1661 +485,6781 @@ impl BSLPSD {
         Self {
             stores: arc_stores,
             travel_times,
+            edge_costs: HashMap::new(),
             product_to_stores: HashMap::new(),
+            keep_weak_alternatives: false,
+            linear_skyline: false,
+            distance_metric: DistanceMetric::default(),
+            start_minute: None,
+            wait_for_opening: false,
+            max_stores: None,
+            required_stores: HashSet::new(),
+            speed_m_per_min: 1.0,
+            straight_line_fallback: true,
+            return_location: None,
+            reservations: Arc::new(Mutex::new(Vec::new())),
+            road_graph: None,
+            routing_objective: RoutingObjective::Distance,
+            endpoint_legs: Arc::new(Mutex::new(EndpointLegCache::default())),
+            cost_cache: None,
         }
     }
 
-    /// Precomputes necessary data structures
-    pub fn precompute_data(&mut self) {
-        self.build_inverted_list();
+    /// Creates a solver from the distance and time of every store pair, optimizing
+    /// the dimension `objective` selects
+    pub fn new_with_edge_costs(
+        stores: HashMap<StoreId, Store>,
+        edge_costs: HashMap<(StoreId, StoreId), EdgeCost>,
+        objective: RoutingObjective,
+    ) -> Self {
+        let travel_times = edge_costs
+            .iter()
+            .map(|(&pair, cost)| (pair, cost.get(objective)))
+            .collect();
+        let mut solver = Self::new_with_travel_times(stores, travel_times);
+        solver.edge_costs = edge_costs;
+        solver.routing_objective = objective;
+        solver
     }
 
-    /// Builds the inverted list of products to stores
-    fn build_inverted_list(&mut self) {
-        // Clear existing data
-        self.product_to_stores.clear();
-
-        // Build the inverted list
-        for (store_id, store_arc) in &self.stores {
-            // Use lock() instead of borrow() for Arc<Mutex<T>>
-            let store = store_arc.read().unwrap();
-            for (product_id, product) in &store.products {
-                // Only include products with available inventory
-                if *store.inventory.get(product_id).unwrap_or(&0) > 0 {
-                    self.product_to_stores
-                        .entry(*product_id)
-                        .or_insert_with(Vec::new)
-                        .push((*store_id, product.cost));
-                }
-            }
-        }
+    /// When set, the skyline only prunes routes that are worse on both time and cost,
+    /// keeping routes that tie on one objective as alternatives
+    pub fn set_keep_weak_alternatives(&mut self, keep: bool) {
+        self.keep_weak_alternatives = keep;
+    }
 
-        // Sort each product's stores by cost (ascending)
-        for stores in self.product_to_stores.values_mut() {
-            stores.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(Ordering::Equal));
-        }
+    /// When set, the solvers keep a linear skyline: besides conventionally dominated
+    /// routes, a route on or above the line joining the neighbouring routes of the
+    /// skyline (in time-cost space) is dropped, so only the lower convex boundary remains.
+    /// Takes precedence over `set_keep_weak_alternatives`.
+    pub fn set_linear_skyline(&mut self, linear: bool) {
+        self.linear_skyline = linear;
     }
 
-    /// Find the route with minimum shopping cost considering inventory
-    pub fn find_min_cost_route(
-        &self,
-        shopping_list: &ShoppingList,
-        shopper_location: Location,
-        customer_location: Location,
-        // ) -> Option<ShoppingRoute> {
-    ) -> Option<f64> {
-        // First verify if the shopping list can be fulfilled by all stores combined
-        let mut total_available: HashMap<ProductId, u32> = HashMap::new();
+    /// When set, routes reaching a closed store wait until it opens instead of being
+    /// rejected (only relevant for `solve_with_start_time`)
+    pub fn set_wait_for_opening(&mut self, wait: bool) {
+        self.wait_for_opening = wait;
+    }
 
-        // Calculate available quantities across all stores
-        for (product_id, qty_needed) in &shopping_list.items {
-            let mut available = 0;
-            if let Some(stores) = self.product_to_stores.get(product_id) {
-                for &(store_id, _) in stores {
-                    let store = self.stores[&store_id].read().unwrap();
-                    available += store.get_inventory_level(product_id);
-                }
-            }
-            total_available.insert(*product_id, available);
+    /// Limits the routes generated by the skyline search (`solve_with_parallel` and its
+    /// variants, and `solve_exact`) to at most `max_stores` stores; `None` lifts the limit
+    /// A list that no route within the limit can fulfill gives an empty skyline.
+    pub fn set_max_stores(&mut self, max_stores: Option<usize>) {
+        self.max_stores = max_stores;
+    }
 
-            // Check if enough quantity is available across all stores
-            if available < *qty_needed {
-                return None; // Cannot fulfill the shopping list
-            }
-        }
+    /// Set the shopper's speed in metres per minute (500 is 30 km/h), so travel distances
+    /// are reported as minutes
+    /// Applies to the travel times between stores and to the shopper, customer and
+    /// return legs, not to store service times. The default of 1 leaves travel times as
+    /// given; keep it when they already are minutes (time-optimal routing).
+    pub fn set_speed(&mut self, speed_m_per_min: f64) {
+        self.speed_m_per_min = speed_m_per_min;
+    }
 
-        // For each product, find the lowest cost stores
-        let mut total_cost = 0.0;
+    /// Whether the min-time search (`find_min_time_route_dijkstra` and the A* variant)
+    /// may travel between stores with no known travel time, timing the leg by the
+    /// straight line as `calculate_shopping_time` and the shuffled generator do
+    /// On by default, so a route is feasible for all of them or for none; when off,
+    /// such pairs are disconnected in the min-time search only.
+    pub fn set_straight_line_fallback(&mut self, fallback: bool) {
+        self.straight_line_fallback = fallback;
+    }
 
-        for (product_id, qty_needed) in &shopping_list.items {
-            let mut remaining_qty = *qty_needed;
-            let mut options = Vec::new();
+    // Travel time between two stores as the min-time search sees it
+    fn search_leg_time(&self, from: StoreId, to: StoreId) -> Option<Time> {
+        if self.straight_line_fallback {
+            Some(self.travel_time_or_euclidean(from, to))
+        } else {
+            self.leg_time(from, to)
+        }
+    }
 
-            // Get all store options for this product
-            if let Some(stores) = self.product_to_stores.get(product_id) {
-                for &(store_id, _) in stores {
-                    let store = self.stores[&store_id].read().unwrap();
-                    let available_qty = store.get_inventory_level(product_id);
+    /// Stores every route of the parallel search (`solve_with_parallel` and its
+    /// variants) must visit, e.g. the customer's favourite store
+    /// Each generated route gets the required stores it misses inserted where they add
+    /// the least time before it is checked against the list, so the stores need not
+    /// sell anything listed. A required store the solver doesn't have gives an empty
+    /// skyline.
+    pub fn set_required_stores(&mut self, required_stores: HashSet<StoreId>) {
+        self.required_stores = required_stores;
+    }
 
-                    if available_qty > 0 {
-                        let cost = store.get_product_cost(product_id).unwrap_or(f64::INFINITY);
-                        options.push((store_id, cost, available_qty));
-                    }
-                }
-            }
+    // The route with the required stores it misses inserted one by one, in ID order,
+    // at the position adding the least time
+    fn with_required_stores(
+        &self,
+        route: &RouteCandidate,
+        shopper_location: &Location,
+        customer_location: &Location,
+    ) -> RouteCandidate {
+        let mut missing: Vec<StoreId> = self
+            .required_stores
+            .iter()
+            .filter(|store_id| !route.stores.contains(store_id))
+            .copied()
+            .collect();
+        missing.sort_unstable();
+
+        let mut completed = route.clone();
+        for store_id in missing {
+            let (shopping_time, position) = (0..=completed.stores.len())
+                .map(|position| {
+                    let mut stores = completed.stores.clone();
+                    stores.insert(position, store_id);
+                    let time =
+                        self.calculate_total_time(&stores, shopper_location, customer_location);
+                    (time, position)
+                })
+                .min_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)))
+                .expect("a route has at least one insertion position");
+            completed.stores.insert(position, store_id);
+            completed.shopping_time = shopping_time;
+        }
+        completed
+    }
 
-            // Sort options by cost (lowest first)
-            options.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal));
+    // Whether a route visits no more stores than `set_max_stores` allows
+    fn within_store_limit(&self, route: &[StoreId]) -> bool {
+        self.max_stores
+            .is_none_or(|max_stores| route.len() <= max_stores)
+    }
 
-            // Allocate products to lowest cost stores first
-            for &(_, cost, available_qty) in &options {
-                let purchase_qty = std::cmp::min(available_qty, remaining_qty);
-                if purchase_qty > 0 {
-                    total_cost += cost * purchase_qty as f64;
-                    remaining_qty -= purchase_qty;
-                }
+    /// Elapsed time on leaving a store reached at `elapsed`: any wait for it to open
+    /// plus its service time
+    /// Returns None when the store is closed on arrival and waiting is disabled
+    fn visit_store(&self, store: &Store, elapsed: Time) -> Option<Time> {
+        let Some(start_minute) = self.start_minute else {
+            return Some(elapsed + store.service_time);
+        };
+        let minute = start_minute + elapsed;
 
-                if remaining_qty == 0 {
-                    break;
-                }
-            }
+        if store.is_open_at(minute) {
+            Some(elapsed + store.service_time)
+        } else if self.wait_for_opening {
+            Some(elapsed + store.minutes_until_open(minute) + store.service_time)
+        } else {
+            None
         }
+    }
 
-        Some(total_cost)
+    // Time from the customer back to the depot, or 0 when routes end at the customer
+    // The leg is the same for every route, so it never changes which route is fastest;
+    // it is measured like `route_distance` measures it, on the road graph if attached
+    fn return_leg(&self, customer_location: &Location) -> Time {
+        self.return_location.map_or(0.0, |depot| {
+            self.endpoint_distance(customer_location, &depot) / self.speed_m_per_min
+        })
     }
-    /// Find the route with minimum shopping time using Dijkstra algorithm
-    /// Allows purchasing products across multiple stores
-    pub fn find_min_time_route_dijkstra(
-        &self,
-        shopping_list: &ShoppingList,
-        shopper_location: Location,
-        customer_location: Location,
-    ) -> Option<ShoppingRoute> {
-        let shopping_items: Vec<(ProductId, u32)> =
-            shopping_list.items.iter().map(|(k, v)| (*k, *v)).collect();
 
-        let mut store_products: HashMap<StoreId, HashMap<ProductId, u32>> = HashMap::new();
-        let mut candidate_stores: HashSet<StoreId> = HashSet::new();
-
-        for &(product_id, _) in &shopping_items {
-            for (store_id, store_rc) in &self.stores {
-                let store = store_rc.read().unwrap();
-                if store.has_product(&product_id) {
-                    let inventory_level = store.get_inventory_level(&product_id);
-                    if inventory_level > 0 {
-                        store_products
-                            .entry(*store_id)
-                            .or_insert_with(HashMap::new)
-                            .insert(product_id, inventory_level);
-                        candidate_stores.insert(*store_id);
-                    }
-                }
-            }
-        }
+    fn service_time(&self, store_id: StoreId) -> Time {
+        self.stores[&store_id].read().unwrap().service_time
+    }
 
-        if candidate_stores.is_empty() {
-            return None;
-        }
+    /// Selects how straight-line legs are measured; should match the metric the
+    /// travel times were computed with (see `RoadOptions`)
+    pub fn set_distance_metric(&mut self, metric: DistanceMetric) {
+        self.distance_metric = metric;
+        self.clear_endpoint_legs();
+    }
 
-        let mut can_be_fulfilled = true;
-        for &(product_id, qty_needed) in &shopping_items {
-            let mut available = 0;
-            for &store_id in &candidate_stores {
-                if let Some(store_inventory) = store_products.get(&store_id) {
-                    if let Some(&quantity) = store_inventory.get(&product_id) {
-                        available += quantity;
-                    }
-                }
-            }
+    /// Measures shopper and customer legs on the road network instead of in a straight
+    /// line. Legs use the same scale as `compute_travel_times` and are cached per
+    /// (location, store) so repeated solves from the same endpoints reuse them.
+    pub fn set_road_graph(&mut self, road_graph: Arc<RoadGraph>) {
+        self.road_graph = Some(road_graph);
+        self.clear_endpoint_legs();
+    }
 
-            if available < qty_needed {
-                can_be_fulfilled = false;
-                break;
-            }
-        }
+    /// Switches road legs between distance-optimal and time-optimal routing; should match
+    /// the objective the travel times were computed with (see `RoadOptions`)
+    pub fn set_routing_objective(&mut self, objective: RoutingObjective) {
+        self.routing_objective = objective;
+        self.clear_endpoint_legs();
+    }
 
-        if !can_be_fulfilled {
-            return None;
-        }
+    /// Drops all cached endpoint legs, e.g. after store locations were edited
+    pub fn clear_endpoint_legs(&self) {
+        let mut cache = self.endpoint_legs.lock().unwrap();
+        cache.legs.clear();
+        cache.computed = 0;
+    }
 
-        let mut distances: HashMap<(StoreId, Vec<(ProductId, u32)>), f64> = HashMap::new();
-        let mut predecessors: HashMap<
-            (StoreId, Vec<(ProductId, u32)>),
-            Option<(StoreId, Vec<(ProductId, u32)>)>,
-        > = HashMap::new();
+    /// Memoizes shopping costs per store set and shopping list
+    ///
+    /// Costs depend on inventory, so this is only safe while inventory stays fixed
+    /// during a solve. `reserve_inventory`, `release_inventory` and `restore_inventory`
+    /// clear the cache; call `clear_cost_cache` after editing stores directly.
+    pub fn set_cost_memoization(&mut self, enabled: bool) {
+        self.cost_cache = enabled.then(|| Arc::new(Mutex::new(CostCache::new())));
+    }
 
-        #[derive(Eq, PartialEq)]
-        struct QueueState {
-            distance: F64Wrapper,
-            store_id: StoreId,
-            remaining_items: Vec<(ProductId, u32)>,
+    /// Drops all memoized shopping costs
+    pub fn clear_cost_cache(&self) {
+        if let Some(cache) = &self.cost_cache {
+            cache.lock().unwrap().clear();
         }
+    }
 
-        impl Ord for QueueState {
-            fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-                other.distance.cmp(&self.distance)
-            }
-        }
+    /// Number of endpoint legs computed on the road network since the cache was cleared
+    pub fn endpoint_legs_computed(&self) -> usize {
+        self.endpoint_legs.lock().unwrap().computed
+    }
 
-        impl PartialOrd for QueueState {
-            fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-                Some(self.cmp(other))
-            }
-        }
+    /// Travel time from an endpoint (the shopper) to a store
+    fn endpoint_leg(&self, location: &Location, store: &Store) -> Time {
+        self.road_leg(location, store, true)
+    }
 
-        let mut priority_queue = BinaryHeap::new();
-        let mut visited = HashSet::new();
+    /// Travel time from a store to the customer
+    /// Differs from `endpoint_leg` only on road graphs with one-way streets
+    fn customer_leg(&self, store: &Store, customer_location: &Location) -> Time {
+        self.road_leg(customer_location, store, false)
+    }
 
-        for &store_id in &candidate_stores {
-            let store = self.stores[&store_id].read().unwrap();
+    fn road_leg(&self, location: &Location, store: &Store, to_store: bool) -> Time {
+        self.road_leg_length(location, store, to_store) / self.speed_m_per_min
+    }
 
-            let distance = shopper_location.distance_to(&store.location);
+    // Leg as measured on the road graph (or straight), before the shopper speed applies;
+    // this is what the endpoint cache holds
+    fn road_leg_length(&self, location: &Location, store: &Store, to_store: bool) -> f64 {
+        let Some(road_graph) = &self.road_graph else {
+            return self.distance_metric.distance(location, &store.location);
+        };
 
-            let mut remaining_items = shopping_items.clone();
-            let mut any_purchase = false;
+        let to_store = to_store || !road_graph.is_directed();
+        let key = (
+            location.x.to_bits(),
+            location.y.to_bits(),
+            store.id,
+            to_store,
+        );
+        if let Some(&time) = self.endpoint_legs.lock().unwrap().legs.get(&key) {
+            return time;
+        }
 
-            for i in 0..remaining_items.len() {
-                let (product_id, qty_needed) = &mut remaining_items[i];
-                if *qty_needed > 0 {
-                    if let Some(store_inventory) = store_products.get(&store_id) {
-                        if let Some(&available_qty) = store_inventory.get(product_id) {
-                            let purchase_qty = std::cmp::min(available_qty, *qty_needed);
-                            if purchase_qty > 0 {
-                                *qty_needed -= purchase_qty;
-                                any_purchase = true;
-                            }
-                        }
-                    }
+        // Compute outside the lock so parallel searches don't serialize on Dijkstra
+        let (from, to) = if to_store {
+            (location, &store.location)
+        } else {
+            (&store.location, location)
+        };
+        let time = road_graph
+            .travel_cost(from, to, self.routing_objective)
+            .unwrap_or_else(|| match self.routing_objective {
+                RoutingObjective::Distance => self.straight_line_distance(from, to),
+                RoutingObjective::Time => {
+                    road_graph.straight_line_cost(from, to, RoutingObjective::Time)
                 }
-            }
-
-            if any_purchase {
-                distances.insert((store_id, remaining_items.clone()), distance);
-                predecessors.insert((store_id, remaining_items.clone()), None);
+            });
 
-                priority_queue.push(QueueState {
-                    distance: F64Wrapper(distance),
-                    store_id,
-                    remaining_items,
-                });
-            }
+        let mut cache = self.endpoint_legs.lock().unwrap();
+        if cache.legs.insert(key, time).is_none() {
+            cache.computed += 1;
         }
+        time
+    }
 
-        let mut best_time = f64::INFINITY;
-        let mut best_state = None;
-
-        while let Some(QueueState {
-            distance,
-            store_id: current_store,
-            remaining_items: current_remaining,
-        }) = priority_queue.pop()
-        {
-            let current_dist = distance.0;
-
-            let state = (current_store, current_remaining.clone());
-            if visited.contains(&state) {
-                continue;
-            }
-
-            visited.insert(state);
+    /// Precomputes necessary data structures
+    pub fn precompute_data(&mut self) {
+        // Stores may have changed since the endpoint legs were cached
+        self.clear_endpoint_legs();
+        self.build_inverted_list();
+    }
 
-            let all_purchased = current_remaining.iter().all(|(_, qty)| *qty == 0);
+    /// Builds the inverted list of products to stores
+    /// Stores are read in parallel, each contributing the products it has in stock
+    fn build_inverted_list(&mut self) {
+        let mut product_to_stores = self
+            .stores
+            .par_iter()
+            .fold(
+                HashMap::new,
+                |mut lists: HashMap<ProductId, Vec<(StoreId, Cost)>>, (store_id, store_arc)| {
+                    let store = store_arc.read().unwrap();
+                    for (product_id, product) in &store.products {
+                        // Only include products with available inventory
+                        if store.can_supply(product_id, 1) {
+                            lists
+                                .entry(*product_id)
+                                .or_default()
+                                .push((*store_id, product.unit_price()));
+                        }
+                    }
+                    lists
+                },
+            )
+            .reduce(HashMap::new, |mut lists, other| {
+                for (product_id, stores) in other {
+                    lists.entry(product_id).or_default().extend(stores);
+                }
+                lists
+            });
 
-            if all_purchased {
-                let store = self.stores[&current_store].read().unwrap();
-                let final_distance = current_dist + store.location.distance_to(&customer_location);
+        product_to_stores
+            .par_iter_mut()
+            .for_each(|(_, stores)| sort_by_price(stores));
+        self.product_to_stores = product_to_stores;
+    }
 
-                // If this route is faster, update the best result
-                if final_distance < best_time {
-                    best_time = final_distance;
-                    best_state = Some((current_store, current_remaining.clone()));
+    // Single-threaded `build_inverted_list`, kept to check the parallel build against
+    #[cfg(test)]
+    fn build_inverted_list_sequential(&self) -> HashMap<ProductId, Vec<(StoreId, Cost)>> {
+        let mut product_to_stores: HashMap<ProductId, Vec<(StoreId, Cost)>> = HashMap::new();
+        for (store_id, store_arc) in &self.stores {
+            let store = store_arc.read().unwrap();
+            for (product_id, product) in &store.products {
+                if store.can_supply(product_id, 1) {
+                    product_to_stores
+                        .entry(*product_id)
+                        .or_default()
+                        .push((*store_id, product.unit_price()));
                 }
-
-                continue;
             }
+        }
+        for stores in product_to_stores.values_mut() {
+            sort_by_price(stores);
+        }
+        product_to_stores
+    }
 
-            if current_dist >= best_time {
-                continue;
+    /// Sets a store's stock of a product, e.g. after a restock or a sell-out
+    ///
+    /// The product's inverted list is rebuilt straight away, so the next solve sees the
+    /// store drop out or come back; travel times are unaffected. Returns false, changing
+    /// nothing, if the store is unknown or doesn't sell the product.
+    pub fn set_inventory(
+        &mut self,
+        store_id: StoreId,
+        product_id: ProductId,
+        quantity: u32,
+    ) -> bool {
+        let Some(store_arc) = self.stores.get(&store_id) else {
+            return false;
+        };
+        {
+            let mut store = store_arc.write().unwrap();
+            if !store.has_product(&product_id) {
+                return false;
             }
+            store.inventory.insert(product_id, quantity);
+        }
+        self.rebuild_inverted_list_for(product_id);
+        self.clear_cost_cache();
+        true
+    }
 
-            for &next_store in &candidate_stores {
-                if next_store == current_store {
-                    continue;
-                }
-
-                let edge_weight = self
-                    .travel_times
-                    .get(&(current_store, next_store))
-                    .cloned()
-                    .unwrap_or(f64::INFINITY);
-
-                if edge_weight.is_infinite() {
-                    continue;
-                }
-
-                let next_dist = current_dist + edge_weight;
-
-                if next_dist >= best_time {
-                    continue;
-                }
-
-                let mut new_remaining = current_remaining.clone();
-                let mut any_new_purchases = false;
+    /// Sets the pre-tax cost of a product at a store, e.g. for a discount or surge pricing
+    ///
+    /// Like `set_inventory`, this rebuilds the product's inverted list straight away, as
+    /// its stores may now be in a different price order. Returns false, changing nothing,
+    /// if the store is unknown or doesn't sell the product.
+    pub fn reprice(&mut self, store_id: StoreId, product_id: ProductId, cost: Cost) -> bool {
+        let Some(store_arc) = self.stores.get(&store_id) else {
+            return false;
+        };
+        if !store_arc
+            .write()
+            .unwrap()
+            .set_product_cost(product_id, cost)
+        {
+            return false;
+        }
+        self.rebuild_inverted_list_for(product_id);
+        self.clear_cost_cache();
+        true
+    }
 
-                if !store_products.contains_key(&next_store) {
-                    continue;
-                }
+    /// Rebuilds the inverted list of one product from current stock, leaving the other
+    /// products' lists as they are
+    pub fn rebuild_inverted_list_for(&mut self, product_id: ProductId) {
+        let mut stores: Vec<(StoreId, Cost)> = self
+            .stores
+            .iter()
+            .filter_map(|(store_id, store_arc)| {
+                let store = store_arc.read().unwrap();
+                store
+                    .can_supply(&product_id, 1)
+                    .then(|| (*store_id, store.products[&product_id].unit_price()))
+            })
+            .collect();
+        sort_by_price(&mut stores);
 
-                for i in 0..new_remaining.len() {
-                    let (product_id, qty_needed) = &mut new_remaining[i];
-                    if *qty_needed > 0 {
-                        if let Some(store_inventory) = store_products.get(&next_store) {
-                            if let Some(&available_qty) = store_inventory.get(product_id) {
-                                let purchase_qty = std::cmp::min(available_qty, *qty_needed);
-                                if purchase_qty > 0 {
-                                    *qty_needed -= purchase_qty;
-                                    any_new_purchases = true;
-                                }
-                            }
-                        }
-                    }
-                }
+        if stores.is_empty() {
+            self.product_to_stores.remove(&product_id);
+        } else {
+            self.product_to_stores.insert(product_id, stores);
+        }
+    }
 
-                if !any_new_purchases {
-                    continue;
-                }
+    /// Stores whose catalog lists the product, whatever their current stock, sorted by ID
+    /// Unlike the inverted list used for solving, sold-out stores are included
+    pub fn stores_stocking(&self, product_id: ProductId) -> Vec<StoreId> {
+        let mut stores: Vec<StoreId> = self
+            .stores
+            .iter()
+            .filter(|(_, store_arc)| store_arc.read().unwrap().has_product(&product_id))
+            .map(|(store_id, _)| *store_id)
+            .collect();
+        stores.sort();
+        stores
+    }
 
-                let next_state = (next_store, new_remaining.clone());
-                if !distances.contains_key(&next_state) || next_dist < distances[&next_state] {
-                    distances.insert(next_state.clone(), next_dist);
-                    predecessors.insert(
-                        next_state.clone(),
-                        Some((current_store, current_remaining.clone())),
-                    );
+    /// Stores within `radius` of `center`, sorted by ID
+    /// The radius is measured with the solver's distance metric and in its units
+    pub fn stores_within(&self, center: Location, radius: f64) -> Vec<StoreId> {
+        let mut stores: Vec<StoreId> = self
+            .stores
+            .iter()
+            .filter(|(_, store_arc)| {
+                let location = store_arc.read().unwrap().location;
+                self.distance_metric
+                    .within_radius(&location, &center, radius)
+            })
+            .map(|(store_id, _)| *store_id)
+            .collect();
+        stores.sort();
+        stores
+    }
 
-                    priority_queue.push(QueueState {
-                        distance: F64Wrapper(next_dist),
-                        store_id: next_store,
-                        remaining_items: new_remaining,
-                    });
-                }
+    /// Every product sold by some store, with its name and the total supply across stores
+    pub fn available_products(&self) -> HashMap<ProductId, (String, u32)> {
+        let mut available_products = HashMap::new();
+        for store in self.stores.values() {
+            let store = store.read().unwrap();
+            for (product_id, product) in &store.products {
+                let entry = available_products
+                    .entry(*product_id)
+                    .or_insert((product.name.clone(), 0));
+                entry.1 += store.get_inventory_level(product_id);
             }
         }
+        available_products
+    }
 
-        if best_state.is_none() {
-            return None;
-        }
-
-        let mut current_state = best_state.unwrap();
-        let mut path = Vec::new();
+    /// Find the route with minimum shopping cost considering inventory
+    pub fn find_min_cost_route(
+        &self,
+        shopping_list: &ShoppingList,
+        _shopper_location: Location,
+        _customer_location: Location,
+        // ) -> Option<ShoppingRoute> {
+    ) -> Option<f64> {
+        self.min_cost_allocation(shopping_list)
+            .map(|(total_cost, _)| total_cost)
+    }
 
-        while let Some(prev_state) = predecessors[&current_state].clone() {
-            path.push(current_state.0);
-            current_state = prev_state;
+    /// Find the cheapest route: the stores chosen by cheapest-first allocation,
+    /// visited in the order that minimizes shopping time
+    /// None if the list can't be fulfilled or no order of those stores is feasible, e.g.
+    /// when one is closed on arrival.
+    pub fn find_min_cost_route_full(
+        &self,
+        shopping_list: &ShoppingList,
+        shopper_location: Location,
+        customer_location: Location,
+    ) -> Option<ShoppingRoute> {
+        if shopping_list.is_empty() {
+            return Some(self.direct_route(&shopper_location, &customer_location));
         }
-
-        path.push(current_state.0);
-        path.reverse();
-
-        let shopping_cost = self.calculate_shopping_cost(&path, shopping_list);
+        let (shopping_cost, stores) = self.min_cost_allocation(shopping_list)?;
+        let path = self.find_shortest_path(&stores, &shopper_location, &customer_location);
+        if path.is_empty() {
+            return None;
+        }
+        let shopping_time = self.calculate_total_time(&path, &shopper_location, &customer_location);
 
         Some(ShoppingRoute {
             stores: path,
-            shopping_time: best_time,
+            shopping_time,
             shopping_cost,
         })
     }
 
-    /// Check if the route can fulfill the shopping list
-    fn can_fulfill_shopping_list(&self, route: &[StoreId], shopping_list: &ShoppingList) -> bool {
-        let mut remaining_quantities = shopping_list.items.clone();
-
-        for &store_id in route {
-            let store = self.stores[&store_id].read().unwrap();
-
-            for (product_id, remaining_qty) in remaining_quantities.iter_mut() {
-                if *remaining_qty > 0 && store.has_product(product_id) {
-                    let available_qty = store.get_inventory_level(product_id);
-                    let purchase_qty = std::cmp::min(available_qty, *remaining_qty);
-                    *remaining_qty -= purchase_qty;
-                }
-            }
+    /// The one-stop route: the single store whose stock alone covers the list at the
+    /// lowest cost, the quicker one on a tie (then the lower ID)
+    /// A baseline to compare multi-store savings against. None if no single store
+    /// can fulfill the list.
+    pub fn best_single_store(
+        &self,
+        shopping_list: &ShoppingList,
+        shopper_location: Location,
+        customer_location: Location,
+    ) -> Option<ShoppingRoute> {
+        if shopping_list.is_empty() {
+            return Some(self.direct_route(&shopper_location, &customer_location));
         }
-
-        // Check if all items are fulfilled
-        remaining_quantities.values().all(|&qty| qty == 0)
+        let mut store_ids: Vec<StoreId> = self.stores.keys().copied().collect();
+        store_ids.sort_unstable();
+        store_ids
+            .into_iter()
+            .filter(|&store_id| self.satisfies_list_with_inventory(&[store_id], shopping_list))
+            .map(|store_id| ShoppingRoute {
+                stores: vec![store_id],
+                shopping_time: self.calculate_total_time(
+                    &[store_id],
+                    &shopper_location,
+                    &customer_location,
+                ),
+                shopping_cost: self.calculate_shopping_cost(&[store_id], shopping_list),
+            })
+            .min_by(|a, b| {
+                a.shopping_cost
+                    .total_cmp(&b.shopping_cost)
+                    .then(a.shopping_time.total_cmp(&b.shopping_time))
+            })
     }
 
-    /// Checks if a route satisfies a shopping list considering inventory
-    pub fn satisfies_list_with_inventory(
+    /// Exhaustive, deterministic skyline for regression testing
+    /// Every ordering of candidate stores is considered, including stores that only
+    /// serve as a shortcut, and the complete set of non-dominated routes is returned
+    /// sorted by time, then cost, then store IDs. Among routes with equal time and cost,
+    /// the one whose stores compare lowest is kept. Paths are only cut once even the
+    /// quickest way on to the customer is slower than a cheapest route, so the result
+    /// doesn't rely on travel times obeying the triangle inequality.
+    /// Exponential in the number of candidate stores; meant for small instances.
+    pub fn solve_exact(
         &self,
-        route: &[StoreId],
         shopping_list: &ShoppingList,
-    ) -> bool {
-        // Create a map to track the total available inventory in the route's stores
-        let mut available_inventory: HashMap<ProductId, u32> = HashMap::new();
+        shopper_location: Location,
+        customer_location: Location,
+    ) -> Skyline {
+        if shopping_list.is_empty() {
+            return self.direct_skyline(&shopper_location, &customer_location);
+        }
+        let Some(cheapest) =
+            self.find_min_cost_route_full(shopping_list, shopper_location, customer_location)
+        else {
+            return self.new_skyline();
+        };
 
-        // Collect available inventory from stores in the route
-        for &store_id in route {
-            let store = self.stores[&store_id].read().unwrap();
-            for (product_id, _) in &shopping_list.items {
-                if store.has_product(product_id) {
-                    let inventory = store.get_inventory_level(product_id);
-                    *available_inventory.entry(*product_id).or_insert(0) += inventory;
+        let mut candidates: Vec<StoreId> = self
+            .collect_candidate_stores(shopping_list)
+            .into_keys()
+            .collect();
+        candidates.sort_unstable();
+
+        let count = candidates.len();
+        let mut from_shopper = Vec::with_capacity(count);
+        let mut to_customer = Vec::with_capacity(count);
+        for store_id in &candidates {
+            let store = self.stores[store_id].read().unwrap();
+            from_shopper.push(self.endpoint_leg(&shopper_location, &store));
+            to_customer.push(
+                self.customer_leg(&store, &customer_location) + self.return_leg(&customer_location),
+            );
+        }
+        let legs: Vec<Vec<Time>> = candidates
+            .iter()
+            .map(|&from| {
+                candidates
+                    .iter()
+                    .map(|&to| {
+                        if from == to {
+                            0.0
+                        } else {
+                            self.travel_time_or_euclidean(from, to)
+                        }
+                    })
+                    .collect()
+            })
+            .collect();
+
+        // Shortest detours between candidates (Floyd-Warshall), then the quickest way
+        // from each of them to the customer through any others
+        let mut closure = legs.clone();
+        for via in 0..count {
+            for from in 0..count {
+                for to in 0..count {
+                    let detour = closure[from][via] + closure[via][to];
+                    if detour < closure[from][to] {
+                        closure[from][to] = detour;
+                    }
                 }
             }
         }
+        let finish_floor = (0..count)
+            .map(|from| {
+                (0..count)
+                    .map(|last| closure[from][last] + to_customer[last])
+                    .fold(f64::INFINITY, f64::min)
+            })
+            .collect();
 
-        // Check if all products have sufficient inventory
-        for (product_id, required_qty) in &shopping_list.items {
-            if let Some(available_qty) = available_inventory.get(product_id) {
-                if available_qty < required_qty {
-                    return false;
-                }
+        let mut search = ExactSearch {
+            shopping_list,
+            candidates,
+            from_shopper,
+            legs,
+            to_customer,
+            finish_floor,
+            min_cost: cheapest.shopping_cost,
+            // A cheapest route over the store limit can't bound the routes within it
+            time_bound: if self.within_store_limit(&cheapest.stores) {
+                cheapest.shopping_time
             } else {
-                return false;
+                f64::INFINITY
+            },
+            path: Vec::new(),
+            visited: vec![false; count],
+            routes: Vec::new(),
+        };
+        self.exact_search(&mut search, 0.0);
+
+        // Sorted by time, a route is non-dominated iff it is cheaper than all before it
+        let mut routes = search.routes;
+        routes.sort_by(|a, b| {
+            a.shopping_time
+                .total_cmp(&b.shopping_time)
+                .then(a.shopping_cost.total_cmp(&b.shopping_cost))
+                .then_with(|| a.stores.cmp(&b.stores))
+        });
+        let mut skyline = Skyline::new();
+        for route in routes {
+            if skyline
+                .last()
+                .is_none_or(|last| route.shopping_cost < last.shopping_cost)
+            {
+                skyline.insert(route);
             }
         }
-
-        true
+        skyline
     }
 
-    /// Find the minimum detour store to add to a route
-    /// This corresponds to the MinDetour function in the paper
-    fn find_min_detour_store(
-        &self,
-        from_store: Option<StoreId>,
-        visited_stores: &HashSet<StoreId>,
-    ) -> Option<StoreId> {
-        let mut min_detour = f64::INFINITY;
-        let mut min_detour_store = None;
-
-        for (store_id, _) in &self.stores {
-            // Skip if already visited
-            if visited_stores.contains(store_id) {
+    // Depth-first extension of `search.path`, which reaches its last store at `elapsed`
+    fn exact_search(&self, search: &mut ExactSearch, elapsed: Time) {
+        if self
+            .max_stores
+            .is_some_and(|max_stores| search.path.len() >= max_stores)
+        {
+            return;
+        }
+        for next in 0..search.candidates.len() {
+            if search.visited[next] {
                 continue;
             }
 
-            // Calculate detour
-            let detour = match from_store {
-                Some(from) => self
-                    .travel_times
-                    .get(&(from, *store_id))
-                    .cloned()
-                    .unwrap_or(f64::INFINITY),
-                None => 0.0, // First store in route
+            let travel = match search.path.last() {
+                Some(&last) => search.legs[last][next],
+                None => search.from_shopper[next],
+            };
+            let store = self.stores[&search.candidates[next]].read().unwrap();
+            let Some(arrival) = self.visit_store(&store, elapsed + travel) else {
+                continue;
             };
+            drop(store);
 
-            if detour < min_detour {
-                min_detour = detour;
-                min_detour_store = Some(*store_id);
+            // Every route through here is slower than a cheapest route, so dominated by it
+            if arrival + search.finish_floor[next] > search.time_bound {
+                continue;
             }
-        }
 
-        min_detour_store
-    }
-
-    /// Find the next minimum detour store after the kth one
-    /// This corresponds to the NextMinDetour function in the paper
-    fn find_next_min_detour_store(
-        &self,
-        from_store: Option<StoreId>,
-        visited_stores: &HashSet<StoreId>,
-        current_min_detour_store: StoreId,
-    ) -> Option<StoreId> {
-        let mut next_min_detour = f64::INFINITY;
-        let mut next_min_detour_store = None;
+            search.path.push(next);
+            search.visited[next] = true;
+
+            let stores: Vec<StoreId> = search
+                .path
+                .iter()
+                .map(|&index| search.candidates[index])
+                .collect();
+            let cost = self.exact_allocation(&stores, search.shopping_list);
+            let time = arrival + search.to_customer[next];
+            if cost.is_finite() && time <= search.time_bound {
+                if cost <= search.min_cost {
+                    search.time_bound = time;
+                }
+                search.routes.push(ShoppingRoute {
+                    stores,
+                    shopping_time: time,
+                    shopping_cost: cost,
+                });
+            }
 
-        // Get the detour of the current minimum
-        let current_detour = match from_store {
-            Some(from) => self
-                .travel_times
-                .get(&(from, current_min_detour_store))
-                .cloned()
-                .unwrap_or(f64::INFINITY),
-            None => 0.0, // First store in route
-        };
+            self.exact_search(search, arrival);
 
-        for (store_id, _) in &self.stores {
-            // Skip if already visited or if it's the current minimum
-            if visited_stores.contains(store_id) || *store_id == current_min_detour_store {
-                continue;
-            }
+            search.visited[next] = false;
+            search.path.pop();
+        }
+    }
 
-            // Calculate detour
-            let detour = match from_store {
-                Some(from) => self
-                    .travel_times
-                    .get(&(from, *store_id))
-                    .cloned()
-                    .unwrap_or(f64::INFINITY),
-                None => 0.0, // First store in route
-            };
+    // Cheapest-first allocation over `stores` (ties by store ID) for `solve_exact`
+    // Infinite when the list can't be filled from them
+    fn exact_allocation(&self, stores: &[StoreId], shopping_list: &ShoppingList) -> Cost {
+        let mut total_cost = 0.0;
 
-            // Find the next minimum (greater than current, but less than any other)
-            if detour > current_detour && detour < next_min_detour {
-                next_min_detour = detour;
-                next_min_detour_store = Some(*store_id);
+        for (product_id, &qty_needed) in &shopping_list.items {
+            let mut options: Vec<(Cost, StoreId, u32)> = Vec::new();
+            for store_id in stores {
+                let store = self.stores[store_id].read().unwrap();
+                if store.can_supply(product_id, 1) {
+                    let cost = store.get_unit_price(product_id).unwrap_or(f64::INFINITY);
+                    options.push((cost, *store_id, store.get_inventory_level(product_id)));
+                }
+            }
+            options.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+
+            let mut remaining = qty_needed;
+            for (cost, _, available) in options {
+                let bought = available.min(remaining);
+                total_cost += cost * bought as f64;
+                remaining = remaining.saturating_sub(bought);
+                if remaining == 0 {
+                    break;
+                }
+            }
+            if remaining > 0 {
+                return f64::INFINITY;
             }
         }
 
-        next_min_detour_store
+        total_cost
     }
-    /// Generate next routes according to the generation scheme in the paper
-    fn generate_next_routes(&self, route: &RouteCandidate) -> Vec<RouteCandidate> {
-        let mut next_routes = Vec::new();
-        let mut visited_stores: HashSet<StoreId> = route.stores.iter().cloned().collect();
 
-        // Case 1: Add a new store at the end (θs in the paper)
-        if let Some(last_store) = route.stores.last().cloned() {
-            if let Some(min_detour_store) =
-                self.find_min_detour_store(Some(last_store), &visited_stores)
-            {
-                let mut new_route = route.stores.clone();
-                new_route.push(min_detour_store);
+    /// Allocates every listed product to its lowest-cost stores
+    /// Returns the total cost and the stores that received a purchase (sorted by ID)
+    fn min_cost_allocation(&self, shopping_list: &ShoppingList) -> Option<(Cost, Vec<StoreId>)> {
+        // First verify if the shopping list can be fulfilled by all stores combined
+        let mut total_available: HashMap<ProductId, u32> = HashMap::new();
 
-                // Calculate new shopping time
-                let detour = self
-                    .travel_times
-                    .get(&(last_store, min_detour_store))
-                    .cloned()
-                    .unwrap_or(f64::INFINITY);
+        // Calculate available quantities across all stores
+        for (product_id, qty_needed) in &shopping_list.items {
+            let mut available = 0;
+            if let Some(stores) = self.product_to_stores.get(product_id) {
+                for &(store_id, _) in stores {
+                    let store = self.stores[&store_id].read().unwrap();
+                    available += store.get_inventory_level(product_id);
+                }
+            }
+            total_available.insert(*product_id, available);
+
+            // Check if enough quantity is available across all stores
+            if available < *qty_needed {
+                return None; // Cannot fulfill the shopping list
+            }
+        }
 
-                let new_time = route.shopping_time + detour;
+        // For each product, find the lowest cost stores
+        let mut total_cost = 0.0;
+        let mut used_stores = Vec::new();
 
-                next_routes.push(RouteCandidate {
-                    stores: new_route,
-                    shopping_time: new_time,
-                });
+        for (product_id, qty_needed) in &shopping_list.items {
+            let mut remaining_qty = *qty_needed;
+            let mut options = Vec::new();
+
+            // Get all store options for this product
+            if let Some(stores) = self.product_to_stores.get(product_id) {
+                for &(store_id, _) in stores {
+                    let store = self.stores[&store_id].read().unwrap();
+                    let available_qty = store.get_inventory_level(product_id);
+
+                    if available_qty > 0 {
+                        let cost = store.get_unit_price(product_id).unwrap_or(f64::INFINITY);
+                        options.push((store_id, cost, available_qty));
+                    }
+                }
+            }
+
+            // Sort options by cost (lowest first), then store ID so ties don't depend on
+            // the inverted list's order
+            options.sort_by(|a, b| a.1.total_cmp(&b.1).then(a.0.cmp(&b.0)));
+
+            // Allocate products to lowest cost stores first
+            for &(store_id, cost, available_qty) in &options {
+                let purchase_qty = std::cmp::min(available_qty, remaining_qty);
+                if purchase_qty > 0 {
+                    total_cost += cost * purchase_qty as f64;
+                    remaining_qty = remaining_qty.saturating_sub(purchase_qty);
+                    used_stores.push(store_id);
+                }
+
+                if remaining_qty == 0 {
+                    break;
+                }
+            }
+        }
+
+        used_stores.sort();
+        used_stores.dedup();
+
+        Some((total_cost, used_stores))
+    }
+    /// Collect the stores that can supply at least one unit of a listed product,
+    /// together with their stock level for each such product
+    fn collect_candidate_stores(
+        &self,
+        shopping_list: &ShoppingList,
+    ) -> HashMap<StoreId, HashMap<ProductId, u32>> {
+        let mut store_products: HashMap<StoreId, HashMap<ProductId, u32>> = HashMap::new();
+
+        for (store_id, store_rc) in &self.stores {
+            let store = store_rc.read().unwrap();
+            for product_id in shopping_list.items.keys() {
+                if store.can_supply(product_id, 1) {
+                    store_products
+                        .entry(*store_id)
+                        .or_default()
+                        .insert(*product_id, store.get_inventory_level(product_id));
+                }
+            }
+        }
+
+        store_products
+    }
+
+    /// A solver over only the `count` stores nearest to the shopper or the customer
+    /// among those selling a listed product
+    ///
+    /// Stores are ranked by their distance to the closer endpoint. If the nearest
+    /// `count` can't cover the list, the next nearest are added until they can (or
+    /// every candidate is in). The result is approximate: the skyline of the returned
+    /// solver misses every route through a store left out. Stores and their inventory
+    /// are shared with this solver.
+    pub fn with_nearest_stores(
+        &self,
+        shopping_list: &ShoppingList,
+        shopper_location: &Location,
+        customer_location: &Location,
+        count: usize,
+    ) -> BSLPSD {
+        self.with_store_subset(&self.nearest_store_ids(
+            shopping_list,
+            shopper_location,
+            customer_location,
+            count,
+        ))
+    }
+
+    // The stores `with_nearest_stores` keeps
+    fn nearest_store_ids(
+        &self,
+        shopping_list: &ShoppingList,
+        shopper_location: &Location,
+        customer_location: &Location,
+        count: usize,
+    ) -> HashSet<StoreId> {
+        let mut ranked: Vec<(f64, StoreId)> = self
+            .collect_candidate_stores(shopping_list)
+            .into_keys()
+            .map(|store_id| {
+                let location = self.stores[&store_id].read().unwrap().location;
+                let distance = self
+                    .distance_metric
+                    .distance(shopper_location, &location)
+                    .min(self.distance_metric.distance(customer_location, &location));
+                (distance, store_id)
+            })
+            .collect();
+        ranked.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+
+        let mut kept = HashSet::new();
+        let mut supply: HashMap<ProductId, u32> = HashMap::new();
+        for (_, store_id) in ranked {
+            let covered = shopping_list.items.iter().all(|(product_id, &quantity)| {
+                supply.get(product_id).copied().unwrap_or(0) >= quantity
+            });
+            if kept.len() >= count && covered {
+                break;
+            }
+            let store = self.stores[&store_id].read().unwrap();
+            for product_id in shopping_list.items.keys() {
+                let level = supply.entry(*product_id).or_insert(0);
+                *level = level.saturating_add(store.get_inventory_level(product_id));
+            }
+            kept.insert(store_id);
+        }
+        kept
+    }
+
+    /// A copy of the solver over only the stores in `category` (see `Store::has_category`)
+    pub fn with_category(&self, category: &str) -> BSLPSD {
+        self.with_store_subset(&self.stores_in_category(category))
+    }
+
+    fn stores_in_category(&self, category: &str) -> HashSet<StoreId> {
+        self.stores
+            .iter()
+            .filter(|(_, store)| store.read().unwrap().has_category(category))
+            .map(|(store_id, _)| *store_id)
+            .collect()
+    }
+
+    /// A copy of the solver over only the kept stores, e.g. from `stores_within`
+    ///
+    /// Travel times are cut down to pairs of kept stores and the inverted list is
+    /// rebuilt. Stores stay shared with this solver, so inventory changes and
+    /// reservations apply to both, but `set_inventory` and `reprice` only rebuild the
+    /// inverted list of the solver they are called on; call `rebuild_inverted_list_for`
+    /// on the other. Unknown IDs in `keep` are ignored.
+    pub fn with_store_subset(&self, keep: &HashSet<StoreId>) -> BSLPSD {
+        let mut solver = self.clone();
+        solver.stores.retain(|store_id, _| keep.contains(store_id));
+        solver
+            .travel_times
+            .retain(|(from, to), _| keep.contains(from) && keep.contains(to));
+        solver
+            .edge_costs
+            .retain(|(from, to), _| keep.contains(from) && keep.contains(to));
+        solver.build_inverted_list();
+        solver
+    }
+
+    /// Find the route with minimum shopping time using Dijkstra algorithm
+    /// Allows purchasing products across multiple stores
+    pub fn find_min_time_route_dijkstra(
+        &self,
+        shopping_list: &ShoppingList,
+        shopper_location: Location,
+        customer_location: Location,
+    ) -> Option<ShoppingRoute> {
+        self.find_min_time_route(shopping_list, shopper_location, customer_location, false)
+    }
+
+    /// Find the route with minimum shopping time using A* search
+    /// Guided by the straight-line distance to the customer, and returns the
+    /// same optimal shopping time as `find_min_time_route_dijkstra`
+    pub fn find_min_time_route_astar(
+        &self,
+        shopping_list: &ShoppingList,
+        shopper_location: Location,
+        customer_location: Location,
+    ) -> Option<ShoppingRoute> {
+        self.find_min_time_route(shopping_list, shopper_location, customer_location, true)
+    }
+
+    fn find_min_time_route(
+        &self,
+        shopping_list: &ShoppingList,
+        shopper_location: Location,
+        customer_location: Location,
+        use_heuristic: bool,
+    ) -> Option<ShoppingRoute> {
+        if shopping_list.is_empty() {
+            return Some(self.direct_route(&shopper_location, &customer_location));
+        }
+        let shopping_items: Vec<(ProductId, u32)> =
+            shopping_list.items.iter().map(|(k, v)| (*k, *v)).collect();
+
+        let store_products = self.collect_candidate_stores(shopping_list);
+        let candidate_stores: HashSet<StoreId> = store_products.keys().cloned().collect();
+
+        if candidate_stores.is_empty() {
+            return None;
+        }
+
+        let mut can_be_fulfilled = true;
+        for &(product_id, qty_needed) in &shopping_items {
+            let mut available = 0;
+            for &store_id in &candidate_stores {
+                if let Some(store_inventory) = store_products.get(&store_id) {
+                    if let Some(&quantity) = store_inventory.get(&product_id) {
+                        available += quantity;
+                    }
+                }
+            }
+
+            if available < qty_needed {
+                can_be_fulfilled = false;
+                break;
             }
+        }
+
+        if !can_be_fulfilled {
+            return None;
+        }
+
+        // Fix the product order and align every candidate store's stock to it
+        let quantities: Vec<u32> = shopping_items.iter().map(|&(_, qty)| qty).collect();
+        let stores: Vec<StoreId> = candidate_stores.into_iter().collect();
+        let stocks: Vec<Vec<u32>> = stores
+            .iter()
+            .map(|store_id| {
+                let inventory = &store_products[store_id];
+                shopping_items
+                    .iter()
+                    .map(|(product_id, _)| inventory.get(product_id).copied().unwrap_or(0))
+                    .collect()
+            })
+            .collect();
+        let heuristic = if use_heuristic {
+            self.customer_heuristic(&stores, &customer_location)
         } else {
-            // First store in the route
-            if let Some(min_detour_store) = self.find_min_detour_store(None, &visited_stores) {
-                let new_route = vec![min_detour_store];
+            vec![0.0; stores.len()]
+        };
+
+        let (path, best_time) = match RemainingLayout::packed(&quantities) {
+            Some(layout) => self.min_time_search(
+                layout.pack(&quantities),
+                &layout,
+                &stores,
+                &stocks,
+                &heuristic,
+                &shopper_location,
+                &customer_location,
+            ),
+            None => self.min_time_search(
+                quantities.into_boxed_slice(),
+                &RemainingLayout::default(),
+                &stores,
+                &stocks,
+                &heuristic,
+                &shopper_location,
+                &customer_location,
+            ),
+        }?;
+
+        let shopping_cost = self.calculate_shopping_cost(&path, shopping_list);
+
+        Some(ShoppingRoute {
+            stores: path,
+            shopping_time: best_time,
+            shopping_cost,
+        })
+    }
+
+    /// Lower bound on the time left from each store to the customer, for A*
+    /// Scales the straight-line distance by the smallest time per unit of distance
+    /// seen on any leg, so the bound holds even when times and distances use other units
+    fn customer_heuristic(&self, stores: &[StoreId], customer_location: &Location) -> Vec<Time> {
+        let locked: Vec<_> = stores
+            .iter()
+            .map(|store_id| self.stores[store_id].read().unwrap())
+            .collect();
+        let to_customer: Vec<f64> = locked
+            .iter()
+            .map(|store| {
+                self.distance_metric
+                    .distance(&store.location, customer_location)
+            })
+            .collect();
+
+        let mut scale = f64::INFINITY;
+        for (index, store) in locked.iter().enumerate() {
+            if to_customer[index] > 0.0 {
+                scale = scale.min(self.customer_leg(store, customer_location) / to_customer[index]);
+            }
+
+            for (other_index, other) in locked.iter().enumerate() {
+                let distance = self
+                    .distance_metric
+                    .distance(&store.location, &other.location);
+                let Some(time) = self.search_leg_time(stores[index], stores[other_index]) else {
+                    continue;
+                };
+                if other_index != index && distance > 0.0 {
+                    scale = scale.min(time / distance);
+                }
+            }
+        }
+
+        if !scale.is_finite() || scale <= 0.0 {
+            return vec![0.0; stores.len()];
+        }
+
+        to_customer
+            .iter()
+            .map(|distance| distance * scale)
+            .collect()
+    }
+
+    /// Dijkstra over (store, remaining quantities) states for `find_min_time_route_dijkstra`
+    /// With a non-zero `heuristic` per store this runs as A* instead
+    /// Stores are referred to by index into `stores`; returns the best path and its time
+    #[allow(clippy::too_many_arguments)]
+    fn min_time_search<K: Remaining>(
+        &self,
+        start: K,
+        layout: &RemainingLayout,
+        stores: &[StoreId],
+        stocks: &[Vec<u32>],
+        heuristic: &[Time],
+        shopper_location: &Location,
+        customer_location: &Location,
+    ) -> Option<(Vec<StoreId>, Time)> {
+        let mut distances: HashMap<(usize, K), f64> = HashMap::new();
+        let mut predecessors: HashMap<(usize, K), Option<(usize, K)>> = HashMap::new();
+        let mut priority_queue = BinaryHeap::new();
+        let mut visited = HashSet::new();
+
+        for (index, &store_id) in stores.iter().enumerate() {
+            let Some(remaining) = start.buy(layout, &stocks[index]) else {
+                continue;
+            };
+
+            let store = self.stores[&store_id].read().unwrap();
+            let distance = self.endpoint_leg(shopper_location, &store) + store.service_time;
+
+            distances.insert((index, remaining.clone()), distance);
+            predecessors.insert((index, remaining.clone()), None);
+            priority_queue.push(DijkstraEntry {
+                distance: F64Wrapper(distance + heuristic[index]),
+                store: index,
+                remaining,
+            });
+        }
+
+        let mut best_time = f64::INFINITY;
+        let mut best_state = None;
+
+        while let Some(DijkstraEntry {
+            distance: _,
+            store: current,
+            remaining: current_remaining,
+        }) = priority_queue.pop()
+        {
+            if !visited.insert((current, current_remaining.clone())) {
+                continue;
+            }
+
+            // The queue is ordered by estimated total time, so read the time so far back
+            let current_dist = distances[&(current, current_remaining.clone())];
+
+            if current_remaining.is_empty() {
+                let store = self.stores[&stores[current]].read().unwrap();
+                let final_distance = current_dist
+                    + self.customer_leg(&store, customer_location)
+                    + self.return_leg(customer_location);
+
+                // If this route is faster, update the best result
+                if final_distance < best_time {
+                    best_time = final_distance;
+                    best_state = Some((current, current_remaining));
+                }
+
+                continue;
+            }
+
+            if current_dist + heuristic[current] >= best_time {
+                continue;
+            }
+
+            for (next, &next_store) in stores.iter().enumerate() {
+                if next == current {
+                    continue;
+                }
+
+                let edge_weight = self
+                    .search_leg_time(stores[current], next_store)
+                    .unwrap_or(f64::INFINITY);
+
+                if edge_weight.is_infinite() {
+                    continue;
+                }
+
+                let next_dist = current_dist + edge_weight + self.service_time(next_store);
+
+                if next_dist + heuristic[next] >= best_time {
+                    continue;
+                }
+
+                let Some(new_remaining) = current_remaining.buy(layout, &stocks[next]) else {
+                    continue;
+                };
+
+                let next_state = (next, new_remaining);
+                if !distances.contains_key(&next_state) || next_dist < distances[&next_state] {
+                    distances.insert(next_state.clone(), next_dist);
+                    predecessors.insert(
+                        next_state.clone(),
+                        Some((current, current_remaining.clone())),
+                    );
+
+                    priority_queue.push(DijkstraEntry {
+                        distance: F64Wrapper(next_dist + heuristic[next]),
+                        store: next,
+                        remaining: next_state.1,
+                    });
+                }
+            }
+        }
+
+        let mut current_state = best_state?;
+        let mut path = Vec::new();
+
+        while let Some(prev_state) = predecessors[&current_state].clone() {
+            path.push(stores[current_state.0]);
+            current_state = prev_state;
+        }
+
+        path.push(stores[current_state.0]);
+        path.reverse();
+
+        Some((path, best_time))
+    }
+
+    /// Find only the fastest feasible route, skipping the full skyline search
+    /// The returned route carries its shopping cost
+    pub fn solve_fastest(
+        &self,
+        shopping_list: &ShoppingList,
+        shopper_location: Location,
+        customer_location: Location,
+    ) -> Option<ShoppingRoute> {
+        self.find_min_time_route_dijkstra(shopping_list, shopper_location, customer_location)
+    }
+
+    /// Checks if a route satisfies a shopping list considering inventory
+    pub fn satisfies_list_with_inventory(
+        &self,
+        route: &[StoreId],
+        shopping_list: &ShoppingList,
+    ) -> bool {
+        // Create a map to track the total available inventory in the route's stores
+        let mut available_inventory: HashMap<ProductId, u32> = HashMap::new();
+
+        // Collect available inventory from stores in the route
+        for &store_id in route {
+            let store = self.stores[&store_id].read().unwrap();
+            for product_id in shopping_list.items.keys() {
+                if store.has_product(product_id) {
+                    let inventory = store.get_inventory_level(product_id);
+                    *available_inventory.entry(*product_id).or_insert(0) += inventory;
+                }
+            }
+        }
+
+        // Check if all products have sufficient inventory
+        for (product_id, required_qty) in &shopping_list.items {
+            if let Some(available_qty) = available_inventory.get(product_id) {
+                if available_qty < required_qty {
+                    return false;
+                }
+            } else {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    /// Find the minimum detour store to add to a route
+    /// This corresponds to the MinDetour function in the paper
+    fn find_min_detour_store(
+        &self,
+        from_store: Option<StoreId>,
+        visited_stores: &HashSet<StoreId>,
+    ) -> Option<StoreId> {
+        let mut min_detour = f64::INFINITY;
+        let mut min_detour_store = None;
+
+        for store_id in self.stores.keys() {
+            // Skip if already visited
+            if visited_stores.contains(store_id) {
+                continue;
+            }
+
+            // Calculate detour
+            let detour = match from_store {
+                Some(from) => self.leg_time(from, *store_id).unwrap_or(f64::INFINITY),
+                None => 0.0, // First store in route
+            };
+
+            if detour < min_detour {
+                min_detour = detour;
+                min_detour_store = Some(*store_id);
+            }
+        }
+
+        min_detour_store
+    }
+
+    /// Find the next minimum detour store after the kth one
+    /// This corresponds to the NextMinDetour function in the paper
+    fn find_next_min_detour_store(
+        &self,
+        from_store: Option<StoreId>,
+        visited_stores: &HashSet<StoreId>,
+        current_min_detour_store: StoreId,
+    ) -> Option<StoreId> {
+        let mut next_min_detour = f64::INFINITY;
+        let mut next_min_detour_store = None;
+
+        // Get the detour of the current minimum
+        let current_detour = match from_store {
+            Some(from) => self
+                .leg_time(from, current_min_detour_store)
+                .unwrap_or(f64::INFINITY),
+            None => 0.0, // First store in route
+        };
+
+        for store_id in self.stores.keys() {
+            // Skip if already visited or if it's the current minimum
+            if visited_stores.contains(store_id) || *store_id == current_min_detour_store {
+                continue;
+            }
+
+            // Calculate detour
+            let detour = match from_store {
+                Some(from) => self.leg_time(from, *store_id).unwrap_or(f64::INFINITY),
+                None => 0.0, // First store in route
+            };
+
+            // Find the next minimum (greater than current, but less than any other)
+            if detour > current_detour && detour < next_min_detour {
+                next_min_detour = detour;
+                next_min_detour_store = Some(*store_id);
+            }
+        }
+
+        next_min_detour_store
+    }
+    /// Generate next routes according to the generation scheme in the paper
+    /// Extensions add the detour to the route's time; a route's first store is timed
+    /// from the shopper to the customer
+    fn generate_next_routes(
+        &self,
+        route: &RouteCandidate,
+        shopper_location: &Location,
+        customer_location: &Location,
+    ) -> Vec<RouteCandidate> {
+        let mut next_routes = Vec::new();
+        let mut visited_stores: HashSet<StoreId> = route.stores.iter().cloned().collect();
+
+        // Case 1: Add a new store at the end (θs in the paper)
+        if let Some(last_store) = route.stores.last().cloned() {
+            if let Some(min_detour_store) =
+                self.find_min_detour_store(Some(last_store), &visited_stores)
+            {
+                let mut new_route = route.stores.clone();
+                new_route.push(min_detour_store);
+
+                // Calculate new shopping time
+                let detour = self
+                    .leg_time(last_store, min_detour_store)
+                    .unwrap_or(f64::INFINITY);
+
+                let new_time = route.shopping_time + detour + self.service_time(min_detour_store);
+
+                next_routes.push(RouteCandidate {
+                    stores: new_route,
+                    shopping_time: new_time,
+                });
+            }
+        } else {
+            // First store in the route: the one that makes the quickest single-store trip
+            let first_store = self
+                .stores
+                .keys()
+                .filter(|store_id| !visited_stores.contains(store_id))
+                .map(|&store_id| {
+                    let time =
+                        self.calculate_total_time(&[store_id], shopper_location, customer_location);
+                    (time, store_id)
+                })
+                .filter(|(time, _)| time.is_finite())
+                .min_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+
+            if let Some((shopping_time, store_id)) = first_store {
+                next_routes.push(RouteCandidate {
+                    stores: vec![store_id],
+                    shopping_time,
+                });
+            }
+        }
+
+        // Case 2: Replace the last store (θp in the paper)
+        if !route.stores.is_empty() {
+            let last_store = route.stores.last().unwrap();
+
+            // Remove last store from visited set for consideration of replacements
+            visited_stores.remove(last_store);
+
+            if let Some(second_last_store) = route
+                .stores
+                .get(route.stores.len().saturating_sub(2))
+                .cloned()
+            {
+                if let Some(next_min_detour_store) = self.find_next_min_detour_store(
+                    Some(second_last_store),
+                    &visited_stores,
+                    *last_store,
+                ) {
+                    let mut new_route = route.stores.clone();
+                    new_route.pop(); // Remove last store
+                    new_route.push(next_min_detour_store);
+
+                    // Calculate new shopping time by removing last detour and adding new one
+                    let old_detour = self.leg_time(second_last_store, *last_store).unwrap_or(0.0);
+
+                    let new_detour = self
+                        .leg_time(second_last_store, next_min_detour_store)
+                        .unwrap_or(f64::INFINITY);
+
+                    let new_time =
+                        route.shopping_time - old_detour - self.service_time(*last_store)
+                            + new_detour
+                            + self.service_time(next_min_detour_store);
+
+                    next_routes.push(RouteCandidate {
+                        stores: new_route,
+                        shopping_time: new_time,
+                    });
+                }
+            }
+        }
+
+        debug_assert!(
+            next_routes
+                .iter()
+                .all(|next_route| !has_repeated_stop(&next_route.stores)),
+            "route generation visited a store twice in a row"
+        );
+        next_routes.retain(|next_route| self.within_store_limit(&next_route.stores));
+        next_routes
+    }
+
+    /// Precomputed travel time from one store to another, if known
+    /// Staying at the same store takes no time, though no self-pairs are stored
+    pub fn travel_time(&self, from: StoreId, to: StoreId) -> Option<Time> {
+        if from == to {
+            return Some(0.0);
+        }
+        self.leg_time(from, to)
+    }
+
+    // Stored travel time between two stores at the shopper's speed, with no special
+    // case for a store to itself
+    fn leg_time(&self, from: StoreId, to: StoreId) -> Option<Time> {
+        self.travel_times
+            .get(&(from, to))
+            .map(|distance| distance / self.speed_m_per_min)
+    }
+
+    /// Distance and time from one store to another, if known
+    /// Solvers built from plain travel times treat them as distances driven at the
+    /// default speed
+    pub fn edge_cost(&self, from: StoreId, to: StoreId) -> Option<EdgeCost> {
+        if from == to {
+            return Some(EdgeCost::default());
+        }
+        match self.edge_costs.get(&(from, to)) {
+            Some(cost) => Some(*cost),
+            None => self
+                .travel_times
+                .get(&(from, to))
+                .copied()
+                .map(EdgeCost::from_distance),
+        }
+    }
+
+    /// Distance travelled along a route, from the shopper through the stores to the
+    /// customer (and on to the depot, if there is one), e.g. for fuel estimates
+    /// Legs between stores use the distance of `edge_cost`, and the other legs are
+    /// measured on the road graph if one is attached. Legs with neither fall back to the
+    /// straight line, in metres like the road legs when there is a road graph. Unlike
+    /// `shopping_time`, neither the shopper's speed nor service times play a part.
+    pub fn route_distance(
+        &self,
+        route: &[StoreId],
+        shopper_location: Location,
+        customer_location: Location,
+    ) -> f64 {
+        let location = |store_id: &StoreId| self.stores[store_id].read().unwrap().location;
+        let return_leg = self.return_location.map_or(0.0, |depot| {
+            self.endpoint_distance(&customer_location, &depot)
+        });
+        let (Some(first), Some(last)) = (route.first(), route.last()) else {
+            return self.endpoint_distance(&shopper_location, &customer_location) + return_leg;
+        };
+
+        let store_legs: f64 = route
+            .windows(2)
+            .map(|leg| match self.edge_cost(leg[0], leg[1]) {
+                Some(cost) => cost.distance_m,
+                None => self.straight_line_distance(&location(&leg[0]), &location(&leg[1])),
+            })
+            .sum();
+        self.endpoint_distance(&shopper_location, &location(first))
+            + store_legs
+            + self.endpoint_distance(&location(last), &customer_location)
+            + return_leg
+    }
+
+    /// Emissions of driving a route, in grams: its `route_distance` times the factor
+    /// `grams_per_meter` is per unit of the route distance, metres on a road network.
+    pub fn route_emissions(
+        &self,
+        route: &[StoreId],
+        shopper_location: Location,
+        customer_location: Location,
+        grams_per_meter: f64,
+    ) -> f64 {
+        self.route_distance(route, shopper_location, customer_location) * grams_per_meter
+    }
+
+    /// Summary of a route with its distance and, given a factor in grams per metre, its
+    /// emissions
+    pub fn route_report(
+        &self,
+        route: &ShoppingRoute,
+        shopper_location: Location,
+        customer_location: Location,
+        grams_per_meter: Option<f64>,
+    ) -> RouteReport {
+        let distance = self.route_distance(&route.stores, shopper_location, customer_location);
+        RouteReport {
+            stores: route.stores.clone(),
+            shopping_time: route.shopping_time,
+            shopping_cost: route.shopping_cost,
+            distance,
+            emissions: grams_per_meter.map(|factor| distance * factor),
+        }
+    }
+
+    // Shortest road distance between two locations, or the straight line without a
+    // road path, as the distance-optimal endpoint legs measure it
+    fn endpoint_distance(&self, from: &Location, to: &Location) -> f64 {
+        self.road_graph
+            .as_ref()
+            .and_then(|road_graph| road_graph.travel_cost(from, to, RoutingObjective::Distance))
+            .unwrap_or_else(|| self.straight_line_distance(from, to))
+    }
+
+    // Straight-line distance between two locations, in metres when a road graph is
+    // attached so it adds up with road distances
+    fn straight_line_distance(&self, from: &Location, to: &Location) -> f64 {
+        let distance = self.distance_metric.distance(from, to);
+        match self.road_graph {
+            Some(_) => distance * 1000.0,
+            None => distance,
+        }
+    }
+
+    /// Travel time between two stores, as used for route times
+    /// Falls back to the straight-line distance between them when no travel time is
+    /// known (great-circle unless `set_distance_metric` chose another metric, and in
+    /// metres when a road graph is attached, like the road-derived times)
+    pub fn travel_time_or_euclidean(&self, from: StoreId, to: StoreId) -> Time {
+        if let Some(time) = self.travel_time(from, to) {
+            return time;
+        }
+        let from_store = self.stores[&from].read().unwrap();
+        let to_store = self.stores[&to].read().unwrap();
+        self.straight_line_distance(&from_store.location, &to_store.location) / self.speed_m_per_min
+    }
+
+    /// Generate next routes according to the original strategy but with path optimization
+    /// Find the shortest path that visits all stores in the given set
+    /// Ensures no duplicate stores in the result
+    fn find_shortest_path(
+        &self,
+        stores: &[StoreId],
+        shopper_location: &Location,
+        customer_location: &Location,
+    ) -> Vec<StoreId> {
+        if stores.is_empty() {
+            return Vec::new();
+        }
+
+        if stores.len() == 1 {
+            return stores.to_vec();
+        }
+
+        // Remove any duplicates from the store set first
+        let mut unique_stores = Vec::new();
+        let mut seen = HashSet::new();
+
+        for &store_id in stores {
+            if !seen.contains(&store_id) {
+                unique_stores.push(store_id);
+                seen.insert(store_id);
+            }
+        }
+
+        if unique_stores.len() > MAX_PERMUTATION_STORES {
+            return self.insertion_path(&unique_stores, shopper_location, customer_location);
+        }
+
+        // For small sets of stores, we can try all permutations
+        let permutations = self.generate_permutations(unique_stores);
+        let time_of = |index: usize| {
+            let time = self.calculate_total_time(
+                &permutations[index],
+                shopper_location,
+                customer_location,
+            );
+            (time, index)
+        };
+        // Ties (and all-infinite times) go to the earliest permutation, as a sequential scan would
+        let earlier = |a: (f64, usize), b: (f64, usize)| {
+            if b.0 < a.0 || (b.0 == a.0 && b.1 < a.1) {
+                b
+            } else {
+                a
+            }
+        };
+
+        // Permutations are independent, so larger sets are evaluated in parallel
+        let (min_time, best_index) = if permutations.len() >= PARALLEL_PERMUTATIONS {
+            (0..permutations.len())
+                .into_par_iter()
+                .map(time_of)
+                .reduce(|| (f64::INFINITY, usize::MAX), earlier)
+        } else {
+            (0..permutations.len())
+                .map(time_of)
+                .fold((f64::INFINITY, usize::MAX), earlier)
+        };
+
+        if min_time.is_finite() {
+            permutations[best_index].clone()
+        } else {
+            Vec::new()
+        }
+    }
+
+    // Orders stores by inserting them one by one, in the given order, at the position
+    // adding the least time; empty if the resulting path is infeasible
+    fn insertion_path(
+        &self,
+        stores: &[StoreId],
+        shopper_location: &Location,
+        customer_location: &Location,
+    ) -> Vec<StoreId> {
+        let mut path: Vec<StoreId> = Vec::with_capacity(stores.len());
+        let mut time = 0.0;
+        for &store_id in stores {
+            (time, path) = (0..=path.len())
+                .map(|position| {
+                    let mut candidate = path.clone();
+                    candidate.insert(position, store_id);
+                    let time =
+                        self.calculate_total_time(&candidate, shopper_location, customer_location);
+                    (time, candidate)
+                })
+                .min_by(|a, b| a.0.total_cmp(&b.0))
+                .expect("a path has at least one insertion position");
+        }
+        if time.is_finite() {
+            path
+        } else {
+            Vec::new()
+        }
+    }
+
+    /// Generate all permutations of a vector of StoreId
+    fn generate_permutations(&self, stores: Vec<StoreId>) -> Vec<Vec<StoreId>> {
+        if stores.is_empty() {
+            return vec![vec![]];
+        }
+
+        let mut result = Vec::new();
+
+        for (i, &store) in stores.iter().enumerate() {
+            let mut remaining = stores.clone();
+            remaining.remove(i);
+
+            for mut perm in self.generate_permutations(remaining) {
+                perm.insert(0, store);
+                result.push(perm);
+            }
+        }
+
+        result
+    }
+    /// Calculate the total time of a path from shopper to stores to customer
+    fn calculate_total_time(
+        &self,
+        path: &[StoreId],
+        shopper_location: &Location,
+        customer_location: &Location,
+    ) -> f64 {
+        if path.is_empty() {
+            // Direct path from shopper to customer
+            return self
+                .direct_route(shopper_location, customer_location)
+                .shopping_time;
+        }
+
+        let mut total_time = 0.0;
+
+        // Time from shopper to first store
+        let first_store = self.stores[&path[0]].read().unwrap();
+        total_time += self.endpoint_leg(shopper_location, &first_store);
+        total_time = match self.visit_store(&first_store, total_time) {
+            Some(time) => time,
+            None => return f64::INFINITY,
+        };
+
+        // Time between consecutive stores
+        for i in 0..path.len() - 1 {
+            total_time += self.travel_time_or_euclidean(path[i], path[i + 1]);
+            let to_store = self.stores[&path[i + 1]].read().unwrap();
+            total_time = match self.visit_store(&to_store, total_time) {
+                Some(time) => time,
+                None => return f64::INFINITY,
+            };
+        }
+
+        // Time from last store to customer, and on to the depot if there is one
+        let last_store = self.stores[&path[path.len() - 1]].read().unwrap();
+        total_time += self.customer_leg(&last_store, customer_location);
+        total_time += self.return_leg(customer_location);
+
+        total_time
+    }
+
+    /// Generate next routes according to the original strategy but with path optimization
+    /// Ensures no duplicate stores in routes
+    fn generate_next_routes_shuffle(
+        &self,
+        route: &RouteCandidate,
+        shopper_location: &Location,
+        customer_location: &Location,
+    ) -> Vec<RouteCandidate> {
+        let mut next_routes = Vec::new();
+        let visited_stores: HashSet<StoreId> = route.stores.iter().cloned().collect();
+
+        // First, check if the current route has duplicates and fix if needed
+        let current_route = route.stores.clone();
+        let mut has_duplicates = false;
+        let mut unique_current = Vec::new();
+        let mut seen = HashSet::new();
+
+        for &store_id in &current_route {
+            if !seen.contains(&store_id) {
+                unique_current.push(store_id);
+                seen.insert(store_id);
+            } else {
+                has_duplicates = true;
+            }
+        }
+
+        // If the input route had duplicates, optimize it first
+        if has_duplicates {
+            let optimized_current =
+                self.find_shortest_path(&unique_current, shopper_location, customer_location);
+
+            let shopping_time =
+                self.calculate_total_time(&optimized_current, shopper_location, customer_location);
+
+            if !shopping_time.is_infinite() {
+                next_routes.push(RouteCandidate {
+                    stores: optimized_current,
+                    shopping_time,
+                });
+            }
+
+            // Return early - we need to fix the current route before generating more
+            return next_routes;
+        }
+
+        // Case 1: Add a new store at the end (θs in the paper)
+        if !route.stores.is_empty() {
+            let last_store = route.stores.last().unwrap();
+
+            if let Some(min_detour_store) =
+                self.find_min_detour_store(Some(*last_store), &visited_stores)
+            {
+                // Make sure we're not adding a duplicate
+                if !visited_stores.contains(&min_detour_store) {
+                    // Generate a new route with the additional store
+                    let mut new_route = route.stores.clone();
+                    new_route.push(min_detour_store);
+
+                    // Now optimize the entire route order to find the shortest path
+                    let optimized_route =
+                        self.find_shortest_path(&new_route, shopper_location, customer_location);
+
+                    // Calculate shopping time for the optimized route
+                    let shopping_time = self.calculate_total_time(
+                        &optimized_route,
+                        shopper_location,
+                        customer_location,
+                    );
+
+                    if !shopping_time.is_infinite() {
+                        next_routes.push(RouteCandidate {
+                            stores: optimized_route,
+                            shopping_time,
+                        });
+                    }
+                }
+            }
+        } else {
+            // First store in the route
+            if let Some(min_detour_store) = self.find_min_detour_store(None, &visited_stores) {
+                let new_route = vec![min_detour_store];
+
+                // Calculate shopping time from shopper location to this store to customer location
+                let shopping_time =
+                    self.calculate_total_time(&new_route, shopper_location, customer_location);
+
+                if !shopping_time.is_infinite() {
+                    next_routes.push(RouteCandidate {
+                        stores: new_route,
+                        shopping_time,
+                    });
+                }
+            }
+        }
+
+        // Case 2: Replace the last store (θp in the paper)
+        if !route.stores.is_empty() {
+            let last_store = route.stores.last().unwrap();
+
+            // Create a modified visited set without the last store
+            let mut visited_without_last = visited_stores.clone();
+            visited_without_last.remove(last_store);
+
+            if let Some(second_last_store) = route
+                .stores
+                .get(route.stores.len().saturating_sub(2))
+                .cloned()
+            {
+                if let Some(next_min_detour_store) = self.find_next_min_detour_store(
+                    Some(second_last_store),
+                    &visited_without_last,
+                    *last_store,
+                ) {
+                    // Make sure the replacement isn't already in the route
+                    if !visited_without_last.contains(&next_min_detour_store) {
+                        // Create a new route with the replacement
+                        let mut new_route = route.stores.clone();
+                        new_route.pop(); // Remove last store
+                        new_route.push(next_min_detour_store);
+
+                        // Now optimize the entire route order
+                        let optimized_route = self.find_shortest_path(
+                            &new_route,
+                            shopper_location,
+                            customer_location,
+                        );
+
+                        // Calculate shopping time for the optimized route
+                        let shopping_time = self.calculate_total_time(
+                            &optimized_route,
+                            shopper_location,
+                            customer_location,
+                        );
+
+                        if !shopping_time.is_infinite() {
+                            next_routes.push(RouteCandidate {
+                                stores: optimized_route,
+                                shopping_time,
+                            });
+                        }
+                    }
+                }
+            }
+        }
+
+        debug_assert!(
+            next_routes
+                .iter()
+                .all(|next_route| !has_repeated_stop(&next_route.stores)),
+            "route generation visited a store twice in a row"
+        );
+        next_routes.retain(|next_route| self.within_store_limit(&next_route.stores));
+        next_routes
+    }
+
+    /// Allocates the listed products to the route's stores, cheapest first
+    /// Stores are counted once even if the route repeats them, and equally priced
+    /// stores are used in route order. Quantities the route can't supply are reported
+    /// in `unfulfilled`; the total cost of a complete allocation is the route's
+    /// shopping cost.
+    pub fn allocate_purchases(
+        &self,
+        route: &[StoreId],
+        shopping_list: &ShoppingList,
+    ) -> PurchaseAllocation {
+        // For each product, record available stores, prices, and quantities
+        let mut product_options: HashMap<ProductId, Vec<(StoreId, Cost, u32)>> = HashMap::new();
+        let mut seen = HashSet::new();
+        for &store_id in route {
+            if !seen.insert(store_id) {
+                continue;
+            }
+            let store = self.stores[&store_id].read().unwrap();
+            for product_id in shopping_list.items.keys() {
+                if store.can_supply(product_id, 1) {
+                    let cost = store.get_unit_price(product_id).unwrap_or(f64::INFINITY);
+                    product_options.entry(*product_id).or_default().push((
+                        store_id,
+                        cost,
+                        store.get_inventory_level(product_id),
+                    ));
+                }
+            }
+        }
+
+        let mut allocation = PurchaseAllocation::default();
+        for (product_id, &qty_needed) in &shopping_list.items {
+            let mut options = product_options.remove(product_id).unwrap_or_default();
+            // Stable sort keeps route order among equal prices
+            options.sort_by(|a, b| a.1.total_cmp(&b.1));
+
+            let mut remaining = qty_needed;
+            let mut purchases = Vec::new();
+            for (store_id, cost, available) in options {
+                if remaining == 0 {
+                    break;
+                }
+                let quantity = available.min(remaining);
+                if quantity > 0 {
+                    allocation.total_cost += cost * quantity as f64;
+                    remaining = remaining.saturating_sub(quantity);
+                    purchases.push((store_id, quantity, cost));
+                }
+            }
+            // Never buy more than was asked for
+            debug_assert_eq!(
+                purchases
+                    .iter()
+                    .map(|&(_, quantity, _)| quantity)
+                    .sum::<u32>()
+                    + remaining,
+                qty_needed
+            );
+
+            if remaining > 0 {
+                allocation.unfulfilled.insert(*product_id, remaining);
+            }
+            allocation.purchases.insert(*product_id, purchases);
+        }
+
+        allocation
+    }
+
+    /// What each store of a route supplies under `allocate_purchases`: the products
+    /// bought there and how many, sorted by product ID
+    /// Every store of the route has an entry, empty if nothing is bought there.
+    pub fn store_contributions(
+        &self,
+        route: &[StoreId],
+        shopping_list: &ShoppingList,
+    ) -> HashMap<StoreId, Vec<(ProductId, u32)>> {
+        let mut contributions: HashMap<StoreId, Vec<(ProductId, u32)>> = route
+            .iter()
+            .map(|&store_id| (store_id, Vec::new()))
+            .collect();
+        let allocation = self.allocate_purchases(route, shopping_list);
+        for (product_id, purchases) in allocation.purchases {
+            for (store_id, quantity, _) in purchases {
+                contributions
+                    .entry(store_id)
+                    .or_default()
+                    .push((product_id, quantity));
+            }
+        }
+        for products in contributions.values_mut() {
+            products.sort_unstable();
+        }
+        contributions
+    }
+
+    /// Get the coordinates of a route's stores in visit order
+    /// Unknown store IDs are skipped
+    pub fn route_geometry(&self, route: &[StoreId]) -> Vec<(StoreId, Location)> {
+        route
+            .iter()
+            .filter_map(|store_id| {
+                self.stores
+                    .get(store_id)
+                    .map(|store| (*store_id, store.read().unwrap().location))
+            })
+            .collect()
+    }
+
+    /// The actual (x, y) coordinates of a route's stores, e.g. for plotting it
+    /// Unknown store IDs are skipped
+    pub fn store_coordinates(&self, route: &ShoppingRoute) -> HashMap<StoreId, (f64, f64)> {
+        self.route_geometry(&route.stores)
+            .into_iter()
+            .map(|(store_id, location)| (store_id, (location.x, location.y)))
+            .collect()
+    }
+
+    /// Export a route as a GeoJSON FeatureCollection for web maps
+    /// The first feature is a LineString from the shopper through the stores to the
+    /// customer, following the roads when a road graph is attached (straight segments
+    /// otherwise). It is followed by one Point per store carrying its ID, its stop
+    /// number and what is bought there. Coordinates are `[x, y]`, i.e. `[lon, lat]`.
+    pub fn route_to_geojson(
+        &self,
+        route: &ShoppingRoute,
+        shopping_list: &ShoppingList,
+        shopper_location: Location,
+        customer_location: Location,
+    ) -> String {
+        let stops: Vec<Location> = std::iter::once(shopper_location)
+            .chain(
+                self.route_geometry(&route.stores)
+                    .into_iter()
+                    .map(|(_, location)| location),
+            )
+            .chain(std::iter::once(customer_location))
+            .collect();
+
+        let mut line: Vec<(f64, f64)> = vec![(shopper_location.x, shopper_location.y)];
+        for leg in stops.windows(2) {
+            let road = self
+                .road_graph
+                .as_ref()
+                .and_then(|road_graph| road_graph.location_path(&leg[0], &leg[1]));
+            let points = match road {
+                Some((_, polyline)) => polyline,
+                None => vec![(leg[0].x, leg[0].y), (leg[1].x, leg[1].y)],
+            };
+            for point in points {
+                if line.last() != Some(&point) {
+                    line.push(point);
+                }
+            }
+        }
+
+        let mut features = vec![serde_json::json!({
+            "type": "Feature",
+            "geometry": {
+                "type": "LineString",
+                "coordinates": line.iter().map(|&(x, y)| [x, y]).collect::<Vec<_>>(),
+            },
+            "properties": {
+                "stores": route.stores,
+                "shopping_time": route.shopping_time,
+                "shopping_cost": route.shopping_cost,
+            },
+        })];
+
+        let allocation = self.allocate_purchases(&route.stores, shopping_list);
+        let mut product_ids: Vec<ProductId> = allocation.purchases.keys().cloned().collect();
+        product_ids.sort_unstable();
+        for (stop, (store_id, location)) in
+            self.route_geometry(&route.stores).into_iter().enumerate()
+        {
+            let products: Vec<serde_json::Value> = product_ids
+                .iter()
+                .flat_map(|product_id| {
+                    allocation.purchases[product_id]
+                        .iter()
+                        .filter(move |&&(store, _, _)| store == store_id)
+                        .map(move |&(_, quantity, unit_cost)| {
+                            serde_json::json!({
+                                "product_id": product_id,
+                                "quantity": quantity,
+                                "unit_cost": unit_cost,
+                            })
+                        })
+                })
+                .collect();
+            features.push(serde_json::json!({
+                "type": "Feature",
+                "geometry": {
+                    "type": "Point",
+                    "coordinates": [location.x, location.y],
+                },
+                "properties": {
+                    "store_id": store_id,
+                    "stop": stop + 1,
+                    "products": products,
+                },
+            }));
+        }
+
+        serde_json::json!({
+            "type": "FeatureCollection",
+            "features": features,
+        })
+        .to_string()
+    }
+
+    /// Create a snapshot of the current inventory state
+    pub fn snapshot_inventory(&self) -> HashMap<StoreId, HashMap<ProductId, u32>> {
+        let mut snapshot = HashMap::new();
+
+        for (&store_id, store_arc) in &self.stores {
+            let store = store_arc.read().unwrap();
+            snapshot.insert(store_id, store.inventory.clone());
+        }
+
+        snapshot
+    }
+
+    /// Reserves the shopping list's products from the route's stores, buying from the
+    /// cheapest stores first, the lower store ID on equal prices
+    ///
+    /// Inventory is only changed if the whole list can be allocated; otherwise nothing
+    /// is taken and false is returned.
+    pub fn reserve_inventory(&self, route: &[StoreId], shopping_list: &ShoppingList) -> bool {
+        // Lock each store once, in ascending ID order, so concurrent reservations
+        // can't deadlock on each other
+        let mut lock_order: Vec<StoreId> = route
+            .iter()
+            .filter(|store_id| self.stores.contains_key(store_id))
+            .cloned()
+            .collect();
+        lock_order.sort();
+        lock_order.dedup();
+        let mut guards: HashMap<StoreId, _> = lock_order
+            .iter()
+            .map(|store_id| (*store_id, self.stores[store_id].write().unwrap()))
+            .collect();
+
+        // Gather options in route order, counting each store once
+        let mut product_options: HashMap<ProductId, Vec<(StoreId, Cost, u32)>> = HashMap::new();
+        let mut seen = HashSet::new();
+        for store_id in route {
+            let Some(store) = guards.get(store_id) else {
+                continue;
+            };
+            if !seen.insert(*store_id) {
+                continue;
+            }
+            for product_id in shopping_list.items.keys() {
+                if store.can_supply(product_id, 1) {
+                    let cost = store.get_unit_price(product_id).unwrap_or(f64::INFINITY);
+                    product_options.entry(*product_id).or_default().push((
+                        *store_id,
+                        cost,
+                        store.get_inventory_level(product_id),
+                    ));
+                }
+            }
+        }
+
+        let Some((_, purchases)) = allocate_cheapest_first(product_options, shopping_list) else {
+            return false;
+        };
+
+        for &(store_id, product_id, quantity) in &purchases {
+            let store = guards.get_mut(&store_id).unwrap();
+            store.reduce_inventory(&product_id, quantity);
+        }
+        drop(guards);
+        self.clear_cost_cache();
+
+        self.reservations.lock().unwrap().push(Reservation {
+            route: route.to_vec(),
+            items: sorted_items(shopping_list),
+            purchases,
+        });
+        true
+    }
+
+    /// Returns the inventory taken by the most recent matching `reserve_inventory` call
+    /// Returns false if no reservation was made for this route and list
+    pub fn release_inventory(&self, route: &[StoreId], shopping_list: &ShoppingList) -> bool {
+        let items = sorted_items(shopping_list);
+        let reservation = {
+            let mut reservations = self.reservations.lock().unwrap();
+            match reservations
+                .iter()
+                .rposition(|r| r.route == route && r.items == items)
+            {
+                Some(index) => reservations.remove(index),
+                None => return false,
+            }
+        };
+
+        for (store_id, product_id, quantity) in reservation.purchases {
+            let mut store = self.stores[&store_id].write().unwrap();
+            let level = store.inventory.entry(product_id).or_insert(0);
+            *level = level.saturating_add(quantity);
+        }
+        self.clear_cost_cache();
+        true
+    }
+
+    /// Commits a chosen route by reserving its inventory
+    pub fn apply_route(&self, route: &ShoppingRoute, shopping_list: &ShoppingList) -> bool {
+        self.reserve_inventory(&route.stores, shopping_list)
+    }
+
+    /// Shopping cost of a route given current inventory, and whether the route can
+    /// fulfill the list at all
+    pub fn calculate_shopping_cost_with_inventory(
+        &self,
+        route: &[StoreId],
+        shopping_list: &ShoppingList,
+    ) -> (Cost, bool) {
+        let cost = self.calculate_shopping_cost(route, shopping_list);
+        (cost, cost.is_finite())
+    }
+
+    /// Overwrites store inventories with a snapshot from `snapshot_inventory`
+    ///
+    /// Outstanding reservations are discarded, since the snapshot already determines
+    /// what stock is taken; restoring a snapshot taken right after construction puts
+    /// the solver back in its initial state. Stores missing from the snapshot keep
+    /// their current inventory.
+    pub fn restore_inventory(&self, snapshot: HashMap<StoreId, HashMap<ProductId, u32>>) {
+        for (store_id, inventory) in snapshot {
+            if let Some(store_arc) = self.stores.get(&store_id) {
+                store_arc.write().unwrap().inventory = inventory;
+            }
+        }
+        self.reservations.lock().unwrap().clear();
+        self.clear_cost_cache();
+    }
+
+    // Shopping cost of a route, bypassing the memoized costs
+    fn uncached_shopping_cost(&self, route: &[StoreId], shopping_list: &ShoppingList) -> f64 {
+        // Route cannot fulfill shopping list if any product is left over
+        let allocation = self.allocate_purchases(route, shopping_list);
+        if allocation.is_complete() {
+            allocation.total_cost
+        } else {
+            f64::INFINITY
+        }
+    }
+
+    // Route for an empty shopping list: from the shopper directly to the customer, on
+    // the road graph if one is attached
+    fn direct_route(
+        &self,
+        shopper_location: &Location,
+        customer_location: &Location,
+    ) -> ShoppingRoute {
+        ShoppingRoute {
+            stores: Vec::new(),
+            shopping_time: self.endpoint_distance(shopper_location, customer_location)
+                / self.speed_m_per_min
+                + self.return_leg(customer_location),
+            shopping_cost: 0.0,
+        }
+    }
+
+    fn direct_skyline(&self, shopper_location: &Location, customer_location: &Location) -> Skyline {
+        let mut skyline = self.new_skyline();
+        skyline.insert(self.direct_route(shopper_location, customer_location));
+        skyline
+    }
+
+    // Empty skyline using the domination rule the solver is configured with
+    fn new_skyline(&self) -> Skyline {
+        Skyline::with_domination(if self.linear_skyline {
+            Domination::Linear
+        } else if self.keep_weak_alternatives {
+            Domination::Strict
+        } else {
+            Domination::Conventional
+        })
+    }
+
+    /// Update the skyline with a new route
+    /// Under conventional domination the skyline is kept sorted by
+    /// `ShoppingRoute::skyline_order`, so domination is only checked around the new
+    /// route's position; start from an empty or sorted skyline.
+    pub fn update_skyline(&self, skyline: &mut Vec<ShoppingRoute>, route: ShoppingRoute) -> bool {
+        if self.linear_skyline {
+            self.update_linear_skyline(skyline, route)
+        } else if self.keep_weak_alternatives {
+            self.update_skyline_with(skyline, route, ShoppingRoute::strictly_dominates)
+        } else {
+            remove_slower_permutation(skyline, &route) && insert_sorted_conventional(skyline, route)
+        }
+    }
+
+    /// Update a time-sorted skyline with a new route using linear domination
+    /// Routes between two others that are no cheaper than the line joining them drop out,
+    /// including routes exactly on that line
+    pub fn update_linear_skyline(
+        &self,
+        skyline: &mut Vec<ShoppingRoute>,
+        route: ShoppingRoute,
+    ) -> bool {
+        if !remove_slower_permutation(skyline, &route) {
+            return false;
+        }
+        insert_into_skyline(skyline, route)
+    }
+
+    /// Update the skyline with a new route using a custom domination predicate
+    /// `dominates(a, b)` must return true when route `a` dominates route `b`
+    pub fn update_skyline_with<F>(
+        &self,
+        skyline: &mut Vec<ShoppingRoute>,
+        route: ShoppingRoute,
+        dominates: F,
+    ) -> bool
+    where
+        F: Fn(&ShoppingRoute, &ShoppingRoute) -> bool,
+    {
+        // Keep only the fastest ordering of each store set
+        if !remove_slower_permutation(skyline, &route) {
+            return false;
+        }
+
+        // Check if the route is dominated by any route in the skyline
+        for existing_route in skyline.iter() {
+            if dominates(existing_route, &route) {
+                return false;
+            }
+        }
+
+        // Remove routes that are dominated by the new route
+        skyline.retain(|existing_route| !dominates(&route, existing_route));
+
+        // Add the new route
+        skyline.push(route);
+
+        true
+    }
+
+    // Seed routes and generator (true = shuffle) for each worker of `parallel_search`
+    fn plan_workers(
+        &self,
+        root: RouteCandidate,
+        plan: WorkerPlan,
+        shopper_location: &Location,
+        customer_location: &Location,
+    ) -> Vec<(Vec<RouteCandidate>, bool)> {
+        match plan {
+            // Both generators start from the minimum-time route
+            WorkerPlan::Paired => vec![(vec![root.clone()], false), (vec![root], true)],
+            WorkerPlan::Partitioned(num_workers) => {
+                let mut workers: Vec<(Vec<RouteCandidate>, bool)> = (0..num_workers)
+                    .map(|worker| (Vec::new(), worker % 2 == 1))
+                    .collect();
+                let mut seeds = vec![root.clone()];
+                seeds.extend(self.generate_next_routes(&root, shopper_location, customer_location));
+                for (index, seed) in seeds.into_iter().enumerate() {
+                    workers[index % num_workers].0.push(seed);
+                }
+                workers
+            }
+        }
+    }
+
+    // One worker of `parallel_search`: best-first expansion from `seeds`, sending every
+    // route that satisfies the list until the cost upper bound is found by any worker
+    #[allow(clippy::too_many_arguments)]
+    fn search_worker(
+        &self,
+        seeds: Vec<RouteCandidate>,
+        all_seeds: &[RouteCandidate],
+        shuffle: bool,
+        shopping_list: &ShoppingList,
+        shopper_location: &Location,
+        customer_location: &Location,
+        sc_upper_bound: Cost,
+        found_upper_bound: &AtomicBool,
+        cancel: &AtomicBool,
+        tx: &mpsc::Sender<ShoppingRoute>,
+    ) {
+        // Routes seeded to other workers are marked as visited too, so they are expanded
+        // by their owner only; every worker keeps its own set, so a route skipped by one
+        // worker is never hidden from the worker that owns it. Another ordering of a
+        // visited store set is only queued if it is faster.
+        let mut visited_route = VisitedStoreSets::default();
+        for seed in all_seeds {
+            visited_route.insert(seed);
+        }
+        let mut queue: BinaryHeap<RouteCandidate> = seeds.into_iter().collect();
+
+        while let Some(route_candidate) = queue.pop() {
+            // Check if upper bound route has been found or the search was cancelled
+            if found_upper_bound.load(Relaxed) || cancel.load(Relaxed) {
+                break;
+            }
+
+            // Check if route satisfies shopping list, once it visits the required stores
+            let completed =
+                self.with_required_stores(&route_candidate, shopper_location, customer_location);
+            if self.within_store_limit(&completed.stores)
+                && self.satisfies_list_with_inventory(&completed.stores, shopping_list)
+            {
+                // Calculate shopping cost
+                let shopping_cost = self.calculate_shopping_cost(&completed.stores, shopping_list);
+
+                // Create complete shopping route
+                let shopping_route = ShoppingRoute {
+                    stores: completed.stores,
+                    shopping_time: completed.shopping_time,
+                    shopping_cost,
+                };
+
+                // Send found route; the receiver is gone once the deadline has passed
+                if tx.send(shopping_route).is_err() {
+                    break;
+                }
+
+                // Check if upper bound has been reached
+                if shopping_cost == sc_upper_bound {
+                    println!("A worker found the sc_upper_bound skyline route!");
+                    found_upper_bound.store(true, Relaxed);
+                    break;
+                }
+            }
+
+            // Generate next batch of routes
+            let next_routes = if shuffle {
+                self.generate_next_routes_shuffle(
+                    &route_candidate,
+                    shopper_location,
+                    customer_location,
+                )
+            } else {
+                self.generate_next_routes(&route_candidate, shopper_location, customer_location)
+            };
+
+            // Filter already visited routes and add to queue
+            for next_route in next_routes {
+                if visited_route.insert(&next_route) {
+                    queue.push(next_route);
+                }
+            }
+        }
+    }
+
+    pub fn solve_with_parallel(
+        &self,
+        shopping_list: &ShoppingList,
+        shopper_location: Location,
+        customer_location: Location,
+        threshold: i32,
+    ) -> (Skyline, Duration) {
+        self.parallel_search(
+            shopping_list,
+            shopper_location,
+            customer_location,
+            threshold,
+            None,
+            WorkerPlan::Paired,
+            None,
+            &mut |_| {},
+        )
+    }
+
+    /// Same as `solve_with_parallel`, but tells an infeasible list apart from a search
+    /// that found nothing instead of returning an empty skyline for both
+    pub fn try_solve_with_parallel(
+        &self,
+        shopping_list: &ShoppingList,
+        shopper_location: Location,
+        customer_location: Location,
+        threshold: i32,
+    ) -> Result<Skyline, SolveError> {
+        let missing = self.missing_products(shopping_list);
+        if !missing.is_empty() {
+            let unavailable = self.unavailable_products(shopping_list);
+            return Err(SolveError::Infeasible {
+                missing,
+                unavailable,
+            });
+        }
+
+        let (skyline, _) = self.solve_with_parallel(
+            shopping_list,
+            shopper_location,
+            customer_location,
+            threshold,
+        );
+        if skyline.is_empty() {
+            return Err(SolveError::NoRouteFound);
+        }
+        Ok(skyline)
+    }
+
+    /// Routes non-dominated in time, cost and number of stores, ordered by store count
+    ///
+    /// A route is dropped only if another is no slower, no dearer and visits no more
+    /// stores, while being better in one of them, so the fastest route with fewer stops
+    /// is kept even when routes with more stops are both faster and cheaper. Built on
+    /// `solve_exact`, run once per store limit, so meant for small instances too.
+    pub fn solve_three_objective(
+        &self,
+        shopping_list: &ShoppingList,
+        shopper_location: Location,
+        customer_location: Location,
+    ) -> Vec<ShoppingRoute> {
+        let skyline = self.solve_exact(shopping_list, shopper_location, customer_location);
+        // Routes with more stores than the largest skyline route are all dominated
+        let most_stores = skyline.iter().map(|route| route.stores.len()).max();
+        let mut routes = skyline.into_vec();
+
+        let mut limited = self.clone();
+        for max_stores in 1..most_stores.unwrap_or(0) {
+            limited.set_max_stores(Some(max_stores));
+            routes.extend(limited.solve_exact(shopping_list, shopper_location, customer_location));
+        }
+        three_objective_front(&routes)
+    }
+
+    /// What allowing one more store stop is worth: `(time_added, cost_saved)` going
+    /// from the cheapest route with at most `current_max_stores` stores to the cheapest
+    /// with one more
+    /// The time added is negative when the extra stop also gives a quicker route. None
+    /// if no route within `current_max_stores` fulfills the list. Built on `solve_exact`, so meant for small
+    /// instances.
+    pub fn marginal_store_tradeoff(
+        &self,
+        shopping_list: &ShoppingList,
+        shopper_location: Location,
+        customer_location: Location,
+        current_max_stores: usize,
+    ) -> Option<(f64, f64)> {
+        let mut limited = self.clone();
+        let mut cheapest_within = |max_stores: usize| {
+            limited.set_max_stores(Some(max_stores));
+            limited
+                .solve_exact(shopping_list, shopper_location, customer_location)
+                .cheapest()
+                .cloned()
+        };
+        let current = cheapest_within(current_max_stores)?;
+        let extended = cheapest_within(current_max_stores + 1)?;
+        Some((
+            extended.shopping_time - current.shopping_time,
+            current.shopping_cost - extended.shopping_cost,
+        ))
+    }
+
+    /// Same as `solve_with_parallel`, also measuring the run as a `BenchmarkResult`
+    pub fn solve_and_measure(
+        &self,
+        shopping_list: &ShoppingList,
+        shopper_location: Location,
+        customer_location: Location,
+        threshold: i32,
+    ) -> (Skyline, BenchmarkResult) {
+        let start = Instant::now();
+        let (skyline, best_route_time) = self.solve_with_parallel(
+            shopping_list,
+            shopper_location,
+            customer_location,
+            threshold,
+        );
+        let result = BenchmarkResult::new(
+            shopping_list.items.len(),
+            threshold,
+            start.elapsed(),
+            best_route_time,
+            &skyline,
+        );
+        (skyline, result)
+    }
+
+    /// Products the stores together can't supply in full, with the quantity missing,
+    /// sorted by product ID
+    pub fn missing_products(&self, shopping_list: &ShoppingList) -> Vec<(ProductId, u32)> {
+        let mut missing: Vec<(ProductId, u32)> = shopping_list
+            .items
+            .iter()
+            .filter_map(|(product_id, &quantity)| {
+                let available: u32 = self
+                    .stores
+                    .values()
+                    .map(|store| store.read().unwrap().get_inventory_level(product_id))
+                    .sum();
+                (available < quantity).then(|| (*product_id, quantity - available))
+            })
+            .collect();
+        missing.sort();
+        missing
+    }
+
+    /// Per-product breakdown of the stores able to supply the shopping list, sorted by
+    /// product ID
+    /// Lists what the cheapest-first allocation chooses from, so when a search comes
+    /// back empty the products with a `shortfall` show which one is the bottleneck.
+    pub fn coverage_report(&self, shopping_list: &ShoppingList) -> Vec<ProductCoverage> {
+        let mut report: Vec<ProductCoverage> = shopping_list
+            .items
+            .iter()
+            .map(|(&product_id, &required)| {
+                let mut stores: Vec<(StoreId, u32, Cost)> = self
+                    .product_to_stores
+                    .get(&product_id)
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|&(store_id, _)| {
+                        let store = self.stores[&store_id].read().unwrap();
+                        let inventory = store.get_inventory_level(&product_id);
+                        let cost = store.get_unit_price(&product_id)?;
+                        (inventory > 0).then_some((store_id, inventory, cost))
+                    })
+                    .collect();
+                stores.sort_by(|a, b| a.2.total_cmp(&b.2).then(a.0.cmp(&b.0)));
+                ProductCoverage {
+                    product: product_id,
+                    required,
+                    available: stores.iter().map(|&(_, inventory, _)| inventory).sum(),
+                    stores,
+                }
+            })
+            .collect();
+        report.sort_by_key(|coverage| coverage.product);
+        report
+    }
+
+    /// Listed products that no store has in stock at all, sorted by product ID
+    /// These are the products of `missing_products` missing in full; the others are
+    /// only short of the listed quantity.
+    pub fn unavailable_products(&self, shopping_list: &ShoppingList) -> Vec<ProductId> {
+        self.missing_products(shopping_list)
+            .into_iter()
+            .filter(|&(product_id, missing)| missing == shopping_list.items[&product_id])
+            .map(|(product_id, _)| product_id)
+            .collect()
+    }
+
+    /// Same as `solve_with_parallel`, but calls `on_route` with every route accepted
+    /// into the skyline as it is found, e.g. to show progress in a UI
+    /// The callback runs on the calling thread; accepted routes may later be pushed out
+    /// of the skyline by better ones.
+    pub fn solve_with_callback(
+        &self,
+        shopping_list: &ShoppingList,
+        shopper_location: Location,
+        customer_location: Location,
+        threshold: i32,
+        mut on_route: impl FnMut(&ShoppingRoute),
+    ) -> Skyline {
+        self.parallel_search(
+            shopping_list,
+            shopper_location,
+            customer_location,
+            threshold,
+            None,
+            WorkerPlan::Paired,
+            None,
+            &mut on_route,
+        )
+        .0
+    }
+
+    /// Same as `solve_with_parallel`, but stops once `cancel` is set (from any thread)
+    /// and returns the skyline found so far
+    /// Unlike reaching the cost upper bound, cancelling leaves the skyline incomplete.
+    pub fn solve_with_cancel(
+        &self,
+        shopping_list: &ShoppingList,
+        shopper_location: Location,
+        customer_location: Location,
+        threshold: i32,
+        cancel: Arc<AtomicBool>,
+    ) -> Skyline {
+        self.parallel_search(
+            shopping_list,
+            shopper_location,
+            customer_location,
+            threshold,
+            None,
+            WorkerPlan::Paired,
+            Some(cancel),
+            &mut |_| {},
+        )
+        .0
+    }
+
+    /// Same as `solve_with_parallel`, but with `num_workers` worker threads
+    /// The minimum-time route and its first generation of successors are dealt out
+    /// round-robin as seeds; odd-numbered workers expand with the shuffle generator.
+    /// With one worker the search is single-threaded and deterministic.
+    pub fn solve_with_parallel_n(
+        &self,
+        shopping_list: &ShoppingList,
+        shopper_location: Location,
+        customer_location: Location,
+        threshold: i32,
+        num_workers: usize,
+    ) -> (Skyline, Duration) {
+        self.parallel_search(
+            shopping_list,
+            shopper_location,
+            customer_location,
+            threshold,
+            None,
+            WorkerPlan::Partitioned(num_workers.max(1)),
+            None,
+            &mut |_| {},
+        )
+    }
+
+    /// Same as `solve_with_parallel`, but stops the workers once `max_duration` has
+    /// passed since the call and returns the (sorted) skyline found so far
+    pub fn solve_with_deadline(
+        &self,
+        shopping_list: &ShoppingList,
+        shopper_location: Location,
+        customer_location: Location,
+        max_duration: Duration,
+    ) -> Skyline {
+        let deadline = Instant::now() + max_duration;
+        self.parallel_search(
+            shopping_list,
+            shopper_location,
+            customer_location,
+            i32::MAX,
+            Some(deadline),
+            WorkerPlan::Paired,
+            None,
+            &mut |_| {},
+        )
+        .0
+    }
+
+    /// Multi-worker skyline search behind `solve_with_parallel` and its variants
+    /// `on_route` is called on this thread with each route accepted into the skyline;
+    /// setting `cancel` stops the search with the skyline found so far
+    #[allow(clippy::too_many_arguments)]
+    fn parallel_search(
+        &self,
+        shopping_list: &ShoppingList,
+        shopper_location: Location,
+        customer_location: Location,
+        threshold: i32,
+        deadline: Option<Instant>,
+        plan: WorkerPlan,
+        cancel: Option<Arc<AtomicBool>>,
+        on_route: &mut dyn FnMut(&ShoppingRoute),
+    ) -> (Skyline, Duration) {
+        if shopping_list.is_empty() {
+            let skyline = self.direct_skyline(&shopper_location, &customer_location);
+            on_route(&skyline[0]);
+            return (skyline, Duration::default());
+        }
+        if !self
+            .required_stores
+            .iter()
+            .all(|store_id| self.stores.contains_key(store_id))
+        {
+            println!("A required store is not on the map, aborting.");
+            return (self.new_skyline(), Duration::default());
+        }
+        let cancel = cancel.unwrap_or_default();
+        println!("Starting parallel BSL-PSD algorithm with channels...");
+        let start_time_find_best_route = std::time::Instant::now();
+        // Step 1: Find route with minimum shopping cost
+        // let min_cost_route =
+        let min_cost =
+            match self.find_min_cost_route(shopping_list, shopper_location, customer_location) {
+                Some(route) => route,
+                None => {
+                    println!("No minimum cost route found, aborting.");
+                    return (self.new_skyline(), Duration::default());
+                }
+            };
+        println!("Found_min_cost: {:?}", min_cost);
+
+        // Find route with minimum time cost
+        let min_time_route = match self.find_min_time_route_dijkstra(
+            shopping_list,
+            shopper_location,
+            customer_location,
+        ) {
+            Some(route) => route,
+            None => {
+                println!("No minimum time route found, aborting.");
+                return (self.new_skyline(), Duration::default());
+            }
+        };
+        let elapsed_limited = start_time_find_best_route.elapsed();
+        println!("Found_min_time: {:?}", min_time_route);
+
+        // let sc_upper_bound = min_cost_route.shopping_cost;
+        let sc_upper_bound = min_cost;
+        // println!(
+        //     "Found min cost route with cost: ${:.2}, sc_upper_bound: ${:.2}",
+        //     min_cost_route.shopping_cost, sc_upper_bound
+        // );
+
+        // Create termination signal
+        let found_upper_bound = Arc::new(AtomicBool::new(false));
+
+        // Create communication channel for sending found skyline routes
+        let (tx, rx) = mpsc::channel();
+
+        // A minimum-time route over the store limit can't seed the search; start from
+        // the empty route instead, whose successors are single-store routes
+        let root = if self.within_store_limit(&min_time_route.stores) {
+            RouteCandidate {
+                stores: min_time_route.stores.clone(),
+                shopping_time: min_time_route.shopping_time,
+            }
+        } else {
+            RouteCandidate {
+                stores: Vec::new(),
+                shopping_time: 0.0,
+            }
+        };
+        let workers = self.plan_workers(root, plan, &shopper_location, &customer_location);
+        // Every worker's seeds, marked as visited by all workers
+        let all_seeds: Arc<Vec<RouteCandidate>> = Arc::new(
+            workers
+                .iter()
+                .flat_map(|(seeds, _)| seeds.iter().cloned())
+                .collect(),
+        );
+        for (worker, (seeds, shuffle)) in workers.into_iter().enumerate() {
+            let all_seeds = Arc::clone(&all_seeds);
+            let self_clone = self.clone();
+            let shopping_list_clone = shopping_list.clone();
+            let found_upper_bound_clone = Arc::clone(&found_upper_bound);
+            let cancel_clone = Arc::clone(&cancel);
+            let tx = tx.clone();
+
+            thread::spawn(move || {
+                println!("Start thread {}", worker + 1);
+                self_clone.search_worker(
+                    seeds,
+                    &all_seeds,
+                    shuffle,
+                    &shopping_list_clone,
+                    &shopper_location,
+                    &customer_location,
+                    sc_upper_bound,
+                    &found_upper_bound_clone,
+                    &cancel_clone,
+                    &tx,
+                );
+            });
+        }
+        // Only the workers' senders should keep the channel open
+        drop(tx);
+
+        // Main thread processes received skyline routes
+        let mut linear_skyline = self.new_skyline();
+        // let mut last_size = 0;
+        let mut unchanged_count = 0;
+        // let max_unchanged = 10000; // Set a threshold for how many unchanged iterations before exiting
+        // Process all received routes
+        loop {
+            let received = match deadline {
+                None => rx.recv().ok(),
+                Some(deadline) if Instant::now() < deadline => {
+                    match rx.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                        Ok(route) => Some(route),
+                        Err(RecvTimeoutError::Timeout) => None,
+                        Err(RecvTimeoutError::Disconnected) => break,
+                    }
+                }
+                Some(_) => None,
+            };
+            let Some(route) = received else {
+                if deadline.is_some() {
+                    println!("Deadline reached, stopping workers");
+                    found_upper_bound.store(true, Relaxed);
+                }
+                break;
+            };
+            let old_size = linear_skyline.len();
+            let accepted = route.clone();
+            let update = linear_skyline.insert(route);
+            if update {
+                on_route(&accepted);
+            }
+            if cancel.load(Relaxed) {
+                println!("Search cancelled, stopping workers");
+                found_upper_bound.store(true, Relaxed);
+                break;
+            }
+            // self.update_skyline(&mut linear_skyline, route);
+            // println!("routes: {}", linear_skyline.len());
+            if linear_skyline.len() == old_size && !update {
+                // unchanged_count += 1;
+                // println!("Skyline unchanged for {} iterations", unchanged_count);
+
+                // If unchanged for multiple iterations, consider exhaustive search complete
+                if unchanged_count >= threshold {
+                    println!(
+                        "Skyline size remained at {} for {} iterations, breaking",
+                        linear_skyline.len(),
+                        threshold
+                    );
+                    found_upper_bound.store(true, Relaxed);
+                    // break;
+                }
+            } else {
+                unchanged_count = 0
+            }
+            // if linear_skyline.len() >= 15 {
+            //     println!("Already got 15 routes in optimal route set, start break");
+            //     found_upper_bound.store(true, Relaxed);
+            // }
+        }
+
+        println!("Final skyline size: {}", linear_skyline.len());
+        (linear_skyline, elapsed_limited)
+    }
+
+    /// Debug version of solve function with logging and timeout
+    pub fn solve_with_debug(
+        &self,
+        shopping_list: &ShoppingList,
+        shopper_location: Location,
+        customer_location: Location,
+        threshold: i32,
+    ) -> Skyline {
+        self.run_debug_search(
+            shopping_list,
+            shopper_location,
+            customer_location,
+            threshold,
+            None,
+        )
+    }
+
+    /// Solves with `solve_with_parallel` and keeps at most `k` routes spread evenly
+    /// across the time-cost frontier (see `skyline::select_spread`)
+    pub fn solve_top_k(
+        &self,
+        shopping_list: &ShoppingList,
+        shopper_location: Location,
+        customer_location: Location,
+        k: usize,
+        threshold: i32,
+    ) -> Skyline {
+        let (mut skyline, _) = self.solve_with_parallel(
+            shopping_list,
+            shopper_location,
+            customer_location,
+            threshold,
+        );
+        let spread = select_spread(&skyline, k);
+        skyline.retain(|route| spread.contains(route));
+        skyline
+    }
+
+    /// Finds the single route minimizing `time_weight * time + cost_weight * cost`
+    ///
+    /// Weights are normalized to sum to 1, so (1, 0) gives the min-time route and
+    /// (0, 1) the min-cost route. The minimum of a non-negative weighted sum always
+    /// lies on the skyline, so the search runs as usual and only the best route is
+    /// kept. Returns None for negative weights, zero total weight, or no solution.
+    pub fn solve_weighted(
+        &self,
+        shopping_list: &ShoppingList,
+        shopper_location: Location,
+        customer_location: Location,
+        time_weight: f64,
+        cost_weight: f64,
+    ) -> Option<ShoppingRoute> {
+        let total_weight = time_weight + cost_weight;
+        if time_weight < 0.0 || cost_weight < 0.0 || total_weight <= 0.0 {
+            return None;
+        }
+        let time_weight = time_weight / total_weight;
+        let cost_weight = cost_weight / total_weight;
+        let objective = |route: &ShoppingRoute| {
+            time_weight * route.shopping_time + cost_weight * route.shopping_cost
+        };
+
+        // The skyline is sorted by time, so ties go to the faster route
+        let skyline =
+            self.solve_with_debug(shopping_list, shopper_location, customer_location, 10000);
+        skyline.into_iter().reduce(|best, route| {
+            if objective(&route) < objective(&best) {
+                route
+            } else {
+                best
+            }
+        })
+    }
+
+    /// Total weight of a shopping list: quantity times unit weight, summed over products
+    /// Where stores list different weights for a product the heaviest is used. The
+    /// weight doesn't depend on where the products are bought.
+    pub fn shopping_list_weight(&self, shopping_list: &ShoppingList) -> f64 {
+        shopping_list
+            .items
+            .iter()
+            .map(|(product_id, &quantity)| {
+                let unit_weight = self
+                    .stores
+                    .values()
+                    .filter_map(|store| store.read().unwrap().get_product_weight(product_id))
+                    .fold(0.0, f64::max);
+                unit_weight * quantity as f64
+            })
+            .sum()
+    }
+
+    /// Solves for the skyline routes of a shopper who can carry at most `max_weight`
+    /// Every route buys the whole list, so either all routes fit or none do; the
+    /// skyline is empty when the list is heavier than `max_weight`.
+    pub fn solve_with_capacity(
+        &self,
+        shopping_list: &ShoppingList,
+        shopper_location: Location,
+        customer_location: Location,
+        max_weight: f64,
+        threshold: i32,
+    ) -> Skyline {
+        if self.shopping_list_weight(shopping_list) > max_weight {
+            return self.new_skyline();
+        }
+        self.solve_with_debug(
+            shopping_list,
+            shopper_location,
+            customer_location,
+            threshold,
+        )
+    }
+
+    /// Solves for the skyline routes costing at most `max_cost`, sorted by time
+    /// Returns an empty vector when even the cheapest route is over budget
+    pub fn solve_with_budget(
+        &self,
+        shopping_list: &ShoppingList,
+        shopper_location: Location,
+        customer_location: Location,
+        max_cost: Cost,
+        threshold: i32,
+    ) -> Skyline {
+        // The minimum cost is a lower bound for every route
+        match self.find_min_cost_route(shopping_list, shopper_location, customer_location) {
+            Some(min_cost) if min_cost <= max_cost => {}
+            _ => return self.new_skyline(),
+        }
+
+        // A route can only be dominated by one at most as expensive, so dropping the
+        // unaffordable routes leaves a valid skyline of the affordable ones
+        let mut skyline = self.solve_with_debug(
+            shopping_list,
+            shopper_location,
+            customer_location,
+            threshold,
+        );
+        skyline.retain(|route| route.shopping_cost <= max_cost);
+        skyline
+    }
+
+    /// Solves several orders that share store inventory, one after another
+    ///
+    /// Lists are processed in descending `priority`; lists with equal priority keep
+    /// their order in `lists`. For each list the fastest skyline route is reserved
+    /// with `reserve_inventory` before the next list is solved, so later lists see the
+    /// depleted stock. A list that can no longer be fulfilled gets an empty skyline.
+    /// Results are returned in the order of `lists`, and the reservations stay applied.
+    pub fn solve_multi(
+        &self,
+        lists: &[ShoppingList],
+        shopper_location: Location,
+        customer_location: Location,
+    ) -> Vec<Skyline> {
+        let mut order: Vec<usize> = (0..lists.len()).collect();
+        // Stable sort keeps input order among equal priorities
+        order.sort_by(|&a, &b| lists[b].priority.cmp(&lists[a].priority));
+
+        let mut results = vec![self.new_skyline(); lists.len()];
+        for index in order {
+            let shopping_list = &lists[index];
+            let skyline = self.solve(shopping_list, shopper_location, customer_location);
+
+            // Skyline routes are sorted by time, so the first reservable one is the fastest
+            match skyline
+                .iter()
+                .find(|route| self.apply_route(route, shopping_list))
+            {
+                Some(_) => results[index] = skyline,
+                None => println!(
+                    "WARNING: shopping list {} (priority {}) cannot be fulfilled",
+                    index, shopping_list.priority
+                ),
+            }
+        }
+
+        results
+    }
+
+    /// Solves for a shopper setting off at `start_minute` (minutes from midnight),
+    /// respecting store opening hours. Shopping times are taken to be in minutes.
+    ///
+    /// Routes reaching a closed store are dropped, or wait for it to open when
+    /// `set_wait_for_opening(true)` was called. Returned times include any waiting.
+    pub fn solve_with_start_time(
+        &self,
+        shopping_list: &ShoppingList,
+        shopper_location: Location,
+        customer_location: Location,
+        start_minute: f64,
+    ) -> Skyline {
+        let mut solver = self.clone();
+        solver.start_minute = Some(start_minute);
+        solver.solve_with_debug(shopping_list, shopper_location, customer_location, 10000)
+    }
+
+    /// Solves for a courier who returns to `depot` after the delivery
+    ///
+    /// Every route's time includes the leg from the customer to the depot (measured
+    /// with the solver's distance metric). The leg is the same for all routes, so the
+    /// skyline's routes don't change, only their times.
+    pub fn solve_round_trip(
+        &self,
+        shopping_list: &ShoppingList,
+        shopper_location: Location,
+        customer_location: Location,
+        depot: Location,
+    ) -> Skyline {
+        let mut solver = self.clone();
+        solver.return_location = Some(depot);
+        solver
+            .solve_with_parallel(shopping_list, shopper_location, customer_location, 10000)
+            .0
+    }
+
+    /// Same search as `solve_with_debug`, additionally recording every expanded
+    /// candidate route and its parent in a `SearchTrace`
+    pub fn solve_with_trace(
+        &self,
+        shopping_list: &ShoppingList,
+        shopper_location: Location,
+        customer_location: Location,
+        threshold: i32,
+    ) -> (Skyline, SearchTrace) {
+        let mut trace = SearchTrace::default();
+        let skyline = self.run_debug_search(
+            shopping_list,
+            shopper_location,
+            customer_location,
+            threshold,
+            Some(&mut trace),
+        );
+        (skyline, trace)
+    }
+
+    /// Sequential BSL-PSD search shared by `solve_with_debug` and `solve_with_trace`
+    fn run_debug_search(
+        &self,
+        shopping_list: &ShoppingList,
+        shopper_location: Location,
+        customer_location: Location,
+        threshold: i32,
+        mut trace: Option<&mut SearchTrace>,
+    ) -> Skyline {
+        println!("Starting BSL-PSD algorithm with debug mode...");
+        if shopping_list.is_empty() {
+            return self.direct_skyline(&shopper_location, &customer_location);
+        }
+
+        // Step 1: Find route with minimum shopping cost
+        let min_cost_route =
+            match self.find_min_cost_route(shopping_list, shopper_location, customer_location) {
+                Some(route) => route,
+                None => {
+                    println!("No minimum cost route found, aborting.");
+                    return self.new_skyline(); // No solution available
+                }
+            };
+        println!("min cost route: {:?}", min_cost_route);
+
+        // Find route with minimum time cost
+        let min_time_route = match self.find_min_time_route_dijkstra(
+            shopping_list,
+            shopper_location,
+            customer_location,
+        ) {
+            Some(route) => route,
+            None => {
+                println!("No minimum time route found, aborting.");
+                return self.new_skyline();
+            }
+        };
+        println!("min time route: {:?}", min_time_route);
+
+        // println!(
+        //     "Found min cost route: {:?} with cost: {}, sc_upper_bound: {}",
+        //     min_time_route.stores, min_time_route.shopping_cost, min_cost_route.shopping_cost
+        // );
+
+        // let sc_upper_bound = min_cost_route.shopping_cost;
+        let sc_upper_bound = min_cost_route;
+
+        // Step 2: Initialize priority queue and linear skyline
+        let mut queue = BinaryHeap::new();
+        let mut linear_skyline = self.new_skyline();
+
+        let mut visited_routes = HashSet::new();
+
+        // As in `parallel_search`, a minimum-time route over the store limit can't seed
+        // the search, so it starts from the empty route instead
+        if self.within_store_limit(&min_time_route.stores) {
+            queue.push(RouteCandidate {
+                stores: min_time_route.stores.clone(),
+                shopping_time: min_time_route.shopping_time,
+            });
+        } else {
+            queue.push(RouteCandidate {
+                stores: Vec::new(),
+                shopping_time: 0.0,
+            });
+        }
+
+        println!("Initial queue size: {}", queue.len());
+        // let mut last_size = 0;
+        let mut unchanged_count = 0;
+        // let max_unchanged = 10000;
+
+        // Trace node that generated each queued route (only used when tracing)
+        let mut pending_parents: HashMap<Vec<StoreId>, usize> = HashMap::new();
+
+        while let Some(route_candidate) = queue.pop() {
+            // Record the expanded candidate in the trace
+            let trace_node = trace.as_deref_mut().map(|trace| {
+                let parent = pending_parents.remove(&route_candidate.stores);
+                trace.push(parent, &route_candidate)
+            });
+
+            // Only consider the route if it satisfies the shopping list within the store
+            // limit
+            let satisfies = self.within_store_limit(&route_candidate.stores)
+                && self.satisfies_list_with_inventory(&route_candidate.stores, shopping_list);
+
+            // With opening hours the incremental time is re-computed, and routes that
+            // reach a closed store are expanded but never kept
+            let shopping_time = match self.start_minute {
+                Some(_) => self.calculate_total_time(
+                    &route_candidate.stores,
+                    &shopper_location,
+                    &customer_location,
+                ),
+                None => route_candidate.shopping_time,
+            };
+            let open_on_arrival = self.start_minute.is_none() || shopping_time.is_finite();
+
+            if satisfies && open_on_arrival {
+                // Calculate actual shopping cost now that we know the route satisfies the list
+                let shopping_cost =
+                    self.calculate_shopping_cost(&route_candidate.stores, shopping_list);
+
+                // Create a complete ShoppingRoute with shopping cost
+                let shopping_route = ShoppingRoute {
+                    stores: route_candidate.stores.clone(),
+                    shopping_time,
+                    shopping_cost,
+                };
+                // println!("shopping route: {:?}", shopping_route);
+
+                // Update linear skyline with the new route
+                let old_size = linear_skyline.len();
+                let update = linear_skyline.insert(shopping_route);
+                if let (Some(trace), Some(node)) = (trace.as_deref_mut(), trace_node) {
+                    trace.nodes[node].entered_skyline = update;
+                }
+                if linear_skyline.len() == old_size && !update {
+                    unchanged_count += 1;
+                    // println!("Skyline unchanged for {} iterations", unchanged_count);
+
+                    // If unchanged for multiple iterations, consider exhaustive search complete
+                    if unchanged_count >= threshold {
+                        println!(
+                            "Skyline size remained at {} for {} iterations, breaking",
+                            linear_skyline.len(),
+                            threshold
+                        );
+                        break;
+                    }
+                } else {
+                    unchanged_count = 0
+                }
+                // if self.update_skyline(&mut linear_skyline, shopping_route) {
+                //     println!(
+                //         "Found skyline route: {:?}, cost: ${:.2}, time: {:.2}",
+                //         route_candidate.stores, shopping_cost, route_candidate.shopping_time
+                //     );
+                // }
+                if shopping_cost == sc_upper_bound {
+                    println!("Found the sc_upper_bound skyline routes! Exist");
+                    break;
+                }
+            }
+            // Always generate next routes and add to queue
+            let mut next_routes =
+                self.generate_next_routes(&route_candidate, &shopper_location, &customer_location);
+
+            next_routes.retain(|route| {
+                let is_new_route = !visited_routes.contains(&route.stores);
+                if is_new_route {
+                    visited_routes.insert(route.stores.clone());
+                }
+                is_new_route
+            });
+            for next_route in next_routes {
+                // println!("next route: {:?}", next_route);
+                if let Some(node) = trace_node {
+                    pending_parents.insert(next_route.stores.clone(), node);
+                }
+                queue.push(next_route);
+            }
+        }
+
+        println!("Final skyline size: {}", linear_skyline.len());
+
+        linear_skyline
+    }
+
+    /// Fills in missing travel times so every ordered pair of stores has one
+    ///
+    /// A missing time is taken from the reverse direction when that is known, and
+    /// otherwise from the straight-line distance between the stores (in metres when a
+    /// road graph is attached), as the matrix holds distances before the speed applies.
+    /// Pairs whose directions disagree are reported with a warning, as that is often a
+    /// data error, but left as they are: the solver follows each direction's own time.
+    pub fn repair_travel_times(&mut self) -> TravelTimeRepair {
+        let mut store_ids: Vec<StoreId> = self.stores.keys().cloned().collect();
+        store_ids.sort_unstable();
+        let mut report = TravelTimeRepair::default();
+        let mut filled = Vec::new();
+        let straight_line = |from: StoreId, to: StoreId| {
+            let from = self.stores[&from].read().unwrap().location;
+            let to = self.stores[&to].read().unwrap().location;
+            self.straight_line_distance(&from, &to)
+        };
+
+        for (i, &from) in store_ids.iter().enumerate() {
+            for &to in &store_ids[i + 1..] {
+                let forward = self.travel_times.get(&(from, to)).copied();
+                let backward = self.travel_times.get(&(to, from)).copied();
+                match (forward, backward) {
+                    (Some(forward), Some(backward)) => {
+                        if forward != backward {
+                            println!(
+                                "WARNING: Asymmetric travel times: {} -> {} is {}, {} -> {} is {}",
+                                from, to, forward, to, from, backward
+                            );
+                            report.asymmetric += 1;
+                        }
+                    }
+                    (Some(forward), None) => {
+                        filled.push(((to, from), forward));
+                        report.filled_from_reverse += 1;
+                    }
+                    (None, Some(backward)) => {
+                        filled.push(((from, to), backward));
+                        report.filled_from_reverse += 1;
+                    }
+                    (None, None) => {
+                        filled.push(((from, to), straight_line(from, to)));
+                        filled.push(((to, from), straight_line(to, from)));
+                        report.filled_with_distance += 2;
+                    }
+                }
+            }
+        }
+
+        self.travel_times.extend(filled);
+        report
+    }
+
+    /// Verify that travel times are correctly precomputed for all store pairs
+    pub fn verify_travel_times(&self) -> bool {
+        let store_ids: Vec<StoreId> = self.stores.keys().cloned().collect();
+        let mut missing_pairs = Vec::new();
+        let mut infinity_pairs = Vec::new();
+
+        for i in 0..store_ids.len() {
+            for j in 0..store_ids.len() {
+                if i != j {
+                    let from = store_ids[i];
+                    let to = store_ids[j];
+
+                    match self.travel_times.get(&(from, to)) {
+                        None => {
+                            missing_pairs.push((from, to));
+                        }
+                        Some(&time) => {
+                            if time.is_infinite() || time.is_nan() {
+                                infinity_pairs.push((from, to, time));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        if !missing_pairs.is_empty() {
+            println!(
+                "WARNING: Missing travel times for {} store pairs:",
+                missing_pairs.len()
+            );
+            for (from, to) in missing_pairs.iter().take(10) {
+                println!("  - Travel time missing: {} -> {}", from, to);
+            }
+            if missing_pairs.len() > 10 {
+                println!("  - ... and {} more", missing_pairs.len() - 10);
+            }
+        }
+
+        if !infinity_pairs.is_empty() {
+            println!(
+                "WARNING: Infinite/NaN travel times for {} store pairs:",
+                infinity_pairs.len()
+            );
+            for (from, to, time) in infinity_pairs.iter().take(10) {
+                println!("  - Problematic travel time: {} -> {} = {}", from, to, time);
+            }
+            if infinity_pairs.len() > 10 {
+                println!("  - ... and {} more", infinity_pairs.len() - 10);
+            }
+        }
+
+        missing_pairs.is_empty() && infinity_pairs.is_empty()
+    }
+}
+// Implementation of the PSDSolver trait for BSLPSD
+impl PSDSolver for BSLPSD {
+    /// Main BSL-PSD algorithm to solve the Personal Shopper's Dilemma
+    /// with inventory constraints
+    fn solve(
+        &self,
+        shopping_list: &ShoppingList,
+        shopper_location: Location,
+        customer_location: Location,
+    ) -> Skyline {
+        // Use a version with safety checks, limiting max iterations to 10000
+        self.solve_with_debug(shopping_list, shopper_location, customer_location, 10000)
+    }
+
+    /// Runs the parallel search (`solve_with_parallel`, or `solve_with_parallel_n` when
+    /// a worker count is given), stopping at the deadline if one is set and searching
+    /// only the nearest stores if `nearest_stores` is set (see `with_nearest_stores`)
+    /// Forbidden stores and stores outside `category` are left out of the search, and
+    /// required stores kept in it whatever the other filters say (see
+    /// `set_required_stores`).
+    fn solve_skyline(
+        &self,
+        shopping_list: &ShoppingList,
+        shopper_location: Location,
+        customer_location: Location,
+        options: SolveOptions,
+    ) -> Skyline {
+        let plan = match options.workers {
+            Some(workers) => WorkerPlan::Partitioned(workers.max(1)),
+            None => WorkerPlan::Paired,
+        };
+        let mut keep = options.nearest_stores.map(|count| {
+            self.nearest_store_ids(shopping_list, &shopper_location, &customer_location, count)
+        });
+        if let Some(category) = &options.category {
+            let in_category = self.stores_in_category(category);
+            let kept = keep.get_or_insert_with(|| self.stores.keys().copied().collect());
+            kept.retain(|store_id| in_category.contains(store_id));
+        }
+        if !options.forbidden_stores.is_empty() || !options.required_stores.is_empty() {
+            let kept = keep.get_or_insert_with(|| self.stores.keys().copied().collect());
+            kept.extend(&options.required_stores);
+            kept.retain(|store_id| !options.forbidden_stores.contains(store_id));
+        }
+        let mut restricted = keep.map(|keep| self.with_store_subset(&keep));
+        if !options.required_stores.is_empty() {
+            restricted
+                .get_or_insert_with(|| self.clone())
+                .set_required_stores(options.required_stores);
+        }
+        let solver = restricted.as_ref().unwrap_or(self);
+        let (mut skyline, _) = solver.parallel_search(
+            shopping_list,
+            shopper_location,
+            customer_location,
+            options.threshold,
+            options.deadline.map(|deadline| Instant::now() + deadline),
+            plan,
+            None,
+            &mut |_| {},
+        );
+        if let Some(max_cost) = options.max_cost {
+            skyline.retain(|route| route.shopping_cost <= max_cost);
+        }
+        skyline
+    }
+
+    /// Checks if a route satisfies a shopping list
+    fn satisfies_list(&self, route: &[StoreId], shopping_list: &ShoppingList) -> bool {
+        self.satisfies_list_with_inventory(route, shopping_list)
+    }
+
+    /// Calculate shopping time for a route
+    fn calculate_shopping_time(
+        &self,
+        route: &[StoreId],
+        shopper_location: Location,
+        customer_location: Location,
+    ) -> f64 {
+        if route.is_empty() {
+            return 0.0;
+        }
+
+        let mut total_time = 0.0;
+
+        // Time from shopper to first store
+        if let Some(first_store_id) = route.first() {
+            let first_store = self.stores[first_store_id].read().unwrap();
+            total_time += self.endpoint_leg(&shopper_location, &first_store);
+            total_time = match self.visit_store(&first_store, total_time) {
+                Some(time) => time,
+                None => return f64::INFINITY,
+            };
+        }
+
+        // Time between stores
+        for i in 0..route.len() - 1 {
+            let time = self
+                .travel_time(route[i], route[i + 1])
+                .unwrap_or(f64::INFINITY);
+            total_time += time;
+
+            let to_store = self.stores[&route[i + 1]].read().unwrap();
+            total_time = match self.visit_store(&to_store, total_time) {
+                Some(time) => time,
+                None => return f64::INFINITY,
+            };
+        }
+
+        // Time from last store to customer, and on to the depot if there is one
+        if let Some(last_store_id) = route.last() {
+            let last_store = self.stores[last_store_id].read().unwrap();
+            total_time += self.customer_leg(&last_store, &customer_location);
+            total_time += self.return_leg(&customer_location);
+        }
+
+        total_time
+    }
+
+    /// Calculate shopping cost for a route
+    fn calculate_shopping_cost(&self, route: &[StoreId], shopping_list: &ShoppingList) -> f64 {
+        let Some(cache) = &self.cost_cache else {
+            return self.uncached_shopping_cost(route, shopping_list);
+        };
+
+        // The cost only depends on which stores are visited, not their order
+        let mut store_set = route.to_vec();
+        store_set.sort();
+        store_set.dedup();
+        let key = (store_set, sorted_items(shopping_list));
+        if let Some(&cost) = cache.lock().unwrap().get(&key) {
+            return cost;
+        }
+
+        let cost = self.uncached_shopping_cost(route, shopping_list);
+        cache.lock().unwrap().insert(key, cost);
+        cost
+    }
+}
+
+/// Shopping list items in a canonical order, used to match reservations
+fn sorted_items(shopping_list: &ShoppingList) -> Vec<(ProductId, u32)> {
+    let mut items: Vec<(ProductId, u32)> = shopping_list
+        .items
+        .iter()
+        .map(|(product_id, quantity)| (*product_id, *quantity))
+        .collect();
+    items.sort();
+    items
+}
+
+/// A planned purchase: (store, product, quantity)
+type Purchase = (StoreId, ProductId, u32);
+
+// Whether a route visits the same store twice in a row
+fn has_repeated_stop(stores: &[StoreId]) -> bool {
+    stores.windows(2).any(|pair| pair[0] == pair[1])
+}
+
+// Orders a product's inverted list by price, then store ID, so the order doesn't depend
+// on the order stores were visited in
+fn sort_by_price(stores: &mut [(StoreId, Cost)]) {
+    stores.sort_by(|a, b| a.1.total_cmp(&b.1).then(a.0.cmp(&b.0)));
+}
+
+/// Allocates each listed product to the cheapest stores first
+/// `product_options` holds (store, unit price, available quantity) per product
+/// Returns the total cost and the purchases, or None if some product can't be covered
+fn allocate_cheapest_first(
+    mut product_options: HashMap<ProductId, Vec<(StoreId, Cost, u32)>>,
+    shopping_list: &ShoppingList,
+) -> Option<(Cost, Vec<Purchase>)> {
+    // Sort options for each product by price (cheapest first), then store ID
+    for options in product_options.values_mut() {
+        options.sort_by(|a, b| a.1.total_cmp(&b.1).then(a.0.cmp(&b.0)));
+    }
+
+    // Now allocate purchases optimally
+    let mut total_cost = 0.0;
+    let mut purchases = Vec::new();
+    for (product_id, qty_needed) in &shopping_list.items {
+        let mut remaining_qty = *qty_needed;
+
+        if let Some(options) = product_options.get(product_id) {
+            // Buy from cheapest store first
+            for &(store_id, cost, available_qty) in options {
+                let purchase_qty = std::cmp::min(available_qty, remaining_qty);
+                if purchase_qty > 0 {
+                    total_cost += cost * purchase_qty as f64;
+                    remaining_qty = remaining_qty.saturating_sub(purchase_qty);
+                    purchases.push((store_id, *product_id, purchase_qty));
+
+                    if remaining_qty == 0 {
+                        break;
+                    }
+                }
+            }
+        }
+
+        // If we couldn't buy all needed quantity
+        if remaining_qty > 0 {
+            return None;
+        }
+        debug_assert_eq!(
+            purchases
+                .iter()
+                .filter(|(_, purchased, _)| purchased == product_id)
+                .map(|&(_, _, quantity)| quantity)
+                .sum::<u32>(),
+            *qty_needed
+        );
+    }
+
+    Some((total_cost, purchases))
+}
+
+/// Step-by-step configuration of a `BSLPSD` solver and the search settings its
+/// `solve` uses
+///
+/// ```ignore
+/// let solver = BSLPSDBuilder::new(stores)
+///     .travel_times(travel_times)
+///     .speed(500.0)
+///     .workers(4)
+///     .max_cost(120.0)
+///     .build();
+/// let skyline = solver.solve(&list, shopper, customer);
+/// ```
+#[derive(Debug, Clone)]
+pub struct BSLPSDBuilder {
+    stores: HashMap<StoreId, Store>,
+    travel_times: HashMap<(StoreId, StoreId), f64>,
+    distance_metric: DistanceMetric,
+    speed_m_per_min: f64,
+    options: SolveOptions,
+}
+
+impl BSLPSDBuilder {
+    /// Starts from the given stores, no travel times and the default settings
+    pub fn new(stores: HashMap<StoreId, Store>) -> Self {
+        Self {
+            stores,
+            travel_times: HashMap::new(),
+            distance_metric: DistanceMetric::default(),
+            speed_m_per_min: 1.0,
+            options: SolveOptions::default(),
+        }
+    }
+
+    /// Travel times between store pairs (see `BSLPSD::new_with_travel_times`)
+    pub fn travel_times(mut self, travel_times: HashMap<(StoreId, StoreId), f64>) -> Self {
+        self.travel_times = travel_times;
+        self
+    }
+
+    /// Metric for straight-line legs (see `BSLPSD::set_distance_metric`)
+    pub fn distance_metric(mut self, metric: DistanceMetric) -> Self {
+        self.distance_metric = metric;
+        self
+    }
+
+    /// Shopper speed in metres per minute (see `BSLPSD::set_speed`)
+    pub fn speed(mut self, speed_m_per_min: f64) -> Self {
+        self.speed_m_per_min = speed_m_per_min;
+        self
+    }
+
+    /// Number of search worker threads
+    pub fn workers(mut self, workers: usize) -> Self {
+        self.options.workers = Some(workers);
+        self
+    }
+
+    /// Skyline updates without change before the search gives up
+    pub fn threshold(mut self, threshold: i32) -> Self {
+        self.options.threshold = threshold;
+        self
+    }
+
+    /// Only keep routes costing at most `max_cost`
+    pub fn max_cost(mut self, max_cost: Cost) -> Self {
+        self.options.max_cost = Some(max_cost);
+        self
+    }
+
+    /// Stop searching after `deadline` and return the routes found so far
+    pub fn deadline(mut self, deadline: Duration) -> Self {
+        self.options.deadline = Some(deadline);
+        self
+    }
+
+    /// Creates the solver and precomputes its product index
+    pub fn build(self) -> ConfiguredSolver {
+        let mut solver = BSLPSD::new_with_travel_times(self.stores, self.travel_times);
+        solver.set_distance_metric(self.distance_metric);
+        solver.set_speed(self.speed_m_per_min);
+        solver.precompute_data();
+        ConfiguredSolver {
+            solver,
+            options: self.options,
+        }
+    }
+}
+
+/// A `BSLPSD` solver together with the search settings chosen on its builder
+/// Dereferences to the solver, so its other methods stay available.
+#[derive(Clone)]
+pub struct ConfiguredSolver {
+    solver: BSLPSD,
+    options: SolveOptions,
+}
+
+impl ConfiguredSolver {
+    /// Solves with the configured search settings (see `PSDSolver::solve_skyline`)
+    pub fn solve(
+        &self,
+        shopping_list: &ShoppingList,
+        shopper_location: Location,
+        customer_location: Location,
+    ) -> Skyline {
+        self.solver.solve_skyline(
+            shopping_list,
+            shopper_location,
+            customer_location,
+            self.options.clone(),
+        )
+    }
+
+    /// Search settings `solve` uses
+    pub fn options(&self) -> &SolveOptions {
+        &self.options
+    }
+
+    /// Gives back the solver, dropping the search settings
+    pub fn into_inner(self) -> BSLPSD {
+        self.solver
+    }
+}
+
+impl Deref for ConfiguredSolver {
+    type Target = BSLPSD;
+
+    fn deref(&self) -> &BSLPSD {
+        &self.solver
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::road_network::{EdgeDirection, RoadEdge, DEFAULT_SPEED_KMH};
+    use crate::Product;
+    use rand::rngs::StdRng;
+    use rand::seq::SliceRandom;
+    use rand::{Rng, SeedableRng};
+
+    // Create test data with inventory constraints
+    fn create_test_data() -> (HashMap<StoreId, Store>, ShoppingList) {
+        let mut stores = HashMap::new();
+
+        // Store 1
+        let mut store1_products = HashMap::new();
+        store1_products.insert(1, Product::new("A", 7.0));
+        store1_products.insert(2, Product::new("B", 8.0));
+        store1_products.insert(6, Product::new("F", 10.0));
+
+        let mut inventory1 = HashMap::new();
+        inventory1.insert(1, 5); // 5 units of Product A
+        inventory1.insert(2, 3); // 3 units of Product B
+        inventory1.insert(6, 8); // 8 units of Product F
+
+        stores.insert(
+            1,
+            Store::new_with_inventory(1, Location::new(10.0, 6.0), store1_products, inventory1),
+        );
+
+        // Store 2
+        let mut store2_products = HashMap::new();
+        store2_products.insert(3, Product::new("C", 10.0));
+        store2_products.insert(4, Product::new("D", 8.0));
+        store2_products.insert(5, Product::new("E", 10.0));
+
+        let mut inventory2 = HashMap::new();
+        inventory2.insert(3, 4); // 4 units of Product C
+        inventory2.insert(4, 6); // 6 units of Product D
+        inventory2.insert(5, 2); // 2 units of Product E
+
+        stores.insert(
+            2,
+            Store::new_with_inventory(2, Location::new(12.0, 20.0), store2_products, inventory2),
+        );
+
+        // Store 3
+        let mut store3_products = HashMap::new();
+        store3_products.insert(3, Product::new("C", 5.0));
+        store3_products.insert(4, Product::new("D", 4.0));
+        store3_products.insert(6, Product::new("F", 6.0));
+
+        let mut inventory3 = HashMap::new();
+        inventory3.insert(3, 3); // 3 units of Product C
+        inventory3.insert(4, 2); // 2 units of Product D
+        inventory3.insert(6, 5); // 5 units of Product F
+
+        stores.insert(
+            3,
+            Store::new_with_inventory(3, Location::new(20.0, 18.0), store3_products, inventory3),
+        );
+
+        // Store 4
+        let mut store4_products = HashMap::new();
+        store4_products.insert(3, Product::new("C", 8.0));
+        store4_products.insert(4, Product::new("D", 7.0));
+        store4_products.insert(6, Product::new("F", 12.0));
+
+        let mut inventory4 = HashMap::new();
+        inventory4.insert(3, 7); // 7 units of Product C
+        inventory4.insert(4, 4); // 4 units of Product D
+        inventory4.insert(6, 3); // 3 units of Product F
+
+        stores.insert(
+            4,
+            Store::new_with_inventory(4, Location::new(15.0, 22.0), store4_products, inventory4),
+        );
+
+        // Store 5
+        let mut store5_products = HashMap::new();
+        store5_products.insert(1, Product::new("A", 6.0));
+        store5_products.insert(2, Product::new("B", 7.0));
+        store5_products.insert(5, Product::new("E", 8.0));
+
+        let mut inventory5 = HashMap::new();
+        inventory5.insert(1, 3); // 3 units of Product A
+        inventory5.insert(2, 5); // 5 units of Product B
+        inventory5.insert(5, 4); // 4 units of Product E
+
+        stores.insert(
+            5,
+            Store::new_with_inventory(5, Location::new(10.0, 15.0), store5_products, inventory5),
+        );
+
+        // Create shopping list with quantities
+        let mut shopping_list = ShoppingList::new();
+        shopping_list.add_item(1, 2); // 2 units of A
+        shopping_list.add_item(2, 1); // 1 unit of B
+        shopping_list.add_item(3, 3); // 3 units of C
+        shopping_list.add_item(4, 2); // 2 units of D
+
+        (stores, shopping_list)
+    }
+
+    // Build a precomputed solver over the test data with Euclidean travel times
+    fn create_test_solver() -> (BSLPSD, ShoppingList) {
+        let (stores, shopping_list) = create_test_data();
 
-                // For initial route, calculate from shopper location (handled in solve() method)
-                next_routes.push(RouteCandidate {
-                    stores: new_route,
-                    shopping_time: 0.0,
-                });
+        let mut travel_times = HashMap::new();
+        for (&from, from_store) in &stores {
+            for (&to, to_store) in &stores {
+                if from != to {
+                    let time = from_store.location.distance_to(&to_store.location);
+                    travel_times.insert((from, to), time);
+                }
             }
         }
 
-        // Case 2: Replace the last store (θp in the paper)
-        if route.stores.len() >= 1 {
-            let last_store = route.stores.last().unwrap();
+        let mut bsl_psd = BSLPSD::new_with_travel_times(stores, travel_times);
 
-            // Remove last store from visited set for consideration of replacements
-            visited_stores.remove(last_store);
+        bsl_psd.set_distance_metric(DistanceMetric::Euclidean);
+        bsl_psd.precompute_data();
 
-            if let Some(second_last_store) = route
-                .stores
-                .get(route.stores.len().saturating_sub(2))
-                .cloned()
-            {
-                if let Some(next_min_detour_store) = self.find_next_min_detour_store(
-                    Some(second_last_store),
-                    &visited_stores,
-                    *last_store,
-                ) {
-                    let mut new_route = route.stores.clone();
-                    new_route.pop(); // Remove last store
-                    new_route.push(next_min_detour_store);
+        (bsl_psd, shopping_list)
+    }
 
-                    // Calculate new shopping time by removing last detour and adding new one
-                    let old_detour = self
-                        .travel_times
-                        .get(&(second_last_store, *last_store))
-                        .cloned()
-                        .unwrap_or(0.0);
+    #[test]
+    fn test_inventory_tracking() {
+        let (bsl_psd, shopping_list) = create_test_solver();
 
-                    let new_detour = self
-                        .travel_times
-                        .get(&(second_last_store, next_min_detour_store))
-                        .cloned()
-                        .unwrap_or(f64::INFINITY);
+        // Test finding routes with inventory constraints
+        let route = vec![1, 2, 3]; // Contains stores with all needed products
+        assert!(bsl_psd.satisfies_list_with_inventory(&route, &shopping_list));
 
-                    let new_time = route.shopping_time - old_detour + new_detour;
+        // Test when inventory is insufficient
+        let mut limited_list = ShoppingList::new();
+        limited_list.add_item(1, 10); // More A than available in any store
+        assert!(!bsl_psd.satisfies_list_with_inventory(&route, &limited_list));
 
-                    next_routes.push(RouteCandidate {
-                        stores: new_route,
-                        shopping_time: new_time,
-                    });
-                }
-            }
-        }
+        // Test inventory deduction
+        assert!(bsl_psd.reserve_inventory(&route, &shopping_list));
 
-        next_routes
+        // Test cost calculation with inventory
+        let (cost, fulfilled) =
+            bsl_psd.calculate_shopping_cost_with_inventory(&route, &shopping_list);
+        assert!(fulfilled);
+        assert!(cost < f64::INFINITY);
+
+        // Release inventory for subsequent tests
+        assert!(bsl_psd.release_inventory(&route, &shopping_list));
+        assert!(!bsl_psd.release_inventory(&route, &shopping_list));
     }
-    /// Generate next routes according to the original strategy but with path optimization
-    /// Find the shortest path that visits all stores in the given set
-    /// Ensures no duplicate stores in the result
-    fn find_shortest_path(
-        &self,
-        stores: &Vec<StoreId>,
-        shopper_location: &Location,
-        customer_location: &Location,
-    ) -> Vec<StoreId> {
-        if stores.is_empty() {
-            return Vec::new();
-        }
 
-        if stores.len() == 1 {
-            return stores.clone();
-        }
+    #[test]
+    fn test_reserve_and_release_inventory() {
+        let (bsl_psd, shopping_list) = create_test_solver();
+        let initial = bsl_psd.snapshot_inventory();
+
+        // Store 3 sells C cheapest (3 units), so all 3 units of C come from there
+        let route = vec![1, 2, 3];
+        assert!(bsl_psd.reserve_inventory(&route, &shopping_list));
+        let reserved = bsl_psd.snapshot_inventory();
+        assert_eq!(reserved[&1][&1], initial[&1][&1] - 2);
+        assert_eq!(reserved[&3][&3], 0);
+        assert_eq!(reserved[&2][&3], initial[&2][&3]);
+
+        assert!(bsl_psd.release_inventory(&route, &shopping_list));
+        assert_eq!(bsl_psd.snapshot_inventory(), initial);
+
+        // A list the route can't cover leaves inventory untouched
+        let mut too_much = ShoppingList::new();
+        too_much.add_item(1, 2);
+        too_much.add_item(3, 100);
+        assert!(!bsl_psd.reserve_inventory(&route, &too_much));
+        assert_eq!(bsl_psd.snapshot_inventory(), initial);
+    }
 
-        // Remove any duplicates from the store set first
-        let mut unique_stores = Vec::new();
-        let mut seen = HashSet::new();
+    #[test]
+    fn test_solve_with_inventory() {
+        let (bsl_psd, shopping_list) = create_test_solver();
 
-        for &store_id in stores {
-            if !seen.contains(&store_id) {
-                unique_stores.push(store_id);
-                seen.insert(store_id);
-            }
-        }
+        let shopper_location = Location::new(0.0, 0.0);
+        let customer_location = Location::new(20.0, 20.0);
 
-        // For small sets of stores, we can try all permutations
-        let permutations = self.generate_permutations(unique_stores);
-        let mut best_path = Vec::new();
-        let mut min_time = f64::INFINITY;
+        let skyline = bsl_psd.solve(&shopping_list, shopper_location, customer_location);
 
-        for perm in permutations {
-            let time = self.calculate_total_time(&perm, shopper_location, customer_location);
+        // Verify that we have solutions
+        assert!(!skyline.is_empty());
 
-            if time < min_time {
-                min_time = time;
-                best_path = perm;
-            }
+        // Verify skyline properties
+        for i in 1..skyline.len() {
+            // Shopping time should increase
+            assert!(skyline[i - 1].shopping_time <= skyline[i].shopping_time);
+
+            // Shopping cost should decrease (or at least not increase)
+            assert!(skyline[i - 1].shopping_cost >= skyline[i].shopping_cost);
         }
 
-        best_path
+        // Verify that each route in the skyline satisfies inventory constraints
+        for route in &skyline {
+            let (_, fulfilled) =
+                bsl_psd.calculate_shopping_cost_with_inventory(&route.stores, &shopping_list);
+            assert!(
+                fulfilled,
+                "Route {:?} does not satisfy inventory constraints",
+                route.stores
+            );
+        }
     }
-    /// Generate all permutations of a vector of StoreId
-    fn generate_permutations(&self, stores: Vec<StoreId>) -> Vec<Vec<StoreId>> {
-        if stores.is_empty() {
-            return vec![vec![]];
+
+    #[test]
+    fn test_inventory_conflict() {
+        let (bsl_psd, shopping_list) = create_test_solver();
+
+        // Create a second shopping list that competes for the same inventory
+        let mut competing_list = ShoppingList::new();
+        competing_list.add_item(1, 4); // 4 units of A
+        competing_list.add_item(3, 6); // 6 units of C
+
+        // First, apply the first shopping list to reduce inventory
+        let shopper_location = Location::new(0.0, 0.0);
+        let customer_location = Location::new(20.0, 20.0);
+
+        let skyline1 = bsl_psd.solve(&shopping_list, shopper_location, customer_location);
+        assert!(!skyline1.is_empty());
+
+        // Apply the first route's inventory reduction
+        let first_route = &skyline1[0];
+        assert!(bsl_psd.apply_route(first_route, &shopping_list));
+
+        // Now try to solve with the competing list
+        let skyline2 = bsl_psd.solve(&competing_list, shopper_location, customer_location);
+
+        // We expect limited or no solutions due to inventory constraints
+        if !skyline2.is_empty() {
+            // If we have solutions, verify they use different stores or have adjusted for inventory
+            for route in &skyline2 {
+                let (_, fulfilled) =
+                    bsl_psd.calculate_shopping_cost_with_inventory(&route.stores, &competing_list);
+                assert!(
+                    fulfilled,
+                    "Route {:?} does not satisfy inventory constraints",
+                    route.stores
+                );
+            }
         }
+    }
+    #[test]
+    fn test_snapshot_and_restore() {
+        let (bsl_psd, shopping_list) = create_test_solver();
 
-        let mut result = Vec::new();
+        // Take a snapshot of initial inventory
+        let initial_snapshot = bsl_psd.snapshot_inventory();
 
-        for (i, &store) in stores.iter().enumerate() {
-            let mut remaining = stores.clone();
-            remaining.remove(i);
+        // Apply a route to reduce inventory
+        let route = vec![1, 2, 3];
+        assert!(bsl_psd.reserve_inventory(&route, &shopping_list));
 
-            for mut perm in self.generate_permutations(remaining) {
-                perm.insert(0, store);
-                result.push(perm);
-            }
+        // Verify inventory was reduced
+        {
+            let store1 = bsl_psd.stores[&1].read().unwrap();
+            assert!(store1.get_inventory_level(&1) < initial_snapshot[&1][&1]);
         }
 
-        result
+        // Restore inventory
+        bsl_psd.restore_inventory(initial_snapshot.clone());
+
+        // Verify inventory was restored
+        let store1_after = bsl_psd.stores[&1].read().unwrap();
+        assert_eq!(
+            store1_after.get_inventory_level(&1),
+            initial_snapshot[&1][&1]
+        );
+        drop(store1_after);
+        assert_eq!(bsl_psd.snapshot_inventory(), initial_snapshot);
+
+        // The reservation was discarded along with the inventory it took
+        assert!(!bsl_psd.release_inventory(&route, &shopping_list));
     }
-    /// Calculate the total time of a path from shopper to stores to customer
-    fn calculate_total_time(
-        &self,
-        path: &Vec<StoreId>,
-        shopper_location: &Location,
-        customer_location: &Location,
-    ) -> f64 {
-        if path.is_empty() {
-            // Direct path from shopper to customer
-            return shopper_location.distance_to(customer_location);
+
+    #[test]
+    fn test_update_skyline_with_custom_predicate() {
+        let bsl_psd = BSLPSD::new(HashMap::new());
+        let routes = vec![
+            ShoppingRoute::new(vec![1], 10.0, 50.0),
+            ShoppingRoute::new(vec![2], 10.5, 49.5),
+            ShoppingRoute::new(vec![3], 20.0, 30.0),
+            ShoppingRoute::new(vec![4], 20.4, 29.8),
+            ShoppingRoute::new(vec![5], 30.0, 10.0),
+        ];
+
+        let mut default_skyline = Vec::new();
+        for route in routes.clone() {
+            bsl_psd.update_skyline(&mut default_skyline, route);
+        }
+        assert_eq!(default_skyline.len(), 5);
+
+        // Epsilon-domination: a route dominates another if it is at most epsilon worse
+        // in each objective, which collapses near-identical trade-offs
+        let epsilon = 1.0;
+        let epsilon_dominates = |a: &ShoppingRoute, b: &ShoppingRoute| {
+            a.shopping_time <= b.shopping_time + epsilon
+                && a.shopping_cost <= b.shopping_cost + epsilon
+        };
+
+        let mut epsilon_skyline = Vec::new();
+        for route in routes {
+            bsl_psd.update_skyline_with(&mut epsilon_skyline, route, epsilon_dominates);
         }
+        assert_eq!(epsilon_skyline.len(), 3);
+        assert!(epsilon_skyline.len() < default_skyline.len());
+    }
 
-        let mut total_time = 0.0;
+    #[test]
+    fn test_solve_fastest_matches_parallel_skyline() {
+        let (bsl_psd, shopping_list) = create_test_solver();
+        let shopper_location = Location::new(0.0, 0.0);
+        let customer_location = Location::new(20.0, 20.0);
+
+        let fastest = bsl_psd
+            .solve_fastest(&shopping_list, shopper_location, customer_location)
+            .expect("test data can be fulfilled");
+        let (skyline, _) =
+            bsl_psd.solve_with_parallel(&shopping_list, shopper_location, customer_location, 10000);
+
+        assert!(!skyline.is_empty());
+        assert_eq!(fastest, skyline[0]);
+        assert!(fastest.shopping_cost.is_finite());
+    }
 
-        // Time from shopper to first store
-        let first_store = self.stores[&path[0]].read().unwrap();
-        total_time += shopper_location.distance_to(&first_store.location);
+    #[test]
+    fn test_astar_matches_dijkstra() {
+        let (bsl_psd, shopping_list) = create_test_solver();
+        let shopper_location = Location::new(0.0, 0.0);
+        let customer_location = Location::new(20.0, 20.0);
+
+        let dijkstra = bsl_psd
+            .find_min_time_route_dijkstra(&shopping_list, shopper_location, customer_location)
+            .expect("test data can be fulfilled");
+        let astar = bsl_psd
+            .find_min_time_route_astar(&shopping_list, shopper_location, customer_location)
+            .expect("test data can be fulfilled");
+
+        assert!((astar.shopping_time - dijkstra.shopping_time).abs() < 1e-9);
+        assert!(astar.shopping_cost.is_finite());
+    }
 
-        // Time between consecutive stores
-        for i in 0..path.len() - 1 {
-            if let Some(&time) = self.travel_times.get(&(path[i], path[i + 1])) {
-                total_time += time;
-            } else {
-                // If we don't have travel time data, use distance between locations
-                let from_store = self.stores[&path[i]].read().unwrap();
-                let to_store = self.stores[&path[i + 1]].read().unwrap();
-                total_time += from_store.location.distance_to(&to_store.location);
-            }
+    #[test]
+    fn test_customer_heuristic_is_a_lower_bound() {
+        let (mut bsl_psd, _) = create_test_solver();
+        // Store legs an order of magnitude slower than the endpoint legs
+        for time in bsl_psd.travel_times.values_mut() {
+            *time *= 10.0;
         }
+        let customer_location = Location::new(20.0, 20.0);
+        let mut stores: Vec<StoreId> = bsl_psd.stores.keys().cloned().collect();
+        stores.sort();
 
-        // Time from last store to customer
-        let last_store = self.stores[&path[path.len() - 1]].read().unwrap();
-        total_time += last_store.location.distance_to(customer_location);
+        let heuristic = bsl_psd.customer_heuristic(&stores, &customer_location);
 
-        total_time
+        for (index, &store_id) in stores.iter().enumerate() {
+            let store = bsl_psd.stores[&store_id].read().unwrap();
+            let leg = bsl_psd.customer_leg(&store, &customer_location);
+            assert!(heuristic[index] > 0.0);
+            assert!(heuristic[index] <= leg + 1e-9);
+        }
     }
 
-    /// Generate next routes according to the original strategy but with path optimization
-    /// Ensures no duplicate stores in routes
-    fn generate_next_routes_shuffle(
-        &self,
-        route: &RouteCandidate,
-        shopper_location: &Location,
-        customer_location: &Location,
-    ) -> Vec<RouteCandidate> {
-        let mut next_routes = Vec::new();
-        let visited_stores: HashSet<StoreId> = route.stores.iter().cloned().collect();
-
-        // First, check if the current route has duplicates and fix if needed
-        let current_route = route.stores.clone();
-        let mut has_duplicates = false;
-        let mut unique_current = Vec::new();
-        let mut seen = HashSet::new();
+    #[test]
+    fn test_memoized_shopping_costs_match() {
+        let (bsl_psd, shopping_list) = create_test_solver();
+        let mut memoized = bsl_psd.clone();
+        memoized.set_cost_memoization(true);
+        let store_ids: Vec<StoreId> = (1..=5).collect();
+        let mut rng = StdRng::seed_from_u64(785);
+
+        for _ in 0..100 {
+            let length = rng.gen_range(1..=store_ids.len());
+            let route: Vec<StoreId> = store_ids
+                .choose_multiple(&mut rng, length)
+                .cloned()
+                .collect();
 
-        for &store_id in &current_route {
-            if !seen.contains(&store_id) {
-                unique_current.push(store_id);
-                seen.insert(store_id);
-            } else {
-                has_duplicates = true;
+            let expected = bsl_psd.calculate_shopping_cost(&route, &shopping_list);
+            // Once to fill the cache, once to read it back
+            for _ in 0..2 {
+                let cost = memoized.calculate_shopping_cost(&route, &shopping_list);
+                assert!(cost == expected || (cost - expected).abs() < 1e-9);
             }
         }
+    }
 
-        // If the input route had duplicates, optimize it first
-        if has_duplicates {
-            let optimized_current =
-                self.find_shortest_path(&unique_current, shopper_location, customer_location);
-
-            let shopping_time =
-                self.calculate_total_time(&optimized_current, shopper_location, customer_location);
+    #[test]
+    fn test_reserving_inventory_clears_cost_cache() {
+        let (mut bsl_psd, shopping_list) = create_test_solver();
+        bsl_psd.set_cost_memoization(true);
+        let route = [1, 2, 3, 4, 5];
+
+        let before = bsl_psd.calculate_shopping_cost(&route, &shopping_list);
+        assert!(before.is_finite());
+        assert!(bsl_psd.reserve_inventory(&route, &shopping_list));
+
+        let after = bsl_psd.calculate_shopping_cost(&route, &shopping_list);
+        assert_ne!(after, before);
+        assert_eq!(
+            after,
+            bsl_psd.uncached_shopping_cost(&route, &shopping_list)
+        );
+    }
 
-            if !shopping_time.is_infinite() {
-                next_routes.push(RouteCandidate {
-                    stores: optimized_current,
-                    shopping_time,
-                });
-            }
+    #[test]
+    fn test_solve_with_callback_reports_skyline_routes() {
+        let (bsl_psd, shopping_list) = create_test_solver();
+        let shopper_location = Location::new(0.0, 0.0);
+        let customer_location = Location::new(20.0, 20.0);
 
-            // Return early - we need to fix the current route before generating more
-            return next_routes;
+        let mut reported = Vec::new();
+        let skyline = bsl_psd.solve_with_callback(
+            &shopping_list,
+            shopper_location,
+            customer_location,
+            10000,
+            |route| reported.push(route.clone()),
+        );
+        let (parallel, _) =
+            bsl_psd.solve_with_parallel(&shopping_list, shopper_location, customer_location, 10000);
+
+        assert!(!skyline.is_empty());
+        assert!(reported.len() >= skyline.len());
+        for route in &skyline {
+            assert!(reported.contains(route));
         }
+        assert_eq!(
+            skyline.cheapest().map(|route| route.shopping_cost),
+            parallel.cheapest().map(|route| route.shopping_cost)
+        );
+        assert_eq!(
+            skyline.fastest().map(|route| route.shopping_time),
+            parallel.fastest().map(|route| route.shopping_time)
+        );
+    }
 
-        // Case 1: Add a new store at the end (θs in the paper)
-        if !route.stores.is_empty() {
-            let last_store = route.stores.last().unwrap();
-
-            if let Some(min_detour_store) =
-                self.find_min_detour_store(Some(*last_store), &visited_stores)
-            {
-                // Make sure we're not adding a duplicate
-                if !visited_stores.contains(&min_detour_store) {
-                    // Generate a new route with the additional store
-                    let mut new_route = route.stores.clone();
-                    new_route.push(min_detour_store);
+    #[test]
+    fn test_cancel_after_first_route_returns_partial_skyline() {
+        let (bsl_psd, shopping_list) = create_test_solver();
+        let cancel = Arc::new(AtomicBool::new(false));
+
+        let mut reported = 0;
+        let (skyline, _) = bsl_psd.parallel_search(
+            &shopping_list,
+            Location::new(0.0, 0.0),
+            Location::new(20.0, 20.0),
+            10000,
+            None,
+            WorkerPlan::Paired,
+            Some(Arc::clone(&cancel)),
+            &mut |_| {
+                reported += 1;
+                cancel.store(true, Relaxed);
+            },
+        );
+
+        assert_eq!(reported, 1);
+        assert_eq!(skyline.len(), 1);
+    }
 
-                    // Now optimize the entire route order to find the shortest path
-                    let optimized_route =
-                        self.find_shortest_path(&new_route, shopper_location, customer_location);
+    #[test]
+    fn test_solve_with_cancel_matches_parallel_when_not_cancelled() {
+        let (bsl_psd, shopping_list) = create_test_solver();
+        let shopper_location = Location::new(0.0, 0.0);
+        let customer_location = Location::new(20.0, 20.0);
 
-                    // Calculate shopping time for the optimized route
-                    let shopping_time = self.calculate_total_time(
-                        &optimized_route,
-                        shopper_location,
-                        customer_location,
-                    );
+        let skyline = bsl_psd.solve_with_cancel(
+            &shopping_list,
+            shopper_location,
+            customer_location,
+            10000,
+            Arc::new(AtomicBool::new(false)),
+        );
+        let (parallel, _) =
+            bsl_psd.solve_with_parallel(&shopping_list, shopper_location, customer_location, 10000);
+
+        assert_eq!(
+            skyline.cheapest().map(|route| route.shopping_cost),
+            parallel.cheapest().map(|route| route.shopping_cost)
+        );
+    }
 
-                    if !shopping_time.is_infinite() {
-                        next_routes.push(RouteCandidate {
-                            stores: optimized_route,
-                            shopping_time,
-                        });
-                    }
-                }
-            }
-        } else {
-            // First store in the route
-            if let Some(min_detour_store) = self.find_min_detour_store(None, &visited_stores) {
-                let new_route = vec![min_detour_store];
+    #[test]
+    fn test_empty_shopping_list_goes_straight_to_customer() {
+        let (bsl_psd, _) = create_test_solver();
+        let shopper_location = Location::new(0.0, 0.0);
+        let customer_location = Location::new(20.0, 20.0);
+        let direct = ShoppingRoute {
+            stores: Vec::new(),
+            shopping_time: shopper_location.distance_to(&customer_location),
+            shopping_cost: 0.0,
+        };
 
-                // Calculate shopping time from shopper location to this store to customer location
-                let shopping_time =
-                    self.calculate_total_time(&new_route, shopper_location, customer_location);
+        let mut zero_quantities = ShoppingList::new();
+        zero_quantities.items.insert(1, 0);
+        zero_quantities.items.insert(2, 0);
 
-                if !shopping_time.is_infinite() {
-                    next_routes.push(RouteCandidate {
-                        stores: new_route,
-                        shopping_time,
-                    });
-                }
-            }
+        for shopping_list in [ShoppingList::new(), zero_quantities] {
+            let (parallel, _) = bsl_psd.solve_with_parallel(
+                &shopping_list,
+                shopper_location,
+                customer_location,
+                10000,
+            );
+            assert_eq!(parallel, vec![direct.clone()]);
+            assert_eq!(
+                bsl_psd.solve_with_debug(
+                    &shopping_list,
+                    shopper_location,
+                    customer_location,
+                    10000
+                ),
+                vec![direct.clone()]
+            );
+            assert_eq!(
+                bsl_psd.solve_exact(&shopping_list, shopper_location, customer_location),
+                vec![direct.clone()]
+            );
+            assert_eq!(
+                bsl_psd.solve_fastest(&shopping_list, shopper_location, customer_location),
+                Some(direct.clone())
+            );
         }
+    }
 
-        // Case 2: Replace the last store (θp in the paper)
-        if route.stores.len() >= 1 {
-            let last_store = route.stores.last().unwrap();
+    #[test]
+    fn test_try_solve_reports_missing_products() {
+        let (bsl_psd, _) = create_test_solver();
+        let shopper_location = Location::new(0.0, 0.0);
+        let customer_location = Location::new(20.0, 20.0);
 
-            // Create a modified visited set without the last store
-            let mut visited_without_last = visited_stores.clone();
-            visited_without_last.remove(last_store);
+        // Product 1 has 8 units across the stores and product 7 none
+        let mut shopping_list = ShoppingList::new();
+        shopping_list.add_item(1, 11);
+        shopping_list.add_item(2, 1);
+        shopping_list.add_item(7, 2);
 
-            if let Some(second_last_store) = route
-                .stores
-                .get(route.stores.len().saturating_sub(2))
-                .cloned()
-            {
-                if let Some(next_min_detour_store) = self.find_next_min_detour_store(
-                    Some(second_last_store),
-                    &visited_without_last,
-                    *last_store,
-                ) {
-                    // Make sure the replacement isn't already in the route
-                    if !visited_without_last.contains(&next_min_detour_store) {
-                        // Create a new route with the replacement
-                        let mut new_route = route.stores.clone();
-                        new_route.pop(); // Remove last store
-                        new_route.push(next_min_detour_store);
+        let result = bsl_psd.try_solve_with_parallel(
+            &shopping_list,
+            shopper_location,
+            customer_location,
+            10000,
+        );
+        assert_eq!(
+            result,
+            Err(SolveError::Infeasible {
+                missing: vec![(1, 3), (7, 2)],
+                unavailable: vec![7],
+            })
+        );
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "shopping list cannot be fulfilled, missing 3 of product 1, 2 of product 7 \
+             (no store has product 7)"
+        );
+    }
 
-                        // Now optimize the entire route order
-                        let optimized_route = self.find_shortest_path(
-                            &new_route,
-                            shopper_location,
-                            customer_location,
-                        );
+    #[test]
+    fn test_unavailable_products_differ_from_short_ones() {
+        let (bsl_psd, _) = create_test_solver();
+        let shopper_location = Location::new(0.0, 0.0);
+        let customer_location = Location::new(20.0, 20.0);
+
+        // Too few units: product 1 is stocked, only not 11 times over
+        let mut short = ShoppingList::new();
+        short.add_item(1, 11);
+        assert!(bsl_psd.unavailable_products(&short).is_empty());
+        assert_eq!(
+            bsl_psd.try_solve_with_parallel(&short, shopper_location, customer_location, 100),
+            Err(SolveError::Infeasible {
+                missing: vec![(1, 3)],
+                unavailable: vec![],
+            })
+        );
+
+        // No store at all: every unavailable product is reported, not just the first
+        let mut absent = ShoppingList::new();
+        absent.add_item(9, 1);
+        absent.add_item(2, 1);
+        absent.add_item(7, 4);
+        assert_eq!(bsl_psd.unavailable_products(&absent), vec![7, 9]);
+        let error = bsl_psd
+            .try_solve_with_parallel(&absent, shopper_location, customer_location, 100)
+            .unwrap_err();
+        assert_eq!(
+            error,
+            SolveError::Infeasible {
+                missing: vec![(7, 4), (9, 1)],
+                unavailable: vec![7, 9],
+            }
+        );
+        assert!(error.to_string().ends_with("(no store has products 7, 9)"));
 
-                        // Calculate shopping time for the optimized route
-                        let shopping_time = self.calculate_total_time(
-                            &optimized_route,
-                            shopper_location,
-                            customer_location,
-                        );
+        // A product sold out everywhere counts as unavailable too
+        for store in bsl_psd.stores.values() {
+            store.write().unwrap().inventory.remove(&2);
+        }
+        let mut sold_out = ShoppingList::new();
+        sold_out.add_item(2, 1);
+        assert_eq!(bsl_psd.unavailable_products(&sold_out), vec![2]);
+    }
 
-                        if !shopping_time.is_infinite() {
-                            next_routes.push(RouteCandidate {
-                                stores: optimized_route,
-                                shopping_time,
-                            });
-                        }
-                    }
-                }
+    #[test]
+    fn test_try_solve_returns_skyline_for_feasible_list() {
+        let (bsl_psd, shopping_list) = create_test_solver();
+
+        let skyline = bsl_psd
+            .try_solve_with_parallel(
+                &shopping_list,
+                Location::new(0.0, 0.0),
+                Location::new(20.0, 20.0),
+                10000,
+            )
+            .expect("test data can be fulfilled");
+
+        assert!(!skyline.is_empty());
+    }
+
+    #[test]
+    fn test_available_products_sums_supply() {
+        let (stores, _) = create_test_data();
+        let mut expected: HashMap<ProductId, (String, u32)> = HashMap::new();
+        for store in stores.values() {
+            for (product_id, product) in &store.products {
+                let entry = expected
+                    .entry(*product_id)
+                    .or_insert((product.name.clone(), 0));
+                entry.1 += store.get_inventory_level(product_id);
             }
         }
 
-        next_routes
+        let bsl_psd = BSLPSD::new(stores);
+        let available = bsl_psd.available_products();
+
+        assert_eq!(available, expected);
+        // Product A: 5 units at store 1 and 3 at store 5
+        assert_eq!(available[&1], ("A".to_string(), 8));
     }
 
-    /// Create a snapshot of the current inventory state
-    pub fn snapshot_inventory(&self) -> HashMap<StoreId, HashMap<ProductId, u32>> {
-        let mut snapshot = HashMap::new();
+    #[test]
+    fn test_over_demanding_list_does_not_underflow() {
+        let (bsl_psd, _) = create_test_solver();
+        // Product A has 5 units at store 1 and 3 at store 5; stores are repeated
+        let route = [1, 5, 1, 5];
+        let mut shopping_list = ShoppingList::new();
+        shopping_list.add_item(1, 20);
+        shopping_list.add_item(2, u32::MAX);
+
+        let allocation = bsl_psd.allocate_purchases(&route, &shopping_list);
+        assert_eq!(allocation.unfulfilled[&1], 12);
+        assert_eq!(allocation.unfulfilled[&2], u32::MAX - 8);
+        assert!(!bsl_psd.satisfies_list_with_inventory(&route, &shopping_list));
+        assert!(bsl_psd
+            .calculate_shopping_cost(&route, &shopping_list)
+            .is_infinite());
+        assert!(bsl_psd
+            .find_min_cost_route(
+                &shopping_list,
+                Location::new(0.0, 0.0),
+                Location::new(20.0, 20.0)
+            )
+            .is_none());
+        assert!(bsl_psd
+            .find_min_time_route_dijkstra(
+                &shopping_list,
+                Location::new(0.0, 0.0),
+                Location::new(20.0, 20.0)
+            )
+            .is_none());
+
+        let before = bsl_psd.snapshot_inventory();
+        assert!(!bsl_psd.reserve_inventory(&route, &shopping_list));
+        assert_eq!(bsl_psd.snapshot_inventory(), before);
+    }
 
-        for (&store_id, store_arc) in &self.stores {
-            let store = store_arc.read().unwrap();
-            snapshot.insert(store_id, store.inventory.clone());
+    #[test]
+    fn test_visited_store_sets_merge_orderings() {
+        let orderings = [
+            vec![1, 2, 3],
+            vec![1, 3, 2],
+            vec![2, 1, 3],
+            vec![2, 3, 1],
+            vec![3, 1, 2],
+            vec![3, 2, 1],
+        ];
+        let mut by_order = HashSet::new();
+        let mut visited = VisitedStoreSets::default();
+
+        let mut accepted = Vec::new();
+        for (index, stores) in orderings.iter().enumerate() {
+            // Times 30, 25, 35, 20, 40, 10
+            let shopping_time = [30.0, 25.0, 35.0, 20.0, 40.0, 10.0][index];
+            by_order.insert(stores.clone());
+            if visited.insert(&RouteCandidate {
+                stores: stores.clone(),
+                shopping_time,
+            }) {
+                accepted.push(shopping_time);
+            }
         }
 
-        snapshot
+        assert_eq!(by_order.len(), 6);
+        assert_eq!(visited.best_times.len(), 1);
+        // Only orderings faster than all earlier ones are queued
+        assert_eq!(accepted, vec![30.0, 25.0, 20.0, 10.0]);
+        assert_eq!(visited.best_times[&vec![1, 2, 3]], 10.0);
     }
 
-    /// Update the skyline with a new route
-    pub fn update_skyline(&self, skyline: &mut Vec<ShoppingRoute>, route: ShoppingRoute) -> bool {
-        // Check if the route is dominated by any route in the skyline
-        for existing_route in skyline.iter() {
-            if existing_route.conventionally_dominates(&route) || existing_route == &route {
-                return false;
+    #[test]
+    fn test_solve_skyline_through_trait_object() {
+        let (bsl_psd, shopping_list) = create_test_solver();
+        let shopper_location = Location::new(0.0, 0.0);
+        let customer_location = Location::new(20.0, 20.0);
+        let solver: &dyn PSDSolver = &bsl_psd;
+
+        let skyline = solver.solve_skyline(
+            &shopping_list,
+            shopper_location,
+            customer_location,
+            SolveOptions {
+                workers: Some(1),
+                ..SolveOptions::default()
+            },
+        );
+        let (parallel, _) = bsl_psd.solve_with_parallel_n(
+            &shopping_list,
+            shopper_location,
+            customer_location,
+            10000,
+            1,
+        );
+        assert_eq!(skyline, parallel);
+
+        // The budget drops every route dearer than the cheapest one
+        let cheapest = skyline.cheapest().unwrap().clone();
+        let budget = solver.solve_skyline(
+            &shopping_list,
+            shopper_location,
+            customer_location,
+            SolveOptions {
+                workers: Some(1),
+                max_cost: Some(cheapest.shopping_cost),
+                ..SolveOptions::default()
+            },
+        );
+        assert_eq!(budget, vec![cheapest]);
+    }
+
+    #[test]
+    fn test_required_and_forbidden_stores() {
+        let (bsl_psd, shopping_list) = create_test_solver();
+        let shopper_location = Location::new(0.0, 0.0);
+        let customer_location = Location::new(20.0, 20.0);
+        let solve = |required: &[StoreId], forbidden: &[StoreId]| {
+            bsl_psd.solve_skyline(
+                &shopping_list,
+                shopper_location,
+                customer_location,
+                SolveOptions {
+                    workers: Some(1),
+                    required_stores: required.iter().copied().collect(),
+                    forbidden_stores: forbidden.iter().copied().collect(),
+                    ..SolveOptions::default()
+                },
+            )
+        };
+        let check = |skyline: &Skyline, required: &[StoreId], forbidden: &[StoreId]| {
+            assert!(!skyline.is_empty());
+            for route in skyline.iter() {
+                assert!(required.iter().all(|store| route.stores.contains(store)));
+                assert!(!forbidden.iter().any(|store| route.stores.contains(store)));
+                assert!(bsl_psd.satisfies_list(&route.stores, &shopping_list));
             }
+        };
+
+        // Every route goes through store 4, which the unconstrained skyline can avoid
+        let required = solve(&[4], &[]);
+        check(&required, &[4], &[]);
+        assert!(solve(&[], &[])
+            .iter()
+            .any(|route| !route.stores.contains(&4)));
+
+        let forbidden = solve(&[], &[3]);
+        check(&forbidden, &[], &[3]);
+
+        let both = solve(&[4], &[3]);
+        check(&both, &[4], &[3]);
+
+        // Forbidding both stores selling product A leaves the list unfulfillable
+        assert!(solve(&[], &[1, 5]).is_empty());
+        // So does requiring a forbidden or unknown store
+        assert!(solve(&[4], &[4]).is_empty());
+        assert!(solve(&[42], &[]).is_empty());
+    }
+
+    #[test]
+    fn test_category_filter_excludes_cheapest_store() {
+        let (bsl_psd, _) = create_test_solver();
+        // Every store but store 5, the cheapest for product A, is organic
+        for store_id in 1..=4 {
+            let mut store = bsl_psd.stores[&store_id].write().unwrap();
+            store.categories.insert("organic".to_string());
         }
+        let shopper_location = Location::new(0.0, 0.0);
+        let customer_location = Location::new(20.0, 20.0);
+        let mut shopping_list = ShoppingList::new();
+        shopping_list.add_item(1, 2);
+
+        let solve = |category: Option<&str>| {
+            bsl_psd.solve_skyline(
+                &shopping_list,
+                shopper_location,
+                customer_location,
+                SolveOptions {
+                    workers: Some(1),
+                    category: category.map(String::from),
+                    ..SolveOptions::default()
+                },
+            )
+        };
 
-        // Remove routes that are dominated by the new route
-        skyline.retain(|existing_route| !route.conventionally_dominates(existing_route));
+        // Unfiltered, the cheapest route buys at store 5
+        let cheapest = solve(None).cheapest().unwrap().clone();
+        assert!(cheapest.stores.contains(&5));
+        assert_eq!(cheapest.shopping_cost, 12.0);
+
+        // Organic only, store 1 is all that is left
+        let organic = solve(Some("organic"));
+        assert_eq!(organic.len(), 1);
+        assert_eq!(organic[0].stores, vec![1]);
+        assert_eq!(organic[0].shopping_cost, 14.0);
+        assert_eq!(
+            bsl_psd
+                .with_category("organic")
+                .min_cost_allocation(&shopping_list),
+            Some((14.0, vec![1]))
+        );
+
+        // No store is in an unknown category
+        assert!(solve(Some("bakery")).is_empty());
+    }
 
-        // Add the new route
-        skyline.push(route);
+    #[test]
+    fn test_travel_time_falls_back_to_euclidean() {
+        let (mut bsl_psd, _) = create_test_solver();
+        // A road time unlike the straight-line distance
+        bsl_psd.travel_times.insert((1, 3), 99.0);
+        assert_eq!(bsl_psd.travel_time(1, 3), Some(99.0));
+        assert_eq!(bsl_psd.travel_time_or_euclidean(1, 3), 99.0);
+
+        bsl_psd.travel_times.remove(&(3, 1));
+        let euclidean = {
+            let from = bsl_psd.stores[&3].read().unwrap();
+            let to = bsl_psd.stores[&1].read().unwrap();
+            from.location.distance_to(&to.location)
+        };
+        assert_eq!(bsl_psd.travel_time(3, 1), None);
+        assert_eq!(bsl_psd.travel_time_or_euclidean(3, 1), euclidean);
+    }
 
-        true
+    #[test]
+    fn test_repair_travel_times_completes_matrix() {
+        let (stores, _) = create_test_data();
+        let mut travel_times = HashMap::new();
+        travel_times.insert((1, 2), 4.0);
+        travel_times.insert((3, 1), 6.0);
+        travel_times.insert((2, 3), 5.0);
+        travel_times.insert((3, 2), 7.0);
+        let mut bsl_psd = BSLPSD::new_with_travel_times(stores, travel_times);
+        bsl_psd.set_distance_metric(DistanceMetric::Euclidean);
+        bsl_psd.set_speed(4.0);
+
+        let report = bsl_psd.repair_travel_times();
+
+        // 5 stores make 10 unordered pairs: 2 known one way, 1 both ways, 7 unknown
+        assert_eq!(
+            report,
+            TravelTimeRepair {
+                filled_from_reverse: 2,
+                filled_with_distance: 14,
+                asymmetric: 1,
+            }
+        );
+        assert_eq!(report.filled(), 16);
+        assert_eq!(bsl_psd.travel_times.len(), 20);
+        assert!(bsl_psd.verify_travel_times());
+        assert_eq!(bsl_psd.travel_times[&(2, 1)], 4.0);
+        assert_eq!(bsl_psd.travel_times[&(1, 3)], 6.0);
+        // Asymmetric pairs are reported but kept
+        assert_eq!(bsl_psd.travel_times[&(3, 2)], 7.0);
+        let distance = {
+            let from = bsl_psd.stores[&4].read().unwrap();
+            let to = bsl_psd.stores[&5].read().unwrap();
+            from.location.distance_to(&to.location)
+        };
+        // Filled pairs hold distances, which the speed turns into times only once
+        assert_eq!(bsl_psd.travel_times[&(4, 5)], distance);
+        assert_eq!(bsl_psd.travel_time(4, 5), Some(distance / 4.0));
+        assert_eq!(bsl_psd.travel_time(2, 1), Some(1.0));
+
+        // A complete matrix is left alone
+        assert_eq!(bsl_psd.repair_travel_times().filled(), 0);
     }
-    pub fn solve_with_parallel(
-        &self,
-        shopping_list: &ShoppingList,
-        shopper_location: Location,
-        customer_location: Location,
-        threshold: i32,
-    ) -> (Vec<ShoppingRoute>, Duration) {
-        println!("Starting parallel BSL-PSD algorithm with channels...");
-        let start_time_find_best_route = std::time::Instant::now();
-        // Step 1: Find route with minimum shopping cost
-        // let min_cost_route =
-        let min_cost =
-            match self.find_min_cost_route(shopping_list, shopper_location, customer_location) {
-                Some(route) => route,
-                None => {
-                    println!("No minimum cost route found, aborting.");
-                    return (Vec::new(), Duration::default());
-                }
-            };
-        println!("Found_min_cost: {:?}", min_cost);
 
-        // Find route with minimum time cost
-        let min_time_route = match self.find_min_time_route_dijkstra(
-            shopping_list,
+    #[test]
+    fn test_selling_out_removes_store_from_inverted_list() {
+        let (mut bsl_psd, _) = create_test_solver();
+        let travel_times = bsl_psd.travel_times.clone();
+        let suppliers = |bsl_psd: &BSLPSD, product_id: ProductId| -> Vec<StoreId> {
+            let mut stores: Vec<StoreId> = bsl_psd
+                .product_to_stores
+                .get(&product_id)
+                .map(|stores| stores.iter().map(|&(store_id, _)| store_id).collect())
+                .unwrap_or_default();
+            stores.sort();
+            stores
+        };
+        assert_eq!(suppliers(&bsl_psd, 1), vec![1, 5]);
+        let product_2 = bsl_psd.product_to_stores[&2].clone();
+
+        // Store 1 sells out of product A
+        assert!(bsl_psd.set_inventory(1, 1, 0));
+        assert_eq!(suppliers(&bsl_psd, 1), vec![5]);
+
+        // Other products' lists and the travel times are untouched
+        assert_eq!(bsl_psd.product_to_stores[&2], product_2);
+        assert_eq!(bsl_psd.travel_times, travel_times);
+
+        // Selling out everywhere drops the product; restocking brings the store back
+        assert!(bsl_psd.set_inventory(5, 1, 0));
+        assert!(suppliers(&bsl_psd, 1).is_empty());
+        assert!(bsl_psd.set_inventory(1, 1, 2));
+        assert_eq!(suppliers(&bsl_psd, 1), vec![1]);
+
+        // A restocked store is found by the next solve without a manual rebuild
+        let mut shopping_list = ShoppingList::new();
+        shopping_list.add_item(1, 2);
+        assert_eq!(
+            bsl_psd.min_cost_allocation(&shopping_list).unwrap().1,
+            vec![1]
+        );
+
+        // Unknown stores and products a store doesn't sell are rejected
+        assert!(!bsl_psd.set_inventory(99, 1, 5));
+        assert!(!bsl_psd.set_inventory(1, 7, 5));
+    }
+
+    #[test]
+    fn test_max_stores_limits_route_length() {
+        let (mut bsl_psd, shopping_list) = create_test_solver();
+        let shopper_location = Location::new(0.0, 0.0);
+        let customer_location = Location::new(20.0, 20.0);
+
+        // No single store sells A, B, C and D
+        bsl_psd.set_max_stores(Some(1));
+        let (skyline, _) =
+            bsl_psd.solve_with_parallel(&shopping_list, shopper_location, customer_location, 100);
+        assert!(skyline.is_empty());
+        assert!(matches!(
+            bsl_psd.try_solve_with_parallel(
+                &shopping_list,
+                shopper_location,
+                customer_location,
+                100
+            ),
+            Err(SolveError::NoRouteFound)
+        ));
+
+        // Stores 1 and 5 each sell A and B on their own
+        let mut single_store_list = ShoppingList::new();
+        single_store_list.add_item(1, 2);
+        single_store_list.add_item(2, 1);
+        let (skyline, _) = bsl_psd.solve_with_parallel(
+            &single_store_list,
             shopper_location,
             customer_location,
-        ) {
-            Some(route) => route,
-            None => {
-                println!("No minimum time route found, aborting.");
-                return (Vec::new(), Duration::default());
+            100,
+        );
+        assert!(!skyline.is_empty());
+        assert!(skyline.iter().all(|route| route.stores.len() == 1));
+
+        // The sequential search used by `solve` keeps to the limit as well
+        let skyline = bsl_psd.solve(&single_store_list, shopper_location, customer_location);
+        assert!(!skyline.is_empty());
+        assert!(skyline.iter().all(|route| route.stores.len() == 1));
+        assert!(bsl_psd
+            .solve(&shopping_list, shopper_location, customer_location)
+            .is_empty());
+
+        // Two stores are enough for the full list
+        bsl_psd.set_max_stores(Some(2));
+        let (skyline, _) =
+            bsl_psd.solve_with_parallel(&shopping_list, shopper_location, customer_location, 100);
+        assert!(!skyline.is_empty());
+        assert!(skyline.iter().all(|route| route.stores.len() <= 2));
+        for route in skyline.iter() {
+            assert!(bsl_psd.satisfies_list_with_inventory(&route.stores, &shopping_list));
+        }
+        let exact = bsl_psd.solve_exact(&shopping_list, shopper_location, customer_location);
+        assert!(!exact.is_empty());
+        assert!(exact.iter().all(|route| route.stores.len() <= 2));
+    }
+
+    #[test]
+    fn test_tax_rate_changes_cheapest_store() {
+        // Both stores charge 5.00 before tax; store 1 adds 20% tax
+        let mut stores = HashMap::new();
+        for (store_id, tax_rate) in [(1, 0.2), (2, 0.0)] {
+            let mut products = HashMap::new();
+            products.insert(1, Product::new("A", 5.0).with_tax_rate(tax_rate));
+            let mut inventory = HashMap::new();
+            inventory.insert(1, 2);
+            stores.insert(
+                store_id,
+                Store::new_with_inventory(
+                    store_id,
+                    Location::new(store_id as f64, 0.0),
+                    products,
+                    inventory,
+                ),
+            );
+        }
+        let mut bsl_psd = BSLPSD::new(stores);
+        bsl_psd.set_distance_metric(DistanceMetric::Euclidean);
+        bsl_psd.precompute_data();
+        let shopper_location = Location::new(0.0, 0.0);
+        let customer_location = Location::new(3.0, 0.0);
+
+        let mut shopping_list = ShoppingList::new();
+        shopping_list.add_item(1, 3);
+
+        // Both untaxed units come first, then one taxed unit
+        let allocation = bsl_psd.allocate_purchases(&[1, 2], &shopping_list);
+        assert_eq!(allocation.purchases[&1], vec![(2, 2, 5.0), (1, 1, 6.0)]);
+        assert!((allocation.total_cost - 16.0).abs() < 1e-9);
+        assert!((bsl_psd.calculate_shopping_cost(&[1, 2], &shopping_list) - 16.0).abs() < 1e-9);
+        let min_cost = bsl_psd
+            .find_min_cost_route(&shopping_list, shopper_location, customer_location)
+            .unwrap();
+        assert!((min_cost - 16.0).abs() < 1e-9);
+        assert_eq!(bsl_psd.product_to_stores[&1][0].0, 2);
+
+        // Store 2 is now dearer before tax but still cheaper after it
+        bsl_psd.stores[&2]
+            .write()
+            .unwrap()
+            .products
+            .get_mut(&1)
+            .unwrap()
+            .cost = 5.5;
+        bsl_psd.precompute_data();
+        let mut shopping_list = ShoppingList::new();
+        shopping_list.add_item(1, 2);
+        let route = bsl_psd
+            .find_min_cost_route_full(&shopping_list, shopper_location, customer_location)
+            .unwrap();
+        assert_eq!(route.stores, vec![2]);
+        assert!((route.shopping_cost - 11.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_round_trip_adds_the_return_leg() {
+        let (bsl_psd, shopping_list) = create_test_solver();
+        let shopper_location = Location::new(0.0, 0.0);
+        let customer_location = Location::new(20.0, 20.0);
+        let depot = Location::new(5.0, 25.0);
+        let return_leg = customer_location.distance_to(&depot);
+
+        let mut round_trip = bsl_psd.clone();
+        round_trip.return_location = Some(depot);
+        for route in [vec![1, 3], vec![5, 3, 4], vec![]] {
+            let one_way =
+                bsl_psd.calculate_shopping_time(&route, shopper_location, customer_location);
+            let with_return =
+                round_trip.calculate_shopping_time(&route, shopper_location, customer_location);
+            if !route.is_empty() {
+                assert!((with_return - one_way - return_leg).abs() < 1e-9);
             }
-        };
-        let elapsed_limited = start_time_find_best_route.elapsed();
-        println!("Found_min_time: {:?}", min_time_route);
+            let one_way =
+                bsl_psd.calculate_total_time(&route, &shopper_location, &customer_location);
+            let with_return =
+                round_trip.calculate_total_time(&route, &shopper_location, &customer_location);
+            assert!((with_return - one_way - return_leg).abs() < 1e-9);
+        }
 
-        // let sc_upper_bound = min_cost_route.shopping_cost;
-        let sc_upper_bound = min_cost;
-        // println!(
-        //     "Found min cost route with cost: ${:.2}, sc_upper_bound: ${:.2}",
-        //     min_cost_route.shopping_cost, sc_upper_bound
-        // );
+        // The same routes are optimal, each slower by exactly the return leg
+        let exact = bsl_psd.solve_exact(&shopping_list, shopper_location, customer_location);
+        let exact_round_trip =
+            round_trip.solve_exact(&shopping_list, shopper_location, customer_location);
+        assert_eq!(exact.len(), exact_round_trip.len());
+        for (route, round) in exact.iter().zip(exact_round_trip.iter()) {
+            assert_eq!(route.stores, round.stores);
+            assert_eq!(route.shopping_cost, round.shopping_cost);
+            assert!((round.shopping_time - route.shopping_time - return_leg).abs() < 1e-9);
+        }
 
-        // Create termination signal
-        let found_upper_bound = Arc::new(AtomicBool::new(false));
+        let min_time = bsl_psd
+            .find_min_time_route_dijkstra(&shopping_list, shopper_location, customer_location)
+            .unwrap();
+        let round_trip_min_time = round_trip
+            .find_min_time_route_dijkstra(&shopping_list, shopper_location, customer_location)
+            .unwrap();
+        assert_eq!(round_trip_min_time.stores, min_time.stores);
+        assert!(
+            (round_trip_min_time.shopping_time - min_time.shopping_time - return_leg).abs() < 1e-9
+        );
+
+        // The search starts from the minimum-time route, which stays the fastest one
+        let skyline =
+            bsl_psd.solve_round_trip(&shopping_list, shopper_location, customer_location, depot);
+        let fastest = skyline
+            .iter()
+            .map(|route| route.shopping_time)
+            .fold(f64::INFINITY, f64::min);
+        assert!((fastest - round_trip_min_time.shopping_time).abs() < 1e-9);
+
+        // With a road graph the way back follows the roads, in metres like other legs
+        let road = Arc::new(RoadGraph::new_with_metric(
+            HashMap::from([(0, (20.0, 20.0)), (1, (20.0, 25.0)), (2, (5.0, 25.0))]),
+            HashMap::from([(0, (0, 1)), (1, (1, 2))]),
+            DistanceMetric::Euclidean,
+        ));
+        let mut one_way = bsl_psd.clone();
+        one_way.set_road_graph(Arc::clone(&road));
+        round_trip.set_road_graph(road);
+        for route in [vec![1, 3], vec![]] {
+            let with_return =
+                round_trip.calculate_total_time(&route, &shopper_location, &customer_location);
+            let without =
+                one_way.calculate_total_time(&route, &shopper_location, &customer_location);
+            assert!((with_return - without - 20_000.0).abs() < 1e-6);
+        }
+        assert!(
+            (round_trip.route_distance(&[1, 3], shopper_location, customer_location)
+                - one_way.route_distance(&[1, 3], shopper_location, customer_location)
+                - 20_000.0)
+                .abs()
+                < 1e-6
+        );
+    }
 
-        // Create communication channel for sending found skyline routes
-        let (tx1, rx) = mpsc::channel();
-        let tx2 = tx1.clone();
-
-        // Clone data needed for use between threads
-        let min_time_route_clone = min_time_route.clone();
-        let shopping_list_clone = shopping_list.clone();
-        let shopper_location_clone = shopper_location.clone();
-        let customer_location_clone = customer_location.clone();
-        let self_clone = self.clone(); // Need to implement Clone trait
-        let found_upper_bound_clone = Arc::clone(&found_upper_bound);
-
-        // Start normal route generation thread
-        let _handle1 = thread::spawn(move || {
-            let mut visited_route = HashSet::new();
-            println!("Start thread 1");
-            let mut queue = BinaryHeap::new();
-            // Initialize with min time route
-            queue.push(RouteCandidate {
-                stores: min_time_route_clone.stores.clone(),
-                shopping_time: min_time_route_clone.shopping_time,
-            });
+    #[test]
+    fn test_with_nearest_stores_keeps_nearest_candidates() {
+        let (bsl_psd, shopping_list) = create_test_solver();
+        let shopper_location = Location::new(0.0, 0.0);
+        let customer_location = Location::new(20.0, 20.0);
+        let kept = |solver: &BSLPSD| {
+            let mut stores: Vec<StoreId> = solver.stores.keys().cloned().collect();
+            stores.sort();
+            stores
+        };
 
-            // // Mark initial route as visited
-            // {
-            //     let mut visited = visited_routes1.lock().unwrap();
-            //     visited.insert(min_time_route_clone.stores.clone());
-            // }
+        // Stores by distance to the closer endpoint: 3, 4, 2, 5, 1
+        let mut nearby_list = ShoppingList::new();
+        nearby_list.add_item(3, 3);
+        nearby_list.add_item(4, 2);
+        let restricted =
+            bsl_psd.with_nearest_stores(&nearby_list, &shopper_location, &customer_location, 1);
+        assert_eq!(kept(&restricted), vec![3]);
+        let restricted =
+            bsl_psd.with_nearest_stores(&nearby_list, &shopper_location, &customer_location, 2);
+        assert_eq!(kept(&restricted), vec![3, 4]);
+        assert!(!restricted.product_to_stores[&4]
+            .iter()
+            .any(|&(store_id, _)| store_id == 2));
+
+        // Only stores 1 and 5 sell A and B, so the nearest two are widened up to store 5
+        let restricted =
+            bsl_psd.with_nearest_stores(&shopping_list, &shopper_location, &customer_location, 2);
+        assert_eq!(kept(&restricted), vec![2, 3, 4, 5]);
+        let restricted =
+            bsl_psd.with_nearest_stores(&shopping_list, &shopper_location, &customer_location, 10);
+        assert_eq!(kept(&restricted), vec![1, 2, 3, 4, 5]);
+
+        // The filtered skyline only uses kept stores
+        let options = SolveOptions {
+            nearest_stores: Some(2),
+            ..SolveOptions::default()
+        };
+        let skyline =
+            bsl_psd.solve_skyline(&shopping_list, shopper_location, customer_location, options);
+        assert!(!skyline.is_empty());
+        for route in skyline.iter() {
+            assert!(!route.stores.contains(&1));
+        }
+    }
 
-            while let Some(route_candidate) = queue.pop() {
-                // Check if upper bound route has been found
-                if found_upper_bound_clone.load(Relaxed) {
-                    break;
+    #[test]
+    fn test_parallel_inverted_list_matches_sequential() {
+        let mut rng = StdRng::seed_from_u64(11);
+        let mut stores = HashMap::new();
+        for store_id in 0..500 {
+            let mut products = HashMap::new();
+            let mut inventory = HashMap::new();
+            for product_id in 1..=20 {
+                if rng.gen_bool(0.4) {
+                    // Few distinct prices, so many stores tie
+                    let cost = rng.gen_range(1..=5) as f64;
+                    products.insert(product_id, Product::new("P", cost));
+                    inventory.insert(product_id, rng.gen_range(0..3));
                 }
+            }
+            let location = Location::new(rng.gen_range(0.0..10.0), rng.gen_range(0.0..10.0));
+            stores.insert(
+                store_id,
+                Store::new_with_inventory(store_id, location, products, inventory),
+            );
+        }
 
-                // Check if route satisfies shopping list
-                let satisfies = self_clone
-                    .satisfies_list_with_inventory(&route_candidate.stores, &shopping_list_clone);
+        let mut bsl_psd = BSLPSD::new(stores);
 
-                if satisfies {
-                    // Calculate shopping cost
-                    let shopping_cost = self_clone
-                        .calculate_shopping_cost(&route_candidate.stores, &shopping_list_clone);
+        bsl_psd.set_distance_metric(DistanceMetric::Euclidean);
+        bsl_psd.precompute_data();
+        let sequential = bsl_psd.build_inverted_list_sequential();
+        assert_eq!(bsl_psd.product_to_stores, sequential);
+        assert_eq!(sequential.len(), 20);
 
-                    // Create complete shopping route
-                    let shopping_route = ShoppingRoute {
-                        stores: route_candidate.stores.clone(),
-                        shopping_time: route_candidate.shopping_time,
-                        shopping_cost,
-                    };
+        // Rebuilding one product gives the same list too
+        let mut rebuilt = bsl_psd.clone();
+        rebuilt.rebuild_inverted_list_for(3);
+        assert_eq!(rebuilt.product_to_stores[&3], sequential[&3]);
+    }
 
-                    // Send found route
-                    tx1.send(shopping_route.clone()).unwrap();
+    #[test]
+    fn test_solve_and_measure_describes_the_skyline() {
+        let (bsl_psd, shopping_list) = create_test_solver();
+        let (skyline, result) = bsl_psd.solve_and_measure(
+            &shopping_list,
+            Location::new(0.0, 0.0),
+            Location::new(20.0, 20.0),
+            100,
+        );
+
+        assert_eq!(result.product_count, 4);
+        assert_eq!(result.threshold, 100);
+        assert_eq!(result.route_count, skyline.len());
+        assert_eq!(
+            result.fastest_time,
+            skyline.fastest().map(|route| route.shopping_time)
+        );
+        assert_eq!(
+            result.cheapest_cost,
+            skyline.cheapest().map(|route| route.shopping_cost)
+        );
+        assert!(result.total_time >= result.best_route_time);
+    }
 
-                    // println!(
-                    //     "Thread 1 found route: {:?}, cost: ${:.2}, time: {:.2}",
-                    //     route_candidate.stores, shopping_cost, route_candidate.shopping_time
-                    // );
+    #[test]
+    fn test_stores_within_radius_on_a_grid() {
+        // A 5x5 grid of stores one unit apart, IDs row by row from 1
+        let mut stores = HashMap::new();
+        for row in 0..5 {
+            for col in 0..5 {
+                let id = row * 5 + col + 1;
+                let location = Location::new(col as f64, row as f64);
+                stores.insert(id, Store::new(id, location, HashMap::new()));
+            }
+        }
+        let mut bsl_psd = BSLPSD::new(stores);
+        bsl_psd.set_distance_metric(DistanceMetric::Euclidean);
+        let center = Location::new(2.0, 2.0);
+
+        // The center store and its four neighbours
+        assert_eq!(bsl_psd.stores_within(center, 1.0), vec![8, 12, 13, 14, 18]);
+        // Diagonal neighbours join at sqrt(2)
+        assert_eq!(
+            bsl_psd.stores_within(center, 1.5),
+            vec![7, 8, 9, 12, 13, 14, 17, 18, 19]
+        );
+        assert_eq!(bsl_psd.stores_within(center, 0.5), vec![13]);
+        assert_eq!(bsl_psd.stores_within(center, 10.0).len(), 25);
+        assert!(bsl_psd
+            .stores_within(Location::new(-5.0, -5.0), 1.0)
+            .is_empty());
+
+        // Under Manhattan distance a radius of 2 is a diamond, not a square
+        bsl_psd.set_distance_metric(DistanceMetric::Manhattan);
+        assert_eq!(
+            bsl_psd.stores_within(center, 2.0),
+            vec![3, 7, 8, 9, 11, 12, 13, 14, 15, 17, 18, 19, 23]
+        );
+    }
 
-                    // Check if upper bound has been reached
-                    if shopping_cost == sc_upper_bound {
-                        println!("Thread 1 found the sc_upper_bound skyline route!");
-                        found_upper_bound_clone.store(true, Relaxed);
-                        break;
-                    }
-                }
+    #[test]
+    fn test_store_subset_never_uses_removed_stores() {
+        let (bsl_psd, shopping_list) = create_test_solver();
+        let shopper_location = Location::new(0.0, 0.0);
+        let customer_location = Location::new(20.0, 20.0);
+
+        // Store 1 is the only other source of A and B besides store 5
+        let keep: HashSet<StoreId> = [2, 3, 4, 5, 99].into_iter().collect();
+        let subset = bsl_psd.with_store_subset(&keep);
+
+        let mut store_ids: Vec<StoreId> = subset.stores.keys().cloned().collect();
+        store_ids.sort();
+        assert_eq!(store_ids, vec![2, 3, 4, 5]);
+        assert_eq!(subset.travel_times.len(), 4 * 3);
+        assert!(subset.verify_travel_times());
+        assert_eq!(subset.stores_stocking(1), vec![5]);
+
+        let (skyline, _) =
+            subset.solve_with_parallel(&shopping_list, shopper_location, customer_location, 100);
+        assert!(!skyline.is_empty());
+        for route in &skyline {
+            assert!(
+                route.stores.iter().all(|store_id| keep.contains(store_id)),
+                "{:?} visits a removed store",
+                route
+            );
+        }
+        let exact = subset.solve_exact(&shopping_list, shopper_location, customer_location);
+        assert!(exact
+            .iter()
+            .all(|route| route.stores.iter().all(|store_id| keep.contains(store_id))));
+
+        // The original solver is untouched
+        assert_eq!(bsl_psd.stores.len(), 5);
+        assert_eq!(bsl_psd.stores_stocking(1), vec![1, 5]);
+    }
 
-                // Generate next batch of routes
-                let next_routes = self_clone.generate_next_routes(&route_candidate);
-
-                // Filter already visited routes and add to queue
-                for next_route in next_routes {
-                    let is_new_route;
-                    {
-                        // let visited = visited_routes1.read().unwrap();
-                        is_new_route = !visited_route.contains(&next_route.stores);
-                        if is_new_route {
-                            // visited.insert(next_route.stores.clone());
-                            // let mut visited = visited_routes1.write().unwrap();
-                            visited_route.insert(next_route.stores.clone());
-                        }
-                    }
-                    if is_new_route {
-                        queue.push(next_route);
-                    }
-                }
-            }
-        });
+    #[test]
+    fn test_store_subset_keeps_parent_inverted_list() {
+        let (mut bsl_psd, _) = create_test_solver();
+        let suppliers = |solver: &BSLPSD| -> Vec<StoreId> {
+            let mut stores: Vec<StoreId> = solver
+                .product_to_stores
+                .get(&1)
+                .map(|stores| stores.iter().map(|&(store_id, _)| store_id).collect())
+                .unwrap_or_default();
+            stores.sort();
+            stores
+        };
 
-        // Clone data needed for second thread
-        let min_time_route_clone2 = min_time_route.clone();
-        let shopping_list_clone2 = shopping_list.clone();
-        let self_clone2 = self.clone();
-        let found_upper_bound_clone2 = Arc::clone(&found_upper_bound);
-
-        // Start shuffle route generation thread
-        let _handle2 = thread::spawn(move || {
-            let mut visited_route = HashSet::new();
-            println!("Start thread 2");
-            let mut queue = BinaryHeap::new();
-            queue.push(RouteCandidate {
-                stores: min_time_route_clone2.stores.clone(),
-                shopping_time: min_time_route_clone2.shopping_time,
-            });
+        // Building the subset leaves the parent's list as `set_inventory` left it
+        assert!(bsl_psd.set_inventory(1, 1, 0));
+        let keep: HashSet<StoreId> = [1, 2, 5].into_iter().collect();
+        let mut subset = bsl_psd.with_store_subset(&keep);
+        assert_eq!(suppliers(&bsl_psd), vec![5]);
+        assert_eq!(suppliers(&subset), vec![5]);
+
+        // A restock through the parent reaches the subset's list once it is rebuilt
+        assert!(bsl_psd.set_inventory(1, 1, 3));
+        assert_eq!(suppliers(&bsl_psd), vec![1, 5]);
+        assert_eq!(suppliers(&subset), vec![5]);
+        subset.rebuild_inverted_list_for(1);
+        assert_eq!(suppliers(&subset), vec![1, 5]);
+    }
 
-            // No need to mark initial route as visited again since thread1 did it
+    #[test]
+    fn test_self_pairs_take_no_time() {
+        let (bsl_psd, _) = create_test_solver();
+        let shopper_location = Location::new(0.0, 0.0);
+        let customer_location = Location::new(20.0, 20.0);
+
+        assert!(bsl_psd.travel_times.keys().all(|(from, to)| from != to));
+        assert_eq!(bsl_psd.travel_time(1, 1), Some(0.0));
+        assert_eq!(bsl_psd.travel_time_or_euclidean(3, 3), 0.0);
+        assert!(bsl_psd.verify_travel_times());
+
+        // A repeated stop no longer turns the route time infinite
+        let repeated =
+            bsl_psd.calculate_shopping_time(&[1, 1, 2], shopper_location, customer_location);
+        let direct = bsl_psd.calculate_shopping_time(&[1, 2], shopper_location, customer_location);
+        assert!(repeated.is_finite());
+        assert_eq!(repeated, direct + bsl_psd.service_time(1));
+
+        // but route generation must never produce one
+        assert!(has_repeated_stop(&[1, 1, 2]));
+        assert!(!has_repeated_stop(&[1, 2, 1]));
+        assert!(!has_repeated_stop(&[]));
+    }
 
-            while let Some(route_candidate) = queue.pop() {
-                // Check if upper bound route has been found
-                if found_upper_bound_clone2.load(Relaxed) {
-                    break;
-                }
+    #[test]
+    fn test_edge_costs_report_distance_and_time() {
+        let (bsl_psd, _) = create_test_solver();
+        let stores: HashMap<StoreId, Store> = bsl_psd
+            .stores
+            .iter()
+            .map(|(&id, store)| (id, store.read().unwrap().clone()))
+            .collect();
 
-                // Check if route satisfies shopping list
-                let satisfies = self_clone2
-                    .satisfies_list_with_inventory(&route_candidate.stores, &shopping_list_clone2);
+        // Plain travel times are read as distances at the default speed
+        let legacy = bsl_psd.edge_cost(1, 2).unwrap();
+        assert_eq!(legacy.distance_m, bsl_psd.travel_time(1, 2).unwrap());
+        assert_eq!(legacy, EdgeCost::from_distance(legacy.distance_m));
+        assert_eq!(bsl_psd.edge_cost(1, 1), Some(EdgeCost::default()));
+
+        // Driving 3 times slower than the default speed
+        let edge_costs: HashMap<(StoreId, StoreId), EdgeCost> = bsl_psd
+            .travel_times
+            .iter()
+            .map(|(&pair, &distance)| {
+                (
+                    pair,
+                    EdgeCost::from_distance_at(distance, DEFAULT_SPEED_KMH / 3.0),
+                )
+            })
+            .collect();
+        let mut by_time =
+            BSLPSD::new_with_edge_costs(stores.clone(), edge_costs.clone(), RoutingObjective::Time);
+        by_time.set_distance_metric(DistanceMetric::Euclidean);
+        let mut by_distance =
+            BSLPSD::new_with_edge_costs(stores, edge_costs.clone(), RoutingObjective::Distance);
+        by_distance.set_distance_metric(DistanceMetric::Euclidean);
+
+        for (&(from, to), cost) in &edge_costs {
+            assert!(cost.distance_m > 0.0 && cost.time_min > 0.0);
+            assert_eq!(by_time.travel_time(from, to), Some(cost.time_min));
+            assert_eq!(by_distance.travel_time(from, to), Some(cost.distance_m));
+            assert_eq!(by_time.edge_cost(from, to), Some(*cost));
+            let legacy = bsl_psd.edge_cost(from, to).unwrap();
+            assert!((cost.time_min - 3.0 * legacy.time_min).abs() < 1e-9);
+        }
 
-                if satisfies {
-                    // Calculate shopping cost
-                    let shopping_cost = self_clone2
-                        .calculate_shopping_cost(&route_candidate.stores, &shopping_list_clone2);
+        // Route distances are the same whichever dimension is optimized
+        let route = [1, 5, 3];
+        let (shopper, customer) = (Location::new(0.0, 0.0), Location::new(20.0, 20.0));
+        let distance = bsl_psd.route_distance(&route, shopper, customer);
+        for solver in [&by_time, &by_distance] {
+            assert!((solver.route_distance(&route, shopper, customer) - distance).abs() < 1e-9);
+        }
+    }
 
-                    // Create complete shopping route
-                    let shopping_route = ShoppingRoute {
-                        stores: route_candidate.stores.clone(),
-                        shopping_time: route_candidate.shopping_time,
-                        shopping_cost,
-                    };
+    #[test]
+    fn test_asymmetric_travel_times_pick_the_directed_order() {
+        // Shopper and customer at the origin, equally far from both stores
+        let mut stores = HashMap::new();
+        for (id, x, product_id) in [(1, 3.0, 1), (2, -3.0, 2)] {
+            let products = HashMap::from([(product_id, Product::new("item", 5.0))]);
+            let inventory = HashMap::from([(product_id, 10)]);
+            stores.insert(
+                id,
+                Store::new_with_inventory(id, Location::new(x, 4.0), products, inventory),
+            );
+        }
+        let mut shopping_list = ShoppingList::new();
+        shopping_list.add_item(1, 1);
+        shopping_list.add_item(2, 1);
+        let origin = Location::new(0.0, 0.0);
+
+        // Downhill from 1 to 2, a long climb back
+        let travel_times = HashMap::from([((1, 2), 1.0), ((2, 1), 20.0)]);
+        let mut bsl_psd = BSLPSD::new_with_travel_times(stores.clone(), travel_times);
+        bsl_psd.set_distance_metric(DistanceMetric::Euclidean);
+        bsl_psd.precompute_data();
+        assert_eq!(
+            bsl_psd.find_shortest_path(&[2, 1], &origin, &origin),
+            vec![1, 2]
+        );
+        assert_eq!(
+            bsl_psd.calculate_total_time(&[1, 2], &origin, &origin),
+            11.0
+        );
+        assert_eq!(
+            bsl_psd.calculate_total_time(&[2, 1], &origin, &origin),
+            30.0
+        );
+        let fastest = bsl_psd
+            .find_min_time_route_dijkstra(&shopping_list, origin, origin)
+            .unwrap();
+        assert_eq!((fastest.stores, fastest.shopping_time), (vec![1, 2], 11.0));
+
+        // Reversing the matrix reverses the best order
+        let travel_times = HashMap::from([((1, 2), 20.0), ((2, 1), 1.0)]);
+        let mut reversed = BSLPSD::new_with_travel_times(stores, travel_times);
+        reversed.set_distance_metric(DistanceMetric::Euclidean);
+        reversed.precompute_data();
+        assert_eq!(
+            reversed.find_shortest_path(&[1, 2], &origin, &origin),
+            vec![2, 1]
+        );
+        let (skyline, _) = reversed.solve_with_parallel(&shopping_list, origin, origin, 100);
+        assert_eq!(skyline.fastest().unwrap().stores, vec![2, 1]);
+        assert_eq!(
+            reversed.solve_exact(&shopping_list, origin, origin)[0].stores,
+            vec![2, 1]
+        );
+    }
 
-                    // Send found route
-                    tx2.send(shopping_route.clone()).unwrap();
+    #[test]
+    fn test_customer_leg_follows_one_way_streets() {
+        let (mut bsl_psd, _) = create_test_solver();
+        let store = bsl_psd.stores[&1].read().unwrap().clone();
+        let customer_location = Location::new(store.location.x - 10.0, store.location.y);
+
+        // One-way street from the customer to the store, a detour via vertex 3 back
+        let vertices: HashMap<u64, (f64, f64)> = [
+            (1, (customer_location.x, customer_location.y)),
+            (2, (store.location.x, store.location.y)),
+            (3, (store.location.x - 5.0, store.location.y + 5.0)),
+        ]
+        .into();
+        let edges: HashMap<u64, RoadEdge> = [
+            (
+                1,
+                RoadEdge {
+                    direction: EdgeDirection::Forward,
+                    ..RoadEdge::two_way(1, 2)
+                },
+            ),
+            (2, RoadEdge::two_way(2, 3)),
+            (3, RoadEdge::two_way(3, 1)),
+        ]
+        .into();
+        let graph = RoadGraph::from_road_edges(vertices, edges, DistanceMetric::Euclidean);
+        bsl_psd.set_road_graph(Arc::new(graph));
+
+        let inbound = bsl_psd.endpoint_leg(&customer_location, &store);
+        let outbound = bsl_psd.customer_leg(&store, &customer_location);
+        assert!((inbound - 10_000.0).abs() < 1e-6);
+        assert!((outbound - 2.0 * 50.0_f64.sqrt() * 1000.0).abs() < 1e-6);
+
+        // Both directions are cached separately
+        assert_eq!(bsl_psd.endpoint_legs_computed(), 2);
+        assert_eq!(bsl_psd.customer_leg(&store, &customer_location), outbound);
+        assert_eq!(bsl_psd.endpoint_legs_computed(), 2);
+
+        let shopper_location = Location::new(0.0, 0.0);
+        let time = bsl_psd.calculate_total_time(&[1], &shopper_location, &customer_location);
+        let expected =
+            bsl_psd.endpoint_leg(&shopper_location, &store) + store.service_time + outbound;
+        assert!((time - expected).abs() < 1e-6);
+    }
 
-                    // println!(
-                    //     "Thread 2 found route: {:?}, cost: ${:.2}, time: {:.2}",
-                    //     route_candidate.stores, shopping_cost, route_candidate.shopping_time
-                    // );
+    #[test]
+    fn test_store_contributions_reconstruct_the_list() {
+        let (bsl_psd, shopping_list) = create_test_solver();
+        let route = [1, 2, 3, 4, 5];
+        let contributions = bsl_psd.store_contributions(&route, &shopping_list);
+
+        let mut keys: Vec<StoreId> = contributions.keys().cloned().collect();
+        keys.sort();
+        assert_eq!(keys, route);
+
+        // Per-store quantities add up to the list, at the route's shopping cost
+        let mut bought: HashMap<ProductId, u32> = HashMap::new();
+        let mut cost = 0.0;
+        for (store_id, products) in &contributions {
+            let store = bsl_psd.stores[store_id].read().unwrap();
+            for &(product_id, quantity) in products {
+                assert!(quantity > 0);
+                *bought.entry(product_id).or_insert(0) += quantity;
+                cost += store.get_unit_price(&product_id).unwrap() * quantity as f64;
+            }
+        }
+        assert_eq!(bought, shopping_list.items);
+        let expected = bsl_psd.calculate_shopping_cost(&route, &shopping_list);
+        assert!((cost - expected).abs() < 1e-9);
+
+        // Matches the per-product view of the same allocation
+        let allocation = bsl_psd.allocate_purchases(&route, &shopping_list);
+        for (product_id, purchases) in &allocation.purchases {
+            for &(store_id, quantity, _) in purchases {
+                assert!(contributions[&store_id].contains(&(*product_id, quantity)));
+            }
+        }
+    }
 
-                    // Check if upper bound has been reached
-                    if shopping_cost == sc_upper_bound {
-                        println!("Thread 2 found the sc_upper_bound skyline route!");
-                        found_upper_bound_clone2.store(true, Relaxed);
-                        break;
-                    }
+    #[test]
+    fn test_three_objectives_keep_fewer_stops() {
+        // Three cheap stores along the way, and store 4 off to the side selling
+        // products 1 and 2 at twice the price
+        let mut stores = HashMap::new();
+        for (id, x, y, offer) in [
+            (1, 2.0, 0.0, vec![(1, 1.0)]),
+            (2, 5.0, 0.0, vec![(2, 1.0)]),
+            (3, 8.0, 0.0, vec![(3, 1.0)]),
+            (4, 5.0, 3.0, vec![(1, 2.0), (2, 2.0)]),
+        ] {
+            let products = offer
+                .iter()
+                .map(|&(product_id, cost)| (product_id, Product::new("item", cost)))
+                .collect();
+            let inventory = offer
+                .iter()
+                .map(|&(product_id, _)| (product_id, 5))
+                .collect();
+            stores.insert(
+                id,
+                Store::new_with_inventory(id, Location::new(x, y), products, inventory),
+            );
+        }
+        let mut travel_times = HashMap::new();
+        for (&from, from_store) in &stores {
+            for (&to, to_store) in &stores {
+                if from != to {
+                    let time = from_store.location.distance_to(&to_store.location);
+                    travel_times.insert((from, to), time);
                 }
+            }
+        }
+        let mut bsl_psd = BSLPSD::new_with_travel_times(stores, travel_times);
+        bsl_psd.set_distance_metric(DistanceMetric::Euclidean);
+        bsl_psd.precompute_data();
+        let mut shopping_list = ShoppingList::new();
+        for product_id in 1..=3 {
+            shopping_list.add_item(product_id, 1);
+        }
+        let shopper_location = Location::new(0.0, 0.0);
+        let customer_location = Location::new(10.0, 0.0);
+
+        // On time and cost alone the 3-store route along the way wins
+        let (skyline, _) =
+            bsl_psd.solve_with_parallel(&shopping_list, shopper_location, customer_location, 100);
+        assert_eq!(skyline.len(), 1);
+        assert_eq!(skyline[0].stores, vec![1, 2, 3]);
+
+        // With store count as an objective the 2-store detour is kept too
+        let front =
+            bsl_psd.solve_three_objective(&shopping_list, shopper_location, customer_location);
+        let stores: Vec<Vec<StoreId>> = front.iter().map(|route| route.stores.clone()).collect();
+        assert_eq!(stores, vec![vec![4, 3], vec![1, 2, 3]]);
+        assert!(front[0].shopping_time > front[1].shopping_time);
+        assert!(front[0].shopping_cost > front[1].shopping_cost);
+        for route in &front {
+            for other in &front {
+                assert!(!other.dominates_with_store_count(route));
+            }
+        }
+    }
 
-                // Generate next batch of routes (using shuffle version)
-                let next_routes = self_clone2.generate_next_routes_shuffle(
-                    &route_candidate,
-                    &shopper_location_clone,
-                    &customer_location_clone,
-                );
+    #[test]
+    fn test_reprice_reorders_cheapest_first_allocation() {
+        let (mut bsl_psd, _) = create_test_solver();
+        let mut shopping_list = ShoppingList::new();
+        shopping_list.add_item(1, 2);
+
+        // Store 5 sells A for 6.00, undercutting store 1 at 7.00
+        let allocation = bsl_psd.allocate_purchases(&[1, 5], &shopping_list);
+        assert_eq!(allocation.purchases[&1], vec![(5, 2, 6.0)]);
+        assert_eq!(bsl_psd.product_to_stores[&1][0].0, 5);
+
+        assert!(bsl_psd.reprice(1, 1, 5.0));
+        assert!(!bsl_psd.reprice(2, 1, 1.0));
+        assert!(!bsl_psd.reprice(99, 1, 1.0));
+        assert_eq!(bsl_psd.product_to_stores[&1], vec![(1, 5.0), (5, 6.0)]);
+
+        let allocation = bsl_psd.allocate_purchases(&[1, 5], &shopping_list);
+        assert_eq!(allocation.purchases[&1], vec![(1, 2, 5.0)]);
+        assert_eq!(
+            bsl_psd.calculate_shopping_cost(&[1, 5], &shopping_list),
+            10.0
+        );
+
+        // The solve paths see the new order without a manual rebuild
+        assert_eq!(bsl_psd.coverage_report(&shopping_list)[0].stores[0].0, 1);
+        let cheapest = bsl_psd
+            .find_min_cost_route_full(
+                &shopping_list,
+                Location::new(0.0, 0.0),
+                Location::new(20.0, 20.0),
+            )
+            .unwrap();
+        assert_eq!((cheapest.stores, cheapest.shopping_cost), (vec![1], 10.0));
+    }
 
-                // Filter already visited routes and add to queue
-                for next_route in next_routes {
-                    let is_new_route;
-                    {
-                        // let visited = visited_routes2.read().unwrap();
-                        is_new_route = !visited_route.contains(&next_route.stores);
-                        if is_new_route {
-                            // visited.insert(next_route.stores.clone());
+    #[test]
+    fn test_store_coordinates_match_store_locations() {
+        let (bsl_psd, _) = create_test_solver();
+        let route = ShoppingRoute::new(vec![5, 1, 3, 99], 30.0, 40.0);
 
-                            visited_route.insert(next_route.stores.clone());
-                        }
-                    }
-                    if is_new_route {
-                        queue.push(next_route);
-                    }
-                }
-            }
-        });
+        let coordinates = bsl_psd.store_coordinates(&route);
+        assert_eq!(coordinates.len(), 3);
+        for (store_id, (x, y)) in &coordinates {
+            let location = bsl_psd.stores[store_id].read().unwrap().location;
+            assert_eq!(Location::new(*x, *y), location);
+        }
+        assert_eq!(coordinates[&1], (10.0, 6.0));
 
-        // Main thread processes received skyline routes
-        let mut linear_skyline = Vec::new();
-        // let mut last_size = 0;
-        let mut unchanged_count = 0;
-        // let max_unchanged = 10000; // Set a threshold for how many unchanged iterations before exiting
-        // Process all received routes
-        while let Ok(route) = rx.recv() {
-            let old_size = linear_skyline.len();
-            let update = self.update_skyline(&mut linear_skyline, route);
-            // self.update_skyline(&mut linear_skyline, route);
-            // println!("routes: {}", linear_skyline.len());
-            if linear_skyline.len() == old_size && !update {
-                // unchanged_count += 1;
-                // println!("Skyline unchanged for {} iterations", unchanged_count);
+        // The same every time
+        assert_eq!(bsl_psd.store_coordinates(&route), coordinates);
+    }
 
-                // If unchanged for multiple iterations, consider exhaustive search complete
-                if unchanged_count >= threshold {
-                    println!(
-                        "Skyline size remained at {} for {} iterations, breaking",
-                        linear_skyline.len(),
-                        threshold
-                    );
-                    found_upper_bound.store(true, Relaxed);
-                    // break;
-                }
-            } else {
-                unchanged_count = 0
+    #[test]
+    fn test_coverage_report_on_partially_stocked_map() {
+        let (bsl_psd, _) = create_test_solver();
+        // Store 3 sells out of product C
+        bsl_psd.stores[&3].write().unwrap().inventory.remove(&3);
+
+        let mut shopping_list = ShoppingList::new();
+        shopping_list.add_item(7, 1);
+        shopping_list.add_item(4, 2);
+        shopping_list.add_item(3, 12);
+        shopping_list.add_item(1, 11);
+
+        let report = bsl_psd.coverage_report(&shopping_list);
+        let products: Vec<ProductId> = report.iter().map(|coverage| coverage.product).collect();
+        assert_eq!(products, vec![1, 3, 4, 7]);
+
+        assert_eq!(
+            report[0],
+            ProductCoverage {
+                product: 1,
+                required: 11,
+                available: 8,
+                stores: vec![(5, 3, 6.0), (1, 5, 7.0)],
             }
-            // if linear_skyline.len() >= 15 {
-            //     println!("Already got 15 routes in optimal route set, start break");
-            //     found_upper_bound.store(true, Relaxed);
-            // }
+        );
+        assert_eq!(report[0].shortfall(), 3);
+
+        // The sold-out store is left out of the breakdown and the total
+        assert_eq!(report[1].stores, vec![(4, 7, 8.0), (2, 4, 10.0)]);
+        assert_eq!(report[1].available, 11);
+        assert_eq!(report[1].shortfall(), 1);
+
+        assert_eq!(
+            report[2].stores,
+            vec![(3, 2, 4.0), (4, 4, 7.0), (2, 6, 8.0)]
+        );
+        assert_eq!(report[2].available, 12);
+        assert_eq!(report[2].shortfall(), 0);
+
+        assert!(report[3].stores.is_empty());
+        assert_eq!(report[3].available, 0);
+        assert_eq!(report[3].shortfall(), 1);
+    }
+
+    #[test]
+    fn test_best_single_store() {
+        let (bsl_psd, shopping_list) = create_test_solver();
+        let shopper_location = Location::new(0.0, 0.0);
+        let customer_location = Location::new(20.0, 20.0);
+
+        // The default list needs at least two stores
+        assert_eq!(
+            bsl_psd.best_single_store(&shopping_list, shopper_location, customer_location),
+            None
+        );
+
+        // Only store 4 has five units of C along with F
+        let mut one_stop = ShoppingList::new();
+        one_stop.add_item(3, 5);
+        one_stop.add_item(6, 1);
+        let route = bsl_psd
+            .best_single_store(&one_stop, shopper_location, customer_location)
+            .unwrap();
+        assert_eq!(route.stores, vec![4]);
+        assert_eq!(route.shopping_cost, 5.0 * 8.0 + 12.0);
+        assert_eq!(
+            route.shopping_time,
+            bsl_psd.calculate_shopping_time(&[4], shopper_location, customer_location)
+        );
+
+        // With two C, store 3 covers it for less
+        let mut cheaper = ShoppingList::new();
+        cheaper.add_item(3, 2);
+        cheaper.add_item(6, 1);
+        let route = bsl_psd
+            .best_single_store(&cheaper, shopper_location, customer_location)
+            .unwrap();
+        assert_eq!(route.stores, vec![3]);
+        assert_eq!(route.shopping_cost, 2.0 * 5.0 + 6.0);
+    }
+
+    #[test]
+    fn test_doubling_speed_halves_shopping_times() {
+        let (mut bsl_psd, shopping_list) = create_test_solver();
+        let shopper_location = Location::new(0.0, 0.0);
+        let customer_location = Location::new(20.0, 20.0);
+
+        let slow = bsl_psd.solve_exact(&shopping_list, shopper_location, customer_location);
+        let slow_direct =
+            bsl_psd.solve_exact(&ShoppingList::new(), shopper_location, customer_location);
+        let slow_min_time = bsl_psd
+            .find_min_time_route_dijkstra(&shopping_list, shopper_location, customer_location)
+            .unwrap();
+        bsl_psd.set_speed(2.0);
+        let fast = bsl_psd.solve_exact(&shopping_list, shopper_location, customer_location);
+        let fast_direct =
+            bsl_psd.solve_exact(&ShoppingList::new(), shopper_location, customer_location);
+
+        assert!(slow.len() > 1);
+        assert_eq!(slow.len(), fast.len());
+        for (slow, fast) in slow
+            .iter()
+            .chain(&slow_direct)
+            .zip(fast.iter().chain(&fast_direct))
+        {
+            assert_eq!(slow.stores, fast.stores);
+            assert_eq!(slow.shopping_cost, fast.shopping_cost);
+            assert!((slow.shopping_time / 2.0 - fast.shopping_time).abs() < 1e-9);
         }
 
-        // Sort skyline
-        linear_skyline.sort_by(|a, b| {
-            a.shopping_time
-                .partial_cmp(&b.shopping_time)
-                .unwrap_or(Ordering::Equal)
-        });
+        let fast_min_time = bsl_psd
+            .find_min_time_route_dijkstra(&shopping_list, shopper_location, customer_location)
+            .unwrap();
+        assert_eq!(slow_min_time.stores, fast_min_time.stores);
+        assert!((slow_min_time.shopping_time / 2.0 - fast_min_time.shopping_time).abs() < 1e-9);
+    }
 
-        println!("Final skyline size: {}", linear_skyline.len());
-        (linear_skyline, elapsed_limited)
+    #[test]
+    fn test_route_distance_sums_every_leg() {
+        let (mut bsl_psd, _) = create_test_solver();
+        let shopper_location = Location::new(0.0, 0.0);
+        let customer_location = Location::new(20.0, 20.0);
+        let location = |store_id: StoreId| bsl_psd.stores[&store_id].read().unwrap().location;
+
+        let route = [1, 5, 3];
+        let manual = shopper_location.distance_to(&location(1))
+            + bsl_psd.travel_time(1, 5).unwrap()
+            + bsl_psd.travel_time(5, 3).unwrap()
+            + location(3).distance_to(&customer_location);
+        let distance = bsl_psd.route_distance(&route, shopper_location, customer_location);
+        assert!((distance - manual).abs() < 1e-9);
+        assert!(
+            (bsl_psd.route_distance(&[], shopper_location, customer_location)
+                - shopper_location.distance_to(&customer_location))
+            .abs()
+                < 1e-9
+        );
+
+        // The shopper's speed changes times, not distances
+        bsl_psd.set_speed(4.0);
+        let time = bsl_psd.calculate_shopping_time(&route, shopper_location, customer_location);
+        assert!((time - distance / 4.0).abs() < 1e-9);
+        assert!(
+            (bsl_psd.route_distance(&route, shopper_location, customer_location) - distance).abs()
+                < 1e-9
+        );
+
+        // Unknown store legs fall back to the straight line
+        bsl_psd.travel_times.remove(&(1, 5));
+        assert!(
+            (bsl_psd.route_distance(&route, shopper_location, customer_location) - distance).abs()
+                < 1e-9
+        );
+
+        // A round trip adds the way back to the depot
+        bsl_psd.return_location = Some(shopper_location);
+        let round_trip = bsl_psd.route_distance(&route, shopper_location, customer_location);
+        assert!(
+            (round_trip - distance - customer_location.distance_to(&shopper_location)).abs() < 1e-9
+        );
     }
 
-    /// Debug version of solve function with logging and timeout
-    pub fn solve_with_debug(
-        &self,
-        shopping_list: &ShoppingList,
-        shopper_location: Location,
-        customer_location: Location,
-        threshold: i32,
-    ) -> Vec<ShoppingRoute> {
-        println!("Starting BSL-PSD algorithm with debug mode...");
+    #[test]
+    fn test_route_distance_falls_back_in_road_units() {
+        let (mut bsl_psd, _) = create_test_solver();
+        let shopper_location = Location::new(0.0, 0.0);
+        let customer_location = Location::new(20.0, 20.0);
+        let location = |bsl_psd: &BSLPSD, store_id: StoreId| {
+            bsl_psd.stores[&store_id].read().unwrap().location
+        };
 
-        // Step 1: Find route with minimum shopping cost
-        let min_cost_route =
-            match self.find_min_cost_route(shopping_list, shopper_location, customer_location) {
-                Some(route) => route,
-                None => {
-                    println!("No minimum cost route found, aborting.");
-                    return Vec::new(); // No solution available
-                }
-            };
-        println!("min cost route: {:?}", min_cost_route);
+        // A road graph with no edges has no path for any endpoint leg
+        let mut points = vec![shopper_location, customer_location];
+        points.extend([1, 5, 3].map(|store_id| location(&bsl_psd, store_id)));
+        let vertices: HashMap<u64, (f64, f64)> = (0..)
+            .zip(points.iter().map(|point| (point.x, point.y)))
+            .collect();
+        bsl_psd.set_road_graph(Arc::new(RoadGraph::new_with_metric(
+            vertices,
+            HashMap::new(),
+            DistanceMetric::Euclidean,
+        )));
+
+        // The matrix misses 1 -> 5, so that leg is measured straight, like the endpoints
+        bsl_psd.travel_times.remove(&(1, 5));
+        let straight = |from: &Location, to: &Location| from.distance_to(to) * 1000.0;
+        let expected = straight(&shopper_location, &location(&bsl_psd, 1))
+            + straight(&location(&bsl_psd, 1), &location(&bsl_psd, 5))
+            + bsl_psd.travel_time(5, 3).unwrap()
+            + straight(&location(&bsl_psd, 3), &customer_location);
+        let distance = bsl_psd.route_distance(&[1, 5, 3], shopper_location, customer_location);
+        assert!((distance - expected).abs() < 1e-6);
+
+        // The direct route is measured the same way, for its distance and its time
+        let direct = straight(&shopper_location, &customer_location);
+        assert!(
+            (bsl_psd.route_distance(&[], shopper_location, customer_location) - direct).abs()
+                < 1e-6
+        );
+        assert!(
+            (bsl_psd.calculate_total_time(&[], &shopper_location, &customer_location) - direct)
+                .abs()
+                < 1e-6
+        );
+    }
 
-        // Find route with minimum time cost
-        let min_time_route = match self.find_min_time_route_dijkstra(
-            shopping_list,
-            shopper_location,
-            customer_location,
-        ) {
-            Some(route) => route,
-            None => {
-                println!("No minimum time route found, aborting.");
-                return Vec::new();
-            }
+    #[test]
+    fn test_missing_travel_times_fall_back_in_road_units() {
+        let (mut complete, shopping_list) = create_test_solver();
+        let shopper_location = Location::new(0.0, 0.0);
+        let customer_location = Location::new(20.0, 20.0);
+
+        // Road-derived matrix in metres, over a road graph with no path between stores
+        let mut points = vec![shopper_location, customer_location];
+        points.extend(
+            complete
+                .stores
+                .values()
+                .map(|store| store.read().unwrap().location),
+        );
+        let vertices: HashMap<u64, (f64, f64)> = (0..)
+            .zip(points.iter().map(|point| (point.x, point.y)))
+            .collect();
+        complete.set_road_graph(Arc::new(RoadGraph::new_with_metric(
+            vertices,
+            HashMap::new(),
+            DistanceMetric::Euclidean,
+        )));
+        for distance in complete.travel_times.values_mut() {
+            *distance *= 1000.0;
+        }
+        complete.set_speed(2.0);
+
+        // Dropping a pair times it by the straight line, just as the matrix had it
+        let mut sparse = complete.clone();
+        sparse.travel_times.remove(&(1, 5));
+        sparse.travel_times.remove(&(5, 1));
+        assert_eq!(sparse.travel_time(1, 5), None);
+        assert!(
+            (sparse.travel_time_or_euclidean(1, 5) - complete.travel_time(1, 5).unwrap()).abs()
+                < 1e-6
+        );
+        let route = [1, 5, 3];
+        assert!(
+            (sparse.calculate_total_time(&route, &shopper_location, &customer_location)
+                - complete.calculate_total_time(&route, &shopper_location, &customer_location))
+            .abs()
+                < 1e-6
+        );
+        let fastest = |solver: &BSLPSD| {
+            solver
+                .find_min_time_route_dijkstra(&shopping_list, shopper_location, customer_location)
+                .unwrap()
+                .shopping_time
         };
-        println!("min time route: {:?}", min_time_route);
+        assert!((fastest(&sparse) - fastest(&complete)).abs() < 1e-6);
+    }
 
-        // println!(
-        //     "Found min cost route: {:?} with cost: {}, sc_upper_bound: {}",
-        //     min_time_route.stores, min_time_route.shopping_cost, min_cost_route.shopping_cost
-        // );
+    #[test]
+    fn test_route_emissions_scale_with_the_factor() {
+        let (bsl_psd, shopping_list) = create_test_solver();
+        let shopper_location = Location::new(0.0, 0.0);
+        let customer_location = Location::new(20.0, 20.0);
+        let route = [1, 5, 3];
+        let distance = bsl_psd.route_distance(&route, shopper_location, customer_location);
+
+        let emissions = |factor: f64| {
+            bsl_psd.route_emissions(&route, shopper_location, customer_location, factor)
+        };
+        assert_eq!(emissions(0.0), 0.0);
+        assert!((emissions(1.0) - distance).abs() < 1e-9);
+        for factor in [0.12, 0.5, 3.0] {
+            assert!((emissions(2.0 * factor) - 2.0 * emissions(factor)).abs() < 1e-9);
+            assert!((emissions(factor) - factor * distance).abs() < 1e-9);
+        }
+
+        // The report only has emissions when given a factor
+        let skyline = bsl_psd.solve_exact(&shopping_list, shopper_location, customer_location);
+        let fastest = skyline.fastest().unwrap();
+        let report = bsl_psd.route_report(fastest, shopper_location, customer_location, None);
+        assert_eq!(report.stores, fastest.stores);
+        assert_eq!(report.shopping_cost, fastest.shopping_cost);
+        assert_eq!(report.emissions, None);
+        let report = bsl_psd.route_report(fastest, shopper_location, customer_location, Some(0.2));
+        assert_eq!(report.emissions, Some(report.distance * 0.2));
+
+        // The greenest route is the shortest on the frontier
+        let distance_of = |route: &ShoppingRoute| {
+            bsl_psd.route_distance(&route.stores, shopper_location, customer_location)
+        };
+        let greenest = skyline.greenest_route(distance_of).unwrap();
+        assert!(skyline
+            .iter()
+            .all(|route| distance_of(greenest) <= distance_of(route)));
+        assert_eq!(Skyline::new().greenest_route(distance_of), None);
+    }
+
+    #[test]
+    fn test_sparse_travel_times_are_crossed_in_a_straight_line() {
+        let (mut bsl_psd, shopping_list) = create_test_solver();
+        let shopper_location = Location::new(0.0, 0.0);
+        let customer_location = Location::new(20.0, 20.0);
+
+        // Only store 1 has travel times, and only to stores 2 and 3
+        bsl_psd
+            .travel_times
+            .retain(|&(from, to), _| from == 1 && (to == 2 || to == 3));
+
+        let route = bsl_psd
+            .find_min_time_route_dijkstra(&shopping_list, shopper_location, customer_location)
+            .unwrap();
+        assert!(route.stores.len() > 1);
+        assert!(bsl_psd.satisfies_list(&route.stores, &shopping_list));
+        // The shuffled generator's timing agrees with the search
+        let time =
+            bsl_psd.calculate_total_time(&route.stores, &shopper_location, &customer_location);
+        assert!((route.shopping_time - time).abs() < 1e-9);
+
+        // Without the fallback the search only uses known legs, though the route above
+        // still has a finite time under `calculate_total_time`
+        bsl_psd.set_straight_line_fallback(false);
+        let known_legs = bsl_psd
+            .find_min_time_route_dijkstra(&shopping_list, shopper_location, customer_location)
+            .unwrap();
+        assert!(known_legs
+            .stores
+            .windows(2)
+            .all(|leg| bsl_psd.travel_time(leg[0], leg[1]).is_some()));
+        assert!(known_legs.shopping_time >= route.shopping_time);
+        assert!(bsl_psd
+            .calculate_total_time(&route.stores, &shopper_location, &customer_location)
+            .is_finite());
+
+        // With no travel times at all, only the fallback can combine stores
+        bsl_psd.travel_times.clear();
+        assert_eq!(
+            bsl_psd.find_min_time_route_dijkstra(
+                &shopping_list,
+                shopper_location,
+                customer_location
+            ),
+            None
+        );
+        bsl_psd.set_straight_line_fallback(true);
+        assert!(bsl_psd
+            .find_min_time_route_dijkstra(&shopping_list, shopper_location, customer_location)
+            .is_some());
+    }
 
-        // let sc_upper_bound = min_cost_route.shopping_cost;
-        let sc_upper_bound = min_cost_route;
+    #[test]
+    fn test_builder_applies_settings() {
+        let shopper = Location::new(0.0, 0.0);
+        let customer = Location::new(20.0, 20.0);
+        let (default_solver, list) = create_test_solver();
+        let (stores, _) = create_test_data();
+        let travel_times = default_solver.travel_times.clone();
+
+        let solver = BSLPSDBuilder::new(stores)
+            .travel_times(travel_times)
+            .distance_metric(DistanceMetric::Manhattan)
+            .speed(2.0)
+            .workers(1)
+            .threshold(500)
+            .max_cost(43.0)
+            .deadline(Duration::from_secs(30))
+            .build();
+
+        assert_eq!(solver.options().workers, Some(1));
+        assert_eq!(solver.options().threshold, 500);
+        assert_eq!(solver.options().deadline, Some(Duration::from_secs(30)));
+        assert!(!solver.product_to_stores.is_empty());
+        assert_eq!(solver.distance_metric, DistanceMetric::Manhattan);
+
+        // Half the default speed's leg times, with Manhattan endpoint legs
+        let route = vec![3, 5];
+        let expected = (shopper.manhattan_distance_to(&solver.stores[&3].read().unwrap().location)
+            + default_solver.travel_times[&(3, 5)]
+            + solver.stores[&5]
+                .read()
+                .unwrap()
+                .location
+                .manhattan_distance_to(&customer))
+            / 2.0;
+        let time = solver.calculate_shopping_time(&route, shopper, customer);
+        assert!((time - expected).abs() < 1e-9, "{time} vs {expected}");
+
+        let skyline = solver.solve(&list, shopper, customer);
+        assert!(!skyline.is_empty());
+        assert!(skyline.iter().all(|route| route.shopping_cost <= 43.0));
+        let unbounded = default_solver.solve_exact(&list, shopper, customer);
+        assert!(unbounded.iter().any(|route| route.shopping_cost > 43.0));
+    }
 
-        // Step 2: Initialize priority queue and linear skyline
-        let mut queue = BinaryHeap::new();
-        let mut linear_skyline = Vec::new();
+    #[test]
+    fn test_marginal_store_tradeoff() {
+        let (bsl_psd, _) = create_test_solver();
+        let shopper_location = Location::new(0.0, 0.0);
+        let customer_location = Location::new(12.0, 21.0);
+
+        // Store 2 sells D and E for 18 next to the customer; D at store 3 and E at
+        // store 5 cost 12 but take a detour
+        let mut shopping_list = ShoppingList::new();
+        shopping_list.add_item(4, 1);
+        shopping_list.add_item(5, 1);
+
+        let (time_added, cost_saved) = bsl_psd
+            .marginal_store_tradeoff(&shopping_list, shopper_location, customer_location, 1)
+            .unwrap();
+        let single = bsl_psd.calculate_total_time(&[2], &shopper_location, &customer_location);
+        let pair = bsl_psd.calculate_total_time(&[5, 3], &shopper_location, &customer_location);
+        assert!((cost_saved - 6.0).abs() < 1e-9);
+        assert!(time_added > 0.0);
+        assert!((time_added - (pair - single)).abs() < 1e-9);
+
+        // A third store saves nothing more
+        assert_eq!(
+            bsl_psd.marginal_store_tradeoff(&shopping_list, shopper_location, customer_location, 2),
+            Some((0.0, 0.0))
+        );
+
+        // No single store sells A, B, C and D
+        let (_, full_list) = create_test_data();
+        assert_eq!(
+            bsl_psd.marginal_store_tradeoff(&full_list, shopper_location, customer_location, 1),
+            None
+        );
+    }
 
-        let mut visited_routes = HashSet::new();
+    #[test]
+    fn test_equal_prices_are_allocated_by_store_id() {
+        let mut shopping_list = ShoppingList::new();
+        shopping_list.add_item(1, 1);
+
+        // Fresh solvers iterate their stores in different orders
+        for _ in 0..20 {
+            let mut stores = HashMap::new();
+            for id in [7, 3, 5] {
+                let products = HashMap::from([(1, Product::new("A", 4.0))]);
+                let inventory = HashMap::from([(1, 1)]);
+                let location = Location::new(id as f64, 0.0);
+                stores.insert(
+                    id,
+                    Store::new_with_inventory(id, location, products, inventory),
+                );
+            }
+            let mut bsl_psd = BSLPSD::new(stores);
+            bsl_psd.set_distance_metric(DistanceMetric::Euclidean);
+            bsl_psd.precompute_data();
+
+            let route = bsl_psd
+                .find_min_cost_route_full(
+                    &shopping_list,
+                    Location::new(0.0, 0.0),
+                    Location::new(10.0, 0.0),
+                )
+                .unwrap();
+            assert_eq!(route.stores, vec![3]);
+
+            assert!(bsl_psd.reserve_inventory(&[7, 5, 3], &shopping_list));
+            assert_eq!(bsl_psd.snapshot_inventory()[&3][&1], 0);
+            assert_eq!(bsl_psd.snapshot_inventory()[&5][&1], 1);
+        }
+    }
 
-        queue.push(RouteCandidate {
-            stores: min_time_route.stores.clone(),
-            shopping_time: min_time_route.shopping_time,
-        });
+    #[test]
+    fn test_route_geometry_follows_visit_order() {
+        let (bsl_psd, _) = create_test_solver();
+        let route = vec![3, 1, 5];
 
-        println!("Initial queue size: {}", queue.len());
-        // let mut last_size = 0;
-        let mut unchanged_count = 0;
-        // let max_unchanged = 10000;
+        let geometry = bsl_psd.route_geometry(&route);
 
-        while let Some(route_candidate) = queue.pop() {
-            // Only consider the route if it satisfies the shopping list
-            let satisfies =
-                self.satisfies_list_with_inventory(&route_candidate.stores, shopping_list);
+        assert_eq!(geometry.len(), route.len());
+        for ((store_id, location), expected_id) in geometry.iter().zip(&route) {
+            assert_eq!(store_id, expected_id);
+            let store = bsl_psd.stores[expected_id].read().unwrap();
+            assert_eq!(*location, store.location);
+        }
+    }
 
-            if satisfies {
-                // Calculate actual shopping cost now that we know the route satisfies the list
-                let shopping_cost =
-                    self.calculate_shopping_cost(&route_candidate.stores, shopping_list);
+    #[test]
+    fn test_zero_stock_store_is_not_a_candidate() {
+        let (mut stores, shopping_list) = create_test_data();
+
+        // Store 6 lists product A but has none in stock
+        let mut products = HashMap::new();
+        products.insert(1, Product::new("A", 1.0));
+        let mut inventory = HashMap::new();
+        inventory.insert(1, 0);
+        stores.insert(
+            6,
+            Store::new_with_inventory(6, Location::new(1.0, 1.0), products, inventory),
+        );
+
+        let mut bsl_psd = BSLPSD::new(stores);
+
+        bsl_psd.set_distance_metric(DistanceMetric::Euclidean);
+        bsl_psd.precompute_data();
+
+        let candidates = bsl_psd.collect_candidate_stores(&shopping_list);
+        assert!(!candidates.contains_key(&6));
+        assert!(candidates.contains_key(&1));
+        assert!(!bsl_psd.product_to_stores[&1]
+            .iter()
+            .any(|&(store_id, _)| store_id == 6));
+
+        // The store still stocks the product in its catalog
+        assert_eq!(bsl_psd.stores_stocking(1), vec![1, 5, 6]);
+        assert!(bsl_psd.stores_stocking(7).is_empty());
+    }
 
-                // Create a complete ShoppingRoute with shopping cost
-                let shopping_route = ShoppingRoute {
-                    stores: route_candidate.stores.clone(),
-                    shopping_time: route_candidate.shopping_time,
-                    shopping_cost,
-                };
-                // println!("shopping route: {:?}", shopping_route);
+    #[test]
+    fn test_solve_with_trace_records_parents() {
+        let (bsl_psd, shopping_list) = create_test_solver();
+        let shopper_location = Location::new(0.0, 0.0);
+        let customer_location = Location::new(20.0, 20.0);
+
+        let min_time_route = bsl_psd
+            .find_min_time_route_dijkstra(&shopping_list, shopper_location, customer_location)
+            .unwrap();
+        let (skyline, trace) =
+            bsl_psd.solve_with_trace(&shopping_list, shopper_location, customer_location, 10000);
+
+        let root = trace.root().expect("trace has a root");
+        assert_eq!(root.parent, None);
+        assert_eq!(root.stores, min_time_route.stores);
+        assert_eq!(root.shopping_time, min_time_route.shopping_time);
+
+        for (i, node) in trace.nodes.iter().enumerate().skip(1) {
+            let parent = node.parent.expect("non-root node has a parent");
+            assert!(parent < i);
+        }
 
-                // Update linear skyline with the new route
-                let old_size = linear_skyline.len();
-                let update = self.update_skyline(&mut linear_skyline, shopping_route);
-                if linear_skyline.len() == old_size && !update {
-                    unchanged_count += 1;
-                    // println!("Skyline unchanged for {} iterations", unchanged_count);
+        assert!(trace.nodes.iter().any(|node| node.entered_skyline));
+        assert_eq!(
+            skyline,
+            bsl_psd.solve_with_debug(&shopping_list, shopper_location, customer_location, 10000)
+        );
+    }
 
-                    // If unchanged for multiple iterations, consider exhaustive search complete
-                    if unchanged_count >= threshold {
-                        println!(
-                            "Skyline size remained at {} for {} iterations, breaking",
-                            linear_skyline.len(),
-                            threshold
-                        );
-                        break;
-                    }
-                } else {
-                    unchanged_count = 0
-                }
-                // if self.update_skyline(&mut linear_skyline, shopping_route) {
-                //     println!(
-                //         "Found skyline route: {:?}, cost: ${:.2}, time: {:.2}",
-                //         route_candidate.stores, shopping_cost, route_candidate.shopping_time
-                //     );
-                // }
-                if shopping_cost == sc_upper_bound {
-                    println!("Found the sc_upper_bound skyline routes! Exist");
-                    break;
-                }
-            }
-            // Always generate next routes and add to queue
-            let mut next_routes = self.generate_next_routes(&route_candidate);
-            // let mut next_routes = self.generate_next_routes(
-            //     &route_candidate,
-            //     &shopper_location,
-            //     &customer_location,
-            // );
+    #[test]
+    fn test_keep_weak_alternatives_keeps_equal_time_routes() {
+        let routes = [
+            ShoppingRoute::new(vec![1], 10.0, 30.0),
+            ShoppingRoute::new(vec![2], 10.0, 25.0),
+            ShoppingRoute::new(vec![3], 15.0, 20.0),
+        ];
 
-            next_routes.retain(|route| {
-                let is_new_route = !visited_routes.contains(&route.stores);
-                if is_new_route {
-                    visited_routes.insert(route.stores.clone());
-                }
-                is_new_route
-            });
-            for next_route in next_routes {
-                // println!("next route: {:?}", next_route);
-                queue.push(next_route);
-            }
+        let mut bsl_psd = BSLPSD::new(HashMap::new());
+
+        bsl_psd.set_distance_metric(DistanceMetric::Euclidean);
+        let mut skyline = Vec::new();
+        for route in routes.iter().cloned() {
+            bsl_psd.update_skyline(&mut skyline, route);
         }
+        assert_eq!(skyline.len(), 2);
 
-        println!("Final skyline size: {}", linear_skyline.len());
+        bsl_psd.set_keep_weak_alternatives(true);
+        let mut weak_skyline = Vec::new();
+        for route in routes.iter().cloned() {
+            bsl_psd.update_skyline(&mut weak_skyline, route);
+        }
+        assert_eq!(weak_skyline.len(), 3);
+    }
 
-        // Sort skyline by shopping time (ascending)
-        linear_skyline.sort_by(|a, b| {
-            a.shopping_time
-                .partial_cmp(&b.shopping_time)
-                .unwrap_or(Ordering::Equal)
-        });
+    #[test]
+    fn test_endpoint_legs_are_cached_across_solves() {
+        let (mut bsl_psd, shopping_list) = create_test_solver();
+        let shopper_location = Location::new(0.0, 0.0);
+        let customer_location = Location::new(20.0, 20.0);
+
+        // Road chain through the shopper, every store (in ID order) and the customer
+        let mut points = vec![(shopper_location.x, shopper_location.y)];
+        let mut store_ids: Vec<StoreId> = bsl_psd.stores.keys().cloned().collect();
+        store_ids.sort();
+        for store_id in &store_ids {
+            let location = bsl_psd.stores[store_id].read().unwrap().location;
+            points.push((location.x, location.y));
+        }
+        points.push((customer_location.x, customer_location.y));
+        let vertices: HashMap<u64, (f64, f64)> = (0..).zip(points.iter().cloned()).collect();
+        let edges: HashMap<u64, (u64, u64)> = (0..points.len() as u64 - 1)
+            .map(|i| (i, (i, i + 1)))
+            .collect();
+        bsl_psd.set_road_graph(Arc::new(RoadGraph::new_with_metric(
+            vertices,
+            edges,
+            DistanceMetric::Euclidean,
+        )));
+
+        let first =
+            bsl_psd.solve_with_debug(&shopping_list, shopper_location, customer_location, 10000);
+        let computed_after_first = bsl_psd.endpoint_legs_computed();
+        assert!(computed_after_first > 0);
+
+        let second =
+            bsl_psd.solve_with_debug(&shopping_list, shopper_location, customer_location, 10000);
+        assert_eq!(bsl_psd.endpoint_legs_computed(), computed_after_first);
+        assert_eq!(first, second);
+    }
 
-        linear_skyline
+    #[test]
+    fn test_distance_metric_selects_endpoint_legs() {
+        let (mut bsl_psd, _) = create_test_solver();
+        let shopper_location = Location::new(4.8950, 52.3664);
+        let customer_location = Location::new(4.9050, 52.3764);
+        let route = vec![1];
+
+        let store_location = bsl_psd.stores[&1].read().unwrap().location;
+        let euclidean_time =
+            bsl_psd.calculate_shopping_time(&route, shopper_location, customer_location);
+        assert_eq!(
+            euclidean_time,
+            shopper_location.distance_to(&store_location)
+                + store_location.distance_to(&customer_location)
+        );
+
+        bsl_psd.set_distance_metric(DistanceMetric::Haversine);
+        let haversine_time =
+            bsl_psd.calculate_shopping_time(&route, shopper_location, customer_location);
+        assert_eq!(
+            haversine_time,
+            shopper_location.haversine_distance_to(&store_location)
+                + store_location.haversine_distance_to(&customer_location)
+        );
+
+        bsl_psd.set_distance_metric(DistanceMetric::Manhattan);
+        let manhattan_time =
+            bsl_psd.calculate_shopping_time(&route, shopper_location, customer_location);
+        assert_eq!(
+            manhattan_time,
+            shopper_location.manhattan_distance_to(&store_location)
+                + store_location.manhattan_distance_to(&customer_location)
+        );
+        assert!(manhattan_time >= euclidean_time);
+
+        // Legs between stores keep their precomputed travel times
+        let two_stores = vec![1, 5];
+        let store5_location = bsl_psd.stores[&5].read().unwrap().location;
+        assert_eq!(
+            bsl_psd.calculate_shopping_time(&two_stores, shopper_location, customer_location),
+            shopper_location.manhattan_distance_to(&store_location)
+                + bsl_psd.travel_time(1, 5).unwrap()
+                + store5_location.manhattan_distance_to(&customer_location)
+        );
     }
 
-    /// Verify that travel times are correctly precomputed for all store pairs
-    pub fn verify_travel_times(&self) -> bool {
-        let store_ids: Vec<StoreId> = self.stores.keys().cloned().collect();
-        let mut missing_pairs = Vec::new();
-        let mut infinity_pairs = Vec::new();
+    #[test]
+    fn test_solve_with_start_time_respects_opening_hours() {
+        let (mut bsl_psd, shopping_list) = create_test_solver();
+        let shopper_location = Location::new(0.0, 0.0);
+        let customer_location = Location::new(20.0, 20.0);
 
-        for i in 0..store_ids.len() {
-            for j in 0..store_ids.len() {
-                if i != j {
-                    let from = store_ids[i];
-                    let to = store_ids[j];
+        // Store 3 opens at 10:00; the shopper leaves at 08:00
+        {
+            let mut store3 = bsl_psd.stores[&3].write().unwrap();
+            store3.hours = Some((600.0, 1200.0));
+        }
 
-                    match self.travel_times.get(&(from, to)) {
-                        None => {
-                            missing_pairs.push((from, to));
-                        }
-                        Some(&time) => {
-                            if time.is_infinite() || time.is_nan() {
-                                infinity_pairs.push((from, to, time));
-                            }
-                        }
-                    }
-                }
+        let skyline = bsl_psd.solve_with_start_time(
+            &shopping_list,
+            shopper_location,
+            customer_location,
+            480.0,
+        );
+        assert!(!skyline.is_empty());
+        assert!(skyline.iter().all(|route| !route.stores.contains(&3)));
+
+        bsl_psd.set_wait_for_opening(true);
+        let waiting_skyline = bsl_psd.solve_with_start_time(
+            &shopping_list,
+            shopper_location,
+            customer_location,
+            480.0,
+        );
+        assert!(waiting_skyline.len() >= skyline.len());
+        for route in &waiting_skyline {
+            if route.stores.contains(&3) {
+                assert!(route.shopping_time >= 120.0);
             }
         }
+    }
 
-        if !missing_pairs.is_empty() {
-            println!(
-                "WARNING: Missing travel times for {} store pairs:",
-                missing_pairs.len()
+    #[test]
+    fn test_find_min_cost_route_full_without_feasible_order() {
+        let (mut bsl_psd, shopping_list) = create_test_solver();
+        let shopper_location = Location::new(0.0, 0.0);
+        let customer_location = Location::new(20.0, 20.0);
+
+        // Every store has closed by the time the shopper leaves
+        for store in bsl_psd.stores.values() {
+            store.write().unwrap().hours = Some((0.0, 60.0));
+        }
+        bsl_psd.start_minute = Some(480.0);
+        assert!(bsl_psd
+            .find_min_cost_route_full(&shopping_list, shopper_location, customer_location)
+            .is_none());
+        assert!(bsl_psd
+            .solve_exact(&shopping_list, shopper_location, customer_location)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_min_cost_route_over_many_stores_is_ordered_by_insertion() {
+        // Twelve stores along a road, each the only one selling its product
+        let mut stores = HashMap::new();
+        let mut shopping_list = ShoppingList::new();
+        for id in 1..=12 {
+            let products = HashMap::from([(id, Product::new("P", 1.0))]);
+            let inventory = HashMap::from([(id, 1)]);
+            stores.insert(
+                id,
+                Store::new_with_inventory(id, Location::new(id as f64, 0.0), products, inventory),
             );
-            for (from, to) in missing_pairs.iter().take(10) {
-                println!("  - Travel time missing: {} -> {}", from, to);
-            }
-            if missing_pairs.len() > 10 {
-                println!("  - ... and {} more", missing_pairs.len() - 10);
-            }
+            shopping_list.add_item(id, 1);
         }
+        let mut bsl_psd = BSLPSD::new(stores);
+        bsl_psd.set_distance_metric(DistanceMetric::Euclidean);
+        bsl_psd.precompute_data();
+
+        let route = bsl_psd
+            .find_min_cost_route_full(
+                &shopping_list,
+                Location::new(0.0, 0.0),
+                Location::new(13.0, 0.0),
+            )
+            .unwrap();
+        assert_eq!(route.stores, (1..=12).collect::<Vec<StoreId>>());
+        assert!((route.shopping_time - 13.0).abs() < 1e-9);
+        assert_eq!(route.shopping_cost, 12.0);
+    }
 
-        if !infinity_pairs.is_empty() {
-            println!(
-                "WARNING: Infinite/NaN travel times for {} store pairs:",
-                infinity_pairs.len()
+    #[test]
+    fn test_find_min_cost_route_full_returns_stores() {
+        let (bsl_psd, shopping_list) = create_test_solver();
+        let shopper_location = Location::new(0.0, 0.0);
+        let customer_location = Location::new(20.0, 20.0);
+
+        let min_cost = bsl_psd
+            .find_min_cost_route(&shopping_list, shopper_location, customer_location)
+            .unwrap();
+        let route = bsl_psd
+            .find_min_cost_route_full(&shopping_list, shopper_location, customer_location)
+            .unwrap();
+
+        assert_eq!(route.shopping_cost, min_cost);
+        assert_eq!(
+            bsl_psd.calculate_shopping_cost(&route.stores, &shopping_list),
+            min_cost
+        );
+        assert_eq!(
+            route.shopping_time,
+            bsl_psd.calculate_shopping_time(&route.stores, shopper_location, customer_location)
+        );
+
+        // Every store on the route is needed for the cheapest allocation
+        for i in 0..route.stores.len() {
+            let mut without = route.stores.clone();
+            without.remove(i);
+            assert!(
+                !bsl_psd.satisfies_list(&without, &shopping_list)
+                    || bsl_psd.calculate_shopping_cost(&without, &shopping_list) > min_cost
             );
-            for (from, to, time) in infinity_pairs.iter().take(10) {
-                println!("  - Problematic travel time: {} -> {} = {}", from, to, time);
-            }
-            if infinity_pairs.len() > 10 {
-                println!("  - ... and {} more", infinity_pairs.len() - 10);
-            }
         }
+    }
 
-        missing_pairs.is_empty() && infinity_pairs.is_empty()
+    #[test]
+    fn test_solve_multi_serves_higher_priority_first() {
+        let (bsl_psd, _) = create_test_solver();
+        let shopper_location = Location::new(0.0, 0.0);
+        let customer_location = Location::new(20.0, 20.0);
+
+        // Only 6 units of E exist (stores 2 and 5), so just one of these lists fits
+        let mut low_priority = ShoppingList::new_with_priority(1);
+        low_priority.add_item(5, 4);
+        let mut high_priority = ShoppingList::new_with_priority(5);
+        high_priority.add_item(5, 4);
+
+        let results = bsl_psd.solve_multi(
+            &[low_priority, high_priority],
+            shopper_location,
+            customer_location,
+        );
+
+        assert_eq!(results.len(), 2);
+        assert!(results[0].is_empty());
+        assert!(!results[1].is_empty());
+
+        let remaining_e: u32 = bsl_psd
+            .snapshot_inventory()
+            .values()
+            .filter_map(|inventory| inventory.get(&5))
+            .sum();
+        assert_eq!(remaining_e, 2);
     }
-}
-// Helper function to sort remaining items for consistent state representation
-fn sort_remaining_items(items: Vec<(ProductId, u32)>) -> Vec<(ProductId, u32)> {
-    let mut sorted_items = items;
-    sorted_items.sort_by_key(|(product_id, _)| *product_id);
-    sorted_items
-}
 
-// Implementation of the PSDSolver trait for BSLPSD
-impl PSDSolver for BSLPSD {
-    /// Main BSL-PSD algorithm to solve the Personal Shopper's Dilemma
-    /// with inventory constraints
-    fn solve(
-        &self,
-        shopping_list: &ShoppingList,
-        shopper_location: Location,
-        customer_location: Location,
-    ) -> Vec<ShoppingRoute> {
-        // Use a version with safety checks, limiting max iterations to 10000
-        self.solve_with_debug(shopping_list, shopper_location, customer_location, 10000)
+    #[test]
+    fn test_solve_with_budget_respects_ceiling() {
+        let (bsl_psd, shopping_list) = create_test_solver();
+        let shopper_location = Location::new(0.0, 0.0);
+        let customer_location = Location::new(20.0, 20.0);
+
+        let full_skyline =
+            bsl_psd.solve_with_debug(&shopping_list, shopper_location, customer_location, 10000);
+        let min_cost = bsl_psd
+            .find_min_cost_route(&shopping_list, shopper_location, customer_location)
+            .unwrap();
+
+        let mut budgets: Vec<Cost> = full_skyline.iter().map(|r| r.shopping_cost).collect();
+        budgets.push(min_cost - 1.0);
+        budgets.sort_by(|a, b| b.partial_cmp(a).unwrap());
+
+        let mut previous_len = usize::MAX;
+        for max_cost in budgets {
+            let skyline = bsl_psd.solve_with_budget(
+                &shopping_list,
+                shopper_location,
+                customer_location,
+                max_cost,
+                10000,
+            );
+            assert!(skyline.iter().all(|route| route.shopping_cost <= max_cost));
+            assert!(skyline
+                .windows(2)
+                .all(|pair| pair[0].shopping_time <= pair[1].shopping_time));
+            assert!(skyline.len() <= previous_len);
+            previous_len = skyline.len();
+        }
+        assert_eq!(previous_len, 0);
     }
 
-    /// Checks if a route satisfies a shopping list
-    fn satisfies_list(&self, route: &[StoreId], shopping_list: &ShoppingList) -> bool {
-        self.satisfies_list_with_inventory(route, shopping_list)
+    #[test]
+    fn test_solve_weighted_extremes() {
+        let (bsl_psd, shopping_list) = create_test_solver();
+        let shopper_location = Location::new(0.0, 0.0);
+        let customer_location = Location::new(20.0, 20.0);
+
+        let min_time_route = bsl_psd
+            .find_min_time_route_dijkstra(&shopping_list, shopper_location, customer_location)
+            .unwrap();
+        let fastest = bsl_psd
+            .solve_weighted(
+                &shopping_list,
+                shopper_location,
+                customer_location,
+                1.0,
+                0.0,
+            )
+            .unwrap();
+        assert_eq!(fastest.stores, min_time_route.stores);
+        assert_eq!(fastest.shopping_time, min_time_route.shopping_time);
+
+        let min_cost = bsl_psd
+            .find_min_cost_route(&shopping_list, shopper_location, customer_location)
+            .unwrap();
+        let cheapest = bsl_psd
+            .solve_weighted(
+                &shopping_list,
+                shopper_location,
+                customer_location,
+                0.0,
+                3.0,
+            )
+            .unwrap();
+        assert_eq!(cheapest.shopping_cost, min_cost);
+
+        assert!(bsl_psd
+            .solve_weighted(
+                &shopping_list,
+                shopper_location,
+                customer_location,
+                0.0,
+                0.0
+            )
+            .is_none());
     }
 
-    /// Calculate shopping time for a route
-    fn calculate_shopping_time(
-        &self,
-        route: &[StoreId],
-        shopper_location: Location,
-        customer_location: Location,
-    ) -> f64 {
-        if route.is_empty() {
-            return 0.0;
+    #[test]
+    fn test_routing_objective_selects_endpoint_legs() {
+        let (mut bsl_psd, _) = create_test_solver();
+        let shopper_location = Location::new(0.0, 0.0);
+        let store = bsl_psd.stores[&1].read().unwrap().clone();
+
+        // Single road from the shopper to store 1
+        let vertices: HashMap<u64, (f64, f64)> =
+            [(0, (0.0, 0.0)), (1, (store.location.x, store.location.y))].into();
+        let mut graph =
+            RoadGraph::new_with_metric(vertices, [(0, (0, 1))].into(), DistanceMetric::Euclidean);
+        graph.set_default_speed(60.0);
+        bsl_psd.set_road_graph(Arc::new(graph));
+
+        let length = shopper_location.distance_to(&store.location);
+        let by_distance = bsl_psd.endpoint_leg(&shopper_location, &store);
+        assert!((by_distance - length * 1000.0).abs() < 1e-9);
+
+        // At 60 km/h a kilometre takes a minute
+        bsl_psd.set_routing_objective(RoutingObjective::Time);
+        let by_time = bsl_psd.endpoint_leg(&shopper_location, &store);
+        assert!((by_time - length).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_packed_and_unpacked_remaining_states_agree() {
+        let (bsl_psd, shopping_list) = create_test_solver();
+        let shopper_location = Location::new(0.0, 0.0);
+        let customer_location = Location::new(20.0, 20.0);
+
+        let mut products: Vec<ProductId> = shopping_list.items.keys().cloned().collect();
+        products.sort();
+        let quantities: Vec<u32> = products.iter().map(|p| shopping_list.items[p]).collect();
+        let store_products = bsl_psd.collect_candidate_stores(&shopping_list);
+        let mut stores: Vec<StoreId> = store_products.keys().cloned().collect();
+        stores.sort();
+        let stocks: Vec<Vec<u32>> = stores
+            .iter()
+            .map(|s| {
+                let inventory = &store_products[s];
+                products
+                    .iter()
+                    .map(|p| inventory.get(p).copied().unwrap_or(0))
+                    .collect()
+            })
+            .collect();
+
+        let layout = RemainingLayout::packed(&quantities).unwrap();
+        assert_eq!(
+            layout
+                .pack(&quantities)
+                .buy(&layout, &vec![0; products.len()]),
+            None
+        );
+
+        let (packed_path, packed_time) = bsl_psd
+            .min_time_search(
+                layout.pack(&quantities),
+                &layout,
+                &stores,
+                &stocks,
+                &vec![0.0; stores.len()],
+                &shopper_location,
+                &customer_location,
+            )
+            .unwrap();
+        let (boxed_path, boxed_time) = bsl_psd
+            .min_time_search(
+                quantities.clone().into_boxed_slice(),
+                &RemainingLayout::default(),
+                &stores,
+                &stocks,
+                &vec![0.0; stores.len()],
+                &shopper_location,
+                &customer_location,
+            )
+            .unwrap();
+
+        assert_eq!(packed_path, boxed_path);
+        assert!((packed_time - boxed_time).abs() < 1e-9);
+
+        // Quantities needing more than 128 bits fall back to the unpacked state
+        assert!(RemainingLayout::packed(&[u32::MAX; 5]).is_none());
+    }
+
+    #[test]
+    fn test_parallel_shortest_path_matches_brute_force() {
+        let (bsl_psd, _) = create_test_solver();
+        let shopper_location = Location::new(0.0, 0.0);
+        let customer_location = Location::new(20.0, 20.0);
+        let stores = vec![5, 3, 1, 4, 2, 3];
+
+        // Sequential reference: the first permutation with the strictly smallest time
+        let mut expected = Vec::new();
+        let mut min_time = f64::INFINITY;
+        for perm in bsl_psd.generate_permutations(vec![5, 3, 1, 4, 2]) {
+            let time = bsl_psd.calculate_total_time(&perm, &shopper_location, &customer_location);
+            if time < min_time {
+                min_time = time;
+                expected = perm;
+            }
         }
 
-        let mut total_time = 0.0;
+        let path = bsl_psd.find_shortest_path(&stores, &shopper_location, &customer_location);
+        assert_eq!(path, expected);
+    }
 
-        // Time from shopper to first store
-        if let Some(first_store_id) = route.first() {
-            let first_store = self.stores[first_store_id].read().unwrap();
-            total_time += shopper_location.distance_to(&first_store.location);
+    #[test]
+    fn test_solve_with_deadline_returns_valid_skyline() {
+        let (bsl_psd, shopping_list) = create_test_solver();
+        let shopper_location = Location::new(0.0, 0.0);
+        let customer_location = Location::new(20.0, 20.0);
+
+        let (full, _) =
+            bsl_psd.solve_with_parallel(&shopping_list, shopper_location, customer_location, 10);
+        let generous = bsl_psd.solve_with_deadline(
+            &shopping_list,
+            shopper_location,
+            customer_location,
+            Duration::from_secs(60),
+        );
+        // Workers may reach the same stores in another order, so compare store sets
+        let sorted_stores = |routes: &[ShoppingRoute]| {
+            let mut stores: Vec<Vec<StoreId>> = routes
+                .iter()
+                .map(|r| {
+                    let mut set = r.stores.clone();
+                    set.sort();
+                    set
+                })
+                .collect();
+            stores.sort();
+            stores
+        };
+        assert_eq!(sorted_stores(&generous), sorted_stores(&full));
+
+        // An already expired deadline still yields a sorted, non-dominated partial skyline
+        let partial = bsl_psd.solve_with_deadline(
+            &shopping_list,
+            shopper_location,
+            customer_location,
+            Duration::ZERO,
+        );
+        assert!(partial.len() <= full.len());
+        assert!(partial
+            .windows(2)
+            .all(|pair| pair[0].shopping_time <= pair[1].shopping_time));
+        for a in &partial {
+            assert!(partial.iter().all(|b| !b.strictly_dominates(a)));
         }
+    }
 
-        // Time between stores
-        for i in 0..route.len() - 1 {
-            let time = self
-                .travel_times
-                .get(&(route[i], route[i + 1]))
-                .cloned()
-                .unwrap_or(f64::INFINITY);
-            total_time += time;
+    #[test]
+    fn test_solve_with_parallel_n_workers() {
+        let (bsl_psd, shopping_list) = create_test_solver();
+        let shopper_location = Location::new(0.0, 0.0);
+        let customer_location = Location::new(20.0, 20.0);
+
+        let fastest = bsl_psd
+            .find_min_time_route_dijkstra(&shopping_list, shopper_location, customer_location)
+            .unwrap();
+        let min_cost = bsl_psd
+            .find_min_cost_route(&shopping_list, shopper_location, customer_location)
+            .unwrap();
+
+        let solve = |num_workers| {
+            bsl_psd
+                .solve_with_parallel_n(
+                    &shopping_list,
+                    shopper_location,
+                    customer_location,
+                    10,
+                    num_workers,
+                )
+                .0
+        };
+
+        // A single worker is deterministic
+        let single = solve(1);
+        assert_eq!(single, solve(1));
+
+        for skyline in [single, solve(2), solve(4), solve(0)] {
+            // Both ends of the frontier are always reached
+            assert!((skyline[0].shopping_time - fastest.shopping_time).abs() < 1e-9);
+            assert_eq!(skyline.last().unwrap().shopping_cost, min_cost);
+            for a in &skyline {
+                assert!(skyline.iter().all(|b| !b.strictly_dominates(a)));
+            }
         }
+    }
 
-        // Time from last store to customer
-        if let Some(last_store_id) = route.last() {
-            let last_store = self.stores[last_store_id].read().unwrap();
-            total_time += last_store.location.distance_to(&customer_location);
+    #[test]
+    fn test_solve_exact_spans_cheapest_to_fastest() {
+        let (bsl_psd, shopping_list) = create_test_solver();
+        let shopper = Location::new(0.0, 0.0);
+        let customer = Location::new(20.0, 20.0);
+
+        let skyline = bsl_psd.solve_exact(&shopping_list, shopper, customer);
+        assert!(!skyline.is_empty());
+        assert_eq!(
+            skyline,
+            bsl_psd.solve_exact(&shopping_list, shopper, customer)
+        );
+
+        // Sorted by time with strictly falling cost, so nothing dominates anything else
+        for pair in skyline.windows(2) {
+            assert!(pair[0].shopping_time < pair[1].shopping_time);
+            assert!(pair[0].shopping_cost > pair[1].shopping_cost);
         }
 
-        total_time
-    }
+        let cheapest = bsl_psd
+            .find_min_cost_route_full(&shopping_list, shopper, customer)
+            .unwrap();
+        let last = skyline.last().unwrap();
+        assert!((last.shopping_cost - cheapest.shopping_cost).abs() < 1e-9);
+        assert!(last.shopping_time <= cheapest.shopping_time);
 
-    /// Calculate shopping cost for a route
-    fn calculate_shopping_cost(&self, route: &[StoreId], shopping_list: &ShoppingList) -> f64 {
-        // First, verify the route can satisfy the shopping list
-        let can_fulfill = self.can_fulfill_shopping_list(route, shopping_list);
-        if !can_fulfill {
-            return f64::INFINITY;
+        for route in &skyline {
+            assert!(bsl_psd.satisfies_list_with_inventory(&route.stores, &shopping_list));
         }
 
-        // For each product, record available stores, prices, and quantities
-        let mut product_options: HashMap<ProductId, Vec<(StoreId, f64, u32)>> = HashMap::new();
+        // Without a solution the empty skyline still follows the configured domination
+        let mut linear = bsl_psd.clone();
+        linear.set_linear_skyline(true);
+        let mut unfulfillable = ShoppingList::new();
+        unfulfillable.add_item(1, 100);
+        let empty = linear.solve_exact(&unfulfillable, shopper, customer);
+        assert!(empty.is_empty());
+        assert_eq!(empty.domination(), Domination::Linear);
+    }
 
-        // Gather all options for each product from stores in the route
-        for &store_id in route {
-            let store = self.stores[&store_id].read().unwrap();
+    #[test]
+    fn test_generate_next_routes_from_empty_route() {
+        let (bsl_psd, _) = create_test_solver();
+        let shopper_location = Location::new(0.0, 0.0);
+        let customer_location = Location::new(20.0, 20.0);
 
-            for (product_id, _qty_needed) in &shopping_list.items {
-                if store.has_product(product_id) {
-                    let available_qty = store.get_inventory_level(product_id);
-                    if available_qty > 0 {
-                        let cost = store.get_product_cost(product_id).unwrap_or(f64::INFINITY);
-                        product_options
-                            .entry(*product_id)
-                            .or_insert_with(Vec::new)
-                            .push((store_id, cost, available_qty));
-                    }
-                }
-            }
+        let empty = RouteCandidate {
+            stores: Vec::new(),
+            shopping_time: 0.0,
+        };
+        let next_routes =
+            bsl_psd.generate_next_routes(&empty, &shopper_location, &customer_location);
+        assert_eq!(next_routes.len(), 1);
+
+        // The first store is timed from the shopper through it to the customer
+        let first = &next_routes[0];
+        assert_eq!(first.stores.len(), 1);
+        let expected =
+            bsl_psd.calculate_total_time(&first.stores, &shopper_location, &customer_location);
+        assert!(first.shopping_time > 0.0);
+        assert!((first.shopping_time - expected).abs() < 1e-9);
+
+        // And no other single-store trip is quicker
+        for &store_id in bsl_psd.stores.keys() {
+            let time =
+                bsl_psd.calculate_total_time(&[store_id], &shopper_location, &customer_location);
+            assert!(first.shopping_time <= time);
+        }
+    }
+
+    #[test]
+    fn test_linear_skyline_drops_collinear_route() {
+        // Three routes on one line in time-cost space, plus a dominated one
+        let routes = [
+            ShoppingRoute::new(vec![1], 10.0, 30.0),
+            ShoppingRoute::new(vec![2], 20.0, 20.0),
+            ShoppingRoute::new(vec![3], 30.0, 10.0),
+            ShoppingRoute::new(vec![4], 25.0, 25.0),
+        ];
+
+        let mut bsl_psd = BSLPSD::new(HashMap::new());
+
+        bsl_psd.set_distance_metric(DistanceMetric::Euclidean);
+        let mut conventional = Vec::new();
+        for route in routes.iter().cloned() {
+            bsl_psd.update_skyline(&mut conventional, route);
+        }
+        let mut stores: Vec<Vec<StoreId>> = conventional.iter().map(|r| r.stores.clone()).collect();
+        stores.sort();
+        assert_eq!(stores, vec![vec![1], vec![2], vec![3]]);
+
+        // The middle route lies on the line between the other two
+        bsl_psd.set_linear_skyline(true);
+        let mut linear = Vec::new();
+        for route in routes.iter().cloned() {
+            bsl_psd.update_skyline(&mut linear, route);
         }
+        assert_eq!(linear, vec![routes[0].clone(), routes[2].clone()]);
+
+        // Moving it below the line brings it back
+        let below = ShoppingRoute::new(vec![5], 20.0, 15.0);
+        assert!(bsl_psd.update_linear_skyline(&mut linear, below.clone()));
+        assert!(!bsl_psd.update_linear_skyline(&mut linear, below.clone()));
+        assert_eq!(linear, vec![routes[0].clone(), below, routes[2].clone()]);
+    }
 
-        // Sort options for each product by price (cheapest first)
-        for options in product_options.values_mut() {
-            options.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal));
+    #[test]
+    fn test_allocate_purchases_matches_shopping_cost() {
+        let (bsl_psd, shopping_list) = create_test_solver();
+
+        let allocation = bsl_psd.allocate_purchases(&[1, 2, 3], &shopping_list);
+        assert!(allocation.is_complete());
+        assert_eq!(
+            allocation.total_cost,
+            bsl_psd.calculate_shopping_cost(&[1, 2, 3], &shopping_list)
+        );
+
+        for (product_id, &qty) in &shopping_list.items {
+            let purchases = &allocation.purchases[product_id];
+            let bought: u32 = purchases.iter().map(|&(_, quantity, _)| quantity).sum();
+            assert_eq!(bought, qty);
+            // Cheapest stores first
+            assert!(purchases.windows(2).all(|pair| pair[0].2 <= pair[1].2));
         }
 
-        // Now allocate purchases optimally
-        let mut total_cost = 0.0;
-        for (product_id, qty_needed) in &shopping_list.items {
-            let mut remaining_qty = *qty_needed;
+        // Repeating a store doesn't double its stock
+        let repeated = bsl_psd.allocate_purchases(&[1, 2, 3, 1], &shopping_list);
+        assert_eq!(repeated, allocation);
+
+        // A single store leaves part of the list unfulfilled
+        let partial = bsl_psd.allocate_purchases(&[1], &shopping_list);
+        assert!(!partial.is_complete());
+        assert!(partial.unfulfilled.values().all(|&qty| qty > 0));
+        assert!(bsl_psd
+            .calculate_shopping_cost(&[1], &shopping_list)
+            .is_infinite());
+    }
 
-            if let Some(options) = product_options.get(product_id) {
-                // Buy from cheapest store first
-                for &(_, cost, available_qty) in options {
-                    let purchase_qty = std::cmp::min(available_qty, remaining_qty);
-                    if purchase_qty > 0 {
-                        total_cost += cost * purchase_qty as f64;
-                        remaining_qty -= purchase_qty;
+    #[test]
+    fn test_route_to_geojson() {
+        let (mut bsl_psd, shopping_list) = create_test_solver();
+        let shopper_location = Location::new(0.0, 0.0);
+        let customer_location = Location::new(20.0, 20.0);
+        let route = ShoppingRoute::new(
+            vec![1, 3],
+            0.0,
+            bsl_psd.calculate_shopping_cost(&[1, 3], &shopping_list),
+        );
+
+        let parse = |bsl_psd: &BSLPSD| -> serde_json::Value {
+            let geojson = bsl_psd.route_to_geojson(
+                &route,
+                &shopping_list,
+                shopper_location,
+                customer_location,
+            );
+            serde_json::from_str(&geojson).unwrap()
+        };
 
-                        if remaining_qty == 0 {
-                            break;
-                        }
-                    }
-                }
-            }
+        // Straight segments: shopper, two stores, customer
+        let collection = parse(&bsl_psd);
+        assert_eq!(collection["type"], "FeatureCollection");
+        let features = collection["features"].as_array().unwrap();
+        assert_eq!(features.len(), 1 + route.stores.len());
+        let line = features[0]["geometry"]["coordinates"].as_array().unwrap();
+        assert_eq!(line.len(), 4);
+        assert_eq!(line[0], serde_json::json!([0.0, 0.0]));
+        assert_eq!(line[3], serde_json::json!([20.0, 20.0]));
+
+        let store3 = bsl_psd.stores[&3].read().unwrap().location;
+        assert_eq!(features[2]["geometry"]["type"], "Point");
+        assert_eq!(
+            features[2]["geometry"]["coordinates"],
+            serde_json::json!([store3.x, store3.y])
+        );
+        assert_eq!(features[2]["properties"]["store_id"], 3);
+        assert_eq!(features[2]["properties"]["stop"], 2);
+        let bought: u64 = features[1..]
+            .iter()
+            .flat_map(|feature| feature["properties"]["products"].as_array().unwrap())
+            .map(|product| product["quantity"].as_u64().unwrap())
+            .sum();
+        assert_eq!(bought, shopping_list.total_item_count() as u64);
+
+        // With a road chain through every store in ID order, 1 -> 3 passes store 2
+        let mut points = vec![(shopper_location.x, shopper_location.y)];
+        let mut store_ids: Vec<StoreId> = bsl_psd.stores.keys().cloned().collect();
+        store_ids.sort();
+        for store_id in &store_ids {
+            let location = bsl_psd.stores[store_id].read().unwrap().location;
+            points.push((location.x, location.y));
+        }
+        points.push((customer_location.x, customer_location.y));
+        let vertices: HashMap<u64, (f64, f64)> = (0..).zip(points.iter().cloned()).collect();
+        let edges: HashMap<u64, (u64, u64)> = (0..points.len() as u64 - 1)
+            .map(|i| (i, (i, i + 1)))
+            .collect();
+        bsl_psd.set_road_graph(Arc::new(RoadGraph::new_with_metric(
+            vertices,
+            edges,
+            DistanceMetric::Euclidean,
+        )));
+
+        let collection = parse(&bsl_psd);
+        let line = collection["features"][0]["geometry"]["coordinates"]
+            .as_array()
+            .unwrap();
+        let store2 = bsl_psd.stores[&2].read().unwrap().location;
+        assert!(line.contains(&serde_json::json!([store2.x, store2.y])));
+        assert_eq!(line[0], serde_json::json!([0.0, 0.0]));
+        assert_eq!(line[line.len() - 1], serde_json::json!([20.0, 20.0]));
+    }
 
-            // If we couldn't buy all needed quantity
-            if remaining_qty > 0 {
-                return f64::INFINITY; // Route cannot fulfill shopping list
+    #[test]
+    fn test_solve_with_capacity() {
+        let (bsl_psd, shopping_list) = create_test_solver();
+        let shopper_location = Location::new(0.0, 0.0);
+        let customer_location = Location::new(20.0, 20.0);
+        for store in bsl_psd.stores.values() {
+            for product in store.write().unwrap().products.values_mut() {
+                product.weight = 1.5;
             }
         }
 
-        total_cost
+        let weight = bsl_psd.shopping_list_weight(&shopping_list);
+        assert_eq!(weight, 1.5 * shopping_list.total_item_count() as f64);
+
+        // The stock suffices, but the shopper can't carry it all
+        let unconstrained =
+            bsl_psd.solve_with_debug(&shopping_list, shopper_location, customer_location, 10000);
+        assert!(!unconstrained.is_empty());
+        let too_heavy = bsl_psd.solve_with_capacity(
+            &shopping_list,
+            shopper_location,
+            customer_location,
+            weight - 0.5,
+            10000,
+        );
+        assert!(too_heavy.is_empty());
+
+        let relaxed = bsl_psd.solve_with_capacity(
+            &shopping_list,
+            shopper_location,
+            customer_location,
+            weight,
+            10000,
+        );
+        assert_eq!(relaxed, unconstrained);
     }
-}
 
-#[cfg(test)]
-mod tests {
-    // use super::*;
-    // use crate::Product;
-
-    // // Create test data with inventory constraints
-    // fn create_test_data() -> (HashMap<StoreId, Store>, ShoppingList) {
-    //     let mut stores = HashMap::new();
-
-    //     // Store 1
-    //     let mut store1_products = HashMap::new();
-    //     store1_products.insert(1, Product::new("A", 7.0));
-    //     store1_products.insert(2, Product::new("B", 8.0));
-    //     store1_products.insert(6, Product::new("F", 10.0));
-
-    //     let mut inventory1 = HashMap::new();
-    //     inventory1.insert(1, 5); // 5 units of Product A
-    //     inventory1.insert(2, 3); // 3 units of Product B
-    //     inventory1.insert(6, 8); // 8 units of Product F
-
-    //     stores.insert(
-    //         1,
-    //         Store::new_with_inventory(1, Location::new(10.0, 6.0), store1_products, inventory1),
-    //     );
-
-    //     // Store 2
-    //     let mut store2_products = HashMap::new();
-    //     store2_products.insert(3, Product::new("C", 10.0));
-    //     store2_products.insert(4, Product::new("D", 8.0));
-    //     store2_products.insert(5, Product::new("E", 10.0));
-
-    //     let mut inventory2 = HashMap::new();
-    //     inventory2.insert(3, 4); // 4 units of Product C
-    //     inventory2.insert(4, 6); // 6 units of Product D
-    //     inventory2.insert(5, 2); // 2 units of Product E
-
-    //     stores.insert(
-    //         2,
-    //         Store::new_with_inventory(2, Location::new(12.0, 20.0), store2_products, inventory2),
-    //     );
-
-    //     // Store 3
-    //     let mut store3_products = HashMap::new();
-    //     store3_products.insert(3, Product::new("C", 5.0));
-    //     store3_products.insert(4, Product::new("D", 4.0));
-    //     store3_products.insert(6, Product::new("F", 6.0));
-
-    //     let mut inventory3 = HashMap::new();
-    //     inventory3.insert(3, 3); // 3 units of Product C
-    //     inventory3.insert(4, 2); // 2 units of Product D
-    //     inventory3.insert(6, 5); // 5 units of Product F
-
-    //     stores.insert(
-    //         3,
-    //         Store::new_with_inventory(3, Location::new(20.0, 18.0), store3_products, inventory3),
-    //     );
-
-    //     // Store 4
-    //     let mut store4_products = HashMap::new();
-    //     store4_products.insert(3, Product::new("C", 8.0));
-    //     store4_products.insert(4, Product::new("D", 7.0));
-    //     store4_products.insert(6, Product::new("F", 12.0));
-
-    //     let mut inventory4 = HashMap::new();
-    //     inventory4.insert(3, 7); // 7 units of Product C
-    //     inventory4.insert(4, 4); // 4 units of Product D
-    //     inventory4.insert(6, 3); // 3 units of Product F
-
-    //     stores.insert(
-    //         4,
-    //         Store::new_with_inventory(4, Location::new(15.0, 22.0), store4_products, inventory4),
-    //     );
-
-    //     // Store 5
-    //     let mut store5_products = HashMap::new();
-    //     store5_products.insert(1, Product::new("A", 6.0));
-    //     store5_products.insert(2, Product::new("B", 7.0));
-    //     store5_products.insert(5, Product::new("E", 8.0));
-
-    //     let mut inventory5 = HashMap::new();
-    //     inventory5.insert(1, 3); // 3 units of Product A
-    //     inventory5.insert(2, 5); // 5 units of Product B
-    //     inventory5.insert(5, 4); // 4 units of Product E
-
-    //     stores.insert(
-    //         5,
-    //         Store::new_with_inventory(5, Location::new(10.0, 15.0), store5_products, inventory5),
-    //     );
-
-    //     // Create shopping list with quantities
-    //     let mut shopping_list = ShoppingList::new();
-    //     shopping_list.add_item(1, 2); // 2 units of A
-    //     shopping_list.add_item(2, 1); // 1 unit of B
-    //     shopping_list.add_item(3, 3); // 3 units of C
-    //     shopping_list.add_item(4, 2); // 2 units of D
-
-    //     (stores, shopping_list)
-    // }
-
-    // #[test]
-    // fn test_inventory_tracking() {
-    //     let (stores, shopping_list) = create_test_data();
-    //     let mut bsl_psd = BSLPSD::new(stores);
-    //     bsl_psd.precompute_data();
-
-    //     // Test finding routes with inventory constraints
-    //     let route = vec![1, 2, 3]; // Contains stores with all needed products
-    //     assert!(bsl_psd.satisfies_list_with_inventory(&route, &shopping_list));
-
-    //     // Test when inventory is insufficient
-    //     let mut limited_list = ShoppingList::new();
-    //     limited_list.add_item(1, 10); // More A than available in any store
-    //     assert!(!bsl_psd.satisfies_list_with_inventory(&route, &limited_list));
-
-    //     // Test inventory deduction
-    //     assert!(bsl_psd.reserve_inventory(&route, &shopping_list));
-
-    //     // Test cost calculation with inventory
-    //     let (cost, fulfilled) =
-    //         bsl_psd.calculate_shopping_cost_with_inventory(&route, &shopping_list);
-    //     assert!(fulfilled);
-    //     assert!(cost < f64::INFINITY);
-
-    //     // Release inventory for subsequent tests
-    //     bsl_psd.release_inventory(&route, &shopping_list);
-    // }
-
-    // #[test]
-    // fn test_solve_with_inventory() {
-    //     let (stores, shopping_list) = create_test_data();
-    //     let mut bsl_psd = BSLPSD::new(stores);
-    //     bsl_psd.precompute_data();
-
-    //     let shopper_location = Location::new(0.0, 0.0);
-    //     let customer_location = Location::new(20.0, 20.0);
-
-    //     let skyline = bsl_psd.solve(&shopping_list, shopper_location, customer_location);
-
-    //     // Verify that we have solutions
-    //     assert!(!skyline.is_empty());
-
-    //     // Verify skyline properties
-    //     for i in 1..skyline.len() {
-    //         // Shopping time should increase
-    //         assert!(skyline[i - 1].shopping_time <= skyline[i].shopping_time);
-
-    //         // Shopping cost should decrease (or at least not increase)
-    //         assert!(skyline[i - 1].shopping_cost >= skyline[i].shopping_cost);
-    //     }
-
-    //     // Verify that each route in the skyline satisfies inventory constraints
-    //     for route in &skyline {
-    //         let (_, fulfilled) =
-    //             bsl_psd.calculate_shopping_cost_with_inventory(&route.stores, &shopping_list);
-    //         assert!(
-    //             fulfilled,
-    //             "Route {:?} does not satisfy inventory constraints",
-    //             route.stores
-    //         );
-    //     }
-    // }
-
-    // #[test]
-    // fn test_inventory_conflict() {
-    //     let (stores, shopping_list) = create_test_data();
-    //     let mut bsl_psd = BSLPSD::new(stores);
-    //     bsl_psd.precompute_data();
-
-    //     // Create a second shopping list that competes for the same inventory
-    //     let mut competing_list = ShoppingList::new();
-    //     competing_list.add_item(1, 4); // 4 units of A
-    //     competing_list.add_item(3, 6); // 6 units of C
-
-    //     // First, apply the first shopping list to reduce inventory
-    //     let shopper_location = Location::new(0.0, 0.0);
-    //     let customer_location = Location::new(20.0, 20.0);
-
-    //     let skyline1 = bsl_psd.solve(&shopping_list, shopper_location, customer_location);
-    //     assert!(!skyline1.is_empty());
-
-    //     // Apply the first route's inventory reduction
-    //     let first_route = &skyline1[0];
-    //     assert!(bsl_psd.apply_route(first_route, &shopping_list));
-
-    //     // Now try to solve with the competing list
-    //     let skyline2 = bsl_psd.solve(&competing_list, shopper_location, customer_location);
-
-    //     // We expect limited or no solutions due to inventory constraints
-    //     if !skyline2.is_empty() {
-    //         // If we have solutions, verify they use different stores or have adjusted for inventory
-    //         for route in &skyline2 {
-    //             let (_, fulfilled) =
-    //                 bsl_psd.calculate_shopping_cost_with_inventory(&route.stores, &competing_list);
-    //             assert!(
-    //                 fulfilled,
-    //                 "Route {:?} does not satisfy inventory constraints",
-    //                 route.stores
-    //             );
-    //         }
-    //     }
-    // }
-    // #[test]
-    // fn test_snapshot_and_restore() {
-    //     let (stores, shopping_list) = create_test_data();
-    //     let mut bsl_psd = BSLPSD::new(stores);
-    //     bsl_psd.precompute_data();
-
-    //     // Take a snapshot of initial inventory
-    //     let initial_snapshot = bsl_psd.snapshot_inventory();
-
-    //     // Apply a route to reduce inventory
-    //     let route = vec![1, 2, 3];
-    //     assert!(bsl_psd.reserve_inventory(&route, &shopping_list));
-
-    //     // Verify inventory was reduced
-    //     let store1 = bsl_psd.stores[&1].lock().unwrap();
-    //     assert!(store1.get_inventory_level(&1) < initial_snapshot[&1][&1]);
-
-    //     // Restore inventory
-    //     bsl_psd.restore_inventory(initial_snapshot.clone());
-
-    //     // Verify inventory was restored
-    //     let store1_after = bsl_psd.stores[&1].lock().unwrap();
-    //     assert_eq!(
-    //         store1_after.get_inventory_level(&1),
-    //         initial_snapshot[&1][&1]
-    //     );
-    // }
+    #[test]
+    fn test_service_time_adds_to_shopping_time() {
+        let (bsl_psd, shopping_list) = create_test_solver();
+        let shopper_location = Location::new(0.0, 0.0);
+        let customer_location = Location::new(20.0, 20.0);
+        let route = [1, 2];
+
+        let before = bsl_psd.calculate_shopping_time(&route, shopper_location, customer_location);
+        let before_total =
+            bsl_psd.calculate_total_time(&route, &shopper_location, &customer_location);
+
+        for (store_id, minutes) in [(1, 4.0), (2, 2.5)] {
+            let mut store = bsl_psd.stores[&store_id].write().unwrap();
+            *store = store.clone().with_service_time(minutes);
+        }
+
+        let after = bsl_psd.calculate_shopping_time(&route, shopper_location, customer_location);
+        let after_total =
+            bsl_psd.calculate_total_time(&route, &shopper_location, &customer_location);
+        assert!((after - before - 6.5).abs() < 1e-9);
+        assert!((after_total - before_total - 6.5).abs() < 1e-9);
+
+        // The min-time route accounts for service times as well
+        let fastest = bsl_psd
+            .find_min_time_route_dijkstra(&shopping_list, shopper_location, customer_location)
+            .unwrap();
+        let expected =
+            bsl_psd.calculate_total_time(&fastest.stores, &shopper_location, &customer_location);
+        assert!((fastest.shopping_time - expected).abs() < 1e-9);
+    }
 }
@@ -1,7 +1,57 @@
 pub mod bsl_psd;
 
 // Common algorithm traits
-use crate::models::{Location, ShoppingList, ShoppingRoute};
+use crate::models::{Cost, Location, ShoppingList, StoreId};
+use crate::utils::skyline::Skyline;
+use std::collections::HashSet;
+use std::time::Duration;
+
+/// Search settings for `PSDSolver::solve_skyline`
+/// Solvers ignore settings they have no use for.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SolveOptions {
+    /// Skyline updates without change before the search gives up
+    pub threshold: i32,
+
+    /// Number of worker threads; None leaves the choice to the solver
+    pub workers: Option<usize>,
+
+    /// Only keep routes costing at most this much
+    pub max_cost: Option<Cost>,
+
+    /// Stop searching after this long and return the routes found so far
+    pub deadline: Option<Duration>,
+
+    /// Only search the stores nearest to the shopper or the customer
+    /// Makes the result approximate: routes through farther stores are never found,
+    /// even when they would be cheaper or quicker.
+    pub nearest_stores: Option<usize>,
+
+    /// Stores every returned route must visit
+    pub required_stores: HashSet<StoreId>,
+
+    /// Stores no returned route may visit; leaving them out can make the list
+    /// impossible to fulfill, giving an empty skyline
+    pub forbidden_stores: HashSet<StoreId>,
+
+    /// Only search the stores in this category (see `Store::has_category`)
+    pub category: Option<String>,
+}
+
+impl Default for SolveOptions {
+    fn default() -> Self {
+        Self {
+            threshold: 10000,
+            workers: None,
+            max_cost: None,
+            deadline: None,
+            nearest_stores: None,
+            required_stores: HashSet::new(),
+            forbidden_stores: HashSet::new(),
+            category: None,
+        }
+    }
+}
 
 /// Trait for Personal Shopper's Dilemma solvers
 pub trait PSDSolver {
@@ -11,7 +61,23 @@ pub trait PSDSolver {
         shopping_list: &ShoppingList,
         shopper_location: Location,
         customer_location: Location,
-    ) -> Vec<ShoppingRoute>;
+    ) -> Skyline;
+
+    /// Solve with explicit search settings
+    /// Defaults to `solve`, keeping only the routes within `max_cost`.
+    fn solve_skyline(
+        &self,
+        shopping_list: &ShoppingList,
+        shopper_location: Location,
+        customer_location: Location,
+        options: SolveOptions,
+    ) -> Skyline {
+        let mut skyline = self.solve(shopping_list, shopper_location, customer_location);
+        if let Some(max_cost) = options.max_cost {
+            skyline.retain(|route| route.shopping_cost <= max_cost);
+        }
+        skyline
+    }
 
     /// Check if a route satisfies a shopping list
     fn satisfies_list(&self, route: &[u32], shopping_list: &ShoppingList) -> bool;
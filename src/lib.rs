@@ -4,5 +4,5 @@ pub mod models;
 pub mod utils;
 
 // Re-exports for convenience
-pub use algorithms::bsl_psd::BSLPSD;
+pub use algorithms::bsl_psd::{BSLPSDBuilder, BSLPSD};
 pub use models::{Product, RouteCandidate, ShoppingList, ShoppingRoute, Store};
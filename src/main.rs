@@ -1,10 +1,9 @@
-use personal_shopper::models::{ProductId, StoreId};
 use personal_shopper::utils::init_map::init_map_with_road_network;
 use personal_shopper::{
     algorithms::bsl_psd::BSLPSD,
     models::{Location, ShoppingList},
+    utils::skyline::format_skyline,
 };
-use std::collections::HashMap;
 
 fn main() {
     let city_code = "AMS";
@@ -39,17 +38,11 @@ fn main() {
     // create shopping lists
     let mut shopping_list = ShoppingList::new();
 
+    let mut bsl_psd = BSLPSD::new_with_travel_times(stores, travel_times);
+    let mut test_bsl_psd = BSLPSD::new_with_travel_times(test_stores, test_travel_times);
+
     // find avalible products
-    let mut available_products = HashMap::new();
-    for (_store_id, store) in &stores {
-        let store = store;
-        for (product_id, product) in &store.products {
-            let entry = available_products
-                .entry(*product_id)
-                .or_insert((product.name.clone(), 0));
-            entry.1 += store.get_inventory_level(product_id);
-        }
-    }
+    let available_products = bsl_psd.available_products();
     println!("\navalible products:");
     for (product_id, (name, store_id)) in &available_products {
         println!(
@@ -77,8 +70,6 @@ fn main() {
             println!("  Product {} ({}): {} units", product_id, name, quantity);
         }
     }
-    let mut bsl_psd = BSLPSD::new_with_travel_times(stores, travel_times);
-    let mut test_bsl_psd = BSLPSD::new_with_travel_times(test_stores, test_travel_times);
     bsl_psd.precompute_data();
     test_bsl_psd.precompute_data();
 
@@ -106,96 +97,46 @@ fn main() {
     let elapsed = start_time.elapsed();
 
     // Print results
-    println!("Linear Skyline Results (found in {:.2?}):", elapsed);
+    println!("Skyline Results (found in {:.2?}):", elapsed);
     println!("------------------------------------------");
 
     if results.is_empty() {
         println!("No feasible routes found with current inventory constraints!");
     } else {
         for (i, route) in results.iter().enumerate() {
-            println!("Route {}: {:?}", i + 1, route.stores);
-            println!("  Shopping Time: {:.2} minutes", route.shopping_time);
-            println!("  Shopping Cost: ${:.2}", route.shopping_cost);
+            println!("{}: {}", i + 1, route);
 
             // Show optimized product allocation across stores
             println!("  Product Allocation:");
 
-            // For each product, find optimal allocation across stores in the route
-            let mut product_allocations: HashMap<ProductId, Vec<(StoreId, u32, f64)>> =
-                HashMap::new();
-
-            // First, collect all options for each product from all stores in the route
-            for &store_id in &route.stores {
-                let store = bsl_psd.stores[&store_id].read().unwrap();
-
-                for (product_id, _qty_needed) in &shopping_list.items {
-                    if store.has_product(product_id) {
-                        let available_qty = store.get_inventory_level(product_id);
-                        if available_qty > 0 {
-                            let cost = store.get_product_cost(product_id).unwrap_or(f64::INFINITY);
-                            product_allocations
-                                .entry(*product_id)
-                                .or_insert_with(Vec::new)
-                                .push((store_id, available_qty, cost));
-                        }
-                    }
+            let allocation = bsl_psd.allocate_purchases(&route.stores, &shopping_list);
+            for product_id in shopping_list.items.keys() {
+                let purchases = &allocation.purchases[product_id];
+                if purchases.is_empty() {
+                    println!("    Product {}: No allocation found!", product_id);
+                    continue;
                 }
-            }
-
-            // Then, for each product, sort options by cost and allocate optimally
-            for (product_id, qty_needed) in &shopping_list.items {
-                if let Some(options) = product_allocations.get_mut(product_id) {
-                    // Sort by cost (lowest first)
-                    options
-                        .sort_by(|a, b| a.2.partial_cmp(&b.2).unwrap_or(std::cmp::Ordering::Equal));
 
-                    // Calculate allocations
-                    let mut remaining = *qty_needed;
-                    let mut allocations = Vec::new();
-
-                    for &(store_id, available, cost) in options.iter() {
-                        if remaining == 0 {
-                            break;
-                        }
-
-                        let purchase_qty = std::cmp::min(available, remaining);
-                        if purchase_qty > 0 {
-                            allocations.push((store_id, purchase_qty, cost));
-                            remaining -= purchase_qty;
-                        }
-                    }
-
-                    // Print allocations for this product
-                    println!("    Product {}:", product_id);
-                    for (store_id, qty, cost) in allocations {
-                        println!(
-                            "      Store {}: Buy {} units at ${:.2} each (${:.2} total)",
-                            store_id,
-                            qty,
-                            cost,
-                            qty as f64 * cost
-                        );
-                    }
+                println!("    Product {}:", product_id);
+                for &(store_id, qty, cost) in purchases {
+                    println!(
+                        "      Store {}: Buy {} units at ${:.2} each (${:.2} total)",
+                        store_id,
+                        qty,
+                        cost,
+                        qty as f64 * cost
+                    );
+                }
 
-                    if remaining > 0 {
-                        println!("      WARNING: Could not allocate {} units", remaining);
-                    }
-                } else {
-                    println!("    Product {}: No allocation found!", product_id);
+                if let Some(remaining) = allocation.unfulfilled.get(product_id) {
+                    println!("      WARNING: Could not allocate {} units", remaining);
                 }
             }
             println!();
         }
 
         println!("Trade-off analysis:");
-        if results.len() >= 2 {
-            let fastest = &results.first().unwrap();
-            let cheapest = &results.last().unwrap();
-
-            println!("  Fastest route is {:.1}% faster but {:.1}% more expensive than the cheapest route.",
-                100.0 * (cheapest.shopping_time - fastest.shopping_time) / cheapest.shopping_time,
-                100.0 * (fastest.shopping_cost - cheapest.shopping_cost) / cheapest.shopping_cost);
-        }
+        print!("{}", format_skyline(&results));
     }
     println!("Start searching with infinity product amout:");
     let start_time = std::time::Instant::now();
@@ -209,95 +150,45 @@ fn main() {
     let elapsed = start_time.elapsed();
 
     // Print results
-    println!("Linear Skyline Results (found in {:.2?}):", elapsed);
+    println!("Skyline Results (found in {:.2?}):", elapsed);
     println!("------------------------------------------");
 
     if results.is_empty() {
         println!("No feasible routes found with current inventory constraints!");
     } else {
         for (i, route) in results.iter().enumerate() {
-            println!("Route {}: {:?}", i + 1, route.stores);
-            println!("  Shopping Time: {:.2} minutes", route.shopping_time);
-            println!("  Shopping Cost: ${:.2}", route.shopping_cost);
+            println!("{}: {}", i + 1, route);
 
             // Show optimized product allocation across stores
             println!("  Product Allocation:");
 
-            // For each product, find optimal allocation across stores in the route
-            let mut product_allocations: HashMap<ProductId, Vec<(StoreId, u32, f64)>> =
-                HashMap::new();
-
-            // First, collect all options for each product from all stores in the route
-            for &store_id in &route.stores {
-                let store = test_bsl_psd.stores[&store_id].read().unwrap();
-
-                for (product_id, _qty_needed) in &shopping_list.items {
-                    if store.has_product(product_id) {
-                        let available_qty = store.get_inventory_level(product_id);
-                        if available_qty > 0 {
-                            let cost = store.get_product_cost(product_id).unwrap_or(f64::INFINITY);
-                            product_allocations
-                                .entry(*product_id)
-                                .or_insert_with(Vec::new)
-                                .push((store_id, available_qty, cost));
-                        }
-                    }
+            let allocation = test_bsl_psd.allocate_purchases(&route.stores, &shopping_list);
+            for product_id in shopping_list.items.keys() {
+                let purchases = &allocation.purchases[product_id];
+                if purchases.is_empty() {
+                    println!("    Product {}: No allocation found!", product_id);
+                    continue;
                 }
-            }
-
-            // Then, for each product, sort options by cost and allocate optimally
-            for (product_id, qty_needed) in &shopping_list.items {
-                if let Some(options) = product_allocations.get_mut(product_id) {
-                    // Sort by cost (lowest first)
-                    options
-                        .sort_by(|a, b| a.2.partial_cmp(&b.2).unwrap_or(std::cmp::Ordering::Equal));
 
-                    // Calculate allocations
-                    let mut remaining = *qty_needed;
-                    let mut allocations = Vec::new();
-
-                    for &(store_id, available, cost) in options.iter() {
-                        if remaining == 0 {
-                            break;
-                        }
-
-                        let purchase_qty = std::cmp::min(available, remaining);
-                        if purchase_qty > 0 {
-                            allocations.push((store_id, purchase_qty, cost));
-                            remaining -= purchase_qty;
-                        }
-                    }
-
-                    // Print allocations for this product
-                    println!("    Product {}:", product_id);
-                    for (store_id, qty, cost) in allocations {
-                        println!(
-                            "      Store {}: Buy {} units at ${:.2} each (${:.2} total)",
-                            store_id,
-                            qty,
-                            cost,
-                            qty as f64 * cost
-                        );
-                    }
+                println!("    Product {}:", product_id);
+                for &(store_id, qty, cost) in purchases {
+                    println!(
+                        "      Store {}: Buy {} units at ${:.2} each (${:.2} total)",
+                        store_id,
+                        qty,
+                        cost,
+                        qty as f64 * cost
+                    );
+                }
 
-                    if remaining > 0 {
-                        println!("      WARNING: Could not allocate {} units", remaining);
-                    }
-                } else {
-                    println!("    Product {}: No allocation found!", product_id);
+                if let Some(remaining) = allocation.unfulfilled.get(product_id) {
+                    println!("      WARNING: Could not allocate {} units", remaining);
                 }
             }
             println!();
         }
 
         println!("Trade-off analysis:");
-        if results.len() >= 2 {
-            let fastest = &results.first().unwrap();
-            let cheapest = &results.last().unwrap();
-
-            println!("  Fastest route is {:.1}% faster but {:.1}% more expensive than the cheapest route.",
-                100.0 * (cheapest.shopping_time - fastest.shopping_time) / cheapest.shopping_time,
-                100.0 * (fastest.shopping_cost - cheapest.shopping_cost) / cheapest.shopping_cost);
-        }
+        print!("{}", format_skyline(&results));
     }
 }
@@ -1,7 +1,12 @@
 // Location model representing coordinates in 2D space
 
+use serde::{Deserialize, Serialize};
+
+/// Mean Earth radius in kilometers, used by the Haversine formula
+const EARTH_RADIUS_KM: f64 = 6371.0;
+
 /// Represents a location with (x, y) coordinates
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub struct Location {
     pub x: f64,
     pub y: f64,
@@ -14,6 +19,10 @@ impl Location {
     }
 
     /// Calculates the Euclidean distance between two locations
+    ///
+    /// The result is in the same units as the coordinates. No geographic projection
+    /// is applied, so for longitude/latitude data the distance is in raw degrees.
+    /// The solver's time objective depends on this staying a plain Euclidean metric.
     pub fn distance_to(&self, other: &Location) -> f64 {
         ((self.x - other.x).powi(2) + (self.y - other.y).powi(2)).sqrt()
     }
@@ -22,6 +31,62 @@ impl Location {
     pub fn manhattan_distance_to(&self, other: &Location) -> f64 {
         (self.x - other.x).abs() + (self.y - other.y).abs()
     }
+
+    /// Great-circle distance in kilometers, treating `x` as longitude and `y` as
+    /// latitude in degrees
+    pub fn haversine_distance_to(&self, other: &Location) -> f64 {
+        let lat1 = self.y.to_radians();
+        let lat2 = other.y.to_radians();
+        let d_lat = (other.y - self.y).to_radians();
+        let d_lon = (other.x - self.x).to_radians();
+
+        let a = (d_lat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (d_lon / 2.0).sin().powi(2);
+        2.0 * EARTH_RADIUS_KM * a.sqrt().asin()
+    }
+
+    /// Whether the location is at most `radius` from `center` in Euclidean distance
+    /// Use `DistanceMetric::within_radius` for other metrics
+    pub fn within_radius(&self, center: &Location, radius: f64) -> bool {
+        DistanceMetric::Euclidean.within_radius(self, center, radius)
+    }
+
+    /// Whether the location lies in the axis-aligned box spanned by two corners,
+    /// boundary included
+    pub fn within_box(&self, corner: &Location, opposite: &Location) -> bool {
+        (corner.x.min(opposite.x)..=corner.x.max(opposite.x)).contains(&self.x)
+            && (corner.y.min(opposite.y)..=corner.y.max(opposite.y)).contains(&self.y)
+    }
+}
+
+/// How straight-line distances between locations are measured
+///
+/// `Haversine`, the default, suits the longitude/latitude map data and returns
+/// kilometers. `Euclidean` is for planar coordinates such as synthetic test maps, which
+/// must select it explicitly. `Manhattan` measures city-block distance along the
+/// coordinate axes, for grid-like street layouts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DistanceMetric {
+    Euclidean,
+    #[default]
+    Haversine,
+    Manhattan,
+}
+
+impl DistanceMetric {
+    /// Distance between two locations under this metric
+    pub fn distance(&self, from: &Location, to: &Location) -> f64 {
+        match self {
+            DistanceMetric::Euclidean => from.distance_to(to),
+            DistanceMetric::Haversine => from.haversine_distance_to(to),
+            DistanceMetric::Manhattan => from.manhattan_distance_to(to),
+        }
+    }
+
+    /// Whether `location` is at most `radius` from `center`; the radius is in this
+    /// metric's units (kilometers for `Haversine`)
+    pub fn within_radius(&self, location: &Location, center: &Location, radius: f64) -> bool {
+        self.distance(location, center) <= radius
+    }
 }
 
 #[cfg(test)]
@@ -36,6 +101,33 @@ mod tests {
         assert_eq!(loc1.distance_to(&loc2), 5.0);
     }
 
+    #[test]
+    fn test_distance_to_self_is_zero() {
+        let loc = Location::new(4.8950, 52.3664);
+        assert_eq!(loc.distance_to(&loc), 0.0);
+    }
+
+    #[test]
+    fn test_distance_is_symmetric() {
+        let loc1 = Location::new(-1.5, 2.25);
+        let loc2 = Location::new(7.0, -3.0);
+
+        assert_eq!(loc1.distance_to(&loc2), loc2.distance_to(&loc1));
+    }
+
+    #[test]
+    fn test_distance_known_triangle() {
+        // Right triangle with legs 6 and 8
+        let a = Location::new(0.0, 0.0);
+        let b = Location::new(6.0, 0.0);
+        let c = Location::new(6.0, 8.0);
+
+        assert_eq!(a.distance_to(&b), 6.0);
+        assert_eq!(b.distance_to(&c), 8.0);
+        assert_eq!(a.distance_to(&c), 10.0);
+        assert!(a.distance_to(&c) <= a.distance_to(&b) + b.distance_to(&c));
+    }
+
     #[test]
     fn test_manhattan_distance() {
         let loc1 = Location::new(0.0, 0.0);
@@ -43,4 +135,76 @@ mod tests {
 
         assert_eq!(loc1.manhattan_distance_to(&loc2), 7.0);
     }
+
+    #[test]
+    fn test_metrics_on_the_same_points() {
+        let from = Location::new(4.8950, 52.3664);
+        let to = Location::new(4.9050, 52.3764);
+
+        let euclidean = DistanceMetric::Euclidean.distance(&from, &to);
+        let manhattan = DistanceMetric::Manhattan.distance(&from, &to);
+        let haversine = DistanceMetric::Haversine.distance(&from, &to);
+        assert_eq!(euclidean, from.distance_to(&to));
+        assert_eq!(manhattan, from.manhattan_distance_to(&to));
+        assert_eq!(haversine, from.haversine_distance_to(&to));
+
+        // A diagonal offset of 0.01 degrees both ways
+        assert!((euclidean - 0.01 * 2.0_f64.sqrt()).abs() < 1e-9);
+        assert!((manhattan - 0.02).abs() < 1e-9);
+        assert!((haversine - 1.303).abs() < 0.001);
+
+        // City blocks are never shorter than the straight line
+        assert!(manhattan >= euclidean);
+        for (x, y) in [(0.0, 0.0), (3.0, 0.0), (-2.5, 7.0), (1e-6, -1e6)] {
+            let point = Location::new(x, y);
+            assert!(point.manhattan_distance_to(&from) >= point.distance_to(&from));
+        }
+    }
+
+    #[test]
+    fn test_haversine_vs_euclidean_in_amsterdam() {
+        let origin = Location::new(4.8950, 52.3664);
+        let east = Location::new(4.9050, 52.3664);
+        let north = Location::new(4.8950, 52.3764);
+
+        // Both offsets are 0.01 degrees, so Euclidean cannot tell them apart
+        let euclidean = DistanceMetric::Euclidean;
+        assert!((euclidean.distance(&origin, &east) - 0.01).abs() < 1e-9);
+        assert!((euclidean.distance(&origin, &north) - 0.01).abs() < 1e-9);
+
+        // On the ground a degree of longitude is much shorter at this latitude
+        let haversine = DistanceMetric::Haversine;
+        assert!((haversine.distance(&origin, &east) - 0.679).abs() < 0.001);
+        assert!((haversine.distance(&origin, &north) - 1.112).abs() < 0.001);
+
+        // Amsterdam Centraal to the Rijksmuseum is roughly 2.36 km
+        let centraal = Location::new(4.9003, 52.3791);
+        let rijksmuseum = Location::new(4.8852, 52.3600);
+        assert!((centraal.haversine_distance_to(&rijksmuseum) - 2.358).abs() < 0.001);
+        assert_eq!(origin.haversine_distance_to(&origin), 0.0);
+    }
+
+    #[test]
+    fn test_location_json_round_trip() {
+        let location = Location::new(4.895, 52.3664);
+        let json = serde_json::to_string(&location).unwrap();
+        assert_eq!(json, r#"{"x":4.895,"y":52.3664}"#);
+        assert_eq!(serde_json::from_str::<Location>(&json).unwrap(), location);
+    }
+
+    #[test]
+    fn test_within_radius_and_box() {
+        let center = Location::new(0.0, 0.0);
+        let edge = Location::new(3.0, 4.0);
+
+        // The boundary counts as inside
+        assert!(edge.within_radius(&center, 5.0));
+        assert!(!edge.within_radius(&center, 4.9));
+        assert!(DistanceMetric::Manhattan.within_radius(&edge, &center, 7.0));
+        assert!(!DistanceMetric::Manhattan.within_radius(&edge, &center, 5.0));
+
+        // Corners may be given in any order
+        assert!(edge.within_box(&Location::new(3.0, 0.0), &Location::new(0.0, 4.0)));
+        assert!(!edge.within_box(&center, &Location::new(2.0, 10.0)));
+    }
 }
@@ -7,10 +7,11 @@ mod shopping_list;
 mod store;
 
 // Re-export model types
-pub use self::location::Location;
+pub use self::location::{DistanceMetric, Location};
 pub use self::product::Product;
-pub use self::route::{RouteCandidate, ShoppingRoute};
-pub use self::shopping_list::ShoppingList;
+pub(crate) use self::route::format_stores;
+pub use self::route::{PurchaseAllocation, RouteCandidate, ShoppingRoute};
+pub use self::shopping_list::{ShoppingList, ShoppingListError};
 pub use self::store::Store;
 
 // Common type aliases for improved code readability
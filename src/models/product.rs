@@ -1,25 +1,63 @@
 // Product model representing items that can be purchased
 
 use crate::models::Cost;
+use serde::{Deserialize, Serialize};
 
 /// Represents a product that can be sold in a store
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Product {
     /// Name of the product
     pub name: String,
 
     /// Cost of the product
     pub cost: Cost,
+
+    /// Weight of one unit, in the unit used for carrying capacities (0 if unknown)
+    #[serde(default)]
+    pub weight: f64,
+
+    /// Tax or fee charged on top of the cost, as a fraction (0.21 for 21% VAT)
+    #[serde(default)]
+    pub tax_rate: f64,
+
+    /// Category the product belongs to, e.g. "organic"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub category: Option<String>,
 }
 
 impl Product {
     /// Creates a new product with the given name and cost
     pub fn new<S: Into<String>>(name: S, cost: Cost) -> Self {
+        Self::new_with_weight(name, cost, 0.0)
+    }
+
+    /// Creates a new product with the given name, cost and unit weight
+    pub fn new_with_weight<S: Into<String>>(name: S, cost: Cost, weight: f64) -> Self {
         Self {
             name: name.into(),
             cost,
+            weight,
+            tax_rate: 0.0,
+            category: None,
         }
     }
+
+    /// Sets the product's tax rate
+    pub fn with_tax_rate(mut self, tax_rate: f64) -> Self {
+        self.tax_rate = tax_rate;
+        self
+    }
+
+    /// Sets the product's category
+    pub fn with_category<S: Into<String>>(mut self, category: S) -> Self {
+        self.category = Some(category.into());
+        self
+    }
+
+    /// Price of one unit including tax, as paid by the shopper
+    pub fn unit_price(&self) -> Cost {
+        self.cost * (1.0 + self.tax_rate)
+    }
 }
 
 #[cfg(test)]
@@ -40,4 +78,41 @@ mod tests {
         assert_eq!(cloned.name, product.name);
         assert_eq!(cloned.cost, product.cost);
     }
+
+    #[test]
+    fn test_product_json_round_trip() {
+        let product = Product::new("Test Product", 10.5);
+        let json = serde_json::to_string(&product).unwrap();
+        assert_eq!(
+            json,
+            r#"{"name":"Test Product","cost":10.5,"weight":0.0,"tax_rate":0.0}"#
+        );
+
+        let parsed: Product = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.name, product.name);
+        assert_eq!(parsed.cost, product.cost);
+
+        // Products saved without a weight or tax rate still load
+        let parsed: Product = serde_json::from_str(r#"{"name":"Old","cost":1.0}"#).unwrap();
+        assert_eq!(parsed.weight, 0.0);
+        assert_eq!(parsed.tax_rate, 0.0);
+        assert_eq!(parsed.category, None);
+
+        // A category is only written when set
+        let organic = product.with_category("organic");
+        let json = serde_json::to_string(&organic).unwrap();
+        assert!(json.ends_with(r#","category":"organic"}"#));
+        let parsed: Product = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.category.as_deref(), Some("organic"));
+    }
+
+    #[test]
+    fn test_unit_price_includes_tax() {
+        let product = Product::new("Wine", 10.0);
+        assert_eq!(product.unit_price(), 10.0);
+
+        let taxed = product.with_tax_rate(0.21);
+        assert_eq!(taxed.cost, 10.0);
+        assert!((taxed.unit_price() - 12.1).abs() < 1e-12);
+    }
 }
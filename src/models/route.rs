@@ -1,10 +1,13 @@
 // Route models for representing shopping routes
 
-use crate::models::{Cost, StoreId, Time};
+use crate::models::{Cost, ProductId, StoreId, Time};
+use serde::{Deserialize, Serialize};
 use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt;
 
 /// Represents a complete shopping route with cost information
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ShoppingRoute {
     /// Sequence of stores to visit
     pub stores: Vec<StoreId>,
@@ -41,6 +44,108 @@ impl ShoppingRoute {
 
         condition1 || condition2 || condition3
     }
+
+    /// Checks if both routes visit the same set of stores, in whatever order
+    ///
+    /// Such routes cost the same, so a skyline keeps only the faster of them. Times and
+    /// costs are not compared: derived equality compares them exactly, with no epsilon.
+    pub fn same_stores(&self, other: &ShoppingRoute) -> bool {
+        let covers = |a: &ShoppingRoute, b: &ShoppingRoute| {
+            b.stores.iter().all(|store| a.stores.contains(store))
+        };
+        covers(self, other) && covers(other, self)
+    }
+
+    /// Order routes are listed in a skyline: by shopping time, then shopping cost, then
+    /// the sorted store IDs, then the visiting order
+    ///
+    /// This is a total order, so skylines list equally fast routes the same way on
+    /// every run whatever order they were found in.
+    pub fn skyline_order(&self, other: &ShoppingRoute) -> Ordering {
+        let sorted_stores = |route: &ShoppingRoute| {
+            let mut stores = route.stores.clone();
+            stores.sort_unstable();
+            stores
+        };
+        self.shopping_time
+            .total_cmp(&other.shopping_time)
+            .then(self.shopping_cost.total_cmp(&other.shopping_cost))
+            .then_with(|| sorted_stores(self).cmp(&sorted_stores(other)))
+            .then_with(|| self.stores.cmp(&other.stores))
+    }
+
+    /// Checks if this route is strictly better than another in both time and cost
+    pub fn strictly_dominates(&self, other: &ShoppingRoute) -> bool {
+        self.shopping_time < other.shopping_time && self.shopping_cost < other.shopping_cost
+    }
+
+    /// Number of distinct stores the route visits
+    pub fn store_count(&self) -> usize {
+        let mut stores = self.stores.clone();
+        stores.sort_unstable();
+        stores.dedup();
+        stores.len()
+    }
+
+    /// Conventional domination extended to the number of stores as a third objective
+    /// This route must be no worse in time, cost and store count, and strictly better
+    /// in at least one, so a slower, dearer route with fewer stops is not dominated.
+    pub fn dominates_with_store_count(&self, other: &ShoppingRoute) -> bool {
+        let (count, other_count) = (self.store_count(), other.store_count());
+        let no_worse = self.shopping_time <= other.shopping_time
+            && self.shopping_cost <= other.shopping_cost
+            && count <= other_count;
+        let better = self.shopping_time < other.shopping_time
+            || self.shopping_cost < other.shopping_cost
+            || count < other_count;
+        no_worse && better
+    }
+}
+
+impl fmt::Display for ShoppingRoute {
+    /// e.g. `Route[stores=1→3→5, time=12.4min, cost=$42.10]`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Route[stores={}, time={:.1}min, cost=${:.2}]",
+            format_stores(&self.stores),
+            self.shopping_time,
+            self.shopping_cost
+        )
+    }
+}
+
+/// Store IDs in visiting order joined by arrows, or "none" for a direct route
+pub(crate) fn format_stores(stores: &[StoreId]) -> String {
+    if stores.is_empty() {
+        return "none".to_string();
+    }
+    stores
+        .iter()
+        .map(|store_id| store_id.to_string())
+        .collect::<Vec<_>>()
+        .join("→")
+}
+
+/// Breakdown of what to buy where along a route, cheapest stores first
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct PurchaseAllocation {
+    /// Purchases per listed product as (store, quantity, tax-inclusive unit price), in
+    /// buying order
+    pub purchases: HashMap<ProductId, Vec<(StoreId, u32, Cost)>>,
+
+    /// Quantities per product that the route's stores can't supply
+    pub unfulfilled: HashMap<ProductId, u32>,
+
+    /// Total cost of the allocated purchases
+    pub total_cost: Cost,
+}
+
+impl PurchaseAllocation {
+    /// Whether the whole shopping list is covered
+    pub fn is_complete(&self) -> bool {
+        self.unfulfilled.is_empty()
+    }
 }
 
 /// Candidate route used in the priority queue for route generation
@@ -56,16 +161,19 @@ pub struct RouteCandidate {
 // Custom ordering for min-priority queue based on shopping time
 impl PartialOrd for RouteCandidate {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        // For floating point comparison in priority queue
-        // Convert to ordered floating point representation for comparison
-        // Reversed to create min-heap instead of default max-heap
-        other.shopping_time.partial_cmp(&self.shopping_time)
+        Some(self.cmp(other))
     }
 }
 
 impl Ord for RouteCandidate {
     fn cmp(&self, other: &Self) -> Ordering {
-        self.partial_cmp(other).unwrap_or(Ordering::Equal)
+        // For floating point comparison in priority queue
+        // Convert to ordered floating point representation for comparison
+        // Reversed to create min-heap instead of default max-heap
+        other
+            .shopping_time
+            .partial_cmp(&self.shopping_time)
+            .unwrap_or(Ordering::Equal)
     }
 }
 impl Eq for RouteCandidate {}
@@ -73,6 +181,43 @@ impl Eq for RouteCandidate {}
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_skyline_order_breaks_ties() {
+        let mut routes = [
+            ShoppingRoute::new(vec![4, 2], 10.0, 30.0),
+            ShoppingRoute::new(vec![3], 10.0, 30.0),
+            ShoppingRoute::new(vec![9], 10.0, 25.0),
+            ShoppingRoute::new(vec![2, 5], 10.0, 30.0),
+            ShoppingRoute::new(vec![1], 12.0, 5.0),
+            ShoppingRoute::new(vec![2, 4], 10.0, 30.0),
+        ];
+        routes.sort_by(ShoppingRoute::skyline_order);
+
+        // Time, then cost, then the sorted stores ([2, 4] < [2, 5] < [3]), then visit order
+        let stores: Vec<Vec<StoreId>> = routes.iter().map(|route| route.stores.clone()).collect();
+        assert_eq!(
+            stores,
+            vec![
+                vec![9],
+                vec![2, 4],
+                vec![4, 2],
+                vec![2, 5],
+                vec![3],
+                vec![1]
+            ]
+        );
+    }
+
+    #[test]
+    fn test_same_stores_ignores_order() {
+        let route = ShoppingRoute::new(vec![1, 3, 5], 12.0, 40.0);
+        assert!(route.same_stores(&ShoppingRoute::new(vec![5, 1, 3], 15.0, 40.0)));
+        assert!(route.same_stores(&route));
+        assert!(!route.same_stores(&ShoppingRoute::new(vec![1, 3], 12.0, 40.0)));
+        assert!(!route.same_stores(&ShoppingRoute::new(vec![1, 3, 4], 12.0, 40.0)));
+        assert_ne!(route, ShoppingRoute::new(vec![5, 1, 3], 12.0, 40.0));
+    }
+
     #[test]
     fn test_route_candidate_ordering() {
         let route1 = RouteCandidate {
@@ -87,4 +232,47 @@ mod tests {
         // In a min-heap, the lesser element comes first
         assert!(route1 > route2);
     }
+
+    #[test]
+    fn test_shopping_route_json_round_trip() {
+        let route = ShoppingRoute::new(vec![4, 1, 9], 12.5, 37.25);
+        let json = serde_json::to_string(&route).unwrap();
+        assert_eq!(
+            json,
+            r#"{"stores":[4,1,9],"shopping_time":12.5,"shopping_cost":37.25}"#
+        );
+        assert_eq!(serde_json::from_str::<ShoppingRoute>(&json).unwrap(), route);
+    }
+
+    #[test]
+    fn test_shopping_route_display() {
+        let route = ShoppingRoute::new(vec![1, 3, 5], 12.44, 42.1);
+        assert_eq!(
+            route.to_string(),
+            "Route[stores=1→3→5, time=12.4min, cost=$42.10]"
+        );
+
+        let direct = ShoppingRoute::new(Vec::new(), 3.0, 0.0);
+        assert_eq!(
+            direct.to_string(),
+            "Route[stores=none, time=3.0min, cost=$0.00]"
+        );
+    }
+
+    #[test]
+    fn test_domination_with_store_count() {
+        let three_stops = ShoppingRoute::new(vec![1, 2, 3], 10.0, 3.0);
+        let two_stops = ShoppingRoute::new(vec![4, 3], 12.0, 5.0);
+
+        assert!(three_stops.conventionally_dominates(&two_stops));
+        assert!(!three_stops.dominates_with_store_count(&two_stops));
+        assert!(!two_stops.dominates_with_store_count(&three_stops));
+
+        // Repeated stores count once; equal routes don't dominate each other
+        let revisit = ShoppingRoute::new(vec![4, 3, 4], 12.0, 5.0);
+        assert_eq!(revisit.store_count(), 2);
+        assert!(!revisit.dominates_with_store_count(&two_stops));
+        let slower = ShoppingRoute::new(vec![4, 3], 13.0, 5.0);
+        assert!(two_stops.dominates_with_store_count(&slower));
+    }
 }
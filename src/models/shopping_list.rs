@@ -1,18 +1,75 @@
 // Shopping list model representing customer requests with inventory awareness
 
 use crate::models::{ProductId, Store, StoreId};
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize, Serializer};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::Path;
+
+/// Errors raised while reading a shopping list from CSV
+/// Line numbers are 1-based
+#[derive(Debug)]
+pub enum ShoppingListError {
+    /// The CSV source could not be read
+    Io(io::Error),
+
+    /// A row that isn't `product_id,quantity` with non-negative integers
+    ParseRow { line: usize, row: String },
+}
+
+impl fmt::Display for ShoppingListError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ShoppingListError::Io(e) => write!(f, "failed to read shopping list: {}", e),
+            ShoppingListError::ParseRow { line, row } => write!(
+                f,
+                "malformed shopping list row on line {}: {:?} (expected product_id,quantity)",
+                line, row
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ShoppingListError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ShoppingListError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ShoppingListError {
+    fn from(e: io::Error) -> Self {
+        ShoppingListError::Io(e)
+    }
+}
 
 /// Represents a customer's shopping list with products and quantities
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ShoppingList {
     /// Map of product IDs to their required quantities
+    #[serde(serialize_with = "serialize_sorted")]
     pub items: HashMap<ProductId, u32>,
 
     /// Priority level of this shopping list (for multi-order processing)
+    #[serde(default)]
     pub priority: u32,
 }
 
+// Writes the items ordered by product ID so the output is deterministic
+fn serialize_sorted<S: Serializer>(
+    items: &HashMap<ProductId, u32>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    items
+        .iter()
+        .collect::<BTreeMap<_, _>>()
+        .serialize(serializer)
+}
+
 impl ShoppingList {
     /// Creates a new empty shopping list with default priority
     pub fn new() -> Self {
@@ -51,11 +108,35 @@ impl ShoppingList {
         }
     }
 
+    /// Adds every item of `other` to this list, summing quantities per product
+    /// The merged list keeps the higher of the two priorities.
+    pub fn merge(&mut self, other: &ShoppingList) {
+        for (&product_id, &quantity) in &other.items {
+            self.add_item(product_id, quantity);
+        }
+        self.priority = self.priority.max(other.priority);
+    }
+
+    /// Takes fulfilled quantities off the list, e.g. what was bought at one store
+    /// Quantities stop at zero, and items that reach zero are removed.
+    pub fn subtract(&mut self, fulfilled: &HashMap<ProductId, u32>) {
+        for (product_id, &quantity) in fulfilled {
+            if let Some(&needed) = self.items.get(product_id) {
+                self.update_quantity(*product_id, needed.saturating_sub(quantity));
+            }
+        }
+    }
+
     /// Gets the total number of unique products in the list
     pub fn unique_product_count(&self) -> usize {
         self.items.len()
     }
 
+    /// Whether the list asks for nothing, i.e. no product has a positive quantity
+    pub fn is_empty(&self) -> bool {
+        self.items.values().all(|&quantity| quantity == 0)
+    }
+
     /// Gets the total number of items (including quantities)
     pub fn total_item_count(&self) -> u32 {
         self.items.values().sum()
@@ -71,6 +152,62 @@ impl ShoppingList {
         self.priority
     }
 
+    /// Parses a shopping list from JSON, e.g. `{"items": {"3": 2}, "priority": 1}`
+    /// The priority is optional and defaults to 0.
+    pub fn from_json(json: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(json)
+    }
+
+    /// Serializes the shopping list to JSON, with items ordered by product ID
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("shopping lists always serialize")
+    }
+
+    /// Reads a shopping list from `product_id,quantity` CSV rows
+    ///
+    /// Blank lines are skipped. The first row is taken as a header when none of its
+    /// fields is a number. Quantities for a repeated product add up, as with
+    /// `add_item`. Any other row that doesn't hold two non-negative integers is an
+    /// error reporting its line number.
+    pub fn from_csv_reader<R: Read>(reader: R) -> Result<ShoppingList, ShoppingListError> {
+        let mut list = ShoppingList::new();
+        let mut first_row = true;
+
+        for (index, line) in BufReader::new(reader).lines().enumerate() {
+            let line = line?;
+            let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+            if fields.iter().all(|field| field.is_empty()) {
+                continue;
+            }
+
+            let is_header = first_row && fields.iter().all(|field| field.parse::<f64>().is_err());
+            first_row = false;
+            if is_header {
+                continue;
+            }
+
+            let parsed = match fields.as_slice() {
+                [product_id, quantity] => product_id
+                    .parse::<ProductId>()
+                    .ok()
+                    .zip(quantity.parse::<u32>().ok()),
+                _ => None,
+            };
+            let (product_id, quantity) = parsed.ok_or_else(|| ShoppingListError::ParseRow {
+                line: index + 1,
+                row: line.clone(),
+            })?;
+            list.add_item(product_id, quantity);
+        }
+
+        Ok(list)
+    }
+
+    /// Reads a shopping list from a CSV file (see `from_csv_reader`)
+    pub fn from_csv_path<P: AsRef<Path>>(path: P) -> Result<ShoppingList, ShoppingListError> {
+        ShoppingList::from_csv_reader(File::open(path)?)
+    }
+
     /// Checks if this shopping list can be fulfilled by the given stores
     /// considering both product availability and inventory levels
     pub fn can_be_fulfilled_by(&self, stores: &HashMap<StoreId, Store>) -> bool {
@@ -107,9 +244,9 @@ impl ShoppingList {
             .values()
             .filter(|store| {
                 // Check if the store has any product from the shopping list in stock
-                self.items.keys().any(|product_id| {
-                    store.has_product(product_id) && store.get_inventory_level(product_id) > 0
-                })
+                self.items
+                    .keys()
+                    .any(|product_id| store.can_supply(product_id, 1))
             })
             .collect()
     }
@@ -247,4 +384,124 @@ mod tests {
         assert_eq!(fulfillable.items.get(&2), Some(&5)); // Unchanged
         assert!(!fulfillable.items.contains_key(&4)); // Removed completely
     }
+
+    #[test]
+    fn test_json_round_trip() {
+        let mut list = ShoppingList::new_with_priority(2);
+        for product_id in [12, 3, 7, 1] {
+            list.add_item(product_id, product_id + 1);
+        }
+
+        // Items are written in product ID order, whatever the map's order
+        let json = list.to_json();
+        assert_eq!(
+            json,
+            r#"{"items":{"1":2,"3":4,"7":8,"12":13},"priority":2}"#
+        );
+
+        let parsed = ShoppingList::from_json(&json).unwrap();
+        assert_eq!(parsed.items, list.items);
+        assert_eq!(parsed.priority, 2);
+
+        let without_priority = ShoppingList::from_json(r#"{"items":{"5":1}}"#).unwrap();
+        assert_eq!(without_priority.items.get(&5), Some(&1));
+        assert_eq!(without_priority.priority, 0);
+
+        assert!(ShoppingList::from_json(r#"{"items":{"x":1}}"#).is_err());
+    }
+
+    #[test]
+    fn test_from_csv_reader() {
+        // Header, blank lines, padding and a repeated product
+        let csv = "product_id, quantity\n\n1,2\r\n 3 , 4\n\n1,5\n";
+        let list = ShoppingList::from_csv_reader(csv.as_bytes()).unwrap();
+        assert_eq!(list.items.len(), 2);
+        assert_eq!(list.items.get(&1), Some(&7));
+        assert_eq!(list.items.get(&3), Some(&4));
+
+        // No header
+        let list = ShoppingList::from_csv_reader("\n8,1\n".as_bytes()).unwrap();
+        assert_eq!(list.items.get(&8), Some(&1));
+
+        // A text row after the first is an error, with its line number
+        for (csv, bad_line) in [
+            ("1,2\nid,qty\n", 2),
+            ("1,2\n\n3,-1\n", 3),
+            ("1\n", 1),
+            ("product,quantity\n1,2,3\n", 2),
+        ] {
+            match ShoppingList::from_csv_reader(csv.as_bytes()) {
+                Err(ShoppingListError::ParseRow { line, .. }) => assert_eq!(line, bad_line),
+                other => panic!("expected a parse error for {:?}, got {:?}", csv, other),
+            }
+        }
+        let error = ShoppingList::from_csv_reader("1,x\n".as_bytes()).unwrap_err();
+        assert!(error.to_string().contains("line 1"));
+
+        assert!(matches!(
+            ShoppingList::from_csv_path("data/no_such_list.csv"),
+            Err(ShoppingListError::Io(_))
+        ));
+    }
+
+    #[test]
+    fn test_is_empty_ignores_zero_quantities() {
+        let mut list = ShoppingList::new();
+        assert!(list.is_empty());
+
+        list.items.insert(1, 0);
+        assert!(list.is_empty());
+
+        list.add_item(2, 1);
+        assert!(!list.is_empty());
+    }
+
+    #[test]
+    fn test_merge_overlapping_products() {
+        let mut list = ShoppingList::new_with_priority(1);
+        list.add_item(1, 2);
+        list.add_item(2, 3);
+        let mut other = ShoppingList::new_with_priority(4);
+        other.add_item(2, 5);
+        other.items.insert(3, 0);
+
+        list.merge(&other);
+
+        assert_eq!(list.items.len(), 2);
+        assert_eq!(list.items[&1], 2);
+        assert_eq!(list.items[&2], 8);
+        assert_eq!(list.get_priority(), 4);
+    }
+
+    #[test]
+    fn test_merge_disjoint_products() {
+        let mut list = ShoppingList::new_with_priority(3);
+        list.add_item(1, 2);
+        let mut other = ShoppingList::new();
+        other.add_item(2, 1);
+        other.add_item(3, 4);
+
+        list.merge(&other);
+
+        assert_eq!(list.unique_product_count(), 3);
+        assert_eq!(list.total_item_count(), 7);
+        assert_eq!(list.get_priority(), 3);
+    }
+
+    #[test]
+    fn test_subtract_clamps_at_zero() {
+        let mut list = ShoppingList::new();
+        list.add_item(1, 5);
+        list.add_item(2, 3);
+        list.add_item(3, 1);
+
+        let fulfilled = HashMap::from([(1, 2), (2, 10), (4, 7)]);
+        list.subtract(&fulfilled);
+
+        assert_eq!(list.items.len(), 2);
+        assert_eq!(list.items[&1], 3);
+        assert!(!list.items.contains_key(&2));
+        assert_eq!(list.items[&3], 1);
+        assert!(!list.items.contains_key(&4));
+    }
 }
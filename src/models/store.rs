@@ -1,7 +1,10 @@
 // Store model representing shopping locations with inventory tracking
 
 use crate::models::{Location, Product, ProductId, StoreId};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+
+/// Minutes in a day, used to wrap opening hours
+const MINUTES_PER_DAY: f64 = 1440.0;
 
 /// Represents a store where products can be purchased
 #[derive(Debug, Clone)]
@@ -17,6 +20,20 @@ pub struct Store {
 
     /// Inventory tracking for each product
     pub inventory: HashMap<ProductId, u32>,
+
+    /// Identifier of the store in the source data set (e.g. the restaurant id),
+    /// if the store was loaded from one
+    pub external_id: Option<u64>,
+
+    /// Opening hours as (open, close) minutes from midnight; None means always open.
+    /// A close time earlier than the open time means the store is open overnight.
+    pub hours: Option<(f64, f64)>,
+
+    /// Minutes spent shopping and checking out on each visit
+    pub service_time: f64,
+
+    /// Categories the store belongs to, e.g. "organic"
+    pub categories: HashSet<String>,
 }
 
 impl Store {
@@ -33,10 +50,15 @@ impl Store {
             location,
             products,
             inventory,
+            external_id: None,
+            hours: None,
+            service_time: 0.0,
+            categories: HashSet::new(),
         }
     }
 
     /// Creates a new store with explicit inventory control
+    /// Each product appears once here; use `from_product_entries` for raw data with repeats
     pub fn new_with_inventory(
         id: StoreId,
         location: Location,
@@ -48,7 +70,59 @@ impl Store {
             location,
             products,
             inventory,
+            external_id: None,
+            hours: None,
+            service_time: 0.0,
+            categories: HashSet::new(),
+        }
+    }
+
+    /// Creates a store from raw `(product_id, product, quantity)` entries, e.g. as read
+    /// from a data file where the same product may be listed more than once
+    ///
+    /// Duplicate entries are merged: quantities are summed and the lower price is kept.
+    /// The IDs of duplicated products are returned alongside the store so strict
+    /// loaders can reject the data instead.
+    pub fn from_product_entries<I>(
+        id: StoreId,
+        location: Location,
+        entries: I,
+    ) -> (Self, Vec<ProductId>)
+    where
+        I: IntoIterator<Item = (ProductId, Product, u32)>,
+    {
+        let mut products: HashMap<ProductId, Product> = HashMap::new();
+        let mut inventory: HashMap<ProductId, u32> = HashMap::new();
+        let mut duplicates = Vec::new();
+
+        for (product_id, product, quantity) in entries {
+            match products.get_mut(&product_id) {
+                Some(existing) => {
+                    if !duplicates.contains(&product_id) {
+                        duplicates.push(product_id);
+                    }
+                    if product.cost < existing.cost {
+                        *existing = product;
+                    }
+                    let level = inventory.entry(product_id).or_insert(0);
+                    *level = level.saturating_add(quantity);
+                }
+                None => {
+                    products.insert(product_id, product);
+                    inventory.insert(product_id, quantity);
+                }
+            }
         }
+
+        (
+            Self::new_with_inventory(id, location, products, inventory),
+            duplicates,
+        )
+    }
+
+    /// Checks if the store belongs to a category
+    pub fn has_category(&self, category: &str) -> bool {
+        self.categories.contains(category)
     }
 
     /// Checks if the store sells a specific product
@@ -61,6 +135,17 @@ impl Store {
         self.products.get(product_id).map(|p| p.cost)
     }
 
+    /// Gets the tax-inclusive unit price of a specific product if available
+    /// This is what route shopping costs are made of
+    pub fn get_unit_price(&self, product_id: &ProductId) -> Option<f64> {
+        self.products.get(product_id).map(|p| p.unit_price())
+    }
+
+    /// Gets the unit weight of a specific product if available
+    pub fn get_product_weight(&self, product_id: &ProductId) -> Option<f64> {
+        self.products.get(product_id).map(|p| p.weight)
+    }
+
     /// Gets all product IDs available at this store
     pub fn get_available_product_ids(&self) -> Vec<ProductId> {
         self.products.keys().cloned().collect()
@@ -75,6 +160,60 @@ impl Store {
         }
     }
 
+    /// Sets the identifier of the store in the source data set
+    pub fn with_external_id(mut self, external_id: u64) -> Self {
+        self.external_id = Some(external_id);
+        self
+    }
+
+    /// Sets the opening hours as (open, close) minutes from midnight
+    pub fn with_hours(mut self, open: f64, close: f64) -> Self {
+        self.hours = Some((open, close));
+        self
+    }
+
+    /// Sets the minutes spent shopping and checking out on each visit
+    pub fn with_service_time(mut self, minutes: f64) -> Self {
+        self.service_time = minutes;
+        self
+    }
+
+    /// Adds a category the store belongs to
+    pub fn with_category<S: Into<String>>(mut self, category: S) -> Self {
+        self.categories.insert(category.into());
+        self
+    }
+
+    /// Checks if the store is open at the given minute (wrapped to a single day)
+    pub fn is_open_at(&self, minute: f64) -> bool {
+        let Some((open, close)) = self.hours else {
+            return true;
+        };
+        let minute = minute.rem_euclid(MINUTES_PER_DAY);
+
+        if open <= close {
+            open <= minute && minute < close
+        } else {
+            // Overnight hours, e.g. 22:00 - 02:00
+            minute >= open || minute < close
+        }
+    }
+
+    /// Minutes to wait from the given minute until the store opens (0 if already open)
+    pub fn minutes_until_open(&self, minute: f64) -> f64 {
+        match self.hours {
+            Some((open, _)) if !self.is_open_at(minute) => {
+                (open - minute.rem_euclid(MINUTES_PER_DAY)).rem_euclid(MINUTES_PER_DAY)
+            }
+            _ => 0.0,
+        }
+    }
+
+    /// Checks if the store sells a product and has at least `quantity` units in stock
+    pub fn can_supply(&self, product_id: &ProductId, quantity: u32) -> bool {
+        self.has_product(product_id) && self.has_sufficient_quantity(product_id, quantity)
+    }
+
     /// Reduces the inventory of a product by the specified quantity
     /// Returns true if successful, false if insufficient inventory
     pub fn reduce_inventory(&mut self, product_id: &ProductId, quantity: u32) -> bool {
@@ -92,6 +231,47 @@ impl Store {
         *self.inventory.get(product_id).unwrap_or(&0)
     }
 
+    /// Number of distinct products the store sells
+    pub fn product_count(&self) -> usize {
+        self.products.len()
+    }
+
+    /// Total units in stock across all products
+    pub fn total_inventory_units(&self) -> u32 {
+        self.inventory.values().sum()
+    }
+
+    /// Value of the stock at the store's prices; units without a known price are skipped
+    pub fn total_inventory_value(&self) -> f64 {
+        self.inventory
+            .iter()
+            .filter_map(|(product_id, &quantity)| {
+                self.get_product_cost(product_id)
+                    .map(|cost| cost * quantity as f64)
+            })
+            .sum()
+    }
+
+    /// Products with at least one unit in stock, with their inventory level
+    pub fn products_in_stock(&self) -> impl Iterator<Item = (&ProductId, u32)> {
+        self.inventory
+            .iter()
+            .filter(|(_, &quantity)| quantity > 0)
+            .map(|(product_id, &quantity)| (product_id, quantity))
+    }
+
+    /// Changes the pre-tax cost of a product, e.g. for a discount or surge pricing
+    /// Returns false, changing nothing, if the store doesn't sell the product
+    pub fn set_product_cost(&mut self, product_id: ProductId, cost: f64) -> bool {
+        match self.products.get_mut(&product_id) {
+            Some(product) => {
+                product.cost = cost;
+                true
+            }
+            None => false,
+        }
+    }
+
     /// Restocks a product to the specified quantity
     pub fn restock(&mut self, product_id: &ProductId, quantity: u32) {
         if self.has_product(product_id) {
@@ -133,6 +313,20 @@ mod tests {
         assert!(!store.has_sufficient_quantity(&3, 1));
     }
 
+    #[test]
+    fn test_can_supply() {
+        let mut store = create_test_store();
+        assert!(store.can_supply(&1, 1));
+        assert!(store.can_supply(&2, 3));
+        assert!(!store.can_supply(&2, 4));
+        assert!(!store.can_supply(&3, 1));
+
+        // Listed but out of stock
+        store.reduce_inventory(&2, 3);
+        assert!(store.has_product(&2));
+        assert!(!store.can_supply(&2, 1));
+    }
+
     #[test]
     fn test_reduce_inventory() {
         let mut store = create_test_store();
@@ -169,4 +363,95 @@ mod tests {
         store.restock(&3, 10);
         assert_eq!(store.get_inventory_level(&3), 0);
     }
+
+    #[test]
+    fn test_set_product_cost() {
+        let mut store = create_test_store();
+
+        assert!(store.set_product_cost(1, 7.5));
+        assert_eq!(store.get_product_cost(&1), Some(7.5));
+        assert_eq!(store.get_product_cost(&2), Some(20.0));
+
+        // Products the store doesn't carry can't be priced
+        assert!(!store.set_product_cost(3, 1.0));
+        assert!(!store.has_product(&3));
+    }
+
+    #[test]
+    fn test_from_product_entries_merges_duplicates() {
+        let entries = vec![
+            (1, Product::new("Product A", 10.0), 5),
+            (2, Product::new("Product B", 20.0), 3),
+            (1, Product::new("Product A", 8.0), 4),
+        ];
+
+        let (store, duplicates) = Store::from_product_entries(1, Location::new(0.0, 0.0), entries);
+
+        assert_eq!(duplicates, vec![1]);
+        assert_eq!(store.products.len(), 2);
+        // Quantities are summed and the lower price is kept
+        assert_eq!(store.get_inventory_level(&1), 9);
+        assert_eq!(store.get_product_cost(&1), Some(8.0));
+        assert_eq!(store.get_inventory_level(&2), 3);
+        assert_eq!(store.get_product_cost(&2), Some(20.0));
+    }
+
+    #[test]
+    fn test_opening_hours() {
+        let always_open = create_test_store();
+        assert!(always_open.is_open_at(0.0));
+        assert!(always_open.is_open_at(1439.0));
+        assert_eq!(always_open.minutes_until_open(300.0), 0.0);
+
+        // 09:00 - 17:00
+        let day_store = create_test_store().with_hours(540.0, 1020.0);
+        assert!(!day_store.is_open_at(480.0));
+        assert!(day_store.is_open_at(540.0));
+        assert!(day_store.is_open_at(1019.0));
+        assert!(!day_store.is_open_at(1020.0));
+        assert!(day_store.is_open_at(540.0 + 1440.0)); // 09:00 the next day
+        assert_eq!(day_store.minutes_until_open(480.0), 60.0);
+        assert_eq!(day_store.minutes_until_open(1080.0), 900.0);
+
+        // 22:00 - 02:00
+        let night_store = create_test_store().with_hours(1320.0, 120.0);
+        assert!(night_store.is_open_at(1380.0));
+        assert!(night_store.is_open_at(60.0));
+        assert!(!night_store.is_open_at(120.0));
+        assert!(!night_store.is_open_at(720.0));
+        assert_eq!(night_store.minutes_until_open(720.0), 600.0);
+    }
+
+    #[test]
+    fn test_store_categories() {
+        let store = create_test_store();
+        assert!(!store.has_category("organic"));
+
+        let store = store.with_category("organic").with_category("bakery");
+        assert!(store.has_category("organic"));
+        assert!(store.has_category("bakery"));
+        assert!(!store.has_category("Organic"));
+    }
+
+    #[test]
+    fn test_inventory_summary() {
+        let mut store = create_test_store();
+        assert_eq!(store.product_count(), 2);
+        assert_eq!(store.total_inventory_units(), 8);
+        assert_eq!(store.total_inventory_value(), 5.0 * 10.0 + 3.0 * 20.0);
+
+        let mut in_stock: Vec<(ProductId, u32)> = store
+            .products_in_stock()
+            .map(|(product_id, quantity)| (*product_id, quantity))
+            .collect();
+        in_stock.sort();
+        assert_eq!(in_stock, vec![(1, 5), (2, 3)]);
+
+        // Sold-out products are still counted but no longer in stock
+        store.reduce_inventory(&2, 3);
+        assert_eq!(store.product_count(), 2);
+        assert_eq!(store.total_inventory_units(), 5);
+        assert_eq!(store.total_inventory_value(), 50.0);
+        assert_eq!(store.products_in_stock().collect::<Vec<_>>(), vec![(&1, 5)]);
+    }
 }
@@ -0,0 +1,155 @@
+// Structured performance measurements of a solver run
+
+use crate::models::{Cost, Time};
+use crate::utils::skyline::Skyline;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Measurements of one skyline search, for aggregating benchmarks across runs
+///
+/// Run times are in seconds; route times and costs are in the solver's units.
+/// The fastest and cheapest fields are None when no route was found.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BenchmarkResult {
+    /// Number of distinct products on the shopping list
+    pub product_count: usize,
+
+    /// Skyline update threshold the search ran with
+    pub threshold: i32,
+
+    /// Wall-clock time of the whole search
+    pub total_time: f64,
+
+    /// Time spent finding the minimum-time and minimum-cost routes before the search
+    pub best_route_time: f64,
+
+    /// Number of routes in the skyline
+    pub route_count: usize,
+
+    pub fastest_time: Option<Time>,
+    pub fastest_cost: Option<Cost>,
+    pub cheapest_time: Option<Time>,
+    pub cheapest_cost: Option<Cost>,
+}
+
+impl BenchmarkResult {
+    /// Column names matching `to_csv_row`
+    pub const CSV_HEADER: &'static str = "product_count,threshold,total_time,best_route_time,\
+        route_count,fastest_time,fastest_cost,cheapest_time,cheapest_cost";
+
+    /// Measures a finished search from its skyline and timings
+    pub fn new(
+        product_count: usize,
+        threshold: i32,
+        total_time: Duration,
+        best_route_time: Duration,
+        skyline: &Skyline,
+    ) -> Self {
+        let fastest = skyline.fastest();
+        let cheapest = skyline.cheapest();
+        Self {
+            product_count,
+            threshold,
+            total_time: total_time.as_secs_f64(),
+            best_route_time: best_route_time.as_secs_f64(),
+            route_count: skyline.len(),
+            fastest_time: fastest.map(|route| route.shopping_time),
+            fastest_cost: fastest.map(|route| route.shopping_cost),
+            cheapest_time: cheapest.map(|route| route.shopping_time),
+            cheapest_cost: cheapest.map(|route| route.shopping_cost),
+        }
+    }
+
+    /// Time of the search after the best routes were found
+    pub fn search_time(&self) -> f64 {
+        self.total_time - self.best_route_time
+    }
+
+    /// Route time saved per extra unit of cost by taking the fastest rather than the
+    /// cheapest route; 0 when they cost the same or no route was found
+    pub fn trade_off_efficiency(&self) -> f64 {
+        match (
+            self.fastest_time,
+            self.fastest_cost,
+            self.cheapest_time,
+            self.cheapest_cost,
+        ) {
+            (Some(fastest_time), Some(fastest_cost), Some(cheapest_time), Some(cheapest_cost))
+                if fastest_cost > cheapest_cost =>
+            {
+                (cheapest_time - fastest_time) / (fastest_cost - cheapest_cost)
+            }
+            _ => 0.0,
+        }
+    }
+
+    /// The result as a line of comma-separated values, in `CSV_HEADER` order
+    /// Missing routes leave their fields empty
+    pub fn to_csv_row(&self) -> String {
+        let optional = |value: Option<f64>| value.map_or(String::new(), |v| v.to_string());
+        format!(
+            "{},{},{},{},{},{},{},{},{}",
+            self.product_count,
+            self.threshold,
+            self.total_time,
+            self.best_route_time,
+            self.route_count,
+            optional(self.fastest_time),
+            optional(self.fastest_cost),
+            optional(self.cheapest_time),
+            optional(self.cheapest_cost)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::ShoppingRoute;
+
+    #[test]
+    fn test_benchmark_result_round_trip() {
+        let mut skyline = Skyline::new();
+        skyline.insert(ShoppingRoute::new(vec![1], 10.5, 40.0));
+        skyline.insert(ShoppingRoute::new(vec![2, 3], 14.5, 32.0));
+        let result = BenchmarkResult::new(
+            5,
+            1000,
+            Duration::from_millis(1500),
+            Duration::from_millis(250),
+            &skyline,
+        );
+
+        assert_eq!(result.route_count, 2);
+        assert_eq!(result.fastest_time, Some(10.5));
+        assert_eq!(result.cheapest_cost, Some(32.0));
+        assert!((result.search_time() - 1.25).abs() < 1e-12);
+        assert!((result.trade_off_efficiency() - 0.5).abs() < 1e-12);
+
+        let json = serde_json::to_string(&result).unwrap();
+        assert_eq!(
+            serde_json::from_str::<BenchmarkResult>(&json).unwrap(),
+            result
+        );
+
+        assert_eq!(result.to_csv_row(), "5,1000,1.5,0.25,2,10.5,40,14.5,32");
+        assert_eq!(
+            BenchmarkResult::CSV_HEADER.split(',').count(),
+            result.to_csv_row().split(',').count()
+        );
+    }
+
+    #[test]
+    fn test_benchmark_result_without_routes() {
+        let result = BenchmarkResult::new(3, 10, Duration::ZERO, Duration::ZERO, &Skyline::new());
+        assert_eq!(result.fastest_time, None);
+        assert_eq!(result.trade_off_efficiency(), 0.0);
+        assert_eq!(result.to_csv_row(), "3,10,0,0,0,,,,");
+
+        let json = serde_json::to_string(&result).unwrap();
+        assert_eq!(
+            serde_json::from_str::<BenchmarkResult>(&json).unwrap(),
+            result
+        );
+    }
+}
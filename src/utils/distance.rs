@@ -4,12 +4,12 @@ use crate::models::Location;
 
 /// Calculate the Euclidean distance between two points
 pub fn euclidean_distance(p1: &Location, p2: &Location) -> f64 {
-    ((p1.x - p2.x).powi(2) + (p1.y - p2.y).powi(2)).sqrt()
+    p1.distance_to(p2)
 }
 
 /// Calculate the Manhattan distance between two points
 pub fn manhattan_distance(p1: &Location, p2: &Location) -> f64 {
-    (p1.x - p2.x).abs() + (p1.y - p2.y).abs()
+    p1.manhattan_distance_to(p2)
 }
 
 #[cfg(test)]
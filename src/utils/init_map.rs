@@ -1,27 +1,309 @@
-use rayon::prelude::*;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::collections::HashMap as StdHashMap;
+use std::fmt;
 use std::fs;
 use std::fs::File;
-use std::io::{self, BufRead};
+use std::io::{self, BufRead, Read};
 use std::path::Path;
-use std::sync::{Arc, Mutex};
 
-use crate::models::Location;
+use crate::models::{DistanceMetric, Location, ProductId};
 use crate::{Product, Store};
 
-use super::road_network::RoadGraph;
+use super::road_network::{EdgeCost, EdgeDirection, RoadEdge, RoadGraph, RoutingObjective};
 
 // Assuming StoreId is u32 type
 pub type StoreId = u32;
 
+// Raw restaurant record: (id, longitude, latitude, edge id, distance along edge)
+type RestaurantRecord = (u64, f64, f64, u64, f64);
+
+/// Errors raised while loading a city's map data
+/// Line numbers are 1-based and refer to the data file named by the variant
+#[derive(Debug)]
+pub enum MapLoadError {
+    /// A data file could not be read
+    Io(io::Error),
+
+    /// A malformed line in the road vertex file
+    ParseVertex { line: usize },
+
+    /// A malformed line in the road edge file
+    ParseEdge { line: usize },
+
+    /// A malformed line in the restaurant file
+    ParseRestaurant { line: usize },
+
+    /// A data file contained no records
+    EmptyDataset,
+
+    /// The store catalog is not a valid JSON array of stores
+    ParseStoreJson(serde_json::Error),
+
+    /// The store catalog lists the same store ID twice
+    DuplicateStore { store: StoreId },
+
+    /// A store in the catalog lists the same product ID twice
+    DuplicateProduct { store: StoreId, product: ProductId },
+
+    /// The same product ID is given different names by different stores
+    InconsistentProduct {
+        product: ProductId,
+        names: (String, String),
+    },
+
+    /// The store generator settings can't produce stores (see `StoreGenConfig::validate`)
+    InvalidStoreGenConfig { reason: &'static str },
+}
+
+impl fmt::Display for MapLoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MapLoadError::Io(e) => write!(f, "failed to read map data: {}", e),
+            MapLoadError::ParseVertex { line } => {
+                write!(f, "malformed road vertex on line {}", line)
+            }
+            MapLoadError::ParseEdge { line } => write!(f, "malformed road edge on line {}", line),
+            MapLoadError::ParseRestaurant { line } => {
+                write!(f, "malformed restaurant on line {}", line)
+            }
+            MapLoadError::EmptyDataset => write!(f, "map data file contains no records"),
+            MapLoadError::ParseStoreJson(e) => write!(f, "malformed store catalog: {}", e),
+            MapLoadError::DuplicateStore { store } => {
+                write!(f, "store {} is listed more than once", store)
+            }
+            MapLoadError::DuplicateProduct { store, product } => {
+                write!(
+                    f,
+                    "store {} lists product {} more than once",
+                    store, product
+                )
+            }
+            MapLoadError::InconsistentProduct { product, names } => write!(
+                f,
+                "product {} is named both {:?} and {:?}",
+                product, names.0, names.1
+            ),
+            MapLoadError::InvalidStoreGenConfig { reason } => {
+                write!(f, "invalid store generator config: {}", reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for MapLoadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            MapLoadError::Io(e) => Some(e),
+            MapLoadError::ParseStoreJson(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for MapLoadError {
+    fn from(e: io::Error) -> Self {
+        MapLoadError::Io(e)
+    }
+}
+
+/// Product range, stock and pricing of one synthetic store type
+#[derive(Debug, Clone, PartialEq)]
+pub struct StoreTypeParams {
+    /// Fewest and most product types the store sells, both inclusive
+    pub product_range: (usize, usize),
+
+    /// Chance that the store sells a given product
+    /// Only used without a seed: seeded stores draw their product count from
+    /// `product_range` and then pick that many products uniformly at random.
+    pub product_preference: f64,
+
+    /// Multiplier applied to every product's base price
+    pub price_factor: f64,
+
+    /// Multiplier applied to every product's base stock
+    pub inventory_factor: u32,
+}
+
+impl StoreTypeParams {
+    pub fn new(
+        product_range: (usize, usize),
+        product_preference: f64,
+        price_factor: f64,
+        inventory_factor: u32,
+    ) -> Self {
+        Self {
+            product_range,
+            product_preference,
+            price_factor,
+            inventory_factor,
+        }
+    }
+}
+
+/// Parameters of the synthetic store generator used by the `init_map_*` loaders
+///
+/// The default reproduces the original data set: 60% small stores (the first half of
+/// them convenience stores with higher prices), 30% medium and 10% large stores.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StoreGenConfig {
+    /// Product IDs run from 1 to this value
+    pub total_product_type: u32,
+
+    /// Seed for random store contents; `None` uses fixed formulas of the store ID
+    pub seed: Option<u64>,
+
+    /// Store types, drawn with equal chance; repeat an entry to make it more common
+    pub store_types: Vec<StoreTypeParams>,
+
+    /// Every store sells at least this many products (all of them when fewer exist),
+    /// whatever its type's product range
+    pub min_products: usize,
+
+    /// Base price of product `p` is `base_price + p % price_cycle`
+    pub base_price: f64,
+    pub price_cycle: u32,
+
+    /// Prices vary by up to this many percent either way
+    pub price_variation_percent: u32,
+
+    /// Prices never drop below this value
+    pub min_price: f64,
+
+    /// Fewest and most units of a product in stock before the store type's multiplier
+    pub base_inventory: (u32, u32),
+
+    /// Stock of every product when inventory is unlimited
+    pub unlimited_inventory: u32,
+}
+
+impl Default for StoreGenConfig {
+    fn default() -> Self {
+        let convenience = StoreTypeParams::new((3, 8), 0.25, 1.2, 1);
+        let small = StoreTypeParams::new((3, 8), 0.25, 1.0, 1);
+        let medium = StoreTypeParams::new((8, 15), 0.35, 0.9, 2);
+        let large = StoreTypeParams::new((15, 25), 0.60, 0.8, 3);
+
+        let mut store_types = vec![convenience; 3];
+        store_types.extend(vec![small; 3]);
+        store_types.extend(vec![medium; 3]);
+        store_types.push(large);
+
+        Self {
+            total_product_type: 10,
+            seed: None,
+            store_types,
+            min_products: 3,
+            base_price: 5.0,
+            price_cycle: 10,
+            price_variation_percent: 10,
+            min_price: 3.0,
+            base_inventory: (3, 7),
+            unlimited_inventory: 1000000,
+        }
+    }
+}
+
+impl StoreGenConfig {
+    /// Default generator parameters for the given number of product types
+    pub fn new(total_product_type: u32) -> Self {
+        Self {
+            total_product_type,
+            ..Self::default()
+        }
+    }
+
+    /// Draws store contents from a random generator seeded with `seed`
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Checks that the settings can generate stores: at least one store type, ranges
+    /// whose minimum doesn't exceed their maximum and a nonzero price cycle
+    /// A zero price variation is valid and gives every store type fixed prices.
+    pub fn validate(&self) -> Result<(), MapLoadError> {
+        let reason = if self.store_types.is_empty() {
+            Some("no store types")
+        } else if self
+            .store_types
+            .iter()
+            .any(|store_type| store_type.product_range.0 > store_type.product_range.1)
+        {
+            Some("a store type's product range has its minimum above its maximum")
+        } else if self.base_inventory.0 > self.base_inventory.1 {
+            Some("the base inventory has its minimum above its maximum")
+        } else if self.price_cycle == 0 {
+            Some("the price cycle is zero")
+        } else {
+            None
+        };
+        match reason {
+            Some(reason) => Err(MapLoadError::InvalidStoreGenConfig { reason }),
+            None => Ok(()),
+        }
+    }
+}
+
+/// How the road network is measured and its travel times cached by the `init_map_*` and
+/// `precompute_travel_times_*` loaders
+/// Configure the solver with the same metric and objective so endpoint legs match the
+/// travel times (see `BSLPSD::set_distance_metric` and `BSLPSD::set_routing_objective`).
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct RoadOptions {
+    /// How road lengths and off-road legs are measured; Haversine for the lon/lat data
+    pub metric: DistanceMetric,
+
+    /// Whether travel times are shortest road distances or fastest times in minutes
+    pub objective: RoutingObjective,
+
+    /// Format the travel time cache is kept in
+    pub cache_format: CacheFormat,
+}
+
+/// Settings for `init_map_with_options`
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct MapOptions {
+    /// How store products, prices and stock are generated
+    pub store_gen: StoreGenConfig,
+
+    /// How travel times between the stores are computed
+    pub road: RoadOptions,
+}
+
+impl MapOptions {
+    /// Default settings for the given number of product types
+    pub fn new(total_product_type: u32) -> Self {
+        Self {
+            store_gen: StoreGenConfig::new(total_product_type),
+            road: RoadOptions::default(),
+        }
+    }
+}
+
+// Stores together with the pre-computed travel times between them
+pub type MapData = (HashMap<StoreId, Store>, HashMap<(StoreId, StoreId), f64>);
+
 // Extended init_map function that returns road network data and pre-computed travel times
+// Uses the default store generator and great-circle road lengths
 pub fn init_map_with_road_network(
     city_code: &str,
     infinity: bool,
     total_product_type: u32,
-) -> Result<(HashMap<StoreId, Store>, HashMap<(StoreId, StoreId), f64>), io::Error> {
+) -> Result<MapData, MapLoadError> {
+    init_map_with_options(city_code, infinity, &MapOptions::new(total_product_type))
+}
+
+/// Loads a city and pre-computes its travel times with the given store generator and
+/// road settings
+pub fn init_map_with_options(
+    city_code: &str,
+    infinity: bool,
+    options: &MapOptions,
+) -> Result<MapData, MapLoadError> {
     println!("Initializing map data for city {}...", city_code);
 
     // Load road vertex data
@@ -38,348 +320,1472 @@ pub fn init_map_with_road_network(
 
     // Convert restaurants to stores
     let stores =
-        convert_restaurants_to_stores(restaurants, &vertices, infinity, total_product_type);
+        convert_restaurants_to_stores(restaurants, &vertices, infinity, &options.store_gen)?;
     println!("Converted restaurant data to {} stores", stores.len());
 
-    // Use road network to pre-compute travel times
+    let travel_times = road_travel_times(
+        city_code,
+        city_code,
+        &stores,
+        vertices,
+        edges,
+        &options.road,
+    );
+    Ok((stores, travel_times))
+}
+
+/// Loads stores from a JSON catalog instead of generating them, and pre-computes their
+/// travel times on the city's road network
+///
+/// The catalog is an array of `{"id", "lon", "lat", "products": [{"id", "name", "cost",
+/// "inventory", "tax_rate"}]}` objects, where `tax_rate` is optional. Store IDs must be
+/// unique, no store may list a product twice and a product ID must have the same name in
+/// every store.
+pub fn init_map_from_store_json<P: AsRef<Path>>(
+    stores_path: P,
+    city_code: &str,
+) -> Result<MapData, MapLoadError> {
+    println!(
+        "Initializing map data for city {} from {}...",
+        city_code,
+        stores_path.as_ref().display()
+    );
+
+    let vertices = load_road_vertices(city_code)?;
+    println!("Loaded {} road vertices", vertices.len());
+
+    let edges = load_road_edges(city_code)?;
+    println!("Loaded {} roads", edges.len());
+
+    let stores = parse_store_json(io::BufReader::new(File::open(stores_path)?))?;
+    println!("Loaded {} stores from the catalog", stores.len());
+
+    // Catalog stores get their own cache file so they never evict the synthetic stores' one
+    let travel_times = road_travel_times(
+        city_code,
+        &format!("{}_catalog", city_code),
+        &stores,
+        vertices,
+        edges,
+        &RoadOptions::default(),
+    );
+    Ok((stores, travel_times))
+}
+
+// Pre-computes travel times between the stores on the road network, cached under `cache_name`
+fn road_travel_times(
+    city_code: &str,
+    cache_name: &str,
+    stores: &HashMap<StoreId, Store>,
+    vertices: HashMap<u64, (f64, f64)>,
+    edges: HashMap<u64, RoadEdge>,
+    road: &RoadOptions,
+) -> HashMap<(StoreId, StoreId), f64> {
     println!("Calculating travel times between stores based on road network...");
-    let travel_times = precompute_travel_times_with_road_network(&stores, &vertices, &edges);
+    let directed = edges
+        .values()
+        .any(|edge| edge.direction != EdgeDirection::TwoWay);
+    let cache_path = travel_times_cache_path(cache_name, road, directed);
+    let (travel_times, _) = precompute_travel_times_cached(
+        city_code,
+        stores,
+        &cache_path,
+        road.cache_format,
+        road.objective,
+        || RoadGraph::from_road_edges(vertices, edges, road.metric),
+    );
     println!(
         "Calculated travel times for {} store pairs",
         travel_times.len()
     );
+    travel_times
+}
 
-    Ok((stores, travel_times))
+// One store of a JSON store catalog
+#[derive(Deserialize)]
+struct StoreRecord {
+    id: StoreId,
+    lon: f64,
+    lat: f64,
+    #[serde(default)]
+    products: Vec<ProductRecord>,
+}
+
+// One product line of a catalog store
+#[derive(Deserialize)]
+struct ProductRecord {
+    id: ProductId,
+    name: String,
+    cost: f64,
+    inventory: u32,
+    #[serde(default)]
+    tax_rate: f64,
+}
+
+// Parse a JSON store catalog, rejecting repeated stores or products and products whose
+// name differs between stores
+fn parse_store_json<R: Read>(reader: R) -> Result<HashMap<StoreId, Store>, MapLoadError> {
+    let records: Vec<StoreRecord> =
+        serde_json::from_reader(reader).map_err(MapLoadError::ParseStoreJson)?;
+    if records.is_empty() {
+        return Err(MapLoadError::EmptyDataset);
+    }
+
+    let mut stores = HashMap::new();
+    let mut product_names: HashMap<ProductId, String> = HashMap::new();
+
+    for record in records {
+        for product in &record.products {
+            match product_names.get(&product.id) {
+                Some(name) if *name != product.name => {
+                    return Err(MapLoadError::InconsistentProduct {
+                        product: product.id,
+                        names: (name.clone(), product.name.clone()),
+                    });
+                }
+                Some(_) => {}
+                None => {
+                    product_names.insert(product.id, product.name.clone());
+                }
+            }
+        }
+
+        let entries = record.products.into_iter().map(|product| {
+            (
+                product.id,
+                Product::new(product.name, product.cost).with_tax_rate(product.tax_rate),
+                product.inventory,
+            )
+        });
+        let (store, duplicates) =
+            Store::from_product_entries(record.id, Location::new(record.lon, record.lat), entries);
+        if let Some(&product) = duplicates.first() {
+            return Err(MapLoadError::DuplicateProduct {
+                store: record.id,
+                product,
+            });
+        }
+        if stores.insert(record.id, store).is_some() {
+            return Err(MapLoadError::DuplicateStore { store: record.id });
+        }
+    }
+
+    Ok(stores)
 }
 
 // Load road vertex data
-fn load_road_vertices(city_code: &str) -> Result<HashMap<u64, (f64, f64)>, io::Error> {
-    let file_name = format!("data/RoadVertices{}.txt", city_code);
-    let path = Path::new(&file_name);
-    let file = File::open(path)?;
-    let reader = io::BufReader::new(file);
+fn load_road_vertices(city_code: &str) -> Result<HashMap<u64, (f64, f64)>, MapLoadError> {
+    let file = File::open(format!("data/RoadVertices{}.txt", city_code))?;
+    parse_road_vertices(io::BufReader::new(file))
+}
 
+// Parse "id longitude latitude" lines
+fn parse_road_vertices<R: BufRead>(reader: R) -> Result<HashMap<u64, (f64, f64)>, MapLoadError> {
     let mut vertices = HashMap::new();
 
-    for line in reader.lines() {
+    for (index, line) in reader.lines().enumerate() {
         let line = line?;
         let parts: Vec<&str> = line.split_whitespace().collect();
-
-        if parts.len() >= 3 {
-            let id = parts[0].parse::<u64>().unwrap_or(0);
-            let longitude = parts[1].parse::<f64>().unwrap_or(0.0);
-            let latitude = parts[2].parse::<f64>().unwrap_or(0.0);
-
-            vertices.insert(id, (longitude, latitude));
+        if parts.is_empty() {
+            continue;
         }
+
+        let parsed = (|| {
+            Some((
+                parts.first()?.parse::<u64>().ok()?,
+                parts.get(1)?.parse::<f64>().ok()?,
+                parts.get(2)?.parse::<f64>().ok()?,
+            ))
+        })();
+        let (id, longitude, latitude) =
+            parsed.ok_or(MapLoadError::ParseVertex { line: index + 1 })?;
+
+        vertices.insert(id, (longitude, latitude));
     }
 
+    if vertices.is_empty() {
+        return Err(MapLoadError::EmptyDataset);
+    }
     Ok(vertices)
 }
 
 // Load road edge data
-fn load_road_edges(city_code: &str) -> Result<HashMap<u64, (u64, u64)>, io::Error> {
-    let file_name = format!("data/RoadEdges{}.txt", city_code);
-    let path = Path::new(&file_name);
-    let file = File::open(path)?;
-    let reader = io::BufReader::new(file);
+fn load_road_edges(city_code: &str) -> Result<HashMap<u64, RoadEdge>, MapLoadError> {
+    let file = File::open(format!("data/RoadEdges{}.txt", city_code))?;
+    parse_road_edges(io::BufReader::new(file))
+}
 
+// Parse "id start_vertex end_vertex [direction [speed]]" lines
+// Direction is 0 = two-way, 1 = forward only, 2 = backward only; two-way when absent
+// Speed is in km/h and must be positive; the graph's default speed is used when absent
+fn parse_road_edges<R: BufRead>(reader: R) -> Result<HashMap<u64, RoadEdge>, MapLoadError> {
     let mut edges = HashMap::new();
 
-    for line in reader.lines() {
+    for (index, line) in reader.lines().enumerate() {
         let line = line?;
         let parts: Vec<&str> = line.split_whitespace().collect();
-
-        if parts.len() >= 3 {
-            let id = parts[0].parse::<u64>().unwrap_or(0);
-            let start_id = parts[1].parse::<u64>().unwrap_or(0);
-            let end_id = parts[2].parse::<u64>().unwrap_or(0);
-
-            edges.insert(id, (start_id, end_id));
+        if parts.is_empty() {
+            continue;
         }
+
+        let parsed = (|| {
+            let direction = match parts.get(3).map(|d| d.parse::<u8>()) {
+                None | Some(Ok(0)) => EdgeDirection::TwoWay,
+                Some(Ok(1)) => EdgeDirection::Forward,
+                Some(Ok(2)) => EdgeDirection::Backward,
+                Some(_) => return None,
+            };
+            let speed_kmh = match parts.get(4) {
+                Some(speed) => Some(speed.parse::<f64>().ok().filter(|&v| v > 0.0)?),
+                None => None,
+            };
+            Some((
+                parts.first()?.parse::<u64>().ok()?,
+                RoadEdge {
+                    start: parts.get(1)?.parse::<u64>().ok()?,
+                    end: parts.get(2)?.parse::<u64>().ok()?,
+                    direction,
+                    speed_kmh,
+                },
+            ))
+        })();
+        let (id, edge) = parsed.ok_or(MapLoadError::ParseEdge { line: index + 1 })?;
+
+        edges.insert(id, edge);
     }
 
+    if edges.is_empty() {
+        return Err(MapLoadError::EmptyDataset);
+    }
     Ok(edges)
 }
 
 // Load restaurant data
-fn load_restaurants(city_code: &str) -> Result<Vec<(u64, f64, f64, u64, f64)>, io::Error> {
-    let file_name = format!("data/Restaurants{}.txt", city_code);
-    let path = Path::new(&file_name);
-    let file = File::open(path)?;
-    let reader = io::BufReader::new(file);
+fn load_restaurants(city_code: &str) -> Result<Vec<RestaurantRecord>, MapLoadError> {
+    let file = File::open(format!("data/Restaurants{}.txt", city_code))?;
+    parse_restaurants(io::BufReader::new(file))
+}
 
+// Parse "id longitude latitude edge_id distance" lines
+// The edge id is not checked against the edge file: the shipped AMS and OSLO data
+// reference edges that are missing from it, and stores are placed by coordinates
+fn parse_restaurants<R: BufRead>(reader: R) -> Result<Vec<RestaurantRecord>, MapLoadError> {
     let mut restaurants = Vec::new();
 
-    for line in reader.lines() {
+    for (index, line) in reader.lines().enumerate() {
         let line = line?;
         let parts: Vec<&str> = line.split_whitespace().collect();
-
-        if parts.len() >= 5 {
-            let id = parts[0].parse::<u64>().unwrap_or(0);
-            let longitude = parts[1].parse::<f64>().unwrap_or(0.0);
-            let latitude = parts[2].parse::<f64>().unwrap_or(0.0);
-            let edge_id = parts[3].parse::<u64>().unwrap_or(0);
-            let distance = parts[4].parse::<f64>().unwrap_or(0.0);
-
-            restaurants.push((id, longitude, latitude, edge_id, distance));
+        if parts.is_empty() {
+            continue;
         }
+
+        let parsed = (|| {
+            Some((
+                parts.first()?.parse::<u64>().ok()?,
+                parts.get(1)?.parse::<f64>().ok()?,
+                parts.get(2)?.parse::<f64>().ok()?,
+                parts.get(3)?.parse::<u64>().ok()?,
+                parts.get(4)?.parse::<f64>().ok()?,
+            ))
+        })();
+        let record = parsed.ok_or(MapLoadError::ParseRestaurant { line: index + 1 })?;
+
+        restaurants.push(record);
     }
 
+    if restaurants.is_empty() {
+        return Err(MapLoadError::EmptyDataset);
+    }
     Ok(restaurants)
 }
 
 // Convert restaurant data to stores
 fn convert_restaurants_to_stores(
-    restaurants: Vec<(u64, f64, f64, u64, f64)>,
+    restaurants: Vec<RestaurantRecord>,
     _vertices: &HashMap<u64, (f64, f64)>,
     infinity: bool,
-    total_product_type: u32,
-) -> HashMap<StoreId, Store> {
+    config: &StoreGenConfig,
+) -> Result<HashMap<StoreId, Store>, MapLoadError> {
+    config.validate()?;
     let mut stores = HashMap::new();
+    let mut rng = config.seed.map(StdRng::seed_from_u64);
 
-    for (i, (_rest_id, longitude, latitude, _, _)) in restaurants.iter().enumerate() {
-        // Convert u64 ID to u32 StoreId (ensuring it doesn't exceed u32 range)
-        let store_id = i;
+    for (store_id, (rest_id, longitude, latitude, _, _)) in restaurants.iter().enumerate() {
+        // Store IDs are the record index (the travel time cache is keyed by them);
+        // the restaurant id is kept on the store as its external id, see
+        // `store_ids_by_external_id`
         // println!("store id: {:?}", store_id);
 
-        // Create random products and inventory for each restaurant
-        let mut products = HashMap::new();
-        let mut inventory = HashMap::new();
-
-        // Randomly select products for each store in a more realistic way
-        // Randomly assign based on actual store scale and type
-        // Small stores: 3-8 products
-        // Medium stores: 8-15 products
-        // Large stores: 15-25 products
-        // We calculate a store type based on the store ID
-        let store_type = (store_id % 10) as usize;
-        let num_products = if store_type < 6 {
-            // 60% of stores are small stores (3-8)
-            3 + (store_id as usize % 6)
-        } else if store_type < 9 {
-            // 30% of stores are medium stores (8-15)
-            8 + (store_id as usize % 8)
-        } else {
-            // 10% of stores are large stores (15-25)
-            15 + (store_id as usize % 11)
+        let (products, inventory) = match rng.as_mut() {
+            Some(rng) => seeded_store_products(rng, infinity, config),
+            None => formula_store_products(store_id, infinity, config),
         };
 
-        // Ensure product count doesn't exceed total available
-        let final_num_products = std::cmp::min(num_products, total_product_type as usize);
-
-        let mut available_product_ids = Vec::new();
-
-        // Each store has a certain probability of selling specific types of products
-        // Use a pseudo-random function to generate product preferences for each store
-        let product_preference = |product_id: u32| -> bool {
-            // Generate a deterministic pseudo-random value based on store ID and product ID
-            let seed = ((store_id as u32 * 13 + product_id * 17) % 100) as f64 / 100.0;
+        // Create store
+        let store_location = Location::new(*longitude, *latitude);
+        let store = Store::new_with_inventory(store_id as u32, store_location, products, inventory)
+            .with_external_id(*rest_id);
+        stores.insert(store_id as u32, store);
+    }
 
-            // Adjust product probability based on store type
-            let threshold = match store_type {
-                0..=5 => 0.25, // Small stores tend to only sell popular products
-                6..=8 => 0.35, // Medium stores have more diverse products
-                _ => 0.60,     // Large stores sell almost everything
-            };
+    Ok(stores)
+}
 
-            seed < threshold
-        };
+// Generates a store's products and inventory from fixed formulas of the store ID
+// With the default config this is the original data set used by the experiments
+fn formula_store_products(
+    store_id: usize,
+    infinity: bool,
+    config: &StoreGenConfig,
+) -> (HashMap<u32, Product>, HashMap<u32, u32>) {
+    let total_product_type = config.total_product_type;
+
+    // Create random products and inventory for each restaurant
+    let mut products = HashMap::new();
+    let mut inventory = HashMap::new();
+
+    // Randomly select products for each store in a more realistic way
+    // Randomly assign based on actual store scale and type
+    // We calculate a store type based on the store ID
+    let store_type = &config.store_types[store_id % config.store_types.len()];
+    let (min_products, max_products) = store_type.product_range;
+    let num_products = min_products + store_id % (max_products - min_products + 1);
+
+    // Ensure product count doesn't exceed total available
+    let final_num_products = std::cmp::min(num_products, total_product_type as usize);
+
+    let mut available_product_ids = Vec::new();
+
+    // Each store has a certain probability of selling specific types of products
+    // Use a pseudo-random function to generate product preferences for each store
+    let product_preference = |product_id: u32| -> bool {
+        // Generate a deterministic pseudo-random value based on store ID and product ID
+        let seed = ((store_id as u32 * 13 + product_id * 17) % 100) as f64 / 100.0;
+
+        // Larger store types tend to sell more diverse products
+        seed < store_type.product_preference
+    };
+
+    // Iterate through all possible products and select based on preference
+    for product_id in 1..=total_product_type {
+        if product_preference(product_id) && !available_product_ids.contains(&product_id) {
+            available_product_ids.push(product_id);
+            if available_product_ids.len() >= final_num_products {
+                break;
+            }
+        }
+    }
 
-        // Iterate through all possible products and select based on preference
+    // If not enough products were selected based on preference, add some basic products
+    if available_product_ids.len() < config.min_products {
+        // Ensure each store has the minimum number of products
         for product_id in 1..=total_product_type {
-            if product_preference(product_id) && !available_product_ids.contains(&product_id) {
+            if !available_product_ids.contains(&product_id) {
                 available_product_ids.push(product_id);
-                if available_product_ids.len() >= final_num_products {
+                if available_product_ids.len() >= config.min_products {
                     break;
                 }
             }
         }
+    }
 
-        // If not enough products were selected based on preference, add some basic products
-        if available_product_ids.len() < 3 {
-            // Ensure each store has at least 3 products
-            for product_id in 1..=total_product_type {
-                if !available_product_ids.contains(&product_id) {
-                    available_product_ids.push(product_id);
-                    if available_product_ids.len() >= 3 {
-                        break;
-                    }
-                }
-            }
-        }
+    println!(
+        "Store {} has {} products",
+        store_id,
+        available_product_ids.len()
+    );
 
-        println!(
-            "Store {} has {} products",
-            store_id,
-            available_product_ids.len()
-        );
+    // Create products and inventory for the selected product IDs
+    let variation = config.price_variation_percent;
+    let (min_inventory, max_inventory) = config.base_inventory;
+    for &product_id in &available_product_ids {
+        let product_name = product_name(product_id);
 
-        // Create products and inventory for the selected product IDs
-        for &product_id in &available_product_ids {
-            // Dynamically generate product names
-            let product_name = if product_id <= 26 {
-                (('A' as u8 + (product_id - 1) as u8) as char).to_string()
-            } else {
-                format!("Product{}", product_id)
-            };
+        // Product pricing logic - more realistic
+        let base_price = config.base_price + (product_id % config.price_cycle) as f64;
 
-            // Product pricing logic - more realistic
-            // Base price between 5-15
-            let base_price = 5.0 + (product_id % 10) as f64;
+        // Add some random variation
+        let price_variation = if variation == 0 {
+            0.0
+        } else {
+            (((store_id as u32 + product_id) % (2 * variation)) as f64 - variation as f64) / 100.0
+        };
 
-            // Adjust price based on store type
-            let price_factor = match store_type {
-                0..=2 => 1.2, // Small convenience stores have higher prices
-                3..=5 => 1.0, // Regular small stores have normal prices
-                6..=8 => 0.9, // Medium stores offer some discount
-                _ => 0.8,     // Large stores have lower prices
-            };
+        // Final price
+        let product_cost =
+            (base_price * store_type.price_factor * (1.0 + price_variation)).max(config.min_price);
+        products.insert(product_id, Product::new(&product_name, product_cost));
 
-            // Add some random variation
-            let price_variation = (((store_id as u32 + product_id) % 20) as f64 - 10.0) / 100.0;
+        // Inventory logic - more realistic
+        if !infinity {
+            // Base inventory amount, adjusted by store type
+            let base_inventory = min_inventory + product_id % (max_inventory - min_inventory + 1);
+            inventory.insert(product_id, base_inventory * store_type.inventory_factor);
+        } else {
+            inventory.insert(product_id, config.unlimited_inventory);
+        }
+    }
 
-            // Final price
-            let product_cost = (base_price * price_factor * (1.0 + price_variation)).max(3.0);
-            products.insert(product_id, Product::new(&product_name, product_cost));
+    (products, inventory)
+}
 
-            // Inventory logic - more realistic
-            if !infinity {
-                // Base inventory amount
-                let base_inventory = 3 + (product_id % 5) as u32;
+// Generates a store's products and inventory from a seeded random generator, following
+// the same store types, price and stock levels as `formula_store_products`
+fn seeded_store_products(
+    rng: &mut StdRng,
+    infinity: bool,
+    config: &StoreGenConfig,
+) -> (HashMap<u32, Product>, HashMap<u32, u32>) {
+    let mut products = HashMap::new();
+    let mut inventory = HashMap::new();
+
+    let store_type = rng.gen_range(0..config.store_types.len() as u32);
+    let store_type = &config.store_types[store_type as usize];
+    let (min_products, max_products) = store_type.product_range;
+    let num_products = rng
+        .gen_range(min_products..=max_products)
+        .max(config.min_products);
+    let final_num_products = std::cmp::min(num_products, config.total_product_type as usize);
+
+    let mut available_product_ids: Vec<u32> = (1..=config.total_product_type).collect();
+    available_product_ids.shuffle(rng);
+    available_product_ids.truncate(final_num_products);
+    available_product_ids.sort();
+
+    let variation = config.price_variation_percent as f64 / 100.0;
+    let (min_inventory, max_inventory) = config.base_inventory;
+    for product_id in available_product_ids {
+        let base_price = config.base_price + (product_id % config.price_cycle) as f64;
+        let price_variation: f64 = if variation > 0.0 {
+            rng.gen_range(-variation..variation)
+        } else {
+            0.0
+        };
+        let product_cost =
+            (base_price * store_type.price_factor * (1.0 + price_variation)).max(config.min_price);
+        products.insert(
+            product_id,
+            Product::new(product_name(product_id), product_cost),
+        );
 
-                // Adjust inventory based on store type
-                let inventory_factor = match store_type {
-                    0..=5 => 1, // Small stores have less inventory
-                    6..=8 => 2, // Medium stores have moderate inventory
-                    _ => 3,     // Large stores have abundant inventory
-                };
+        let quantity = if infinity {
+            config.unlimited_inventory
+        } else {
+            rng.gen_range(min_inventory..=max_inventory) * store_type.inventory_factor
+        };
+        inventory.insert(product_id, quantity);
+    }
 
-                inventory.insert(product_id, base_inventory * inventory_factor);
-            } else {
-                inventory.insert(product_id, 1000000);
-            }
-        }
+    (products, inventory)
+}
 
-        // Create store
-        let store_location = Location::new(*longitude, *latitude);
-        let store = Store::new_with_inventory(store_id as u32, store_location, products, inventory);
-        stores.insert(store_id as u32, store);
+// Product names are letters for the first 26 products
+fn product_name(product_id: u32) -> String {
+    if product_id <= 26 {
+        ((b'A' + (product_id - 1) as u8) as char).to_string()
+    } else {
+        format!("Product{}", product_id)
     }
+}
 
+/// Maps the source data set ids (e.g. restaurant ids) back to solver store IDs
+/// The other direction is `Store::external_id`. Stores without a source id, such as
+/// catalog or hand-built stores, are left out.
+pub fn store_ids_by_external_id(stores: &HashMap<StoreId, Store>) -> HashMap<u64, StoreId> {
     stores
+        .values()
+        .filter_map(|store| store.external_id.map(|external_id| (external_id, store.id)))
+        .collect()
+}
+
+/// Summary of how a travel-time matrix was produced
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct PrecomputeReport {
+    /// Number of store pairs computed (ordered pairs when the graph has one-way roads)
+    pub total_pairs: usize,
+
+    /// Number of pairs with no road path that fell back to straight-line distance
+    pub fallback_pairs: usize,
+
+    /// Whether the matrix was loaded from the cache; the counts are then the ones
+    /// recorded when it was computed
+    pub from_cache: bool,
+}
+
+impl PrecomputeReport {
+    /// Fraction of store pairs whose travel time is a straight-line approximation
+    pub fn fallback_fraction(&self) -> f64 {
+        if self.total_pairs == 0 {
+            0.0
+        } else {
+            self.fallback_pairs as f64 / self.total_pairs as f64
+        }
+    }
 }
+
+/// Pre-computes travel times between all stores of `city_code` on the road network
+/// Results are cached per city and reused only while the stores are unchanged
 pub fn precompute_travel_times_with_road_network(
+    city_code: &str,
     stores: &HashMap<u32, crate::Store>,
     road_vertices: &HashMap<u64, (f64, f64)>,
     road_edges: &HashMap<u64, (u64, u64)>,
 ) -> HashMap<(u32, u32), f64> {
-    // Define serializable structure for JSON
-    #[derive(Serialize, Deserialize)]
-    struct TravelTimesCache {
-        // Since tuple keys in HashMap can't be directly serialized, we convert keys to strings
-        times: StdHashMap<String, f64>,
+    precompute_travel_times_with_options(
+        city_code,
+        stores,
+        road_vertices,
+        road_edges,
+        &RoadOptions::default(),
+    )
+    .0
+}
+
+/// Same as `precompute_travel_times_with_road_network` with the given road settings, also
+/// returning a report of how many store pairs fell back to straight-line distance
+pub fn precompute_travel_times_with_options(
+    city_code: &str,
+    stores: &HashMap<u32, crate::Store>,
+    road_vertices: &HashMap<u64, (f64, f64)>,
+    road_edges: &HashMap<u64, (u64, u64)>,
+    road: &RoadOptions,
+) -> (HashMap<(u32, u32), f64>, PrecomputeReport) {
+    let cache_path = travel_times_cache_path(city_code, road, false);
+    precompute_travel_times_cached(
+        city_code,
+        stores,
+        &cache_path,
+        road.cache_format,
+        road.objective,
+        || RoadGraph::new_with_metric(road_vertices.clone(), road_edges.clone(), road.metric),
+    )
+}
+
+/// Same as `precompute_travel_times_with_road_network`, with both the distance and the
+/// travel time of every store pair
+/// Distances follow the shortest road and times the fastest; each is cached separately.
+pub fn precompute_edge_costs_with_road_network(
+    city_code: &str,
+    stores: &HashMap<u32, crate::Store>,
+    road_vertices: &HashMap<u64, (f64, f64)>,
+    road_edges: &HashMap<u64, (u64, u64)>,
+) -> HashMap<(u32, u32), EdgeCost> {
+    let [distances, times] =
+        [RoutingObjective::Distance, RoutingObjective::Time].map(|objective| {
+            let road = RoadOptions {
+                objective,
+                ..RoadOptions::default()
+            };
+            precompute_travel_times_with_options(
+                city_code,
+                stores,
+                road_vertices,
+                road_edges,
+                &road,
+            )
+            .0
+        });
+    combine_edge_costs(&distances, &times)
+}
+
+/// On-disk format of the travel time cache
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CacheFormat {
+    /// Compact binary encoding, fast to load
+    #[default]
+    Bincode,
+    /// Pretty-printed JSON, larger and slower but readable for debugging
+    Json,
+}
+
+impl CacheFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            CacheFormat::Bincode => "bin",
+            CacheFormat::Json => "json",
+        }
+    }
+}
+
+// Travel time matrix together with the store set it was computed for and the pair
+// counts of its `PrecomputeReport`
+#[derive(Serialize, Deserialize)]
+struct TravelTimesCache {
+    store_count: usize,
+    fingerprint: u64,
+    total_pairs: usize,
+    fallback_pairs: usize,
+    times: HashMap<(u32, u32), f64>,
+}
+
+// JSON can't key maps by tuples, so the JSON form keys times by "from-to" strings
+#[derive(Serialize, Deserialize)]
+struct JsonTravelTimesCache {
+    store_count: usize,
+    fingerprint: u64,
+    total_pairs: usize,
+    fallback_pairs: usize,
+    times: StdHashMap<String, f64>,
+}
+
+impl TravelTimesCache {
+    fn read(path: &str, format: CacheFormat) -> Result<Self, Box<dyn std::error::Error>> {
+        let bytes = fs::read(path)?;
+        match format {
+            CacheFormat::Bincode => Ok(bincode::deserialize(&bytes)?),
+            CacheFormat::Json => {
+                let cache: JsonTravelTimesCache = serde_json::from_slice(&bytes)?;
+                let mut times = HashMap::with_capacity(cache.times.len());
+                for (key, value) in cache.times {
+                    let (from, to) = key
+                        .split_once('-')
+                        .ok_or_else(|| format!("malformed key {:?}", key))?;
+                    times.insert((from.parse()?, to.parse()?), value);
+                }
+                Ok(TravelTimesCache {
+                    store_count: cache.store_count,
+                    fingerprint: cache.fingerprint,
+                    total_pairs: cache.total_pairs,
+                    fallback_pairs: cache.fallback_pairs,
+                    times,
+                })
+            }
+        }
     }
 
-    // Cache file path
-    let cache_path = "travel_times_cache.json";
+    fn write(&self, path: &str, format: CacheFormat) -> Result<(), Box<dyn std::error::Error>> {
+        let bytes = match format {
+            CacheFormat::Bincode => bincode::serialize(self)?,
+            CacheFormat::Json => serde_json::to_vec_pretty(&JsonTravelTimesCache {
+                store_count: self.store_count,
+                fingerprint: self.fingerprint,
+                total_pairs: self.total_pairs,
+                fallback_pairs: self.fallback_pairs,
+                times: self
+                    .times
+                    .iter()
+                    .map(|((from, to), value)| (format!("{}-{}", from, to), *value))
+                    .collect(),
+            })?,
+        };
+        fs::write(path, bytes)?;
+        Ok(())
+    }
+}
+
+// FNV-1a over the city code and the sorted (store ID, x, y) tuples; stable across
+// runs and Rust versions, unlike the standard library's hasher
+fn stores_fingerprint(city_code: &str, stores: &HashMap<u32, crate::Store>) -> u64 {
+    let mut entries: Vec<(u32, f64, f64)> = stores
+        .values()
+        .map(|store| (store.id, store.location.x, store.location.y))
+        .collect();
+    entries.sort_unstable_by_key(|&(id, _, _)| id);
+
+    let mut bytes = city_code.as_bytes().to_vec();
+    for (id, x, y) in entries {
+        bytes.extend_from_slice(&id.to_le_bytes());
+        bytes.extend_from_slice(&x.to_bits().to_le_bytes());
+        bytes.extend_from_slice(&y.to_bits().to_le_bytes());
+    }
+
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in bytes {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    hash
+}
+
+// Each city, metric, edge directionality and objective gets its own cache file so
+// cached times never mix
+fn travel_times_cache_path(city_code: &str, road: &RoadOptions, directed: bool) -> String {
+    let metric = match road.metric {
+        DistanceMetric::Haversine => "",
+        DistanceMetric::Euclidean => "_euclidean",
+        DistanceMetric::Manhattan => "_manhattan",
+    };
+    let directed = if directed { "_directed" } else { "" };
+    let objective = match road.objective {
+        RoutingObjective::Distance => "",
+        RoutingObjective::Time => "_time",
+    };
+    format!(
+        "travel_times_cache_{}{}{}{}.{}",
+        city_code,
+        metric,
+        directed,
+        objective,
+        road.cache_format.extension()
+    )
+}
+
+// Loads travel times from the cache file if it was computed for the same stores, or
+// computes them on the graph built by `build_graph` (only called on a cache miss) and
+// saves them
+fn precompute_travel_times_cached<F>(
+    city_code: &str,
+    stores: &HashMap<u32, crate::Store>,
+    cache_path: &str,
+    format: CacheFormat,
+    objective: RoutingObjective,
+    build_graph: F,
+) -> (HashMap<(u32, u32), f64>, PrecomputeReport)
+where
+    F: FnOnce() -> RoadGraph,
+{
+    let fingerprint = stores_fingerprint(city_code, stores);
 
     // Try to load from cache
     if Path::new(cache_path).exists() {
         println!("Attempting to load travel times from cache...");
-        match fs::read_to_string(cache_path) {
-            Ok(json_str) => {
-                match serde_json::from_str::<TravelTimesCache>(&json_str) {
-                    Ok(cache) => {
-                        let mut result = HashMap::new();
-                        // Convert string keys back to tuples
-                        for (key_str, value) in cache.times {
-                            if let Some((from_str, to_str)) = key_str.split_once('-') {
-                                if let (Ok(from), Ok(to)) =
-                                    (from_str.parse::<u32>(), to_str.parse::<u32>())
-                                {
-                                    result.insert((from, to), value);
-                                }
-                            }
-                        }
-                        println!(
-                            "Successfully loaded {} travel time records from cache",
-                            result.len()
-                        );
-                        return result;
-                    }
-                    Err(e) => println!("Failed to parse cache: {}, will recalculate", e),
-                }
+        match TravelTimesCache::read(cache_path, format) {
+            Ok(cache) if cache.store_count == stores.len() && cache.fingerprint == fingerprint => {
+                println!(
+                    "Successfully loaded {} travel time records from cache",
+                    cache.times.len()
+                );
+                let report = PrecomputeReport {
+                    total_pairs: cache.total_pairs,
+                    fallback_pairs: cache.fallback_pairs,
+                    from_cache: true,
+                };
+                return (cache.times, report);
             }
-            Err(e) => println!("Failed to read cache: {}, will recalculate", e),
+            Ok(_) => println!("Cache was computed for different stores, will recalculate"),
+            Err(e) => println!("Failed to load cache: {}, will recalculate", e),
         }
     }
 
     println!("Starting travel time calculation...");
 
     // Build road network graph
-    let graph = Arc::new(RoadGraph::new(road_vertices.clone(), road_edges.clone()));
+    let graph = build_graph();
+    let (result, report) = compute_travel_times_with_objective(stores, &graph, objective, true);
+
+    if report.fallback_pairs > 0 {
+        println!(
+            "WARNING: {} of {} store pairs ({:.1}%) have no road path and use straight-line distance",
+            report.fallback_pairs,
+            report.total_pairs,
+            100.0 * report.fallback_fraction()
+        );
+    }
+
+    // Save to cache
+    println!("Saving results to cache...");
+    let cache = TravelTimesCache {
+        store_count: stores.len(),
+        fingerprint,
+        total_pairs: report.total_pairs,
+        fallback_pairs: report.fallback_pairs,
+        times: result,
+    };
+    match cache.write(cache_path, format) {
+        Ok(_) => println!("Cache successfully saved to {}", cache_path),
+        Err(e) => println!("Failed to save cache: {}", e),
+    }
+
+    (cache.times, report)
+}
+
+/// Compute travel times between every pair of stores on the road network
+/// Pairs without a road path fall back to the graph's straight-line metric and are
+/// counted in the report
+pub fn compute_travel_times(
+    stores: &HashMap<u32, crate::Store>,
+    graph: &RoadGraph,
+) -> (HashMap<(u32, u32), f64>, PrecomputeReport) {
+    compute_travel_times_with_objective(stores, graph, RoutingObjective::Distance, false)
+}
 
-    // Wrap HashMap with Arc and Mutex for safe sharing between threads
-    let travel_times = Arc::new(Mutex::new(HashMap::new()));
-    let stores = Arc::new(stores.clone());
+/// Same as `compute_travel_times`, with both the distance and the travel time of every
+/// store pair; the report is the one for distances
+pub fn compute_edge_costs(
+    stores: &HashMap<u32, crate::Store>,
+    graph: &RoadGraph,
+) -> (HashMap<(u32, u32), EdgeCost>, PrecomputeReport) {
+    let (distances, report) =
+        compute_travel_times_with_objective(stores, graph, RoutingObjective::Distance, false);
+    let (times, _) =
+        compute_travel_times_with_objective(stores, graph, RoutingObjective::Time, false);
+    (combine_edge_costs(&distances, &times), report)
+}
+
+/// Pairs a distance matrix with a time matrix of the same stores
+/// Pairs with no time get one at the default speed, so a distance-only matrix (as
+/// `precompute_travel_times_with_road_network` returns) can be passed with no times
+pub fn combine_edge_costs(
+    distances: &HashMap<(u32, u32), f64>,
+    times: &HashMap<(u32, u32), f64>,
+) -> HashMap<(u32, u32), EdgeCost> {
+    distances
+        .iter()
+        .map(|(&pair, &distance)| {
+            let cost = match times.get(&pair) {
+                Some(&time) => EdgeCost::new(distance, time),
+                None => EdgeCost::from_distance(distance),
+            };
+            (pair, cost)
+        })
+        .collect()
+}
+
+/// How often `compute_travel_times_with_objective` logs its progress, in percent
+pub const PROGRESS_STEP_PERCENT: usize = 10;
+
+/// Same as `compute_travel_times`, with pairs connected by shortest distance or by
+/// fastest time depending on `objective`
+/// When `verbose`, the pair count and every `PROGRESS_STEP_PERCENT`% of the road
+/// searches done are logged; otherwise nothing is printed.
+pub fn compute_travel_times_with_objective(
+    stores: &HashMap<u32, crate::Store>,
+    graph: &RoadGraph,
+    objective: RoutingObjective,
+    verbose: bool,
+) -> (HashMap<(u32, u32), f64>, PrecomputeReport) {
+    let mut travel_times = HashMap::new();
+    let mut fallback_pairs = 0;
 
     let store_ids: Vec<u32> = stores.keys().cloned().collect();
-    println!("Number of stores: {}", store_ids.len());
+    if verbose {
+        println!("Number of stores: {}", store_ids.len());
+    }
 
     // Generate all store pairs that need calculation
+    // One-way roads make the times direction dependent, so both orders are computed
+    let directed = graph.is_directed();
     let pairs: Vec<(usize, usize)> = (0..store_ids.len())
-        .flat_map(|i| ((i + 1)..store_ids.len()).map(move |j| (i, j)))
+        .flat_map(|i| {
+            let first = if directed { 0 } else { i + 1 };
+            (first..store_ids.len())
+                .filter(move |&j| j != i)
+                .map(move |j| (i, j))
+        })
         .collect();
 
-    println!("Store pairs to calculate: {}", pairs.len());
+    if verbose {
+        println!("Store pairs to calculate: {}", pairs.len());
+    }
 
-    // Process all pairs in parallel
-    pairs.par_iter().for_each(|&(i, j)| {
-        let store_i = &stores[&store_ids[i]];
-        let store_j = &stores[&store_ids[j]];
+    // One road search per distinct nearest vertex, run in parallel, covers all pairs
+    let locations: Vec<Location> = store_ids.iter().map(|id| stores[id].location).collect();
+    let log_progress = |done: usize, total: usize| {
+        // Each count is reported once, so each step is logged by exactly one search
+        let step = |count: usize| count * 100 / (total * PROGRESS_STEP_PERCENT);
+        if verbose && step(done) > step(done - 1) {
+            println!(
+                "Road searches done: {}/{} ({}%)",
+                done,
+                total,
+                done * 100 / total
+            );
+        }
+    };
+    let costs = graph.travel_cost_matrix_with_progress(&locations, objective, &log_progress);
+
+    for &(i, j) in &pairs {
+        // If no path is found, fall back to straight-line distance
+        let distance = costs[i][j].unwrap_or_else(|| {
+            fallback_pairs += 1;
+            graph.straight_line_cost(&locations[i], &locations[j], objective)
+        });
+        travel_times.insert((store_ids[i], store_ids[j]), distance);
+        if !directed {
+            travel_times.insert((store_ids[j], store_ids[i]), distance);
+        }
+    }
 
-        // Calculate distance using road network
-        let distance =
-            if let Some(d) = graph.location_distance(&store_i.location, &store_j.location) {
-                d * 1000.0 // Convert to meters
-            } else {
-                // If no path is found, fall back to Euclidean distance
-                store_i.location.distance_to(&store_j.location) * 1000.0
-            };
-        // Print information (consider using atomic operations or other thread-safe logging methods)
-        println!(
-            "distance between {} and {}: {}",
-            store_ids[i], store_ids[j], distance
+    let report = PrecomputeReport {
+        total_pairs: pairs.len(),
+        fallback_pairs,
+        from_cache: false,
+    };
+
+    (travel_times, report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fallback_pairs_counted_for_disconnected_graph() {
+        // Two road components: {1, 2} around the origin and {3, 4} far away
+        let mut vertices = HashMap::new();
+        vertices.insert(1, (0.0, 0.0));
+        vertices.insert(2, (1.0, 0.0));
+        vertices.insert(3, (10.0, 10.0));
+        vertices.insert(4, (11.0, 10.0));
+
+        let mut edges = HashMap::new();
+        edges.insert(1, (1, 2));
+        edges.insert(2, (3, 4));
+
+        let graph = RoadGraph::new_with_metric(vertices, edges, DistanceMetric::Euclidean);
+
+        // Stores 0 and 1 sit on the first component, 2 and 3 on the second
+        let mut stores = HashMap::new();
+        for (id, x, y) in [
+            (0, 0.0, 0.1),
+            (1, 1.0, 0.1),
+            (2, 10.0, 10.1),
+            (3, 11.0, 10.1),
+        ] {
+            stores.insert(id, Store::new(id, Location::new(x, y), HashMap::new()));
+        }
+
+        let (times, report) = compute_travel_times(&stores, &graph);
+
+        // 6 pairs in total, 4 of which cross components
+        assert_eq!(report.total_pairs, 6);
+        assert_eq!(report.fallback_pairs, 4);
+        assert!(!report.from_cache);
+        assert!((report.fallback_fraction() - 4.0 / 6.0).abs() < 1e-12);
+        assert_eq!(times.len(), 12);
+
+        // Cross-component pairs use the Euclidean fallback
+        let expected = Location::new(0.0, 0.1).distance_to(&Location::new(10.0, 10.1)) * 1000.0;
+        assert!((times[&(0, 2)] - expected).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_verbose_flag_leaves_travel_times_unchanged() {
+        // A 10x10 street grid with a store near every other crossing
+        let mut vertices = HashMap::new();
+        let mut edges = HashMap::new();
+        for row in 0..10u64 {
+            for column in 0..10u64 {
+                let id = row * 10 + column;
+                vertices.insert(id, (column as f64, row as f64));
+                if column < 9 {
+                    edges.insert(edges.len() as u64, (id, id + 1));
+                }
+                if row < 9 {
+                    edges.insert(edges.len() as u64, (id, id + 10));
+                }
+            }
+        }
+        let graph = RoadGraph::new_with_metric(vertices, edges, DistanceMetric::Euclidean);
+        let mut stores = HashMap::new();
+        for id in 0..50u32 {
+            let (x, y) = ((id % 10) as f64 + 0.1, (id / 10 * 2) as f64);
+            stores.insert(id, Store::new(id, Location::new(x, y), HashMap::new()));
+        }
+
+        for objective in [RoutingObjective::Distance, RoutingObjective::Time] {
+            let (quiet, quiet_report) =
+                compute_travel_times_with_objective(&stores, &graph, objective, false);
+            let (verbose, verbose_report) =
+                compute_travel_times_with_objective(&stores, &graph, objective, true);
+            assert_eq!(quiet, verbose);
+            assert_eq!(quiet_report, verbose_report);
+            assert_eq!(quiet.len(), 50 * 49);
+        }
+    }
+
+    #[test]
+    fn test_edge_costs_have_consistent_distances_and_times() {
+        // An L-shaped road 1 -- 2 -- 3 with store 2 off the network
+        let vertices = HashMap::from([(1, (0.0, 0.0)), (2, (1.0, 0.0)), (3, (1.0, 1.0))]);
+        let edges = HashMap::from([(1, (1, 2)), (2, (2, 3))]);
+        let graph = RoadGraph::new_with_metric(vertices, edges, DistanceMetric::Euclidean);
+
+        let mut stores = HashMap::new();
+        for (id, x, y) in [(0, 0.0, 0.0), (1, 1.0, 1.0), (2, 1.0, -0.5)] {
+            stores.insert(id, Store::new(id, Location::new(x, y), HashMap::new()));
+        }
+
+        let (distances, _) = compute_travel_times(&stores, &graph);
+        let (edge_costs, report) = compute_edge_costs(&stores, &graph);
+        assert_eq!(report.fallback_pairs, 0);
+        assert_eq!(edge_costs.len(), 6);
+
+        // Without speed data every road is driven at the default speed
+        for (pair, cost) in &edge_costs {
+            assert_eq!(cost.distance_m, distances[pair]);
+            assert!(cost.time_min > 0.0);
+            let expected = EdgeCost::from_distance_at(cost.distance_m, graph.default_speed());
+            assert!((cost.time_min - expected.time_min).abs() < 1e-9);
+        }
+        assert!((edge_costs[&(0, 1)].distance_m - 2000.0).abs() < 1e-9);
+
+        // A distance-only matrix still converts
+        let legacy = combine_edge_costs(&distances, &HashMap::new());
+        assert_eq!(legacy[&(0, 1)], EdgeCost::from_distance(distances[&(0, 1)]));
+    }
+
+    #[test]
+    fn test_restaurant_ids_are_preserved_on_stores() {
+        let restaurants: Vec<RestaurantRecord> = vec![
+            (9_000_000_001, 4.90, 52.37, 1, 0.0),
+            (u64::MAX, 4.91, 52.38, 2, 0.0),
+        ];
+        let stores = convert_restaurants_to_stores(
+            restaurants,
+            &HashMap::new(),
+            false,
+            &StoreGenConfig::new(10),
+        )
+        .unwrap();
+
+        assert_eq!(stores[&0].external_id, Some(9_000_000_001));
+        assert_eq!(stores[&1].external_id, Some(u64::MAX));
+
+        let by_external_id = store_ids_by_external_id(&stores);
+        assert_eq!(by_external_id[&9_000_000_001], 0);
+        assert_eq!(by_external_id[&u64::MAX], 1);
+    }
+
+    // Sorted (store, product, name, price bits, stock) rows for comparing generated stores
+    fn store_rows(stores: &HashMap<StoreId, Store>) -> Vec<(StoreId, u32, String, u64, u32)> {
+        let mut rows: Vec<_> = stores
+            .values()
+            .flat_map(|store| {
+                store.products.iter().map(move |(product_id, product)| {
+                    (
+                        store.id,
+                        *product_id,
+                        product.name.clone(),
+                        product.cost.to_bits(),
+                        store.get_inventory_level(product_id),
+                    )
+                })
+            })
+            .collect();
+        rows.sort();
+        rows
+    }
+
+    #[test]
+    fn test_seeded_stores_are_reproducible() {
+        let restaurants: Vec<RestaurantRecord> = (0..20)
+            .map(|i| (i, 4.9 + i as f64 * 0.001, 52.37, i, 0.0))
+            .collect();
+        let generate = |seed| {
+            convert_restaurants_to_stores(
+                restaurants.clone(),
+                &HashMap::new(),
+                false,
+                &StoreGenConfig::new(30).with_seed(seed),
+            )
+            .unwrap()
+        };
+
+        assert_eq!(store_rows(&generate(7)), store_rows(&generate(7)));
+
+        let first = store_rows(&generate(7));
+        let second = store_rows(&generate(8));
+        assert_ne!(first, second);
+        let total_stock = |rows: &[(StoreId, u32, String, u64, u32)]| -> u32 {
+            rows.iter().map(|row| row.4).sum()
+        };
+        assert_ne!(total_stock(&first), total_stock(&second));
+    }
+
+    #[test]
+    fn test_loaders_report_malformed_lines() {
+        let vertices = "0 4.79 52.33\n\n2 4.80 not-a-number\n";
+        match parse_road_vertices(io::Cursor::new(vertices)) {
+            Err(MapLoadError::ParseVertex { line }) => assert_eq!(line, 3),
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        let edges = "0 1 2\n1 2\n";
+        match parse_road_edges(io::Cursor::new(edges)) {
+            Err(MapLoadError::ParseEdge { line }) => assert_eq!(line, 2),
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        let restaurants = "x 4.87 52.33 1 1.0\n";
+        match parse_restaurants(io::Cursor::new(restaurants)) {
+            Err(MapLoadError::ParseRestaurant { line }) => assert_eq!(line, 1),
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        assert!(matches!(
+            parse_road_vertices(io::Cursor::new("\n")),
+            Err(MapLoadError::EmptyDataset)
+        ));
+
+        let parsed = parse_restaurants(io::Cursor::new("7 4.87 52.33 164918 1.0\n")).unwrap();
+        assert_eq!(parsed, vec![(7, 4.87, 52.33, 164918, 1.0)]);
+    }
+
+    #[test]
+    fn test_edge_direction_column() {
+        let edges =
+            parse_road_edges(io::Cursor::new("0 1 2\n1 2 3 0\n2 3 4 1\n3 4 5 2\n")).unwrap();
+        assert_eq!(edges[&0], RoadEdge::two_way(1, 2));
+        assert_eq!(edges[&1].direction, EdgeDirection::TwoWay);
+        assert_eq!(edges[&2].direction, EdgeDirection::Forward);
+        assert_eq!(edges[&3].direction, EdgeDirection::Backward);
+
+        match parse_road_edges(io::Cursor::new("0 1 2\n1 2 3 7\n")) {
+            Err(MapLoadError::ParseEdge { line }) => assert_eq!(line, 2),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_edge_speed_column() {
+        let edges = parse_road_edges(io::Cursor::new("0 1 2\n1 2 3 0 50\n2 3 4 1 12.5\n")).unwrap();
+        assert_eq!(edges[&0].speed_kmh, None);
+        assert_eq!(edges[&1].speed_kmh, Some(50.0));
+        assert_eq!(edges[&2].speed_kmh, Some(12.5));
+        assert_eq!(edges[&2].direction, EdgeDirection::Forward);
+
+        for bad in ["0 1 2 0 fast\n", "0 1 2 0 0\n", "0 1 2 0 -5\n"] {
+            assert!(matches!(
+                parse_road_edges(io::Cursor::new(bad)),
+                Err(MapLoadError::ParseEdge { line: 1 })
+            ));
+        }
+    }
+
+    #[test]
+    fn test_cache_round_trip_and_store_set_check() {
+        // Store 2 sits on a separate road, so its pairs fall back to straight lines
+        let vertices: HashMap<u64, (f64, f64)> = [
+            (1, (0.0, 0.0)),
+            (2, (1.0, 0.0)),
+            (3, (5.0, 5.0)),
+            (4, (6.0, 5.0)),
+        ]
+        .into();
+        let edges: HashMap<u64, (u64, u64)> = [(1, (1, 2)), (2, (3, 4))].into();
+        let mut stores = HashMap::new();
+        for (id, x, y) in [(0, 0.0, 0.1), (1, 1.0, 0.1), (2, 5.0, 5.1)] {
+            stores.insert(id, Store::new(id, Location::new(x, y), HashMap::new()));
+        }
+        let build = || {
+            RoadGraph::new_with_metric(vertices.clone(), edges.clone(), DistanceMetric::Euclidean)
+        };
+        let objective = RoutingObjective::Distance;
+
+        for format in [CacheFormat::Bincode, CacheFormat::Json] {
+            let path = std::env::temp_dir().join(format!(
+                "personal_shopper_cache_test_{}.{}",
+                std::process::id(),
+                format.extension()
+            ));
+            let path = path.to_str().unwrap();
+            let _ = fs::remove_file(path);
+
+            let (computed, computed_report) =
+                precompute_travel_times_cached("TEST", &stores, path, format, objective, build);
+            assert!(!computed_report.from_cache);
+            assert_eq!(computed_report.total_pairs, 3);
+            assert_eq!(computed_report.fallback_pairs, 2);
+
+            // Same stores: loaded from the cache without building the graph, reporting
+            // the counts of the original computation
+            let (loaded, report) =
+                precompute_travel_times_cached("TEST", &stores, path, format, objective, || {
+                    panic!("cache should have been used")
+                });
+            assert_eq!(
+                report,
+                PrecomputeReport {
+                    from_cache: true,
+                    ..computed_report
+                }
+            );
+            assert_eq!(loaded, computed);
+
+            // Different store set: the cache is stale and gets recomputed
+            let mut more_stores = stores.clone();
+            more_stores.insert(3, Store::new(3, Location::new(0.5, 0.1), HashMap::new()));
+            let (recomputed, report) = precompute_travel_times_cached(
+                "TEST",
+                &more_stores,
+                path,
+                format,
+                objective,
+                build,
+            );
+            assert!(!report.from_cache);
+            assert_eq!(recomputed.len(), 12);
+
+            fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_moved_store_invalidates_cache() {
+        let vertices: HashMap<u64, (f64, f64)> = [(1, (0.0, 0.0)), (2, (1.0, 0.0))].into();
+        let edges: HashMap<u64, (u64, u64)> = [(1, (1, 2))].into();
+        let mut stores = HashMap::new();
+        for (id, x) in [(0, 0.0), (1, 1.0)] {
+            stores.insert(id, Store::new(id, Location::new(x, 0.1), HashMap::new()));
+        }
+        let build = || {
+            RoadGraph::new_with_metric(vertices.clone(), edges.clone(), DistanceMetric::Euclidean)
+        };
+        let objective = RoutingObjective::Distance;
+        let format = CacheFormat::Bincode;
+        let path = std::env::temp_dir().join(format!(
+            "personal_shopper_moved_store_test_{}.bin",
+            std::process::id()
+        ));
+        let path = path.to_str().unwrap();
+        let _ = fs::remove_file(path);
+
+        let (before, _) =
+            precompute_travel_times_cached("TEST", &stores, path, format, objective, build);
+
+        // Same store IDs, but store 1 moved: the fingerprint no longer matches
+        stores.get_mut(&1).unwrap().location = Location::new(0.5, 0.1);
+        let (after, report) =
+            precompute_travel_times_cached("TEST", &stores, path, format, objective, build);
+        assert!(!report.from_cache);
+        assert!(after[&(0, 1)] < before[&(0, 1)]);
+
+        // The same stores in another city don't reuse the cache either
+        let (_, report) =
+            precompute_travel_times_cached("OTHER", &stores, path, format, objective, build);
+        assert!(!report.from_cache);
+
+        fs::remove_file(path).unwrap();
+    }
+
+    const STORE_CATALOG: &str = r#"[
+        {"id": 1, "lon": 4.89, "lat": 52.37, "products": [
+            {"id": 1, "name": "Milk", "cost": 1.25, "inventory": 10},
+            {"id": 2, "name": "Bread", "cost": 2.5, "inventory": 4}
+        ]},
+        {"id": 2, "lon": 4.90, "lat": 52.36, "products": [
+            {"id": 2, "name": "Bread", "cost": 2.25, "inventory": 6}
+        ]},
+        {"id": 3, "lon": 4.91, "lat": 52.35}
+    ]"#;
+
+    #[test]
+    fn test_parse_store_json_builds_stores() {
+        let stores = parse_store_json(STORE_CATALOG.as_bytes()).unwrap();
+
+        assert_eq!(stores.len(), 3);
+        assert_eq!(stores[&1].location, Location::new(4.89, 52.37));
+        assert_eq!(stores[&1].products[&1].name, "Milk");
+        assert_eq!(stores[&1].get_inventory_level(&1), 10);
+        assert_eq!(stores[&2].products[&2].cost, 2.25);
+        assert_eq!(stores[&2].get_inventory_level(&2), 6);
+        assert!(stores[&3].products.is_empty());
+    }
+
+    #[test]
+    fn test_parse_store_json_rejects_bad_catalogs() {
+        let duplicate_store = r#"[{"id": 1, "lon": 0, "lat": 0}, {"id": 1, "lon": 1, "lat": 1}]"#;
+        assert!(matches!(
+            parse_store_json(duplicate_store.as_bytes()),
+            Err(MapLoadError::DuplicateStore { store: 1 })
+        ));
+
+        let duplicate_product = r#"[{"id": 4, "lon": 0, "lat": 0, "products": [
+            {"id": 9, "name": "Eggs", "cost": 3.0, "inventory": 1},
+            {"id": 9, "name": "Eggs", "cost": 2.0, "inventory": 1}
+        ]}]"#;
+        assert!(matches!(
+            parse_store_json(duplicate_product.as_bytes()),
+            Err(MapLoadError::DuplicateProduct {
+                store: 4,
+                product: 9
+            })
+        ));
+
+        let renamed_product = STORE_CATALOG.replacen(
+            r#""name": "Bread", "cost": 2.25"#,
+            r#""name": "Rolls", "cost": 2.25"#,
+            1,
         );
+        match parse_store_json(renamed_product.as_bytes()) {
+            Err(MapLoadError::InconsistentProduct { product, names }) => {
+                assert_eq!(product, 2);
+                assert_eq!(names, ("Bread".to_string(), "Rolls".to_string()));
+            }
+            other => panic!(
+                "expected an inconsistent product, got {:?}",
+                other.map(|s| s.len())
+            ),
+        }
 
-        // Acquire lock and update travel_times
-        let mut times = travel_times.lock().unwrap();
-        times.insert((store_ids[i], store_ids[j]), distance);
-        times.insert((store_ids[j], store_ids[i]), distance);
-    });
+        assert!(matches!(
+            parse_store_json("[]".as_bytes()),
+            Err(MapLoadError::EmptyDataset)
+        ));
+        assert!(matches!(
+            parse_store_json(r#"[{"id": 1}]"#.as_bytes()),
+            Err(MapLoadError::ParseStoreJson(_))
+        ));
+    }
 
-    // Get final result
-    let result = Arc::try_unwrap(travel_times).unwrap().into_inner().unwrap();
+    // FNV-1a over every store's products, prices and stock, in ID order
+    fn generated_stores_digest(stores: &HashMap<StoreId, Store>) -> u64 {
+        let mut ids: Vec<&StoreId> = stores.keys().collect();
+        ids.sort();
+        let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+        let mut feed = |value: u64| {
+            for byte in value.to_le_bytes() {
+                hash ^= byte as u64;
+                hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+            }
+        };
+        for id in ids {
+            let store = &stores[id];
+            let mut product_ids: Vec<&ProductId> = store.products.keys().collect();
+            product_ids.sort();
+            feed(*id as u64);
+            for product_id in product_ids {
+                feed(*product_id as u64);
+                feed(store.products[product_id].cost.to_bits());
+                feed(store.get_inventory_level(product_id) as u64);
+            }
+        }
+        hash
+    }
 
-    // Save to JSON cache
-    println!("Saving results to cache...");
-    let mut cache_data = StdHashMap::new();
-    for ((from, to), distance) in &result {
-        // Convert tuple keys to strings
-        let key = format!("{}-{}", from, to);
-        cache_data.insert(key, *distance);
+    #[test]
+    fn test_default_store_gen_config_matches_legacy_generator() {
+        // Digests of the generator output before its parameters became configurable
+        let legacy = [
+            (None, false, 0xeaba_6e69_6856_e9ee),
+            (None, true, 0x3016_24b3_2b3f_cffc),
+            (Some(7), false, 0x9954_ceaf_fa5d_af84),
+            (Some(7), true, 0xf010_741a_9870_f6d8),
+        ];
+        let restaurants: Vec<RestaurantRecord> =
+            (0..40).map(|i| (i, i as f64, 0.0, 0, 0.0)).collect();
+
+        for (seed, infinity, digest) in legacy {
+            let config = StoreGenConfig {
+                seed,
+                ..StoreGenConfig::new(30)
+            };
+            let stores = convert_restaurants_to_stores(
+                restaurants.clone(),
+                &HashMap::new(),
+                infinity,
+                &config,
+            )
+            .unwrap();
+            assert_eq!(generated_stores_digest(&stores), digest, "seed {:?}", seed);
+        }
+    }
+
+    #[test]
+    fn test_store_gen_config_changes_generated_stores() {
+        let restaurants: Vec<RestaurantRecord> =
+            (0..20).map(|i| (i, i as f64, 0.0, 0, 0.0)).collect();
+        let config = StoreGenConfig {
+            store_types: vec![StoreTypeParams::new((2, 2), 1.0, 2.0, 4)],
+            min_products: 2,
+            price_variation_percent: 1,
+            ..StoreGenConfig::new(30).with_seed(3)
+        };
+        let stores =
+            convert_restaurants_to_stores(restaurants, &HashMap::new(), false, &config).unwrap();
+
+        for store in stores.values() {
+            assert_eq!(store.products.len(), 2);
+            for (product_id, product) in &store.products {
+                let base_price = 5.0 + (product_id % 10) as f64;
+                assert!((product.cost / (2.0 * base_price) - 1.0).abs() <= 0.01);
+                let stock = store.get_inventory_level(product_id);
+                assert_eq!(stock % 4, 0);
+                assert!((12..=28).contains(&stock));
+            }
+        }
+    }
+
+    #[test]
+    fn test_min_products_applies_to_seeded_stores() {
+        let restaurants: Vec<RestaurantRecord> =
+            (0..20).map(|i| (i, i as f64, 0.0, 0, 0.0)).collect();
+        let config = StoreGenConfig {
+            store_types: vec![StoreTypeParams::new((1, 2), 0.1, 1.0, 1)],
+            min_products: 4,
+            ..StoreGenConfig::new(10)
+        };
+
+        for config in [config.clone(), config.with_seed(9)] {
+            let stores =
+                convert_restaurants_to_stores(restaurants.clone(), &HashMap::new(), false, &config)
+                    .unwrap();
+            assert!(stores.values().all(|store| store.products.len() >= 4));
+        }
     }
 
-    let cache = TravelTimesCache { times: cache_data };
+    #[test]
+    fn test_store_gen_config_zero_variation_and_invalid_ranges() {
+        let restaurants: Vec<RestaurantRecord> =
+            (0..10).map(|i| (i, i as f64, 0.0, 0, 0.0)).collect();
+        let generate = |config: &StoreGenConfig| {
+            convert_restaurants_to_stores(restaurants.clone(), &HashMap::new(), false, config)
+        };
 
-    match serde_json::to_string_pretty(&cache) {
-        Ok(json_str) => match fs::write(cache_path, json_str) {
-            Ok(_) => println!("Cache successfully saved to {}", cache_path),
-            Err(e) => println!("Failed to save cache: {}", e),
-        },
-        Err(e) => println!("Failed to serialize cache: {}", e),
+        // Without variation every store of a type charges the base price
+        let fixed = StoreGenConfig {
+            store_types: vec![StoreTypeParams::new((3, 5), 0.5, 1.0, 1)],
+            price_variation_percent: 0,
+            ..StoreGenConfig::new(10)
+        };
+        for config in [fixed.clone(), fixed.with_seed(4)] {
+            let stores = generate(&config).unwrap();
+            for store in stores.values() {
+                for (product_id, product) in &store.products {
+                    let base_price = config.base_price + (product_id % config.price_cycle) as f64;
+                    assert_eq!(product.cost, base_price.max(config.min_price));
+                }
+            }
+        }
+
+        let invalid = [
+            StoreGenConfig {
+                store_types: Vec::new(),
+                ..StoreGenConfig::new(10)
+            },
+            StoreGenConfig {
+                store_types: vec![StoreTypeParams::new((5, 3), 0.5, 1.0, 1)],
+                ..StoreGenConfig::new(10)
+            },
+            StoreGenConfig {
+                base_inventory: (7, 3),
+                ..StoreGenConfig::new(10)
+            },
+            StoreGenConfig {
+                price_cycle: 0,
+                ..StoreGenConfig::new(10)
+            },
+        ];
+        for config in invalid {
+            for config in [config.clone(), config.with_seed(1)] {
+                assert!(matches!(
+                    generate(&config),
+                    Err(MapLoadError::InvalidStoreGenConfig { .. })
+                ));
+            }
+        }
     }
 
-    result
+    #[test]
+    fn test_store_ids_round_trip_to_restaurant_ids() {
+        let source = "9000000001 4.87 52.33 1 0.5\n42 4.88 52.34 2 0.0\n18446744073709551615 4.89 52.35 3 1.0\n";
+        let restaurants = parse_restaurants(io::Cursor::new(source)).unwrap();
+        let source_ids: Vec<u64> = restaurants.iter().map(|record| record.0).collect();
+        let stores = convert_restaurants_to_stores(
+            restaurants,
+            &HashMap::new(),
+            false,
+            &StoreGenConfig::new(5),
+        )
+        .unwrap();
+
+        let store_ids = store_ids_by_external_id(&stores);
+        assert_eq!(store_ids.len(), source_ids.len());
+        for (index, source_id) in source_ids.into_iter().enumerate() {
+            let store_id = store_ids[&source_id];
+            assert_eq!(store_id, index as StoreId);
+            assert_eq!(stores[&store_id].external_id, Some(source_id));
+        }
+    }
 }
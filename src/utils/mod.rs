@@ -1,3 +1,4 @@
+pub mod benchmark;
 pub mod distance;
 pub mod init_map;
 pub mod road_network;
@@ -1,11 +1,225 @@
-use crate::models::Location;
+use crate::models::{DistanceMetric, Location};
+use rayon::prelude::*;
 use std::cmp::Ordering;
+use std::collections::hash_map::Entry;
 use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::sync::atomic::{AtomicUsize, Ordering::Relaxed};
+
+/// Which directions of a road edge may be travelled
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EdgeDirection {
+    /// Both directions (the default when the data has no direction column)
+    #[default]
+    TwoWay,
+    /// Only from the start vertex to the end vertex
+    Forward,
+    /// Only from the end vertex to the start vertex
+    Backward,
+}
+
+/// Speed assumed for roads without speed data and for off-road legs (km/h)
+pub const DEFAULT_SPEED_KMH: f64 = 30.0;
+
+/// What shortest paths on the road network minimize
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RoutingObjective {
+    /// Shortest road length
+    #[default]
+    Distance,
+    /// Fastest travel time given the road speeds
+    Time,
+}
+
+/// Length and travel time of a trip between two points
+///
+/// `distance_m` is in the units the `Distance` objective puts in travel time matrices:
+/// the road length scaled by 1000, i.e. meters when lengths are in kilometers
+/// (`DistanceMetric::Haversine`).
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct EdgeCost {
+    pub distance_m: f64,
+    pub time_min: f64,
+}
+
+impl EdgeCost {
+    pub fn new(distance_m: f64, time_min: f64) -> Self {
+        Self {
+            distance_m,
+            time_min,
+        }
+    }
+
+    /// Edge cost known only by its distance, driven at `DEFAULT_SPEED_KMH`
+    pub fn from_distance(distance_m: f64) -> Self {
+        Self::from_distance_at(distance_m, DEFAULT_SPEED_KMH)
+    }
+
+    /// Edge cost known only by its distance, driven at the given speed (km/h)
+    pub fn from_distance_at(distance_m: f64, speed_kmh: f64) -> Self {
+        Self::new(distance_m, distance_m / 1000.0 / speed_kmh * 60.0)
+    }
+
+    /// The dimension `objective` optimizes, as it appears in a travel time matrix
+    pub fn get(&self, objective: RoutingObjective) -> f64 {
+        match objective {
+            RoutingObjective::Distance => self.distance_m,
+            RoutingObjective::Time => self.time_min,
+        }
+    }
+}
+
+/// A road segment between two vertices
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RoadEdge {
+    pub start: u64,
+    pub end: u64,
+    pub direction: EdgeDirection,
+    pub speed_kmh: Option<f64>, // None uses the graph's default speed
+}
+
+impl RoadEdge {
+    /// Creates an edge that can be travelled both ways
+    pub fn two_way(start: u64, end: u64) -> Self {
+        Self {
+            start,
+            end,
+            direction: EdgeDirection::TwoWay,
+            speed_kmh: None,
+        }
+    }
+}
 
 /// Road network graph structure
 pub struct RoadGraph {
     vertices: HashMap<u64, (f64, f64)>, // Vertex ID -> (longitude, latitude)
-    adjacency_list: HashMap<u64, Vec<(u64, f64)>>, // Vertex ID -> [(adjacent vertex ID, distance)]
+    adjacency_list: HashMap<u64, Vec<(u64, f64, Option<f64>)>>, // Vertex ID -> [(adjacent vertex ID, distance, speed)]
+    metric: DistanceMetric, // How edge lengths and off-road legs are measured
+    grid: VertexGrid,       // Spatial index over the vertex coordinates
+    directed: bool,         // Whether any edge is one-way
+    default_speed_kmh: f64, // Speed for edges without speed data
+    off_road_factor: f64,   // Multiplier on the legs between locations and their nearest vertices
+    max_snap_distance: Option<f64>, // Farthest a location may be from its nearest vertex
+    components: HashMap<u64, usize>, // Vertex ID -> connected component, ignoring edge directions
+}
+
+/// Uniform grid over vertex coordinates, used to find nearest vertices without
+/// scanning the whole graph
+struct VertexGrid {
+    min_x: f64,
+    min_y: f64,
+    cell_size: f64,
+    columns: i64,
+    rows: i64,
+    cells: HashMap<(i64, i64), Vec<u64>>,
+}
+
+impl VertexGrid {
+    fn new(vertices: &HashMap<u64, (f64, f64)>) -> Self {
+        let (mut min_x, mut min_y) = (f64::INFINITY, f64::INFINITY);
+        let (mut max_x, mut max_y) = (f64::NEG_INFINITY, f64::NEG_INFINITY);
+        for &(x, y) in vertices.values() {
+            min_x = min_x.min(x);
+            min_y = min_y.min(y);
+            max_x = max_x.max(x);
+            max_y = max_y.max(y);
+        }
+        if vertices.is_empty() {
+            (min_x, min_y, max_x, max_y) = (0.0, 0.0, 0.0, 0.0);
+        }
+
+        // Aim for a couple of vertices per cell
+        let extent = (max_x - min_x).max(max_y - min_y);
+        let cells_per_side = ((vertices.len() as f64 / 2.0).sqrt().ceil()).max(1.0);
+        let cell_size = if extent > 0.0 {
+            extent / cells_per_side
+        } else {
+            1.0
+        };
+
+        let mut grid = VertexGrid {
+            min_x,
+            min_y,
+            cell_size,
+            columns: 0,
+            rows: 0,
+            cells: HashMap::new(),
+        };
+        for (&vertex_id, &(x, y)) in vertices {
+            let cell = grid.cell_of(x, y);
+            grid.columns = grid.columns.max(cell.0 + 1);
+            grid.rows = grid.rows.max(cell.1 + 1);
+            grid.cells.entry(cell).or_default().push(vertex_id);
+        }
+        grid
+    }
+
+    fn cell_of(&self, x: f64, y: f64) -> (i64, i64) {
+        (
+            ((x - self.min_x) / self.cell_size).floor() as i64,
+            ((y - self.min_y) / self.cell_size).floor() as i64,
+        )
+    }
+
+    /// Vertices in the cells at Chebyshev distance `ring` from `center`
+    /// Only the ring's perimeter cells inside the grid are visited
+    fn ring(&self, center: (i64, i64), ring: i64) -> impl Iterator<Item = &u64> {
+        let (cx, cy) = center;
+        let (x0, x1) = ((cx - ring).max(0), (cx + ring).min(self.columns - 1));
+        let (y0, y1) = ((cy - ring + 1).max(0), (cy + ring - 1).min(self.rows - 1));
+
+        let rows = [cy - ring, cy + ring]
+            .into_iter()
+            .take(if ring == 0 { 1 } else { 2 })
+            .filter(move |&y| 0 <= y && y < self.rows)
+            .flat_map(move |y| (x0..=x1).map(move |x| (x, y)));
+        let columns = [cx - ring, cx + ring]
+            .into_iter()
+            .filter(move |&x| ring > 0 && 0 <= x && x < self.columns)
+            .flat_map(move |x| (y0..=y1).map(move |y| (x, y)));
+
+        rows.chain(columns)
+            .filter_map(|cell| self.cells.get(&cell))
+            .flatten()
+    }
+
+    /// First ring around `center` that touches the grid
+    fn min_ring(&self, center: (i64, i64)) -> i64 {
+        let outside = |value: i64, size: i64| {
+            if value < 0 {
+                -value
+            } else {
+                (value - (size - 1)).max(0)
+            }
+        };
+        outside(center.0, self.columns).max(outside(center.1, self.rows))
+    }
+
+    /// Vertices in all cells overlapping the given coordinate box
+    fn in_box(&self, min: (f64, f64), max: (f64, f64)) -> impl Iterator<Item = &u64> {
+        let (x0, y0) = self.cell_of(min.0, min.1);
+        let (x1, y1) = self.cell_of(max.0, max.1);
+        let (x0, x1) = (x0.max(0), x1.min(self.columns - 1));
+        let (y0, y1) = (y0.max(0), y1.min(self.rows - 1));
+        (x0..=x1)
+            .flat_map(move |x| (y0..=y1).map(move |y| (x, y)))
+            .filter_map(|cell| self.cells.get(&cell))
+            .flatten()
+    }
+
+    /// Number of rings needed from `center` to cover every cell of the grid
+    fn max_ring(&self, center: (i64, i64)) -> i64 {
+        [
+            center.0,
+            self.columns - 1 - center.0,
+            center.1,
+            self.rows - 1 - center.1,
+        ]
+        .iter()
+        .map(|d| d.abs())
+        .max()
+        .unwrap_or(0)
+            + 1
+    }
 }
 
 /// Node for Dijkstra algorithm
@@ -30,71 +244,327 @@ impl PartialOrd for DijkstraNode {
 }
 
 impl RoadGraph {
-    /// Create a new road network graph
+    /// Create a new road network graph over longitude/latitude vertices, with great-circle
+    /// edge lengths
     pub fn new(vertices: HashMap<u64, (f64, f64)>, edges: HashMap<u64, (u64, u64)>) -> Self {
+        RoadGraph::new_with_metric(vertices, edges, DistanceMetric::Haversine)
+    }
+
+    /// Create a new road network graph measuring distances with the given metric
+    /// Every edge is treated as two-way
+    pub fn new_with_metric(
+        vertices: HashMap<u64, (f64, f64)>,
+        edges: HashMap<u64, (u64, u64)>,
+        metric: DistanceMetric,
+    ) -> Self {
+        let edges = edges
+            .into_iter()
+            .map(|(id, (start, end))| (id, RoadEdge::two_way(start, end)))
+            .collect();
+        RoadGraph::from_road_edges(vertices, edges, metric)
+    }
+
+    /// Create a road network graph from edges that may be one-way
+    pub fn from_road_edges(
+        vertices: HashMap<u64, (f64, f64)>,
+        edges: HashMap<u64, RoadEdge>,
+        metric: DistanceMetric,
+    ) -> Self {
         // Build adjacency list
         let mut adjacency_list = HashMap::new();
+        let mut directed = false;
 
-        for (_, (start_id, end_id)) in edges {
+        for edge in edges.into_values() {
             if let (Some(start_pos), Some(end_pos)) =
-                (vertices.get(&start_id), vertices.get(&end_id))
+                (vertices.get(&edge.start), vertices.get(&edge.end))
             {
                 // Calculate edge distance
-                let distance = RoadGraph::euclidean_distance(start_pos, end_pos);
-
-                // Add edge to adjacency list (undirected graph)
-                adjacency_list
-                    .entry(start_id)
-                    .or_insert_with(Vec::new)
-                    .push((end_id, distance));
-
-                adjacency_list
-                    .entry(end_id)
-                    .or_insert_with(Vec::new)
-                    .push((start_id, distance));
+                let distance = RoadGraph::point_distance(metric, start_pos, end_pos);
+
+                // Add only the permitted directions to the adjacency list
+                if edge.direction != EdgeDirection::Backward {
+                    adjacency_list
+                        .entry(edge.start)
+                        .or_insert_with(Vec::new)
+                        .push((edge.end, distance, edge.speed_kmh));
+                }
+                if edge.direction != EdgeDirection::Forward {
+                    adjacency_list
+                        .entry(edge.end)
+                        .or_insert_with(Vec::new)
+                        .push((edge.start, distance, edge.speed_kmh));
+                }
+                directed |= edge.direction != EdgeDirection::TwoWay;
             }
         }
 
+        let grid = VertexGrid::new(&vertices);
+        let links = adjacency_list
+            .iter()
+            .flat_map(|(&from, edges)| edges.iter().map(move |&(to, _, _)| (from, to)));
+        let components = label_components(&vertices, links);
+
         RoadGraph {
             vertices,
             adjacency_list,
+            metric,
+            grid,
+            directed,
+            default_speed_kmh: DEFAULT_SPEED_KMH,
+            off_road_factor: 1.0,
+            max_snap_distance: None,
+            components,
+        }
+    }
+
+    /// Set the speed used for edges without speed data and for off-road legs
+    pub fn set_default_speed(&mut self, speed_kmh: f64) {
+        self.default_speed_kmh = speed_kmh;
+    }
+
+    /// The speed used for edges without speed data (km/h)
+    pub fn default_speed(&self) -> f64 {
+        self.default_speed_kmh
+    }
+
+    /// Set the multiplier on off-road legs, the straight lines between a location and its
+    /// nearest vertex; values above 1 account for the detours a straight line ignores
+    pub fn set_off_road_factor(&mut self, factor: f64) {
+        self.off_road_factor = factor;
+    }
+
+    /// Limit how far a location may be from its nearest vertex, in the metric's units
+    /// Locations beyond the limit count as unreachable by road, so their distances and
+    /// times are None. No limit is set by default.
+    pub fn set_max_snap_distance(&mut self, max_distance: Option<f64>) {
+        self.max_snap_distance = max_distance;
+    }
+
+    /// Nearest vertex to a location and the length of the off-road leg to it, scaled by
+    /// the off-road factor; None if there is no vertex within the snap limit
+    fn snap(&self, location: &Location) -> Option<(u64, f64)> {
+        let vertex = self.find_nearest_vertex(location)?;
+        let distance = RoadGraph::point_distance(
+            self.metric,
+            &self.vertices[&vertex],
+            &(location.x, location.y),
+        );
+        if self.max_snap_distance.is_some_and(|max| distance > max) {
+            return None;
         }
+        Some((vertex, distance * self.off_road_factor))
     }
 
-    /// Calculate Euclidean distance between two points
-    fn euclidean_distance(point1: &(f64, f64), point2: &(f64, f64)) -> f64 {
-        let dx = point1.0 - point2.0;
-        let dy = point1.1 - point2.1;
-        (dx * dx + dy * dy).sqrt()
+    /// Travel time in minutes for a stretch of the given length at the given speed
+    /// Lengths are taken as kilometres, which holds for the Haversine metric
+    fn minutes(&self, distance: f64, speed_kmh: Option<f64>) -> f64 {
+        distance / speed_kmh.unwrap_or(self.default_speed_kmh) * 60.0
+    }
+
+    /// Whether the graph has one-way edges, so distances may differ by direction
+    pub fn is_directed(&self) -> bool {
+        self.directed
+    }
+
+    /// The metric used for edge lengths and off-road legs
+    pub fn metric(&self) -> DistanceMetric {
+        self.metric
+    }
+
+    /// Calculate the distance between two points under the given metric
+    fn point_distance(metric: DistanceMetric, point1: &(f64, f64), point2: &(f64, f64)) -> f64 {
+        metric.distance(
+            &Location::new(point1.0, point1.1),
+            &Location::new(point2.0, point2.1),
+        )
     }
 
     /// Find the nearest road vertex to a given location
+    /// Ties go to the smallest vertex ID
     pub fn find_nearest_vertex(&self, location: &Location) -> Option<u64> {
-        let mut nearest_vertex = None;
-        let mut min_distance = f64::MAX;
-
-        for (vertex_id, (lon, lat)) in &self.vertices {
-            let distance = RoadGraph::euclidean_distance(&(*lon, *lat), &(location.x, location.y));
-            if distance < min_distance {
-                min_distance = distance;
-                nearest_vertex = Some(*vertex_id);
+        let center = self.grid.cell_of(location.x, location.y);
+
+        // Walk outwards until some vertex is found; it bounds the nearest distance
+        let first_hit = (self.grid.min_ring(center)..=self.grid.max_ring(center))
+            .map(|ring| self.nearest_of(self.grid.ring(center, ring), location))
+            .find(|nearest| nearest.is_some())
+            .flatten();
+        let (_, bound) = first_hit?;
+
+        // Every vertex at most `bound` away lies inside this coordinate box
+        let Some((min, max)) = self.search_box(location, bound) else {
+            return self.find_nearest_vertex_linear(location);
+        };
+        self.nearest_of(self.grid.in_box(min, max), location)
+            .map(|(vertex_id, _)| vertex_id)
+    }
+
+    /// Nearest vertex by scanning every vertex (ties go to the smallest vertex ID)
+    fn find_nearest_vertex_linear(&self, location: &Location) -> Option<u64> {
+        self.nearest_of(self.vertices.keys(), location)
+            .map(|(vertex_id, _)| vertex_id)
+    }
+
+    /// Nearest of the given vertices with its distance, ties going to the smallest ID
+    fn nearest_of<'a>(
+        &self,
+        vertex_ids: impl Iterator<Item = &'a u64>,
+        location: &Location,
+    ) -> Option<(u64, f64)> {
+        vertex_ids
+            .map(|vertex_id| {
+                let distance = RoadGraph::point_distance(
+                    self.metric,
+                    &self.vertices[vertex_id],
+                    &(location.x, location.y),
+                );
+                (*vertex_id, distance)
+            })
+            .min_by(|a, b| a.1.total_cmp(&b.1).then(a.0.cmp(&b.0)))
+    }
+
+    /// Coordinate box containing every point within `radius` of `location`
+    /// Returns None where no such box is easily bounded (Haversine near the poles)
+    fn search_box(&self, location: &Location, radius: f64) -> Option<((f64, f64), (f64, f64))> {
+        // Small margin so points exactly at `radius` survive rounding
+        let margin = 1e-9;
+        let (dx, dy) = match self.metric {
+            // The city-block ball fits inside the same box as the Euclidean one
+            DistanceMetric::Euclidean | DistanceMetric::Manhattan => (radius, radius),
+            DistanceMetric::Haversine => {
+                // Bounding box of a spherical cap, x = longitude and y = latitude
+                let angular = radius / 6371.0;
+                let lat = location.y.to_radians();
+                if lat.abs() + angular >= std::f64::consts::FRAC_PI_2 {
+                    return None;
+                }
+                let d_lon = (angular.sin() / lat.cos()).asin();
+                (d_lon.to_degrees(), angular.to_degrees())
             }
-        }
+        };
+        Some((
+            (location.x - dx - margin, location.y - dy - margin),
+            (location.x + dx + margin, location.y + dy + margin),
+        ))
+    }
 
-        nearest_vertex
+    /// Connected component of a vertex, ignoring edge directions
+    /// Components are numbered from 0 in order of their smallest vertex ID. Vertices in
+    /// different components have no road path between them, so searches between them
+    /// return None without exploring the graph.
+    /// Panics if the vertex is not in the graph.
+    pub fn component_of(&self, vertex: u64) -> usize {
+        self.components[&vertex]
     }
 
     /// Calculate the shortest path distance between two vertices using Dijkstra algorithm
     pub fn shortest_path_distance(&self, start_vertex: u64, end_vertex: u64) -> Option<f64> {
+        self.shortest_by(start_vertex, end_vertex, |distance, _| distance, None)
+    }
+
+    /// Calculate the shortest path between two vertices, returning its distance and the
+    /// vertex IDs along it from start to end (both included)
+    pub fn shortest_path(&self, start_vertex: u64, end_vertex: u64) -> Option<(f64, Vec<u64>)> {
+        let mut predecessors = HashMap::new();
+        let distance = self.shortest_by(
+            start_vertex,
+            end_vertex,
+            |distance, _| distance,
+            Some(&mut predecessors),
+        )?;
+
+        // Walk the predecessor map back from the end vertex
+        let mut path = vec![end_vertex];
+        let mut vertex = end_vertex;
+        while vertex != start_vertex {
+            vertex = predecessors[&vertex];
+            path.push(vertex);
+        }
+        path.reverse();
+
+        Some((distance, path))
+    }
+
+    /// Calculate the fastest travel time in minutes between two vertices
+    pub fn shortest_time(&self, start_vertex: u64, end_vertex: u64) -> Option<f64> {
+        self.shortest_by(
+            start_vertex,
+            end_vertex,
+            |distance, speed| self.minutes(distance, speed),
+            None,
+        )
+    }
+
+    /// Shortest path distances from `source` to every reachable vertex
+    pub fn shortest_paths_from(&self, source: u64) -> HashMap<u64, f64> {
+        self.settle_from(source, |distance, _| distance, None, None)
+    }
+
+    /// Fastest travel times in minutes from `source` to every reachable vertex
+    pub fn shortest_times_from(&self, source: u64) -> HashMap<u64, f64> {
+        self.settle_from(
+            source,
+            |distance, speed| self.minutes(distance, speed),
+            None,
+            None,
+        )
+    }
+
+    /// Dijkstra with edge weights computed from (distance, speed)
+    /// When `predecessors` is given, it records the previous vertex on each best path
+    fn shortest_by<W>(
+        &self,
+        start_vertex: u64,
+        end_vertex: u64,
+        weight: W,
+        predecessors: Option<&mut HashMap<u64, u64>>,
+    ) -> Option<f64>
+    where
+        W: Fn(f64, Option<f64>) -> f64,
+    {
         // Special case: start and end vertices are the same
         if start_vertex == end_vertex {
             return Some(0.0);
         }
 
+        if self.components.get(&start_vertex) != self.components.get(&end_vertex) {
+            return None;
+        }
+
+        let targets = HashSet::from([end_vertex]);
+        self.settle_from(start_vertex, weight, Some(&targets), predecessors)
+            .get(&end_vertex)
+            .copied()
+    }
+
+    /// Single-source Dijkstra with edge weights computed from (distance, speed)
+    /// Returns the cost of every settled vertex, stopping early once all `targets`
+    /// in the start vertex's component are settled. When `predecessors` is given, it
+    /// records the previous vertex on each best path.
+    fn settle_from<W>(
+        &self,
+        start_vertex: u64,
+        weight: W,
+        targets: Option<&HashSet<u64>>,
+        mut predecessors: Option<&mut HashMap<u64, u64>>,
+    ) -> HashMap<u64, f64>
+    where
+        W: Fn(f64, Option<f64>) -> f64,
+    {
         // Initialize distance map and visited set
         let mut distances = HashMap::new();
-        let mut visited = HashSet::new();
+        let mut settled = HashMap::new();
         let mut priority_queue = BinaryHeap::new();
+        let component = self.components.get(&start_vertex);
+        let mut targets_left = targets.map_or(usize::MAX, |targets| {
+            targets
+                .iter()
+                .filter(|target| self.components.get(target) == component)
+                .count()
+        });
+        if targets_left == 0 {
+            return HashMap::from([(start_vertex, 0.0)]);
+        }
 
         // Set start vertex distance to 0 and add to queue
         distances.insert(start_vertex, 0.0);
@@ -109,29 +579,32 @@ impl RoadGraph {
             distance: _,
         }) = priority_queue.pop()
         {
-            // If found the end vertex, return the distance
-            if vertex == end_vertex {
-                return Some(distances[&vertex]);
-            }
-
             // If already visited this vertex, skip
-            if visited.contains(&vertex) {
+            if settled.contains_key(&vertex) {
                 continue;
             }
 
             // Mark vertex as visited
-            visited.insert(vertex);
+            settled.insert(vertex, distances[&vertex]);
+
+            // Stop once every target has its final distance
+            if targets.is_some_and(|targets| targets.contains(&vertex)) {
+                targets_left -= 1;
+                if targets_left == 0 {
+                    break;
+                }
+            }
 
             // Traverse adjacent vertices
             if let Some(neighbors) = self.adjacency_list.get(&vertex) {
-                for &(neighbor, edge_distance) in neighbors {
+                for &(neighbor, edge_distance, speed) in neighbors {
                     // If already visited, skip
-                    if visited.contains(&neighbor) {
+                    if settled.contains_key(&neighbor) {
                         continue;
                     }
 
                     // Calculate new distance to neighbor through current vertex
-                    let new_distance = distances[&vertex] + edge_distance;
+                    let new_distance = distances[&vertex] + weight(edge_distance, speed);
 
                     // If found a shorter path, update distance
                     let is_shorter = match distances.get(&neighbor) {
@@ -142,6 +615,9 @@ impl RoadGraph {
                     if is_shorter {
                         // Update distance and add to queue
                         distances.insert(neighbor, new_distance);
+                        if let Some(predecessors) = predecessors.as_deref_mut() {
+                            predecessors.insert(neighbor, vertex);
+                        }
                         priority_queue.push(DijkstraNode {
                             vertex: neighbor,
                             distance: (new_distance * 1000.0) as u64, // Convert to millimeters (integer) for comparison
@@ -151,25 +627,164 @@ impl RoadGraph {
             }
         }
 
-        // No path found
-        None
+        settled
     }
 
-    /// Calculate the distance between two locations on the road network
-    pub fn location_distance(&self, from: &Location, to: &Location) -> Option<f64> {
-        // Find the nearest start and end vertices
-        let start_vertex = self.find_nearest_vertex(from)?;
-        let end_vertex = self.find_nearest_vertex(to)?;
+    /// Travel costs between every ordered pair of `locations`, as `travel_cost` gives
+    /// them; None where no road path exists
+    /// Locations snapping to the same vertex share one single-source search, so this
+    /// runs one Dijkstra per distinct nearest vertex instead of one per pair.
+    pub fn travel_cost_matrix(
+        &self,
+        locations: &[Location],
+        objective: RoutingObjective,
+    ) -> Vec<Vec<Option<f64>>> {
+        self.travel_cost_matrix_with_progress(locations, objective, &|_, _| {})
+    }
+
+    /// Same as `travel_cost_matrix`, calling `on_progress(done, total)` each time one of
+    /// the `total` single-source searches finishes
+    /// Searches run in parallel, so the calls come from several threads, each with a
+    /// different `done` count, though not necessarily in increasing order.
+    pub fn travel_cost_matrix_with_progress(
+        &self,
+        locations: &[Location],
+        objective: RoutingObjective,
+        on_progress: &(dyn Fn(usize, usize) + Sync),
+    ) -> Vec<Vec<Option<f64>>> {
+        let snapped: Vec<Option<(u64, f64)>> = locations
+            .iter()
+            .map(|location| self.snap(location))
+            .collect();
+        let nearest: Vec<Option<u64>> = snapped
+            .iter()
+            .map(|snap| snap.map(|(vertex, _)| vertex))
+            .collect();
+        // Off-road distance from each location to its nearest vertex
+        let off_road: Vec<f64> = snapped
+            .iter()
+            .map(|snap| snap.map_or(0.0, |(_, distance)| distance))
+            .collect();
+
+        let mut groups: HashMap<u64, Vec<usize>> = HashMap::new();
+        for (index, vertex) in nearest.iter().enumerate() {
+            if let Some(vertex) = vertex {
+                groups.entry(*vertex).or_default().push(index);
+            }
+        }
+        let targets: HashSet<u64> = groups.keys().cloned().collect();
+        let searched = AtomicUsize::new(0);
+
+        let rows: Vec<(usize, Vec<Option<f64>>)> = groups
+            .par_iter()
+            .flat_map_iter(|(&source, members)| {
+                let network = match objective {
+                    RoutingObjective::Distance => {
+                        self.settle_from(source, |distance, _| distance, Some(&targets), None)
+                    }
+                    RoutingObjective::Time => self.settle_from(
+                        source,
+                        |distance, speed| self.minutes(distance, speed),
+                        Some(&targets),
+                        None,
+                    ),
+                };
+                on_progress(searched.fetch_add(1, Relaxed) + 1, groups.len());
+                members
+                    .iter()
+                    .map(|&from| {
+                        let row = (0..locations.len())
+                            .map(|to| {
+                                let network_cost = network.get(&nearest[to]?)?;
+                                Some(match objective {
+                                    RoutingObjective::Distance => {
+                                        (off_road[from] + network_cost + off_road[to]) * 1000.0
+                                    }
+                                    RoutingObjective::Time => {
+                                        self.minutes(off_road[from], None)
+                                            + network_cost
+                                            + self.minutes(off_road[to], None)
+                                    }
+                                })
+                            })
+                            .collect();
+                        (from, row)
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+
+        let mut matrix = vec![vec![None; locations.len()]; locations.len()];
+        for (from, row) in rows {
+            matrix[from] = row;
+        }
+        matrix
+    }
+
+    /// Travel cost between two locations for the given objective, in the units of the
+    /// travel time matrix: road distance scaled by 1000, or minutes
+    pub fn travel_cost(
+        &self,
+        from: &Location,
+        to: &Location,
+        objective: RoutingObjective,
+    ) -> Option<f64> {
+        match objective {
+            RoutingObjective::Distance => Some(self.location_distance(from, to)? * 1000.0),
+            RoutingObjective::Time => self.location_time(from, to),
+        }
+    }
+
+    /// Straight-line travel cost, used when no road path exists
+    pub fn straight_line_cost(
+        &self,
+        from: &Location,
+        to: &Location,
+        objective: RoutingObjective,
+    ) -> f64 {
+        let distance = self.metric.distance(from, to);
+        match objective {
+            RoutingObjective::Distance => distance * 1000.0,
+            RoutingObjective::Time => self.minutes(distance, None),
+        }
+    }
+
+    /// Calculate the fastest travel time in minutes between two locations
+    /// Legs between the locations and their nearest vertices use the default speed
+    pub fn location_time(&self, from: &Location, to: &Location) -> Option<f64> {
+        let (start_vertex, start_distance) = self.snap(from)?;
+        let (end_vertex, end_distance) = self.snap(to)?;
+
+        let network_time = self.shortest_time(start_vertex, end_vertex)?;
+
+        Some(self.minutes(start_distance, None) + network_time + self.minutes(end_distance, None))
+    }
+
+    /// Calculate the road route between two locations as a coordinate polyline
+    /// The polyline starts at `from`, follows the road vertices and ends at `to`;
+    /// the distance matches `location_distance`
+    pub fn location_path(&self, from: &Location, to: &Location) -> Option<(f64, Vec<(f64, f64)>)> {
+        let (start_vertex, start_distance) = self.snap(from)?;
+        let (end_vertex, end_distance) = self.snap(to)?;
+
+        let (network_distance, vertex_path) = self.shortest_path(start_vertex, end_vertex)?;
 
-        // Calculate distance from start location to start vertex
-        let start_point = self.vertices.get(&start_vertex)?;
-        let start_distance =
-            RoadGraph::euclidean_distance(&(start_point.0, start_point.1), &(from.x, from.y));
+        let mut polyline = Vec::with_capacity(vertex_path.len() + 2);
+        polyline.push((from.x, from.y));
+        for vertex in &vertex_path {
+            polyline.push(*self.vertices.get(vertex)?);
+        }
+        polyline.push((to.x, to.y));
 
-        // Calculate distance from end vertex to end location
-        let end_point = self.vertices.get(&end_vertex)?;
-        let end_distance =
-            RoadGraph::euclidean_distance(&(end_point.0, end_point.1), &(to.x, to.y));
+        Some((start_distance + network_distance + end_distance, polyline))
+    }
+
+    /// Calculate the distance between two locations on the road network
+    /// None if either location is beyond the snap limit or no road connects them
+    pub fn location_distance(&self, from: &Location, to: &Location) -> Option<f64> {
+        // Find the nearest start and end vertices, with the off-road legs to them
+        let (start_vertex, start_distance) = self.snap(from)?;
+        let (end_vertex, end_distance) = self.snap(to)?;
 
         // Calculate shortest path in the road network
         let network_distance = self.shortest_path_distance(start_vertex, end_vertex)?;
@@ -178,3 +793,414 @@ impl RoadGraph {
         Some(start_distance + network_distance + end_distance)
     }
 }
+
+/// Labels the weakly connected components of a graph given its (from, to) links,
+/// numbering them in order of their smallest vertex ID
+fn label_components(
+    vertices: &HashMap<u64, (f64, f64)>,
+    links: impl Iterator<Item = (u64, u64)>,
+) -> HashMap<u64, usize> {
+    // Links lead either way, so the search needs the reverse of one-way edges too
+    let mut neighbors: HashMap<u64, Vec<u64>> = HashMap::new();
+    for (from, to) in links {
+        neighbors.entry(from).or_default().push(to);
+        neighbors.entry(to).or_default().push(from);
+    }
+
+    let mut ids: Vec<u64> = vertices.keys().copied().collect();
+    ids.sort_unstable();
+
+    let mut components = HashMap::with_capacity(ids.len());
+    let mut next_component = 0;
+    for id in ids {
+        if components.contains_key(&id) {
+            continue;
+        }
+        components.insert(id, next_component);
+        let mut stack = vec![id];
+        while let Some(vertex) = stack.pop() {
+            for &neighbor in neighbors.get(&vertex).into_iter().flatten() {
+                if let Entry::Vacant(entry) = components.entry(neighbor) {
+                    entry.insert(next_component);
+                    stack.push(neighbor);
+                }
+            }
+        }
+        next_component += 1;
+    }
+    components
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    #[test]
+    fn test_indexed_nearest_vertex_matches_linear_scan() {
+        let mut rng = StdRng::seed_from_u64(42);
+
+        // Random vertices around Amsterdam, with a dense cluster and duplicates
+        let mut vertices = HashMap::new();
+        for id in 0..3000u64 {
+            let point = if id % 10 == 0 {
+                (
+                    4.90 + rng.gen_range(0.0..0.002),
+                    52.37 + rng.gen_range(0.0..0.002),
+                )
+            } else {
+                (rng.gen_range(4.75..5.05), rng.gen_range(52.28..52.43))
+            };
+            vertices.insert(id, point);
+        }
+        vertices.insert(3000, vertices[&1]);
+
+        // Queries inside and well outside the vertex bounding box
+        let queries: Vec<Location> = (0..500)
+            .map(|_| Location::new(rng.gen_range(4.6..5.2), rng.gen_range(52.2..52.5)))
+            .chain([vertices[&1], (0.0, 0.0), (10.0, 60.0)].map(|(x, y)| Location::new(x, y)))
+            .collect();
+
+        for metric in [DistanceMetric::Euclidean, DistanceMetric::Haversine] {
+            let graph = RoadGraph::new_with_metric(vertices.clone(), HashMap::new(), metric);
+
+            let indexed: Vec<_> = queries
+                .iter()
+                .map(|q| graph.find_nearest_vertex(q))
+                .collect();
+            let linear: Vec<_> = queries
+                .iter()
+                .map(|q| graph.find_nearest_vertex_linear(q))
+                .collect();
+            assert_eq!(indexed, linear);
+        }
+
+        // Duplicate coordinates resolve to the smaller ID
+        let graph =
+            RoadGraph::new_with_metric(vertices.clone(), HashMap::new(), DistanceMetric::Euclidean);
+        let (x, y) = vertices[&1];
+        assert_eq!(graph.find_nearest_vertex(&Location::new(x, y)), Some(1));
+
+        let empty =
+            RoadGraph::new_with_metric(HashMap::new(), HashMap::new(), DistanceMetric::Euclidean);
+        assert_eq!(empty.find_nearest_vertex(&Location::new(0.0, 0.0)), None);
+    }
+
+    #[test]
+    fn test_one_way_edge_is_only_travelled_forward() {
+        let vertices: HashMap<u64, (f64, f64)> =
+            [(1, (0.0, 0.0)), (2, (1.0, 0.0)), (3, (1.0, 1.0))].into();
+        let edges: HashMap<u64, RoadEdge> = [
+            (
+                10,
+                RoadEdge {
+                    start: 1,
+                    end: 2,
+                    direction: EdgeDirection::Forward,
+                    speed_kmh: None,
+                },
+            ),
+            (
+                11,
+                RoadEdge {
+                    start: 3,
+                    end: 2,
+                    direction: EdgeDirection::Backward,
+                    speed_kmh: None,
+                },
+            ),
+        ]
+        .into();
+        let graph = RoadGraph::from_road_edges(vertices.clone(), edges, DistanceMetric::Euclidean);
+
+        assert!(graph.is_directed());
+        assert_eq!(graph.shortest_path_distance(1, 2), Some(1.0));
+        assert_eq!(graph.shortest_path_distance(2, 1), None);
+        assert_eq!(graph.shortest_path_distance(2, 3), Some(1.0));
+        assert_eq!(graph.shortest_path_distance(3, 2), None);
+
+        let two_way =
+            RoadGraph::new_with_metric(vertices, [(10, (1, 2))].into(), DistanceMetric::Euclidean);
+        assert!(!two_way.is_directed());
+        assert_eq!(two_way.shortest_path_distance(2, 1), Some(1.0));
+    }
+
+    #[test]
+    fn test_time_optimal_path_prefers_faster_roads() {
+        // A short slow road 1-2 and a longer fast detour 1-3-2
+        let vertices: HashMap<u64, (f64, f64)> =
+            [(1, (0.0, 0.0)), (2, (10.0, 0.0)), (3, (5.0, 5.0))].into();
+        let road = |start, end, speed_kmh| RoadEdge {
+            speed_kmh,
+            ..RoadEdge::two_way(start, end)
+        };
+        let edges: HashMap<u64, RoadEdge> = [
+            (10, road(1, 2, Some(10.0))),
+            (11, road(1, 3, Some(100.0))),
+            (12, road(3, 2, None)),
+        ]
+        .into();
+        let mut graph = RoadGraph::from_road_edges(vertices, edges, DistanceMetric::Euclidean);
+
+        let detour = 2.0 * 50.0_f64.sqrt();
+        assert_eq!(graph.shortest_path_distance(1, 2), Some(10.0));
+        // Slow road takes 60 minutes; the detour's second half runs at the default 30 km/h
+        let detour_time = graph.shortest_time(1, 2).unwrap();
+        let expected = detour / 2.0 / 100.0 * 60.0 + detour / 2.0 / DEFAULT_SPEED_KMH * 60.0;
+        assert!((detour_time - expected).abs() < 1e-9);
+
+        // Slowing the default makes the direct road the faster one again
+        graph.set_default_speed(5.0);
+        assert!((graph.shortest_time(1, 2).unwrap() - 60.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_shortest_path_returns_vertices_and_polyline() {
+        // Square 1-2-3-4 with a long diagonal 1-3
+        let vertices: HashMap<u64, (f64, f64)> = [
+            (1, (0.0, 0.0)),
+            (2, (1.0, 0.0)),
+            (3, (1.0, 1.0)),
+            (4, (0.0, 1.0)),
+            (5, (5.0, 5.0)),
+        ]
+        .into();
+        let edges: HashMap<u64, (u64, u64)> =
+            [(0, (1, 2)), (1, (2, 3)), (2, (3, 4)), (3, (4, 1))].into();
+        let graph = RoadGraph::new_with_metric(vertices, edges, DistanceMetric::Euclidean);
+
+        let (distance, path) = graph.shortest_path(1, 3).unwrap();
+        assert_eq!(distance, 2.0);
+        assert_eq!(path.len(), 3);
+        assert_eq!((path[0], path[2]), (1, 3));
+        assert_eq!(graph.shortest_path(2, 2), Some((0.0, vec![2])));
+        assert_eq!(graph.shortest_path(1, 5), None);
+
+        let from = Location::new(-0.5, 0.0);
+        let to = Location::new(1.5, 0.0);
+        let (distance, polyline) = graph.location_path(&from, &to).unwrap();
+        assert_eq!(
+            polyline,
+            vec![(-0.5, 0.0), (0.0, 0.0), (1.0, 0.0), (1.5, 0.0)]
+        );
+        assert_eq!(Some(distance), graph.location_distance(&from, &to));
+    }
+
+    #[test]
+    fn test_single_source_searches_match_pairwise() {
+        let mut rng = StdRng::seed_from_u64(800);
+
+        // A 6x6 grid of roads with random speeds and some one-way streets,
+        // plus an unconnected vertex
+        let mut vertices = HashMap::new();
+        for id in 0..36u64 {
+            let (x, y) = ((id % 6) as f64, (id / 6) as f64);
+            vertices.insert(
+                id,
+                (x + rng.gen_range(-0.2..0.2), y + rng.gen_range(-0.2..0.2)),
+            );
+        }
+        vertices.insert(99, (10.0, 10.0));
+        let mut edges = HashMap::new();
+        for id in 0..36u64 {
+            for next in [id + 1, id + 6] {
+                if next >= 36 || (next == id + 1 && id % 6 == 5) {
+                    continue;
+                }
+                let direction = match rng.gen_range(0..4) {
+                    0 => EdgeDirection::Forward,
+                    1 => EdgeDirection::Backward,
+                    _ => EdgeDirection::TwoWay,
+                };
+                edges.insert(
+                    edges.len() as u64,
+                    RoadEdge {
+                        start: id,
+                        end: next,
+                        direction,
+                        speed_kmh: Some(rng.gen_range(10.0..60.0)),
+                    },
+                );
+            }
+        }
+        let graph = RoadGraph::from_road_edges(vertices, edges, DistanceMetric::Euclidean);
+
+        for source in [0, 14, 35, 99] {
+            let distances = graph.shortest_paths_from(source);
+            let times = graph.shortest_times_from(source);
+            for target in (0..36).chain([99]) {
+                assert_eq!(
+                    distances.get(&target).copied(),
+                    graph.shortest_path_distance(source, target)
+                );
+                assert_eq!(
+                    times.get(&target).copied(),
+                    graph.shortest_time(source, target)
+                );
+            }
+        }
+
+        // Several locations share a nearest vertex; the last one snaps to vertex 99
+        let locations: Vec<Location> = (0..20)
+            .map(|_| Location::new(rng.gen_range(-0.5..5.5), rng.gen_range(-0.5..5.5)))
+            .chain([Location::new(10.1, 9.9)])
+            .collect();
+        for objective in [RoutingObjective::Distance, RoutingObjective::Time] {
+            let matrix = graph.travel_cost_matrix(&locations, objective);
+            for (i, from) in locations.iter().enumerate() {
+                for (j, to) in locations.iter().enumerate() {
+                    match (matrix[i][j], graph.travel_cost(from, to, objective)) {
+                        (Some(batched), Some(pairwise)) => {
+                            assert!((batched - pairwise).abs() < 1e-9)
+                        }
+                        (batched, pairwise) => assert_eq!(batched, pairwise),
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_edge_cost_from_distance() {
+        // 1 km at 30 km/h takes 2 minutes
+        let cost = EdgeCost::from_distance(1000.0);
+        assert_eq!(cost, EdgeCost::new(1000.0, 2.0));
+        assert_eq!(cost.get(RoutingObjective::Distance), 1000.0);
+        assert_eq!(cost.get(RoutingObjective::Time), 2.0);
+        assert_eq!(EdgeCost::from_distance_at(1500.0, 90.0).time_min, 1.0);
+
+        // Matches the graph's own straight-line costs at the default speed
+        let graph =
+            RoadGraph::new_with_metric(HashMap::new(), HashMap::new(), DistanceMetric::Euclidean);
+        let (from, to) = (Location::new(0.0, 0.0), Location::new(3.0, 4.0));
+        let cost = EdgeCost::from_distance(graph.straight_line_cost(
+            &from,
+            &to,
+            RoutingObjective::Distance,
+        ));
+        let time = graph.straight_line_cost(&from, &to, RoutingObjective::Time);
+        assert!((cost.time_min - time).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_off_road_legs_far_from_the_network() {
+        let vertices = HashMap::from([(1, (0.0, 0.0)), (2, (1.0, 0.0))]);
+        let mut graph = RoadGraph::new_with_metric(
+            vertices,
+            HashMap::from([(1, (1, 2))]),
+            DistanceMetric::Euclidean,
+        );
+        let near = Location::new(0.0, 0.1);
+        let far = Location::new(1.0, 50.0);
+
+        // By default the far point is reached by a long straight line
+        assert!((graph.location_distance(&near, &far).unwrap() - 51.1).abs() < 1e-9);
+
+        // which the off-road factor makes more costly
+        graph.set_off_road_factor(1.5);
+        assert!((graph.location_distance(&near, &far).unwrap() - 76.15).abs() < 1e-9);
+        let (distance, _) = graph.location_path(&near, &far).unwrap();
+        assert!((distance - 76.15).abs() < 1e-9);
+        let time = graph.location_time(&near, &far).unwrap();
+        assert!((time - graph.minutes(76.15, None)).abs() < 1e-9);
+
+        // Beyond the snap limit the point is unreachable by road
+        graph.set_max_snap_distance(Some(1.0));
+        assert_eq!(graph.location_distance(&near, &far), None);
+        assert_eq!(graph.location_time(&far, &near), None);
+        assert!(graph.location_path(&near, &far).is_none());
+        assert!(graph
+            .location_distance(&near, &Location::new(1.0, 0.5))
+            .is_some());
+
+        let matrix = graph.travel_cost_matrix(&[near, far], RoutingObjective::Distance);
+        assert!(matrix[0][0].is_some());
+        assert_eq!(matrix[0][1], None);
+        assert_eq!(matrix[1][0], None);
+        assert_eq!(matrix[1][1], None);
+    }
+
+    #[test]
+    fn test_disconnected_clusters_are_not_searched() {
+        // A 60x60 street grid and, far away, a separate three-vertex cluster
+        let side = 60u64;
+        let mut vertices = HashMap::new();
+        let mut edges = HashMap::new();
+        for row in 0..side {
+            for column in 0..side {
+                let id = row * side + column;
+                vertices.insert(id, (column as f64, row as f64));
+                if column + 1 < side {
+                    edges.insert(edges.len() as u64, (id, id + 1));
+                }
+                if row + 1 < side {
+                    edges.insert(edges.len() as u64, (id, id + side));
+                }
+            }
+        }
+        let island = side * side;
+        for offset in 0..3 {
+            vertices.insert(island + offset, (500.0 + offset as f64, 500.0));
+        }
+        edges.insert(edges.len() as u64, (island, island + 1));
+        edges.insert(edges.len() as u64, (island + 1, island + 2));
+        let graph = RoadGraph::new_with_metric(vertices, edges, DistanceMetric::Euclidean);
+
+        let far_corner = side * side - 1;
+        assert_eq!(graph.component_of(0), 0);
+        assert_eq!(graph.component_of(far_corner), 0);
+        assert_eq!(graph.component_of(island + 2), 1);
+        assert_eq!(graph.shortest_path_distance(island, island + 2), Some(2.0));
+
+        // Crossing clusters fails straight away instead of settling the whole grid
+        assert_eq!(
+            graph.shortest_path_distance(0, far_corner),
+            Some(2.0 * (side - 1) as f64)
+        );
+        assert_eq!(graph.shortest_path_distance(0, island), None);
+        let mut predecessors = HashMap::new();
+        assert_eq!(
+            graph.shortest_by(0, island, |distance, _| distance, Some(&mut predecessors)),
+            None
+        );
+        assert!(predecessors.is_empty());
+        let unreachable = HashSet::from([island]);
+        let settled = graph.settle_from(0, |distance, _| distance, Some(&unreachable), None);
+        assert_eq!(settled, HashMap::from([(0, 0.0)]));
+
+        // The matrix searches skip stores whose vertices sit in the other cluster
+        let locations = [Location::new(0.0, 0.0), Location::new(500.0, 500.0)];
+        let matrix = graph.travel_cost_matrix(&locations, RoutingObjective::Distance);
+        assert!(matrix[0][1].is_none());
+        assert!(matrix[1][0].is_none());
+    }
+
+    #[test]
+    fn test_matrix_progress_counts_every_search() {
+        let vertices: HashMap<u64, (f64, f64)> =
+            (0..20u64).map(|id| (id, (id as f64, 0.0))).collect();
+        let edges: HashMap<u64, (u64, u64)> = (0..19u64).map(|id| (id, (id, id + 1))).collect();
+        let graph = RoadGraph::new_with_metric(vertices, edges, DistanceMetric::Euclidean);
+
+        // Two locations share vertex 3, so there is one search fewer than locations
+        let locations: Vec<Location> = [0.0, 3.0, 3.1, 7.0, 12.0, 19.0]
+            .iter()
+            .map(|&x| Location::new(x, 0.0))
+            .collect();
+        let reports = std::sync::Mutex::new(Vec::new());
+        let matrix = graph.travel_cost_matrix_with_progress(
+            &locations,
+            RoutingObjective::Distance,
+            &|done, total| reports.lock().unwrap().push((done, total)),
+        );
+
+        let mut reports = reports.into_inner().unwrap();
+        reports.sort();
+        assert_eq!(reports, (1..=5).map(|done| (done, 5)).collect::<Vec<_>>());
+        assert_eq!(
+            matrix,
+            graph.travel_cost_matrix(&locations, RoutingObjective::Distance)
+        );
+    }
+}
@@ -1,4 +1,278 @@
-use crate::models::{Cost, ShoppingRoute, Time};
+use crate::models::{format_stores, Cost, ShoppingRoute, Time};
+use std::fmt::Write;
+use std::ops::Deref;
+
+/// Domination rule a `Skyline` prunes routes with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Domination {
+    /// Drop routes no better on either objective and worse on one
+    #[default]
+    Conventional,
+    /// Only drop routes worse on both objectives, keeping ties on one as alternatives
+    Strict,
+    /// Also drop routes on or above the line joining two others
+    Linear,
+}
+
+/// How widely a skyline's routes spread over time and cost
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct DiversityMetrics {
+    /// Time between the fastest and the slowest route
+    pub time_range: Time,
+    /// Cost between the cheapest and the most expensive route
+    pub cost_range: Cost,
+    /// Average time between consecutive routes; 0 with fewer than 2 routes
+    pub avg_time_step: Time,
+    /// Number of routes
+    pub count: usize,
+}
+
+/// Set of non-dominated routes, kept sorted by `ShoppingRoute::skyline_order`
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Skyline {
+    routes: Vec<ShoppingRoute>,
+    domination: Domination,
+}
+
+impl Skyline {
+    /// Creates an empty skyline using conventional domination
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates an empty skyline using the given domination rule
+    pub fn with_domination(domination: Domination) -> Self {
+        Self {
+            routes: Vec::new(),
+            domination,
+        }
+    }
+
+    pub fn domination(&self) -> Domination {
+        self.domination
+    }
+
+    /// Inserts a route unless it is dominated by (or equal to) one already in the
+    /// skyline, removing the routes it dominates
+    /// A route over the same stores as one in the skyline, in another order, replaces
+    /// it only if faster. Returns true if the route was inserted
+    pub fn insert(&mut self, route: ShoppingRoute) -> bool {
+        if !remove_slower_permutation(&mut self.routes, &route) {
+            return false;
+        }
+        let dominates = match self.domination {
+            Domination::Conventional => return insert_sorted_conventional(&mut self.routes, route),
+            Domination::Strict => ShoppingRoute::strictly_dominates,
+            Domination::Linear => return insert_into_skyline(&mut self.routes, route),
+        };
+
+        if self
+            .routes
+            .iter()
+            .any(|existing| dominates(existing, &route) || existing == &route)
+        {
+            return false;
+        }
+        self.routes.retain(|existing| !dominates(&route, existing));
+
+        let position = self
+            .routes
+            .partition_point(|existing| existing.skyline_order(&route).is_le());
+        self.routes.insert(position, route);
+        true
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, ShoppingRoute> {
+        self.routes.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.routes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.routes.is_empty()
+    }
+
+    /// The route with the lowest shopping time
+    pub fn fastest(&self) -> Option<&ShoppingRoute> {
+        self.routes.first()
+    }
+
+    /// The route with the lowest shopping cost (the fastest among equally cheap ones)
+    pub fn cheapest(&self) -> Option<&ShoppingRoute> {
+        self.routes.iter().reduce(|best, route| {
+            if route.shopping_cost < best.shopping_cost {
+                route
+            } else {
+                best
+            }
+        })
+    }
+
+    /// Area of the time-cost region dominated by the skyline and bounded by `reference`
+    /// Larger is better; routes not faster and cheaper than `reference` add nothing.
+    pub fn hypervolume(&self, reference: (Time, Cost)) -> f64 {
+        let (reference_time, reference_cost) = reference;
+        let inside: Vec<&ShoppingRoute> = self
+            .routes
+            .iter()
+            .filter(|route| {
+                route.shopping_time < reference_time && route.shopping_cost < reference_cost
+            })
+            .collect();
+
+        // Sweep by time: each slice up to the next route is covered down to the
+        // cheapest cost seen so far
+        let mut volume = 0.0;
+        let mut best_cost = reference_cost;
+        for (i, route) in inside.iter().enumerate() {
+            best_cost = best_cost.min(route.shopping_cost);
+            let next_time = inside
+                .get(i + 1)
+                .map_or(reference_time, |next| next.shopping_time);
+            volume += (next_time - route.shopping_time) * (reference_cost - best_cost);
+        }
+        volume
+    }
+
+    /// Summary of how spread out the skyline's options are
+    pub fn diversity_metrics(&self) -> DiversityMetrics {
+        let count = self.routes.len();
+        if count == 0 {
+            return DiversityMetrics::default();
+        }
+
+        let range = |value: fn(&ShoppingRoute) -> f64| {
+            let (min, max) = self
+                .routes
+                .iter()
+                .map(value)
+                .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), v| {
+                    (min.min(v), max.max(v))
+                });
+            max - min
+        };
+        let time_range = range(|route| route.shopping_time);
+        DiversityMetrics {
+            time_range,
+            cost_range: range(|route| route.shopping_cost),
+            avg_time_step: if count > 1 {
+                time_range / (count - 1) as f64
+            } else {
+                0.0
+            },
+            count,
+        }
+    }
+
+    /// The best balanced route: the knee of the time-cost curve
+    ///
+    /// Picks the route farthest from the line joining the fastest and cheapest
+    /// routes, with both axes scaled to the range between those two so time and cost
+    /// weigh equally. With fewer than 3 routes, or no route bulging below that line,
+    /// the fastest route is returned.
+    pub fn knee_point(&self) -> Option<&ShoppingRoute> {
+        let fastest = self.fastest()?;
+        let cheapest = self.cheapest()?;
+        let time_span = cheapest.shopping_time - fastest.shopping_time;
+        let cost_span = fastest.shopping_cost - cheapest.shopping_cost;
+        if self.routes.len() < 3 || time_span <= 0.0 || cost_span <= 0.0 {
+            return Some(fastest);
+        }
+
+        // In scaled coordinates the line runs from (0, 1) to (1, 0), so the distance
+        // below it is proportional to 1 - time - cost
+        let mut knee = fastest;
+        let mut knee_distance = 0.0;
+        for route in &self.routes {
+            let time = (route.shopping_time - fastest.shopping_time) / time_span;
+            let cost = (route.shopping_cost - cheapest.shopping_cost) / cost_span;
+            let distance = 1.0 - time - cost;
+            if distance > knee_distance {
+                knee = route;
+                knee_distance = distance;
+            }
+        }
+        Some(knee)
+    }
+
+    /// The route covering the least distance, as measured by `distance` (e.g.
+    /// `BSLPSD::route_distance`); the fastest of equally short routes
+    /// Routes don't record their distance, so the caller supplies it.
+    pub fn greenest_route<F>(&self, distance: F) -> Option<&ShoppingRoute>
+    where
+        F: Fn(&ShoppingRoute) -> f64,
+    {
+        self.routes
+            .iter()
+            .map(|route| (distance(route), route))
+            .reduce(|best, candidate| {
+                if candidate.0 < best.0 {
+                    candidate
+                } else {
+                    best
+                }
+            })
+            .map(|(_, route)| route)
+    }
+
+    /// Keeps only the routes matching `keep`
+    /// Removing routes never makes the remaining ones dominated.
+    pub fn retain<F>(&mut self, keep: F)
+    where
+        F: FnMut(&ShoppingRoute) -> bool,
+    {
+        self.routes.retain(keep);
+    }
+
+    pub fn into_vec(self) -> Vec<ShoppingRoute> {
+        self.routes
+    }
+}
+
+impl Deref for Skyline {
+    type Target = [ShoppingRoute];
+
+    fn deref(&self) -> &[ShoppingRoute] {
+        &self.routes
+    }
+}
+
+impl PartialEq<Vec<ShoppingRoute>> for Skyline {
+    fn eq(&self, other: &Vec<ShoppingRoute>) -> bool {
+        &self.routes == other
+    }
+}
+
+impl IntoIterator for Skyline {
+    type Item = ShoppingRoute;
+    type IntoIter = std::vec::IntoIter<ShoppingRoute>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.routes.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a Skyline {
+    type Item = &'a ShoppingRoute;
+    type IntoIter = std::slice::Iter<'a, ShoppingRoute>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.routes.iter()
+    }
+}
+
+impl FromIterator<ShoppingRoute> for Skyline {
+    /// Builds a conventional skyline from the non-dominated routes
+    fn from_iter<I: IntoIterator<Item = ShoppingRoute>>(routes: I) -> Self {
+        let mut skyline = Skyline::new();
+        for route in routes {
+            skyline.insert(route);
+        }
+        skyline
+    }
+}
 
 /// Determines if a point is conventionally dominated by another
 pub fn is_conventionally_dominated(
@@ -12,72 +286,268 @@ pub fn is_conventionally_dominated(
 }
 
 /// Check if a route is linearly dominated by the skyline
+/// A route is linearly dominated when some mix of two skyline routes is no slower and
+/// no cheaper than it, i.e. when it lies on or above the lower convex hull of the
+/// skyline within the hull's time range, or is weakly dominated by a hull route.
+/// The skyline doesn't need to be convex or sorted.
 pub fn is_linearly_dominated(route: &ShoppingRoute, skyline: &[ShoppingRoute]) -> bool {
-    if skyline.is_empty() {
+    let hull = lower_convex_hull(skyline);
+    match hull.as_slice() {
+        [] => false,
+        [only] => {
+            only.shopping_time <= route.shopping_time && only.shopping_cost <= route.shopping_cost
+        }
+        _ => hull
+            .windows(2)
+            .any(|segment| is_dominated_by_segment(route, &segment[0], &segment[1])),
+    }
+}
+
+// Whether some point on the segment from `left` to `right` (left strictly faster) is
+// no slower and no cheaper than the route
+fn is_dominated_by_segment(
+    route: &ShoppingRoute,
+    left: &ShoppingRoute,
+    right: &ShoppingRoute,
+) -> bool {
+    if route.shopping_time < left.shopping_time {
         return false;
     }
 
-    // Find the position where the route would be inserted (by shopping time)
-    let mut pos = skyline.len();
-    for (i, skyline_route) in skyline.iter().enumerate() {
-        if route.shopping_time < skyline_route.shopping_time {
-            pos = i;
-            break;
+    // The cheapest point on the segment that is not slower than the route
+    let time = route.shopping_time.min(right.shopping_time);
+    let slope =
+        (right.shopping_cost - left.shopping_cost) / (right.shopping_time - left.shopping_time);
+    let cost_on_segment = left.shopping_cost + slope * (time - left.shopping_time);
+
+    cost_on_segment <= route.shopping_cost
+}
+
+/// Computes the lower-left convex boundary of the routes in (time, cost) space
+///
+/// This is the linear skyline: every route on or above the segment joining two hull
+/// routes is linearly dominated, so only the segment's end points are kept. The
+/// result is ordered by shopping time.
+pub fn lower_convex_hull(routes: &[ShoppingRoute]) -> Vec<ShoppingRoute> {
+    let mut sorted: Vec<&ShoppingRoute> = routes.iter().collect();
+    sorted.sort_by(|a, b| a.skyline_order(b));
+
+    // Drop conventionally dominated routes so costs strictly decrease with time
+    let mut frontier: Vec<&ShoppingRoute> = Vec::new();
+    for route in sorted {
+        if frontier
+            .last()
+            .is_none_or(|last| route.shopping_cost < last.shopping_cost)
+        {
+            frontier.push(route);
         }
     }
 
-    // Check conventional domination by comparing with the nearest point
-    if pos < skyline.len() {
-        let comparison = &skyline[pos];
-        if is_conventionally_dominated(
-            route.shopping_time,
-            route.shopping_cost,
-            comparison.shopping_time,
-            comparison.shopping_cost,
-        ) {
-            return true;
+    // Monotone chain over the frontier, removing points above the hull
+    let mut hull: Vec<&ShoppingRoute> = Vec::new();
+    for route in frontier {
+        while hull.len() >= 2 {
+            let o = hull[hull.len() - 2];
+            let a = hull[hull.len() - 1];
+            let cross = (a.shopping_time - o.shopping_time)
+                * (route.shopping_cost - o.shopping_cost)
+                - (a.shopping_cost - o.shopping_cost) * (route.shopping_time - o.shopping_time);
+            if cross <= 0.0 {
+                hull.pop();
+            } else {
+                break;
+            }
         }
+        hull.push(route);
     }
 
-    if pos > 0 {
-        let comparison = &skyline[pos - 1];
-        if is_conventionally_dominated(
+    hull.into_iter().cloned().collect()
+}
+
+/// Picks at most `k` routes spread across a time-sorted skyline
+///
+/// The fastest and cheapest ends are always kept (only the fastest for `k == 1`);
+/// the remaining picks repeatedly take the route farthest from those already chosen,
+/// with time and cost normalized to the skyline's range. The selection keeps the
+/// input order, and `k >= skyline.len()` returns the skyline unchanged.
+pub fn select_spread(skyline: &[ShoppingRoute], k: usize) -> Vec<ShoppingRoute> {
+    if k >= skyline.len() {
+        return skyline.to_vec();
+    }
+    if k == 0 {
+        return Vec::new();
+    }
+    if k == 1 {
+        return vec![skyline[0].clone()];
+    }
+
+    let range = |values: Vec<f64>| {
+        let min = values.iter().cloned().fold(f64::INFINITY, f64::min);
+        let max = values.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+        let span = if max > min { max - min } else { 1.0 };
+        (min, span)
+    };
+    let (min_time, time_span) = range(skyline.iter().map(|r| r.shopping_time).collect());
+    let (min_cost, cost_span) = range(skyline.iter().map(|r| r.shopping_cost).collect());
+    let points: Vec<(f64, f64)> = skyline
+        .iter()
+        .map(|r| {
+            (
+                (r.shopping_time - min_time) / time_span,
+                (r.shopping_cost - min_cost) / cost_span,
+            )
+        })
+        .collect();
+    let distance = |a: usize, b: usize| {
+        ((points[a].0 - points[b].0).powi(2) + (points[a].1 - points[b].1).powi(2)).sqrt()
+    };
+
+    let mut chosen = vec![0, skyline.len() - 1];
+    while chosen.len() < k {
+        let next = (0..skyline.len())
+            .filter(|i| !chosen.contains(i))
+            .map(|i| {
+                let gap = chosen
+                    .iter()
+                    .map(|&c| distance(i, c))
+                    .fold(f64::INFINITY, f64::min);
+                (i, gap)
+            })
+            // Earliest index wins ties
+            .fold(None, |best: Option<(usize, f64)>, (i, gap)| match best {
+                Some((_, best_gap)) if best_gap >= gap => best,
+                _ => Some((i, gap)),
+            });
+        match next {
+            Some((i, _)) => chosen.push(i),
+            None => break,
+        }
+    }
+
+    chosen.sort();
+    chosen.into_iter().map(|i| skyline[i].clone()).collect()
+}
+
+/// Keeps the routes no other route dominates in time, cost and store count (see
+/// `ShoppingRoute::dominates_with_store_count`), ordered by store count and then as in
+/// a skyline
+/// Of routes tying on all three objectives only the first in that order is kept.
+pub fn three_objective_front(routes: &[ShoppingRoute]) -> Vec<ShoppingRoute> {
+    let mut sorted: Vec<&ShoppingRoute> = routes.iter().collect();
+    sorted.sort_by(|a, b| {
+        a.store_count()
+            .cmp(&b.store_count())
+            .then_with(|| a.skyline_order(b))
+    });
+
+    let mut front: Vec<ShoppingRoute> = Vec::new();
+    for route in sorted {
+        let covered = front.iter().any(|kept| {
+            kept.dominates_with_store_count(route)
+                || (kept.shopping_time == route.shopping_time
+                    && kept.shopping_cost == route.shopping_cost
+                    && kept.store_count() == route.store_count())
+        });
+        if !covered {
+            front.push(route.clone());
+        }
+    }
+    front
+}
+
+/// Formats routes as a time/cost table, followed by how the fastest route compares
+/// with the cheapest one when they differ
+pub fn format_skyline(routes: &[ShoppingRoute]) -> String {
+    if routes.is_empty() {
+        return "No routes\n".to_string();
+    }
+
+    let mut output = format!("{:>3}  {:>10}  {:>10}  Stores\n", "#", "Time", "Cost");
+    for (index, route) in routes.iter().enumerate() {
+        let _ = writeln!(
+            output,
+            "{:>3}  {:>10.2}  {:>10.2}  {}",
+            index + 1,
             route.shopping_time,
             route.shopping_cost,
-            comparison.shopping_time,
-            comparison.shopping_cost,
-        ) {
-            return true;
-        }
+            format_stores(&route.stores)
+        );
     }
 
-    // Check for linear domination
-    if pos > 0 && pos < skyline.len() {
-        let left = &skyline[pos - 1];
-        let right = &skyline[pos];
+    let fastest = routes
+        .iter()
+        .min_by(|a, b| a.shopping_time.total_cmp(&b.shopping_time))
+        .unwrap();
+    let cheapest = routes
+        .iter()
+        .min_by(|a, b| a.shopping_cost.total_cmp(&b.shopping_cost))
+        .unwrap();
+    if fastest != cheapest {
+        let _ = writeln!(
+            output,
+            "Fastest route is {:.1}% faster but {:.1}% more expensive than the cheapest route.",
+            100.0 * (cheapest.shopping_time - fastest.shopping_time) / cheapest.shopping_time,
+            100.0 * (fastest.shopping_cost - cheapest.shopping_cost) / cheapest.shopping_cost
+        );
+    }
 
-        // Check if the point is above the line connecting left and right
-        // This is a simplified implementation - in practice, we need to check
-        // all pairs of adjacent points in the skyline
-        let x1 = left.shopping_time;
-        let y1 = left.shopping_cost;
-        let x2 = right.shopping_time;
-        let y2 = right.shopping_cost;
-        let x = route.shopping_time;
-        let y = route.shopping_cost;
+    output
+}
 
-        if x2 > x1 {
-            // Calculate the y-coordinate on the line at point x
-            let slope = (y2 - y1) / (x2 - x1);
-            let y_on_line = y1 + slope * (x - x1);
+/// Makes way for `route` among routes over the same stores in another order
+/// Returns false if the skyline already has a route over its stores that is at least
+/// as fast; otherwise removes any slower one and returns true
+pub(crate) fn remove_slower_permutation(
+    skyline: &mut Vec<ShoppingRoute>,
+    route: &ShoppingRoute,
+) -> bool {
+    let Some(index) = skyline
+        .iter()
+        .position(|existing| existing.same_stores(route))
+    else {
+        return true;
+    };
+    if skyline[index].shopping_time <= route.shopping_time {
+        return false;
+    }
+    skyline.remove(index);
+    true
+}
 
-            if y > y_on_line {
-                return true;
-            }
-        }
+/// Inserts a route into a conventional skyline sorted by `ShoppingRoute::skyline_order`
+/// unless a route in it dominates or equals the new one, removing the routes it dominates
+///
+/// Such a skyline gets dearer as it gets faster, so only the last route faster than the
+/// new one and the routes exactly as fast can dominate it, and the routes it dominates
+/// are the run from its position up to the first cheaper route. Both are found by
+/// binary search, leaving the insertion itself as the only linear step.
+pub(crate) fn insert_sorted_conventional(
+    skyline: &mut Vec<ShoppingRoute>,
+    route: ShoppingRoute,
+) -> bool {
+    let faster = skyline.partition_point(|existing| existing.shopping_time < route.shopping_time);
+    let as_fast = faster
+        + skyline[faster..]
+            .partition_point(|existing| existing.shopping_time == route.shopping_time);
+    if skyline[faster.saturating_sub(1)..as_fast]
+        .iter()
+        .any(|existing| existing.conventionally_dominates(&route) || existing == &route)
+    {
+        return false;
     }
 
-    false
+    let no_cheaper = faster
+        + skyline[faster..]
+            .partition_point(|existing| existing.shopping_cost >= route.shopping_cost);
+    let kept: Vec<ShoppingRoute> = skyline
+        .drain(faster..no_cheaper)
+        .filter(|existing| !route.conventionally_dominates(existing))
+        .collect();
+    skyline.splice(faster..faster, kept);
+
+    let position = skyline.partition_point(|existing| existing.skyline_order(&route).is_le());
+    skyline.insert(position, route);
+    true
 }
 
 /// Inserts a route into the skyline if it's not dominated
@@ -91,7 +561,7 @@ pub fn insert_into_skyline(skyline: &mut Vec<ShoppingRoute>, route: ShoppingRout
     // Find insertion position
     let mut pos = skyline.len();
     for (i, skyline_route) in skyline.iter().enumerate() {
-        if route.shopping_time < skyline_route.shopping_time {
+        if route.skyline_order(skyline_route).is_lt() {
             pos = i;
             break;
         }
@@ -113,12 +583,34 @@ pub fn insert_into_skyline(skyline: &mut Vec<ShoppingRoute>, route: ShoppingRout
         }
     }
 
+    remove_linearly_dominated(skyline);
+
     true
 }
 
+/// Removes routes that lie on or above the segment joining their neighbours
+/// The skyline must be sorted by time and free of conventionally dominated routes
+fn remove_linearly_dominated(skyline: &mut Vec<ShoppingRoute>) {
+    let mut i = 1;
+    while i + 1 < skyline.len() {
+        let (left, middle, right) = (&skyline[i - 1], &skyline[i], &skyline[i + 1]);
+        let cross = (middle.shopping_time - left.shopping_time)
+            * (right.shopping_cost - left.shopping_cost)
+            - (middle.shopping_cost - left.shopping_cost)
+                * (right.shopping_time - left.shopping_time);
+        if cross <= 0.0 {
+            skyline.remove(i);
+            // The previous route has a new neighbour, so check it again
+            i = i.saturating_sub(1).max(1);
+        } else {
+            i += 1;
+        }
+    }
+}
+
 /// Checks if a route is dominated by any route in the skyline
 fn is_dominated_by_skyline(route: &ShoppingRoute, skyline: &[ShoppingRoute]) -> bool {
-    for (_i, skyline_route) in skyline.iter().enumerate() {
+    for skyline_route in skyline {
         if std::ptr::eq(route, skyline_route) {
             continue; // Skip comparing with itself
         }
@@ -189,4 +681,385 @@ mod tests {
         assert_eq!(skyline[0].shopping_time, 10.0);
         assert_eq!(skyline[1].shopping_time, 15.0);
     }
+
+    #[test]
+    fn test_lower_convex_hull_excludes_interior_point() {
+        let route = |stores: Vec<u32>, time: f64, cost: f64| ShoppingRoute {
+            stores,
+            shopping_time: time,
+            shopping_cost: cost,
+        };
+        let routes = vec![
+            route(vec![4], 4.0, 2.0),
+            route(vec![1], 1.0, 10.0),
+            route(vec![3], 3.0, 5.0), // above the segment (2, 6) - (4, 2)
+            route(vec![2], 2.0, 6.0),
+            route(vec![5], 5.0, 8.0), // conventionally dominated by (4, 2)
+            route(vec![6], 6.0, 1.0),
+        ];
+
+        let hull = lower_convex_hull(&routes);
+        let hull_stores: Vec<Vec<u32>> = hull.iter().map(|r| r.stores.clone()).collect();
+
+        assert_eq!(hull_stores, vec![vec![1], vec![2], vec![4], vec![6]]);
+        assert!(is_linearly_dominated(&routes[2], &hull));
+    }
+
+    #[test]
+    fn test_select_spread() {
+        // Two near-identical routes at each end of the frontier and one in the middle
+        let skyline: Vec<ShoppingRoute> = [
+            (10.0, 100.0),
+            (10.5, 99.0),
+            (20.0, 50.0),
+            (29.5, 11.0),
+            (30.0, 10.0),
+        ]
+        .iter()
+        .enumerate()
+        .map(|(i, &(time, cost))| ShoppingRoute::new(vec![i as u32], time, cost))
+        .collect();
+
+        assert!(select_spread(&skyline, 0).is_empty());
+        assert_eq!(select_spread(&skyline, 1), vec![skyline[0].clone()]);
+        assert_eq!(select_spread(&skyline, 5), skyline);
+        assert_eq!(select_spread(&skyline, 9), skyline);
+
+        let picked: Vec<Vec<u32>> = select_spread(&skyline, 3)
+            .into_iter()
+            .map(|r| r.stores)
+            .collect();
+        assert_eq!(picked, vec![vec![0], vec![2], vec![4]]);
+    }
+
+    #[test]
+    fn test_insertion_drops_collinear_interior_route() {
+        let collinear = [
+            ShoppingRoute::new(vec![1], 1.0, 10.0),
+            ShoppingRoute::new(vec![2], 2.0, 6.0),
+            ShoppingRoute::new(vec![3], 3.0, 2.0),
+        ];
+
+        // Whatever the insertion order, only the two ends of the line remain
+        for order in [[0, 1, 2], [1, 0, 2], [0, 2, 1], [2, 1, 0]] {
+            let mut skyline = Vec::new();
+            for i in order {
+                insert_into_skyline(&mut skyline, collinear[i].clone());
+            }
+            let stores: Vec<Vec<u32>> = skyline.iter().map(|r| r.stores.clone()).collect();
+            assert_eq!(stores, vec![vec![1], vec![3]], "order {:?}", order);
+        }
+
+        let hull = lower_convex_hull(&collinear);
+        assert_eq!(hull, vec![collinear[0].clone(), collinear[2].clone()]);
+    }
+
+    #[test]
+    fn test_linear_domination_by_non_adjacent_segment() {
+        // A conventional skyline whose middle routes sit above the line from the first
+        // route to the last
+        let skyline = vec![
+            ShoppingRoute::new(vec![1], 0.0, 10.0),
+            ShoppingRoute::new(vec![2], 1.0, 9.5),
+            ShoppingRoute::new(vec![3], 2.0, 9.0),
+            ShoppingRoute::new(vec![4], 10.0, 0.0),
+        ];
+
+        // Below the segment (1, 9.5) - (2, 9.0) it falls between, but above the line
+        // from (0, 10) to (10, 0), which costs 8.5 at time 1.5
+        let route = ShoppingRoute::new(vec![5], 1.5, 8.9);
+        assert!(is_linearly_dominated(&route, &skyline));
+
+        let mut with_route = skyline.clone();
+        assert!(!insert_into_skyline(&mut with_route, route));
+
+        // Below that line nothing dominates it
+        let route = ShoppingRoute::new(vec![6], 1.5, 8.4);
+        assert!(!is_linearly_dominated(&route, &skyline));
+
+        // Outside the time range only the end routes can dominate
+        assert!(!is_linearly_dominated(
+            &ShoppingRoute::new(vec![7], -1.0, 20.0),
+            &skyline
+        ));
+        assert!(is_linearly_dominated(
+            &ShoppingRoute::new(vec![8], 11.0, 0.0),
+            &skyline
+        ));
+        assert!(!is_linearly_dominated(
+            &ShoppingRoute::new(vec![9], 11.0, -0.5),
+            &skyline
+        ));
+    }
+
+    #[test]
+    fn test_skyline_struct_insert_and_queries() {
+        let mut skyline = Skyline::new();
+        assert!(skyline.fastest().is_none());
+        assert!(skyline.cheapest().is_none());
+        assert_eq!(skyline.hypervolume((10.0, 10.0)), 0.0);
+
+        // Inserted out of order, kept sorted by time
+        assert!(skyline.insert(ShoppingRoute::new(vec![3], 6.0, 2.0)));
+        assert!(skyline.insert(ShoppingRoute::new(vec![1], 2.0, 8.0)));
+        assert!(skyline.insert(ShoppingRoute::new(vec![2], 4.0, 4.0)));
+        assert!(!skyline.insert(ShoppingRoute::new(vec![4], 5.0, 4.0)));
+        assert!(!skyline.insert(ShoppingRoute::new(vec![2], 4.0, 4.0)));
+
+        // Dominates (4, 4), which is removed
+        assert!(skyline.insert(ShoppingRoute::new(vec![5], 4.0, 3.0)));
+
+        let stores: Vec<Vec<u32>> = skyline.iter().map(|r| r.stores.clone()).collect();
+        assert_eq!(stores, vec![vec![1], vec![5], vec![3]]);
+        assert_eq!(skyline.len(), 3);
+        assert_eq!(skyline[0].stores, vec![1]);
+        assert_eq!(skyline.fastest().unwrap().stores, vec![1]);
+        assert_eq!(skyline.cheapest().unwrap().stores, vec![3]);
+
+        // Staircase below (10, 10): 2 * 2 + 2 * 7 + 4 * 8
+        assert!((skyline.hypervolume((10.0, 10.0)) - 50.0).abs() < 1e-9);
+        // Only (2, 8) lies below this reference point
+        assert!((skyline.hypervolume((4.0, 9.0)) - 2.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_skyline_struct_domination_rules() {
+        let routes = [
+            ShoppingRoute::new(vec![1], 1.0, 10.0),
+            ShoppingRoute::new(vec![2], 2.0, 6.0),
+            ShoppingRoute::new(vec![3], 2.0, 8.0), // ties the time of (2, 6)
+            ShoppingRoute::new(vec![4], 3.0, 2.0),
+        ];
+        let build = |domination| {
+            let mut skyline = Skyline::with_domination(domination);
+            for route in routes.iter().cloned() {
+                skyline.insert(route);
+            }
+            skyline.iter().map(|r| r.stores[0]).collect::<Vec<u32>>()
+        };
+
+        assert_eq!(build(Domination::Conventional), vec![1, 2, 4]);
+        assert_eq!(build(Domination::Strict), vec![1, 2, 3, 4]);
+        // (2, 6) lies on the line from (1, 10) to (3, 2)
+        assert_eq!(build(Domination::Linear), vec![1, 4]);
+    }
+
+    #[test]
+    fn test_format_skyline() {
+        let routes = vec![
+            ShoppingRoute::new(vec![1, 3], 10.0, 50.0),
+            ShoppingRoute::new(vec![2, 4, 5], 20.0, 40.0),
+        ];
+
+        let table = format_skyline(&routes);
+
+        assert!(table.contains("1→3"));
+        assert!(table.contains("2→4→5"));
+        assert!(table.contains("10.00") && table.contains("50.00"));
+        assert!(table.contains("20.00") && table.contains("40.00"));
+        assert!(table.contains(
+            "Fastest route is 50.0% faster but 25.0% more expensive than the cheapest route."
+        ));
+
+        // A single route has nothing to trade off
+        assert!(!format_skyline(&routes[..1]).contains("Fastest"));
+        assert_eq!(format_skyline(&[]), "No routes\n");
+    }
+
+    #[test]
+    fn test_permutations_of_a_store_set_are_kept_once() {
+        for domination in [
+            Domination::Conventional,
+            Domination::Strict,
+            Domination::Linear,
+        ] {
+            // Same stores, time and cost in another order: only the first survives
+            let mut skyline = Skyline::with_domination(domination);
+            assert!(skyline.insert(ShoppingRoute::new(vec![1, 2], 10.0, 30.0)));
+            assert!(!skyline.insert(ShoppingRoute::new(vec![2, 1], 10.0, 30.0)));
+            assert_eq!(skyline.len(), 1);
+            assert_eq!(skyline[0].stores, vec![1, 2]);
+
+            // A faster ordering replaces a slower one, a slower ordering is dropped
+            assert!(skyline.insert(ShoppingRoute::new(vec![3, 4], 20.0, 25.0)));
+            assert!(skyline.insert(ShoppingRoute::new(vec![4, 3], 18.0, 25.0)));
+            assert!(!skyline.insert(ShoppingRoute::new(vec![3, 4], 19.0, 25.0)));
+            let stores: Vec<Vec<u32>> = skyline.iter().map(|route| route.stores.clone()).collect();
+            assert_eq!(stores, vec![vec![1, 2], vec![4, 3]], "{:?}", domination);
+        }
+    }
+
+    #[test]
+    fn test_equally_fast_routes_have_a_fixed_order() {
+        let routes = [
+            ShoppingRoute::new(vec![7], 10.0, 30.0),
+            ShoppingRoute::new(vec![5, 2], 10.0, 30.0),
+            ShoppingRoute::new(vec![3], 10.0, 20.0),
+            ShoppingRoute::new(vec![1, 8], 10.0, 30.0),
+        ];
+        let mut orders = Vec::new();
+        for rotation in 0..routes.len() {
+            // Strict domination keeps routes that tie on time
+            let mut skyline = Skyline::with_domination(Domination::Strict);
+            for route in routes.iter().cycle().skip(rotation).take(routes.len()) {
+                skyline.insert(route.clone());
+            }
+            orders.push(skyline.into_vec());
+        }
+
+        let expected: Vec<Vec<u32>> = vec![vec![3], vec![1, 8], vec![5, 2], vec![7]];
+        for order in orders {
+            let stores: Vec<Vec<u32>> = order.iter().map(|route| route.stores.clone()).collect();
+            assert_eq!(stores, expected);
+        }
+    }
+
+    #[test]
+    fn test_knee_point() {
+        // Cost drops sharply up to time 2, then barely improves
+        let skyline: Skyline = [(1.0, 100.0), (2.0, 20.0), (3.0, 15.0), (10.0, 10.0)]
+            .into_iter()
+            .enumerate()
+            .map(|(i, (time, cost))| ShoppingRoute::new(vec![i as u32 + 1], time, cost))
+            .collect();
+        let knee = skyline.knee_point().unwrap();
+        assert_eq!((knee.shopping_time, knee.shopping_cost), (2.0, 20.0));
+
+        // Scaling an axis doesn't move the knee
+        let scaled: Skyline = skyline
+            .iter()
+            .map(|route| {
+                ShoppingRoute::new(
+                    route.stores.clone(),
+                    route.shopping_time * 60.0,
+                    route.shopping_cost,
+                )
+            })
+            .collect();
+        assert_eq!(scaled.knee_point().unwrap().stores, knee.stores);
+
+        // Degenerate skylines fall back to the fastest route
+        assert!(Skyline::new().knee_point().is_none());
+        let two: Skyline = [
+            ShoppingRoute::new(vec![1], 1.0, 10.0),
+            ShoppingRoute::new(vec![2], 2.0, 5.0),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(two.knee_point().unwrap().stores, vec![1]);
+
+        // Points on a straight line have no knee
+        let line: Skyline = [(1.0, 3.0), (2.0, 2.0), (3.0, 1.0)]
+            .into_iter()
+            .enumerate()
+            .map(|(i, (time, cost))| ShoppingRoute::new(vec![i as u32 + 1], time, cost))
+            .collect();
+        assert_eq!(line.knee_point().unwrap().stores, vec![1]);
+    }
+
+    #[test]
+    fn test_diversity_metrics() {
+        assert_eq!(
+            Skyline::new().diversity_metrics(),
+            DiversityMetrics::default()
+        );
+
+        let mut skyline = Skyline::new();
+        skyline.insert(ShoppingRoute::new(vec![1], 12.0, 30.0));
+        let one = skyline.diversity_metrics();
+        assert_eq!(one.count, 1);
+        assert_eq!(one.time_range, 0.0);
+        assert_eq!(one.cost_range, 0.0);
+        assert_eq!(one.avg_time_step, 0.0);
+
+        skyline.insert(ShoppingRoute::new(vec![2], 18.0, 22.0));
+        let two = skyline.diversity_metrics();
+        assert_eq!(two.count, 2);
+        assert_eq!(two.time_range, 6.0);
+        assert_eq!(two.cost_range, 8.0);
+        assert_eq!(two.avg_time_step, 6.0);
+
+        skyline.insert(ShoppingRoute::new(vec![3], 20.0, 21.0));
+        skyline.insert(ShoppingRoute::new(vec![4], 30.0, 15.0));
+        skyline.insert(ShoppingRoute::new(vec![5], 10.0, 40.0));
+        let many = skyline.diversity_metrics();
+        assert_eq!(many.count, 5);
+        assert_eq!(many.time_range, 20.0);
+        assert_eq!(many.cost_range, 25.0);
+        assert_eq!(many.avg_time_step, 5.0);
+    }
+
+    #[test]
+    fn test_three_objective_front() {
+        let routes = [
+            ShoppingRoute::new(vec![1, 2, 3], 10.0, 3.0),
+            ShoppingRoute::new(vec![4, 3], 12.0, 5.0),
+            ShoppingRoute::new(vec![3, 4], 12.5, 5.0),
+            ShoppingRoute::new(vec![1, 4, 3], 11.0, 4.0),
+            ShoppingRoute::new(vec![2, 1, 3], 10.0, 3.0),
+        ];
+        let front = three_objective_front(&routes);
+
+        // The 2-store route survives though 3-store routes beat it on time and cost
+        let stores: Vec<Vec<u32>> = front.into_iter().map(|route| route.stores).collect();
+        assert_eq!(stores, vec![vec![4, 3], vec![1, 2, 3]]);
+        assert!(three_objective_front(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_insert_matches_naive_skyline() {
+        use rand::{rngs::StdRng, Rng, SeedableRng};
+
+        // The two linear passes the conventional skyline was pruned with before
+        fn naive_insert(routes: &mut Vec<ShoppingRoute>, route: ShoppingRoute) -> bool {
+            if routes
+                .iter()
+                .any(|existing| existing.conventionally_dominates(&route) || existing == &route)
+            {
+                return false;
+            }
+            routes.retain(|existing| !route.conventionally_dominates(existing));
+            let position =
+                routes.partition_point(|existing| existing.skyline_order(&route).is_le());
+            routes.insert(position, route);
+            true
+        }
+
+        // Mostly trade-off routes on a coarse grid, so the skyline grows large and has ties
+        let mut rng = StdRng::seed_from_u64(828);
+        let routes: Vec<ShoppingRoute> = (0..10_000)
+            .map(|_| {
+                let stores: Vec<u32> = (0..rng.gen_range(1..=4))
+                    .map(|_| rng.gen_range(1..=12))
+                    .collect();
+                let time = rng.gen_range(0..5_000) as f64;
+                let cost = (5_000.0 - time + rng.gen_range(0..40) as f64).floor();
+                ShoppingRoute::new(stores, time, cost)
+            })
+            .collect();
+
+        // Both sides share the permutation scan; the domination step is compared
+        let mut skyline = Skyline::new();
+        let mut sorted = Vec::new();
+        let mut naive = Vec::new();
+        for route in routes {
+            let inserted = skyline.insert(route.clone());
+            let kept = remove_slower_permutation(&mut sorted, &route);
+            assert_eq!(kept, remove_slower_permutation(&mut naive, &route));
+            if !kept {
+                assert!(!inserted);
+                continue;
+            }
+
+            let sorted_inserted = insert_sorted_conventional(&mut sorted, route.clone());
+            let naive_inserted = naive_insert(&mut naive, route);
+
+            assert_eq!(sorted_inserted, naive_inserted);
+            assert_eq!(inserted, naive_inserted);
+        }
+
+        assert_eq!(sorted, naive);
+        assert_eq!(skyline.routes, naive);
+        assert!(skyline.len() > 100);
+    }
 }
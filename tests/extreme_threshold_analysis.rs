@@ -5,7 +5,6 @@ use personal_shopper::models::{Location, ShoppingList};
 use personal_shopper::utils::init_map::init_map_with_road_network;
 use plotters::prelude::*;
 use rand::Rng;
-use std::collections::HashMap;
 use std::error::Error;
 use std::time::Instant;
 
@@ -37,16 +36,12 @@ fn test_extreme_threshold_analysis() -> Result<(), Box<dyn Error>> {
     // Create shopping list
     let mut shopping_list = ShoppingList::new();
 
+    // Initialize BSLPSD algorithm
+    let mut bsl_psd = BSLPSD::new_with_travel_times(stores.clone(), travel_times);
+    bsl_psd.precompute_data();
+
     // Find available products
-    let mut available_products = HashMap::new();
-    for (_store_id, store) in &stores {
-        for (product_id, product) in &store.products {
-            let entry = available_products
-                .entry(*product_id)
-                .or_insert((product.name.clone(), 0));
-            entry.1 += store.get_inventory_level(product_id);
-        }
-    }
+    let available_products = bsl_psd.available_products();
 
     println!("\nAvailable products:");
     for (product_id, (name, total_supply)) in &available_products {
@@ -90,10 +85,6 @@ fn test_extreme_threshold_analysis() -> Result<(), Box<dyn Error>> {
         }
     }
 
-    // Initialize BSLPSD algorithm
-    let mut bsl_psd = BSLPSD::new_with_travel_times(stores.clone(), travel_times);
-    bsl_psd.precompute_data();
-
     // Define start and end points (shopper and customer locations)
     let shopper_location = Location::new(4.8950, 52.3664); // 阿姆斯特丹市中心餐厅密集区
     let customer_location = Location::new(4.8730, 52.3383); // 阿姆斯特丹市中心偏南住宅区
@@ -346,8 +337,8 @@ fn visualize_time_analysis(
     // Add legend
     chart
         .configure_series_labels()
-        .background_style(&WHITE.mix(0.8))
-        .border_style(&BLACK)
+        .background_style(WHITE.mix(0.8))
+        .border_style(BLACK)
         .position(SeriesLabelPosition::UpperRight)
         .draw()?;
 
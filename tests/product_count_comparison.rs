@@ -54,7 +54,7 @@ fn test_product_count_comparison() -> Result<(), Box<dyn Error>> {
 
         // Find available products
         let mut available_products = HashMap::new();
-        for (_store_id, store) in &stores {
+        for store in stores.values() {
             for (product_id, product) in &store.products {
                 let entry = available_products
                     .entry(*product_id)
@@ -422,7 +422,7 @@ fn create_summary_chart(
     let max_time_ms = total_times
         .iter()
         .map(|t| t.as_millis() as f64)
-        .fold(0.0, |a, b| f64::max(a, b));
+        .fold(0.0, f64::max);
 
     let max_route_count = *route_counts.iter().max().unwrap_or(&1) as f64;
 
@@ -561,9 +561,9 @@ fn create_summary_chart(
         .draw()?;
 
     // Draw route count bars
-    for i in 0..product_counts.len() {
+    for (i, &route_count) in route_counts.iter().enumerate().take(product_counts.len()) {
         let idx = i as f64;
-        let count = route_counts[i] as f64;
+        let count = route_count as f64;
 
         chart_routes.draw_series(std::iter::once(Rectangle::new(
             [(idx, 0.0), (idx + 0.8, count)],
@@ -572,7 +572,7 @@ fn create_summary_chart(
 
         // Add label
         chart_routes.draw_series(std::iter::once(Text::new(
-            format!("{}", route_counts[i]),
+            format!("{}", route_count),
             (idx + 0.4, count / 2.0),
             ("sans-serif", 18).into_font(),
         )))?;
@@ -42,7 +42,7 @@ fn test_product_count_threshold_analysis() -> Result<(), Box<dyn Error>> {
 
     // Find available products
     let mut available_products = HashMap::new();
-    for (_store_id, store) in &stores {
+    for store in stores.values() {
         for (product_id, product) in &store.products {
             let entry = available_products
                 .entry(*product_id)
@@ -178,21 +178,19 @@ fn test_product_count_threshold_analysis() -> Result<(), Box<dyn Error>> {
                 );
 
                 // Calculate route diversity metrics for this product count
-                let route_count = routes.len();
-                let time_range = cheapest.shopping_time - fastest.shopping_time;
-                let cost_range = fastest.shopping_cost - cheapest.shopping_cost;
+                let diversity = routes.diversity_metrics();
+                let route_count = diversity.count;
 
                 println!("  Route Diversity Metrics:");
                 println!("    - Number of Pareto-optimal routes: {}", route_count);
-                println!("    - Time range covered: {:.2} minutes", time_range);
-                println!("    - Cost range covered: ${:.2}", cost_range);
+                println!(
+                    "    - Time range covered: {:.2} minutes",
+                    diversity.time_range
+                );
+                println!("    - Cost range covered: ${:.2}", diversity.cost_range);
                 println!(
                     "    - Average time step between routes: {:.2} minutes",
-                    if route_count > 1 {
-                        time_range / (route_count - 1) as f64
-                    } else {
-                        0.0
-                    }
+                    diversity.avg_time_step
                 );
 
                 // Store trade-off metrics for comparison across product counts
@@ -488,8 +486,8 @@ fn visualize_product_count_time(
     // Add legend
     chart
         .configure_series_labels()
-        .background_style(&WHITE.mix(0.8))
-        .border_style(&BLACK)
+        .background_style(WHITE.mix(0.8))
+        .border_style(BLACK)
         .position(SeriesLabelPosition::UpperLeft)
         .draw()?;
 
@@ -516,14 +514,11 @@ fn visualize_product_count_trade_off(
     let count_padding = 1; // Add padding for x-axis
 
     // Calculate max values for other metrics
-    let max_efficiency = results
-        .iter()
-        .map(|&(_, e, _, _, _)| e)
-        .fold(0.0, |a, b| f64::max(a, b));
+    let max_efficiency = results.iter().map(|&(_, e, _, _, _)| e).fold(0.0, f64::max);
     let max_percent = results
         .iter()
         .map(|&(_, _, time_diff, cost_diff, _)| f64::max(time_diff, cost_diff))
-        .fold(0.0, |a, b| f64::max(a, b));
+        .fold(0.0, f64::max);
     let max_routes = results.iter().map(|&(_, _, _, _, r)| r).max().unwrap_or(0);
 
     // 1. Draw efficiency chart
@@ -627,8 +622,8 @@ fn visualize_product_count_trade_off(
     // Add legend
     percent_chart
         .configure_series_labels()
-        .background_style(&WHITE.mix(0.8))
-        .border_style(&BLACK)
+        .background_style(WHITE.mix(0.8))
+        .border_style(BLACK)
         .position(SeriesLabelPosition::UpperRight)
         .draw()?;
 
@@ -718,8 +713,8 @@ fn visualize_product_count_trade_off(
     // Add legend
     route_chart
         .configure_series_labels()
-        .background_style(&WHITE.mix(0.8))
-        .border_style(&BLACK)
+        .background_style(WHITE.mix(0.8))
+        .border_style(BLACK)
         .position(SeriesLabelPosition::UpperRight)
         .draw()?;
 
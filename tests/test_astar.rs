@@ -0,0 +1,48 @@
+// Integration test checking the A* minimum-time route against Dijkstra
+use personal_shopper::algorithms::bsl_psd::BSLPSD;
+use personal_shopper::models::{Location, ShoppingList};
+use personal_shopper::utils::init_map::init_map_with_road_network;
+use std::error::Error;
+
+#[test]
+fn test_astar_matches_dijkstra_shopping_time() -> Result<(), Box<dyn Error>> {
+    let city_code = "AMS";
+    let total_product_supply = 30;
+
+    let (stores, travel_times) =
+        init_map_with_road_network(city_code, false, total_product_supply)?;
+
+    let shopper_location = Location::new(4.8950, 52.3664);
+    let customer_location = Location::new(4.8730, 52.3383);
+
+    // Shopping list with the first 5 products on offer
+    let mut product_ids: Vec<u32> = stores
+        .values()
+        .flat_map(|store| store.products.keys().cloned())
+        .collect();
+    product_ids.sort();
+    product_ids.dedup();
+    let mut shopping_list = ShoppingList::new();
+    for &product_id in &product_ids[..5] {
+        shopping_list.add_item(product_id, 3);
+    }
+
+    let mut solver = BSLPSD::new_with_travel_times(stores, travel_times);
+    solver.precompute_data();
+
+    let dijkstra = solver
+        .find_min_time_route_dijkstra(&shopping_list, shopper_location, customer_location)
+        .expect("the shopping list can be fulfilled");
+    let astar = solver
+        .find_min_time_route_astar(&shopping_list, shopper_location, customer_location)
+        .expect("the shopping list can be fulfilled");
+
+    println!(
+        "Dijkstra: {:?} time {:.3}, A*: {:?} time {:.3}",
+        dijkstra.stores, dijkstra.shopping_time, astar.stores, astar.shopping_time
+    );
+
+    assert!((astar.shopping_time - dijkstra.shopping_time).abs() < 1e-9);
+
+    Ok(())
+}
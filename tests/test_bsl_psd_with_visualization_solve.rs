@@ -33,16 +33,12 @@ fn test_bsl_psd_with_visualization() -> Result<(), Box<dyn Error>> {
     // Create shopping list
     let mut shopping_list = ShoppingList::new();
 
+    // Initialize BSLPSD algorithm
+    let mut bsl_psd = BSLPSD::new_with_travel_times(stores.clone(), travel_times);
+    bsl_psd.precompute_data();
+
     // Find available products
-    let mut available_products = HashMap::new();
-    for (_store_id, store) in &stores {
-        for (product_id, product) in &store.products {
-            let entry = available_products
-                .entry(*product_id)
-                .or_insert((product.name.clone(), 0));
-            entry.1 += store.get_inventory_level(product_id);
-        }
-    }
+    let available_products = bsl_psd.available_products();
 
     println!("\nAvailable products:");
     for (product_id, (name, total_supply)) in &available_products {
@@ -86,10 +82,6 @@ fn test_bsl_psd_with_visualization() -> Result<(), Box<dyn Error>> {
         }
     }
 
-    // Initialize BSLPSD algorithm
-    let mut bsl_psd = BSLPSD::new_with_travel_times(stores.clone(), travel_times);
-    bsl_psd.precompute_data();
-
     // Define start and end points (also make them more spread out)
     let shopper_location = Location::new(4.8950, 52.3664); // 阿姆斯特丹市中心餐厅密集区
     let customer_location = Location::new(4.8730, 52.3383); // 阿姆斯特丹市中心偏南住宅区
@@ -124,7 +116,7 @@ fn test_bsl_psd_with_visualization() -> Result<(), Box<dyn Error>> {
         println!("No feasible routes found with parallel solver!");
     } else {
         // Use actual store locations for the parallel results
-        let parallel_store_locations = generate_store_locations(&parallel_results, &stores);
+        let parallel_store_locations = generate_store_locations(&parallel_results, &bsl_psd);
 
         // Print each route's information for parallel
         for (i, route) in parallel_results.iter().enumerate() {
@@ -182,7 +174,7 @@ fn test_bsl_psd_with_visualization() -> Result<(), Box<dyn Error>> {
         println!("No feasible routes found with debug solver!");
     } else {
         // Use actual store locations for the debug results
-        let debug_store_locations = generate_store_locations(&debug_results, &stores);
+        let debug_store_locations = generate_store_locations(&debug_results, &bsl_psd);
 
         // Print each route's information for debug
         for (i, route) in debug_results.iter().enumerate() {
@@ -252,27 +244,13 @@ fn test_bsl_psd_with_visualization() -> Result<(), Box<dyn Error>> {
 /// Generate store locations from actual store coordinates instead of random positions
 fn generate_store_locations(
     routes: &[ShoppingRoute],
-    stores_map: &HashMap<StoreId, Store>,
+    bsl_psd: &BSLPSD,
 ) -> HashMap<StoreId, (f64, f64)> {
-    let mut store_locations: HashMap<StoreId, (f64, f64)> = HashMap::new();
-
-    // First, identify all stores used in any route
-    let mut used_store_ids = std::collections::HashSet::new();
-    for route in routes {
-        for store_id in &route.stores {
-            used_store_ids.insert(*store_id);
-        }
-    }
-
-    // Use actual store locations
-    for &store_id in &used_store_ids {
-        if let Some(store) = stores_map.get(&store_id) {
-            // Use actual coordinates from the store
-            store_locations.insert(store_id, (store.location.x, store.location.y));
-        }
-    }
-
-    store_locations
+    // Use the actual coordinates of every store used in any route
+    routes
+        .iter()
+        .flat_map(|route| bsl_psd.store_coordinates(route))
+        .collect()
 }
 
 // Visualize all shopping routes
@@ -399,8 +377,8 @@ fn visualize_all_routes(
 
     chart
         .configure_series_labels()
-        .background_style(&WHITE.mix(0.8))
-        .border_style(&BLACK)
+        .background_style(WHITE.mix(0.8))
+        .border_style(BLACK)
         .position(SeriesLabelPosition::UpperLeft)
         .draw()?;
 
@@ -410,6 +388,7 @@ fn visualize_all_routes(
 }
 
 /// Visualize each route individually in a separate image
+#[allow(clippy::too_many_arguments)]
 fn visualize_individual_routes(
     base_output_path: &str,
     routes: &[ShoppingRoute],
@@ -492,11 +471,7 @@ fn visualize_individual_routes(
             let size = if in_route { 10 } else { 6 };
 
             // Get store name if available
-            let store_name = if let Some(store) = stores_map.get(store_id) {
-                format!("Store {}", store_id)
-            } else {
-                format!("Store {}", store_id)
-            };
+            let store_name = format!("Store {}", store_id);
 
             chart
                 .draw_series(std::iter::once(Circle::new((*x, *y), size, style)))?
@@ -538,7 +513,7 @@ fn visualize_individual_routes(
                 path_points.push((x, y));
 
                 // Create label with store info and products available there
-                let store_label = if let Some(store) = stores_map.get(store_id) {
+                let _store_label = if let Some(store) = stores_map.get(store_id) {
                     let mut products_info = String::new();
                     for (product_id, qty) in &shopping_list.items {
                         if store.has_product(product_id) {
@@ -636,8 +611,8 @@ fn visualize_individual_routes(
 
         chart
             .configure_series_labels()
-            .background_style(&WHITE.mix(0.8))
-            .border_style(&BLACK)
+            .background_style(WHITE.mix(0.8))
+            .border_style(BLACK)
             .position(SeriesLabelPosition::UpperLeft)
             .draw()?;
 
@@ -751,8 +726,8 @@ fn create_time_cost_chart(
 
     chart
         .configure_series_labels()
-        .background_style(&WHITE.mix(0.8))
-        .border_style(&BLACK)
+        .background_style(WHITE.mix(0.8))
+        .border_style(BLACK)
         .position(SeriesLabelPosition::UpperRight)
         .draw()?;
 
@@ -0,0 +1,102 @@
+// Integration test measuring how far the nearest-stores filter moves the AMS skyline
+use personal_shopper::algorithms::bsl_psd::BSLPSD;
+use personal_shopper::algorithms::{PSDSolver, SolveOptions};
+use personal_shopper::models::{Location, ShoppingList, ShoppingRoute};
+use personal_shopper::utils::init_map::init_map_with_road_network;
+use std::error::Error;
+use std::time::Duration;
+
+#[test]
+fn test_nearest_stores_approximates_full_skyline() -> Result<(), Box<dyn Error>> {
+    let city_code = "AMS";
+    let total_product_supply = 30;
+    let nearest_stores = 100;
+
+    let (stores, travel_times) =
+        init_map_with_road_network(city_code, false, total_product_supply)?;
+
+    let shopper_location = Location::new(4.8950, 52.3664);
+    let customer_location = Location::new(4.8730, 52.3383);
+
+    // Shopping list with the first 5 products on offer
+    let mut product_ids: Vec<u32> = stores
+        .values()
+        .flat_map(|store| store.products.keys().cloned())
+        .collect();
+    product_ids.sort();
+    product_ids.dedup();
+    let mut shopping_list = ShoppingList::new();
+    for &product_id in &product_ids[..5] {
+        shopping_list.add_item(product_id, 3);
+    }
+
+    let mut solver = BSLPSD::new_with_travel_times(stores, travel_times);
+    solver.precompute_data();
+
+    // The full search doesn't finish in reasonable time on the whole city, so both
+    // searches get the same time budget; the exact extremes come from the dedicated
+    // minimum-time and minimum-cost solvers
+    let deadline = Some(Duration::from_secs(10));
+    let full = solver.solve_skyline(
+        &shopping_list,
+        shopper_location,
+        customer_location,
+        SolveOptions {
+            deadline,
+            ..SolveOptions::default()
+        },
+    );
+    let filtered = solver.solve_skyline(
+        &shopping_list,
+        shopper_location,
+        customer_location,
+        SolveOptions {
+            deadline,
+            nearest_stores: Some(nearest_stores),
+            ..SolveOptions::default()
+        },
+    );
+    let min_time = solver
+        .find_min_time_route_dijkstra(&shopping_list, shopper_location, customer_location)
+        .expect("the shopping list can be fulfilled")
+        .shopping_time;
+    let min_cost = solver
+        .find_min_cost_route(&shopping_list, shopper_location, customer_location)
+        .expect("the shopping list can be fulfilled");
+
+    let fastest = |skyline: &[ShoppingRoute]| -> f64 {
+        skyline
+            .iter()
+            .map(|route| route.shopping_time)
+            .fold(f64::INFINITY, f64::min)
+    };
+    let cheapest = |skyline: &[ShoppingRoute]| -> f64 {
+        skyline
+            .iter()
+            .map(|route| route.shopping_cost)
+            .fold(f64::INFINITY, f64::min)
+    };
+    for (name, skyline) in [("All stores", &full), ("Nearest stores", &filtered)] {
+        let (time, cost) = (fastest(skyline), cheapest(skyline));
+        println!(
+            "{}: {} routes, fastest {:.3} (+{:.1}%), cheapest {:.2} (+{:.1}%)",
+            name,
+            skyline.len(),
+            time,
+            100.0 * (time / min_time - 1.0),
+            cost,
+            100.0 * (cost / min_cost - 1.0)
+        );
+    }
+
+    assert!(!filtered.is_empty());
+    for route in filtered.iter() {
+        assert!(solver.satisfies_list(&route.stores, &shopping_list));
+    }
+
+    // Leaving stores out can only make the extremes worse
+    assert!(fastest(&filtered) >= min_time - 1e-9);
+    assert!(cheapest(&filtered) >= min_cost - 1e-9);
+
+    Ok(())
+}
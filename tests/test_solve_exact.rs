@@ -0,0 +1,103 @@
+// Integration test comparing the exhaustive skyline with the parallel BSL-PSD solver
+use personal_shopper::algorithms::bsl_psd::BSLPSD;
+use personal_shopper::models::{Location, ShoppingList, Store, StoreId};
+use personal_shopper::utils::init_map::init_map_with_road_network;
+use std::collections::HashMap;
+use std::error::Error;
+
+#[test]
+fn test_solve_exact_covers_parallel_skyline() -> Result<(), Box<dyn Error>> {
+    let city_code = "AMS";
+    let total_product_supply = 30;
+    let store_count = 15; // Keeps the exhaustive search small
+    let threshold = 50000;
+
+    let (stores, travel_times) =
+        init_map_with_road_network(city_code, false, total_product_supply)?;
+
+    let shopper_location = Location::new(4.8950, 52.3664);
+    let customer_location = Location::new(4.8730, 52.3383);
+
+    // Restrict the map to the stores nearest to the shopper
+    let mut nearest: Vec<&Store> = stores.values().collect();
+    nearest.sort_by(|a, b| {
+        a.location
+            .distance_to(&shopper_location)
+            .total_cmp(&b.location.distance_to(&shopper_location))
+            .then(a.id.cmp(&b.id))
+    });
+    let stores: HashMap<StoreId, Store> = nearest[..store_count]
+        .iter()
+        .map(|store| (store.id, (*store).clone()))
+        .collect();
+    let travel_times: HashMap<(StoreId, StoreId), f64> = travel_times
+        .into_iter()
+        .filter(|((from, to), _)| stores.contains_key(from) && stores.contains_key(to))
+        .collect();
+
+    // Shopping list with the first 5 products on offer
+    let mut product_ids: Vec<u32> = stores
+        .values()
+        .flat_map(|store| store.products.keys().cloned())
+        .collect();
+    product_ids.sort();
+    product_ids.dedup();
+    let mut shopping_list = ShoppingList::new();
+    for &product_id in &product_ids[..5] {
+        shopping_list.add_item(product_id, 3);
+    }
+
+    let mut solver = BSLPSD::new_with_travel_times(stores, travel_times);
+    solver.precompute_data();
+
+    let exact = solver.solve_exact(&shopping_list, shopper_location, customer_location);
+    let (parallel, _) = solver.solve_with_parallel(
+        &shopping_list,
+        shopper_location,
+        customer_location,
+        threshold,
+    );
+
+    println!("Exact skyline:");
+    for route in &exact {
+        println!(
+            "  {:?} time {:.3} cost {:.2}",
+            route.stores, route.shopping_time, route.shopping_cost
+        );
+    }
+
+    assert!(!exact.is_empty());
+    assert_eq!(
+        exact,
+        solver.solve_exact(&shopping_list, shopper_location, customer_location)
+    );
+
+    // Every route is truly non-dominated, by the rest of the skyline and by the parallel one
+    for route in &exact {
+        for other in exact.iter().chain(&parallel) {
+            assert!(
+                !other.conventionally_dominates(route),
+                "{:?} is dominated by {:?}",
+                route,
+                other
+            );
+        }
+    }
+
+    // Every parallel route shows up in the exact skyline, possibly in another store order;
+    // the parallel solver times routes incrementally, so it may report them as slower
+    for route in &parallel {
+        let mut stores = route.stores.clone();
+        stores.sort();
+        let found = exact.iter().any(|candidate| {
+            let mut candidate_stores = candidate.stores.clone();
+            candidate_stores.sort();
+            candidate_stores == stores
+                && candidate.shopping_time <= route.shopping_time + 1e-9
+                && (candidate.shopping_cost - route.shopping_cost).abs() < 1e-9
+        });
+        assert!(found, "{:?} is missing from the exact skyline", route);
+    }
+
+    Ok(())
+}
@@ -6,7 +6,6 @@ use plotters::prelude::*;
 use rand::Rng;
 use std::collections::HashMap;
 use std::error::Error;
-use std::time::Instant;
 
 #[test]
 fn test_supply_comparison() -> Result<(), Box<dyn Error>> {
@@ -25,8 +24,8 @@ fn test_supply_comparison() -> Result<(), Box<dyn Error>> {
         println!("\n### Testing with {} products ###", product_count);
 
         // Set output filenames for current product count
-        let limited_output_path = format!("bsl_psd_limited_supply_{}_products.png", product_count);
-        let infinite_output_path =
+        let _limited_output_path = format!("bsl_psd_limited_supply_{}_products.png", product_count);
+        let _infinite_output_path =
             format!("bsl_psd_infinite_supply_{}_products.png", product_count);
         let comparison_output_path = format!("supply_comparison_{}_products.png", product_count);
         let performance_comparison_path =
@@ -70,7 +69,7 @@ fn test_supply_comparison() -> Result<(), Box<dyn Error>> {
 
         // Find available products from limited supply stores (as baseline)
         let mut available_products = HashMap::new();
-        for (_store_id, store) in &limited_stores {
+        for store in limited_stores.values() {
             for (product_id, product) in &store.products {
                 let entry = available_products
                     .entry(*product_id)
@@ -101,16 +100,16 @@ fn test_supply_comparison() -> Result<(), Box<dyn Error>> {
         println!("\nShopping List (using {} products):", count_to_use);
 
         // Add specified number of products to the shopping list
-        for i in 0..count_to_use {
+        for &product_id in product_ids.iter().take(count_to_use) {
             let mut rng = rand::thread_rng();
             let quantity = rng.gen_range(5..=10); // Randomly generate a quantity between 5-10
-            shopping_list.add_item(product_ids[i], quantity);
+            shopping_list.add_item(product_id, quantity);
 
-            let product_info = available_products.get(&product_ids[i]);
+            let product_info = available_products.get(&product_id);
             if let Some((name, _)) = product_info {
                 println!(
                     "  Added product {} ({}): {} units",
-                    product_ids[i], name, quantity
+                    product_id, name, quantity
                 );
             }
         }
@@ -166,8 +165,11 @@ fn test_supply_comparison() -> Result<(), Box<dyn Error>> {
         //         "This is expected if the shopping list demands exceed total available inventory."
         //     );
         // } else {
-        //     // Randomize store locations for limited supply
-        //     let limited_store_locations = generate_store_locations(&limited_results);
+        //     // Actual store locations for limited supply
+        //     let limited_store_locations: HashMap<StoreId, (f64, f64)> = limited_results
+        //         .iter()
+        //         .flat_map(|route| limited_bsl_psd.store_coordinates(route))
+        //         .collect();
 
         //     // Print each route's information for limited supply
         //     for (i, route) in limited_results.iter().enumerate() {
@@ -223,8 +225,11 @@ fn test_supply_comparison() -> Result<(), Box<dyn Error>> {
         //     );
         //     println!("This is unexpected as infinite supply should always find a solution.");
         // } else {
-        //     // Randomize store locations for infinite supply
-        //     let infinite_store_locations = generate_store_locations(&infinite_results);
+        //     // Actual store locations for infinite supply
+        //     let infinite_store_locations: HashMap<StoreId, (f64, f64)> = infinite_results
+        //         .iter()
+        //         .flat_map(|route| infinite_bsl_psd.store_coordinates(route))
+        //         .collect();
 
         //     // Print each route's information for infinite supply
         //     for (i, route) in infinite_results.iter().enumerate() {
@@ -338,7 +343,6 @@ fn test_supply_comparison() -> Result<(), Box<dyn Error>> {
             println!("\nRoute Quality Trade-off Analysis:");
 
             // Calculate trade-off for limited supply
-            let mut limited_trade_off: Option<(f64, f64, f64)> = None;
             if limited_results.len() >= 2 {
                 let limited_fastest = &limited_results.first().unwrap();
                 let limited_cheapest = &limited_results.last().unwrap();
@@ -440,36 +444,7 @@ fn test_supply_comparison() -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
-/// Generate random store locations for routes
-// fn generate_store_locations(routes: &[ShoppingRoute]) -> HashMap<StoreId, (f64, f64)> {
-//     let mut store_locations: HashMap<StoreId, (f64, f64)> = HashMap::new();
-
-//     // Define the display area bounds with more space between stores
-//     let display_min_x = -70.0;
-//     let display_max_x = 70.0;
-//     let display_min_y = -70.0;
-//     let display_max_y = 70.0;
-
-//     // First, identify all stores used in any route
-//     let mut used_store_ids = std::collections::HashSet::new();
-//     for route in routes {
-//         for store_id in &route.stores {
-//             used_store_ids.insert(*store_id);
-//         }
-//     }
-
-//     // Only generate positions for stores that are used in routes
-//     for &store_id in &used_store_ids {
-//         // Generate random position for each store
-//         let x = display_min_x + (display_max_x - display_min_x) * rand::random::<f64>();
-//         let y = display_min_y + (display_max_y - display_min_y) * rand::random::<f64>();
-//         store_locations.insert(store_id, (x, y));
-//     }
-
-//     store_locations
-// }
-
-/// Visualize all shopping routes
+// Visualize all shopping routes
 // fn visualize_all_routes(
 //     output_path: &str,
 //     routes: &[ShoppingRoute],
@@ -593,8 +568,8 @@ fn test_supply_comparison() -> Result<(), Box<dyn Error>> {
 
 //     chart
 //         .configure_series_labels()
-//         .background_style(&WHITE.mix(0.8))
-//         .border_style(&BLACK)
+//         .background_style(WHITE.mix(0.8))
+//         .border_style(BLACK)
 //         .position(SeriesLabelPosition::UpperLeft)
 //         .draw()?;
 
@@ -604,6 +579,7 @@ fn test_supply_comparison() -> Result<(), Box<dyn Error>> {
 // }
 
 /// Create a time-cost trade-off analysis chart
+#[allow(dead_code)]
 fn create_time_cost_chart(
     output_path: &str,
     routes: &[ShoppingRoute],
@@ -697,8 +673,8 @@ fn create_time_cost_chart(
 
     chart
         .configure_series_labels()
-        .background_style(&WHITE.mix(0.8))
-        .border_style(&BLACK)
+        .background_style(WHITE.mix(0.8))
+        .border_style(BLACK)
         .position(SeriesLabelPosition::UpperRight)
         .draw()?;
 
@@ -853,8 +829,8 @@ fn create_supply_comparison_chart(
 
     chart
         .configure_series_labels()
-        .background_style(&WHITE.mix(0.8))
-        .border_style(&BLACK)
+        .background_style(WHITE.mix(0.8))
+        .border_style(BLACK)
         .position(SeriesLabelPosition::UpperRight)
         .draw()?;
 
@@ -864,6 +840,7 @@ fn create_supply_comparison_chart(
 }
 
 /// Create a performance comparison chart showing search time and route count differences
+#[allow(clippy::too_many_arguments)]
 fn create_performance_comparison_chart(
     output_path: &str,
     limited_routes: &[ShoppingRoute],
@@ -916,7 +893,7 @@ fn create_performance_comparison_chart(
 
     // Draw best time comparison bars
     chart_best_time.draw_series(
-        vec![(0f64, limited_best_time_ms), (1f64, infinite_best_time_ms)]
+        [(0f64, limited_best_time_ms), (1f64, infinite_best_time_ms)]
             .iter()
             .map(|&(x, y)| {
                 let color = if x == 0f64 { RED } else { BLUE };
@@ -925,7 +902,7 @@ fn create_performance_comparison_chart(
     )?;
 
     // Add supply type labels
-    chart_best_time.draw_series(vec![(0f64, "Limited"), (1f64, "Infinite")].iter().map(
+    chart_best_time.draw_series([(0f64, "Limited"), (1f64, "Infinite")].iter().map(
         |&(x, label)| {
             Text::new(
                 label,
@@ -958,7 +935,7 @@ fn create_performance_comparison_chart(
 
     // Draw extra time comparison bars
     chart_extra_time.draw_series(
-        vec![(0f64, limited_extra_time), (1f64, infinite_extra_time)]
+        [(0f64, limited_extra_time), (1f64, infinite_extra_time)]
             .iter()
             .map(|&(x, y)| {
                 let color = if x == 0f64 { RED } else { BLUE };
@@ -967,7 +944,7 @@ fn create_performance_comparison_chart(
     )?;
 
     // Add supply type labels
-    chart_extra_time.draw_series(vec![(0f64, "Limited"), (1f64, "Infinite")].iter().map(
+    chart_extra_time.draw_series([(0f64, "Limited"), (1f64, "Infinite")].iter().map(
         |&(x, label)| {
             Text::new(
                 label,
@@ -998,14 +975,14 @@ fn create_performance_comparison_chart(
 
     // Draw stacked bars for best time
     chart_total_time.draw_series(
-        vec![(0f64, limited_best_time_ms), (1f64, infinite_best_time_ms)]
+        [(0f64, limited_best_time_ms), (1f64, infinite_best_time_ms)]
             .iter()
             .map(|&(x, y)| Rectangle::new([(x, 0.0), (x + 0.8, y)], CYAN.mix(0.7).filled())),
     )?;
 
     // Draw stacked bars for extra time
     chart_total_time.draw_series(
-        vec![
+        [
             (0f64, limited_best_time_ms, limited_extra_time),
             (1f64, infinite_best_time_ms, infinite_extra_time),
         ]
@@ -1019,7 +996,7 @@ fn create_performance_comparison_chart(
     )?;
 
     // Add supply type labels
-    chart_total_time.draw_series(vec![(0f64, "Limited"), (1f64, "Infinite")].iter().map(
+    chart_total_time.draw_series([(0f64, "Limited"), (1f64, "Infinite")].iter().map(
         |&(x, label)| {
             Text::new(
                 label,
@@ -1073,7 +1050,7 @@ fn create_performance_comparison_chart(
 
     // Draw route count comparison bars
     chart_count.draw_series(
-        vec![(0f64, limited_count as f64), (1f64, infinite_count as f64)]
+        [(0f64, limited_count as f64), (1f64, infinite_count as f64)]
             .iter()
             .map(|&(x, y)| {
                 let color = if x == 0f64 { RED } else { BLUE };
@@ -1082,7 +1059,7 @@ fn create_performance_comparison_chart(
     )?;
 
     // Add supply type labels
-    chart_count.draw_series(vec![(0f64, "Limited"), (1f64, "Infinite")].iter().map(
+    chart_count.draw_series([(0f64, "Limited"), (1f64, "Infinite")].iter().map(
         |&(x, label)| {
             Text::new(
                 label,
@@ -1097,6 +1074,7 @@ fn create_performance_comparison_chart(
 }
 
 /// Determine the visualization chart boundaries
+#[allow(dead_code)]
 fn determine_bounds(
     store_locations: &HashMap<StoreId, (f64, f64)>,
     shopper_start: &Location,
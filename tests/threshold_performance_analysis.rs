@@ -5,7 +5,6 @@ use personal_shopper::models::{Location, ShoppingList};
 use personal_shopper::utils::init_map::init_map_with_road_network;
 use plotters::prelude::*;
 use rand::Rng;
-use std::collections::HashMap;
 use std::error::Error;
 use std::time::Instant;
 
@@ -36,16 +35,12 @@ fn test_threshold_performance_analysis() -> Result<(), Box<dyn Error>> {
     // Create shopping list
     let mut shopping_list = ShoppingList::new();
 
+    // Initialize BSLPSD algorithm
+    let mut bsl_psd = BSLPSD::new_with_travel_times(stores.clone(), travel_times);
+    bsl_psd.precompute_data();
+
     // Find available products
-    let mut available_products = HashMap::new();
-    for (_store_id, store) in &stores {
-        for (product_id, product) in &store.products {
-            let entry = available_products
-                .entry(*product_id)
-                .or_insert((product.name.clone(), 0));
-            entry.1 += store.get_inventory_level(product_id);
-        }
-    }
+    let available_products = bsl_psd.available_products();
 
     println!("\nAvailable products:");
     for (product_id, (name, total_supply)) in &available_products {
@@ -89,10 +84,6 @@ fn test_threshold_performance_analysis() -> Result<(), Box<dyn Error>> {
         }
     }
 
-    // Initialize BSLPSD algorithm
-    let mut bsl_psd = BSLPSD::new_with_travel_times(stores.clone(), travel_times);
-    bsl_psd.precompute_data();
-
     // Define start and end points (shopper and customer locations)
     let shopper_location = Location::new(4.8950, 52.3664); // Amsterdam city center restaurant district
     let customer_location = Location::new(4.8730, 52.3383); // Amsterdam city center southern residential area
@@ -427,8 +418,8 @@ fn visualize_time_analysis(
     // Add legend
     chart
         .configure_series_labels()
-        .background_style(&WHITE.mix(0.8))
-        .border_style(&BLACK)
+        .background_style(WHITE.mix(0.8))
+        .border_style(BLACK)
         .position(SeriesLabelPosition::UpperRight)
         .draw()?;
 
@@ -454,14 +445,11 @@ fn visualize_trade_off_efficiency(
     let threshold_padding = (max_threshold - min_threshold) as f64 * 0.1;
 
     // Calculate max values for other metrics
-    let max_efficiency = results
-        .iter()
-        .map(|&(_, e, _, _)| e)
-        .fold(0.0, |a, b| f64::max(a, b));
+    let max_efficiency = results.iter().map(|&(_, e, _, _)| e).fold(0.0, f64::max);
     let max_percent = results
         .iter()
         .map(|&(_, _, time_diff, cost_diff)| f64::max(time_diff, cost_diff))
-        .fold(0.0, |a, b| f64::max(a, b));
+        .fold(0.0, f64::max);
 
     // 1. Draw efficiency chart
     let mut efficiency_chart = ChartBuilder::on(&areas.0)
@@ -563,8 +551,8 @@ fn visualize_trade_off_efficiency(
     // Add legend
     percent_chart
         .configure_series_labels()
-        .background_style(&WHITE.mix(0.8))
-        .border_style(&BLACK)
+        .background_style(WHITE.mix(0.8))
+        .border_style(BLACK)
         .position(SeriesLabelPosition::UpperRight)
         .draw()?;
 